@@ -55,14 +55,23 @@ async fn run_model(llms: &Llms, model: Model) -> Result<(), LlmsError> {
 		model,
 		user_id: "example_all".into(),
 		tools: vec![],
+		tool_choice: Default::default(),
 		reasoning_effort: None,
+		response_format: Default::default(),
+		params: Default::default(),
+		cache: false,
+		extra: Default::default(),
+		headers: Default::default(),
+		timeouts: Default::default(),
+		transcript: None,
+		snapshot: None,
 	};
 
 	let mut stream = llms.request(&req).await?;
 
 	while let Some(ev) = stream.next().await {
-		match ev? {
-			ResponseEvent::TextDelta { content } => eprint!("{content}"),
+		if let ResponseEvent::TextDelta { content } = ev? {
+			eprint!("{content}");
 		}
 	}
 	eprintln!();
@@ -49,8 +49,19 @@ async fn main() {
 				},
 				"required": ["name"],
 			})),
+			strict: true,
+			cache: false,
 		}],
+		tool_choice: Default::default(),
 		reasoning_effort: None,
+		response_format: Default::default(),
+		params: Default::default(),
+		cache: false,
+		extra: Default::default(),
+		headers: Default::default(),
+		timeouts: Default::default(),
+		transcript: None,
+		snapshot: None,
 	};
 
 	req.input = vec![Input::Text {
@@ -65,8 +76,8 @@ async fn main() {
 	let mut stream = llms.request(&req).await.unwrap();
 
 	while let Some(thing) = stream.next().await {
-		match thing.unwrap() {
-			ResponseEvent::TextDelta { content } => eprint!("{}", content),
+		if let ResponseEvent::TextDelta { content } = thing.unwrap() {
+			eprint!("{}", content);
 		}
 	}
 	eprintln!();
@@ -112,8 +123,8 @@ async fn main() {
 	let mut stream = llms.request(&req).await.unwrap();
 
 	while let Some(thing) = stream.next().await {
-		match thing.unwrap() {
-			ResponseEvent::TextDelta { content } => eprint!("{}", content),
+		if let ResponseEvent::TextDelta { content } = thing.unwrap() {
+			eprint!("{}", content);
 		}
 	}
 	eprintln!();
@@ -121,8 +132,7 @@ async fn main() {
 	let response = stream.into_response().unwrap();
 	eprintln!("Final output: {:?}", response);
 
-	req.input
-		.extend(response.output.into_iter().map(Into::into));
+	req.extend_from_response(&response);
 
 	eprintln!("{:?}", req.input);
 }
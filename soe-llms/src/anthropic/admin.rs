@@ -0,0 +1,243 @@
+use std::fmt;
+
+use futures::Stream;
+use reqwest::{Client, RequestBuilder, Url};
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use super::{ANTHROPIC_VERSION, AnthropicError};
+use crate::llms::ApiKey;
+use crate::utils::error_response;
+use crate::utils::paginate::{Page, paginate};
+
+const ORGANIZATIONS_URL: &str = "https://api.anthropic.com/v1/organizations";
+
+/// Client for the Admin API, managing workspaces and api keys of the
+/// organization and reporting its usage.
+///
+/// It's authenticated with an admin key (`sk-ant-admin...`), which regular
+/// requests don't accept and which should be kept separate from them.
+///
+/// New api keys can only be created in the Console, the Admin API can only
+/// rename, disable or archive them.
+#[derive(Clone)]
+pub struct Admin {
+	pub client: Client,
+	pub admin_key: ApiKey,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Workspace {
+	pub id: String,
+	pub name: String,
+	/// RFC 3339 timestamp.
+	pub created_at: String,
+	/// RFC 3339 timestamp, `None` unless archived.
+	pub archived_at: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AdminApiKey {
+	pub id: String,
+	pub name: String,
+	pub status: ApiKeyStatus,
+	/// RFC 3339 timestamp.
+	pub created_at: String,
+	/// `None` for keys of the default workspace.
+	pub workspace_id: Option<String>,
+	/// The redacted key, e.g. `"sk-ant-api03-R2D...igAA"`.
+	pub partial_key_hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyStatus {
+	Active,
+	/// Disabled, can be activated again.
+	Inactive,
+	Archived,
+}
+
+/// Which keys [`Admin::list_api_keys`] returns, `None` means all.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyFilter {
+	pub workspace_id: Option<String>,
+	pub status: Option<ApiKeyStatus>,
+}
+
+/// Changes made by [`Admin::update_api_key`], `None` keeps the value.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ApiKeyUpdate {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub status: Option<ApiKeyStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct List<T> {
+	data: Vec<T>,
+	#[serde(default)]
+	has_more: bool,
+	last_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateWorkspace<'a> {
+	name: &'a str,
+}
+
+impl Admin {
+	pub fn new(admin_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(admin_key, Client::new())
+	}
+
+	pub fn with_client(admin_key: impl Into<ApiKey>, client: Client) -> Self {
+		Self {
+			client,
+			admin_key: admin_key.into(),
+		}
+	}
+
+	/// Lists the workspaces of the organization, the next page is fetched
+	/// while the stream is polled.
+	pub fn list_workspaces(
+		&self,
+		include_archived: bool,
+	) -> impl Stream<Item = Result<Workspace, AnthropicError>> + '_ {
+		paginate(move |after| {
+			let mut url = self.url("workspaces");
+			if include_archived {
+				url.query_pairs_mut()
+					.append_pair("include_archived", "true");
+			}
+			self.list_page(url, after)
+		})
+	}
+
+	pub async fn create_workspace(
+		&self,
+		name: &str,
+	) -> Result<Workspace, AnthropicError> {
+		let builder = self
+			.client
+			.post(self.url("workspaces"))
+			.json(&CreateWorkspace { name });
+		self.send(builder).await
+	}
+
+	/// Archives a workspace, which also archives all of its api keys.
+	pub async fn archive_workspace(
+		&self,
+		id: &str,
+	) -> Result<Workspace, AnthropicError> {
+		let builder = self
+			.client
+			.post(self.url(&format!("workspaces/{id}/archive")));
+		self.send(builder).await
+	}
+
+	/// Lists the api keys of the organization, the next page is fetched
+	/// while the stream is polled.
+	pub fn list_api_keys<'a>(
+		&'a self,
+		filter: &'a ApiKeyFilter,
+	) -> impl Stream<Item = Result<AdminApiKey, AnthropicError>> + 'a {
+		paginate(move |after| {
+			let mut url = self.url("api_keys");
+			{
+				let mut pairs = url.query_pairs_mut();
+				if let Some(id) = &filter.workspace_id {
+					pairs.append_pair("workspace_id", id);
+				}
+				if let Some(status) = filter.status {
+					pairs.append_pair("status", status.as_str());
+				}
+			}
+			self.list_page(url, after)
+		})
+	}
+
+	pub async fn update_api_key(
+		&self,
+		id: &str,
+		update: &ApiKeyUpdate,
+	) -> Result<AdminApiKey, AnthropicError> {
+		let builder = self
+			.client
+			.post(self.url(&format!("api_keys/{id}")))
+			.json(update);
+		self.send(builder).await
+	}
+
+	/// Disables an api key, requests made with it fail until it's set
+	/// [`ApiKeyStatus::Active`] again.
+	pub async fn disable_api_key(
+		&self,
+		id: &str,
+	) -> Result<AdminApiKey, AnthropicError> {
+		let update = ApiKeyUpdate {
+			status: Some(ApiKeyStatus::Inactive),
+			..Default::default()
+		};
+		self.update_api_key(id, &update).await
+	}
+
+	pub(super) fn url(&self, path: &str) -> Url {
+		Url::parse(&format!("{ORGANIZATIONS_URL}/{path}")).unwrap()
+	}
+
+	async fn list_page<T: DeserializeOwned>(
+		&self,
+		mut url: Url,
+		after: Option<String>,
+	) -> Result<Page<T>, AnthropicError> {
+		url.query_pairs_mut().append_pair("limit", "1000");
+		if let Some(after) = &after {
+			url.query_pairs_mut().append_pair("after_id", after);
+		}
+
+		let list: List<T> = self.send(self.client.get(url)).await?;
+		Ok(Page {
+			items: list.data,
+			next: list.last_id.filter(|_| list.has_more),
+		})
+	}
+
+	/// Authenticates and sends the request, parsing the json response.
+	pub(super) async fn send<T: DeserializeOwned>(
+		&self,
+		builder: RequestBuilder,
+	) -> Result<T, AnthropicError> {
+		let admin_key = self.admin_key.get().await?;
+
+		let resp = builder
+			.header("x-api-key", &*admin_key)
+			.header("anthropic-version", ANTHROPIC_VERSION)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json().await?)
+	}
+}
+
+impl ApiKeyStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Active => "active",
+			Self::Inactive => "inactive",
+			Self::Archived => "archived",
+		}
+	}
+}
+
+impl fmt::Debug for Admin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Admin").field("admin_key", &"***").finish()
+	}
+}
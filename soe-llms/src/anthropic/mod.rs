@@ -1,14 +1,33 @@
-use std::fmt;
+mod admin;
+mod models;
+mod usage;
 
-use reqwest::{Client, StatusCode};
+pub use admin::{
+	Admin, AdminApiKey, ApiKeyFilter, ApiKeyStatus, ApiKeyUpdate, Workspace,
+};
+pub use models::ModelInfo;
+pub use usage::{
+	Bucket, BucketWidth, CacheCreation, Cost, MessagesUsage, UsageQuery,
+};
+
+use std::{fmt, time::Duration};
+
+use reqwest::{Client, StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tracing::{debug, trace};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, DocumentSource,
+		ImageSource, LlmProvider, LlmResponseStream, LlmsError, Transcript,
+		history::{self, AssistantPart, ToolOutput, Turn, UserPart},
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
@@ -19,76 +38,120 @@ const DEFAULT_MAX_TOKENS: u32 = 8096;
 /// reasoning and the final answer within this single cap.
 const EFFORT_MAX_TOKENS: u32 = 32768;
 
+/// Default of [`Anthropic::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
+
 #[derive(Clone)]
 pub struct Anthropic {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/anthropic/v1"`.
+	pub base_url: String,
 }
 
 impl Anthropic {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), AnthropicError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url("models"))
+			.header("x-api-key", &*api_key)
+			.header("anthropic-version", ANTHROPIC_VERSION)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
 		}
+
+		Ok(())
 	}
 
 	pub async fn request(
 		&self,
 		req: &Request,
 	) -> Result<ResponseStream, AnthropicError> {
-		#[derive(Debug, Serialize)]
-		struct ApiReq<'a> {
-			model: &'a str,
-			max_tokens: u32,
-			#[serde(skip_serializing_if = "Option::is_none")]
-			system: Option<&'a str>,
-			messages: &'a Vec<ApiMessage>,
-			#[serde(skip_serializing_if = "Vec::is_empty")]
-			tools: &'a Vec<ApiTool>,
-			#[serde(skip_serializing_if = "Option::is_none")]
-			thinking: Option<Thinking>,
-			#[serde(skip_serializing_if = "Option::is_none")]
-			output_config: Option<OutputConfig>,
-			stream: bool,
-		}
-
-		let (thinking, output_config) = match req.effort {
-			Some(effort) => {
-				(Some(Thinking::Adaptive), Some(OutputConfig { effort }))
-			}
-			None => (None, None),
-		};
-
 		let api_req = ApiReq {
-			model: req.model.as_str(),
-			max_tokens: req.max_tokens,
-			system: req.system.as_deref(),
-			messages: &req.messages,
-			tools: &req.tools,
-			thinking,
-			output_config,
-			stream: true,
+			model: Some(req.model_id()),
+			stream: Some(true),
+			..req.api_req()
 		};
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://api.anthropic.com/v1/messages")
-			.header("x-api-key", &self.api_key)
+			.post(self.url("messages"))
+			.header("x-api-key", &*api_key)
 			.header("anthropic-version", ANTHROPIC_VERSION)
-			.json(&api_req)
-			.send()
-			.await?;
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(AnthropicError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream {
+			inner: SseResponse::new(
+				resp,
+				req.transcript.as_ref(),
+				&api_key,
+				req.read_timeout,
+			),
+			acc: MessageAccumulator::new(llms::ProviderKind::Anthropic),
+		})
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
 	}
 }
 
@@ -112,17 +175,80 @@ impl LlmProvider for Anthropic {
 			llms::Model::ClaudeOpus4_8 => AnthropicModel::Opus4_8,
 			llms::Model::ClaudeSonnet5 => AnthropicModel::Sonnet5,
 			llms::Model::ClaudeHaiku4_5 => AnthropicModel::Haiku4_5,
+			llms::Model::Custom { id, .. } => {
+				AnthropicModel::Custom(id.clone())
+			}
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
-		let system = if req.instructions.is_empty() {
-			None
-		} else {
-			Some(req.instructions.clone())
+		self.request(&Request::from_llms(req, model))
+			.await
+			.map_err(Into::into)
+	}
+}
+
+#[derive(Debug)]
+pub struct Request {
+	pub messages: Vec<ApiMessage>,
+	pub model: AnthropicModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	pub snapshot: Option<String>,
+	pub system: Option<String>,
+	/// Sets a cache breakpoint after the system prompt, caching the tools
+	/// and the system prompt.
+	pub cache_system: bool,
+	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// Max tokens for the whole response. Adaptive thinking tokens count
+	/// toward this cap, so callers should pass a larger value when `effort`
+	/// is set.
+	pub max_tokens: u32,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub stop_sequences: Vec<String>,
+	/// `output_config.effort`. `None` omits the thinking/output_config fields.
+	pub effort: Option<Effort>,
+	/// `output_config.format`, `None` lets the model answer with plain text.
+	pub output_format: Option<OutputFormat>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
+}
+
+impl Request {
+	/// Translates `req` for `model`, see [`LlmProvider::request`].
+	pub(crate) fn from_llms(
+		req: &llms::Request,
+		model: AnthropicModel,
+	) -> Self {
+		let mut system = history::instructions(&req.instructions, &req.input);
+
+		let output_format = match &req.response_format {
+			llms::ResponseFormat::Text => None,
+			// only schemas can be enforced
+			format @ llms::ResponseFormat::JsonObject => {
+				let instruction = format.instruction().unwrap_or_default();
+				system = Some(match system {
+					Some(system) => format!("{system}\n\n{instruction}"),
+					None => instruction,
+				});
+				None
+			}
+			llms::ResponseFormat::JsonSchema { schema, .. } => {
+				Some(OutputFormat::JsonSchema {
+					schema: schema.clone(),
+				})
+			}
 		};
 
 		// Haiku 4.5 doesn't support adaptive thinking; silently ignore.
-		let effort = match (model, req.reasoning_effort) {
+		let effort = match (&model, req.reasoning_effort) {
 			(AnthropicModel::Haiku4_5, Some(_)) => {
 				debug!("reasoning_effort is ignored for Claude Haiku 4.5");
 				None
@@ -135,37 +261,119 @@ impl LlmProvider for Anthropic {
 
 		// Thinking tokens count toward max_tokens; give the model headroom
 		// for both reasoning and the final answer when effort is set.
-		let max_tokens = if effort.is_some() {
-			EFFORT_MAX_TOKENS
-		} else {
-			DEFAULT_MAX_TOKENS
+		let max_tokens = match (req.params.max_tokens, effort) {
+			(Some(max_tokens), _) => max_tokens,
+			(None, Some(_)) => EFFORT_MAX_TOKENS,
+			(None, None) => DEFAULT_MAX_TOKENS,
 		};
 
-		self.request(&Request {
-			messages: req.input.iter().cloned().map(Into::into).collect(),
+		let mut messages = ApiMessage::from_history(&req.input);
+		if req.cache {
+			ApiMessage::cache_breakpoint(&mut messages);
+		}
+
+		Request {
+			messages,
 			model,
+			snapshot: req.snapshot.clone(),
 			system,
+			cache_system: req.cache,
 			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tool_choice: req.tool_choice.clone().into(),
 			max_tokens,
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			stop_sequences: req.params.stop.clone().unwrap_or_default(),
 			effort,
-		})
-		.await
-		.map_err(Into::into)
+			output_format,
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
+		}
+	}
+
+	pub(crate) fn model_id(&self) -> &str {
+		self.snapshot.as_deref().unwrap_or(self.model.as_str())
+	}
+
+	/// The body without the model and `stream`, which Bedrock takes from
+	/// the url.
+	pub(crate) fn api_req(&self) -> ApiReq<'_> {
+		let thinking = self.effort.map(|_| Thinking::Adaptive);
+		let output_config = (self.effort.is_some()
+			|| self.output_format.is_some())
+		.then_some(OutputConfig {
+			effort: self.effort,
+			format: self.output_format.as_ref(),
+		});
+
+		// the block form is only needed to attach the breakpoint
+		let system =
+			self.system.as_deref().map(|text| match self.cache_system {
+				true => System::Blocks([ApiContentBlock::Text {
+					text: text.into(),
+					cache_control: Some(CacheControl::Ephemeral),
+				}]),
+				false => System::Text(text),
+			});
+
+		ApiReq {
+			model: None,
+			anthropic_version: None,
+			max_tokens: self.max_tokens,
+			temperature: self.temperature,
+			top_p: self.top_p,
+			stop_sequences: &self.stop_sequences,
+			system,
+			messages: &self.messages,
+			tools: &self.tools,
+			tool_choice: self
+				.tool_choice
+				.as_ref()
+				.filter(|_| !self.tools.is_empty()),
+			thinking,
+			output_config,
+			stream: None,
+		}
 	}
 }
 
-#[derive(Debug)]
-pub struct Request {
-	pub messages: Vec<ApiMessage>,
-	pub model: AnthropicModel,
-	pub system: Option<String>,
-	pub tools: Vec<ApiTool>,
-	/// Max tokens for the whole response. Adaptive thinking tokens count
-	/// toward this cap, so callers should pass a larger value when `effort`
-	/// is set.
-	pub max_tokens: u32,
-	/// `output_config.effort`. `None` omits the thinking/output_config fields.
-	pub effort: Option<Effort>,
+/// The body of a messages request.
+#[derive(Debug, Serialize)]
+pub(crate) struct ApiReq<'a> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub model: Option<&'a str>,
+	/// Only sent in the body to Bedrock, the api takes it as a header.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub anthropic_version: Option<&'static str>,
+	max_tokens: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	temperature: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	top_p: Option<f32>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop_sequences: &'a Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	system: Option<System<'a>>,
+	messages: &'a Vec<ApiMessage>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	tools: &'a Vec<ApiTool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	tool_choice: Option<&'a ToolChoice>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	thinking: Option<Thinking>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	output_config: Option<OutputConfig<'a>>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stream: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum System<'a> {
+	Text(&'a str),
+	Blocks([ApiContentBlock; 1]),
 }
 
 #[derive(Debug, Serialize)]
@@ -175,8 +383,19 @@ enum Thinking {
 }
 
 #[derive(Debug, Serialize)]
-struct OutputConfig {
-	effort: Effort,
+struct OutputConfig<'a> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	effort: Option<Effort>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	format: Option<&'a OutputFormat>,
+}
+
+/// Constrains the final answer, tool calls aren't affected.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputFormat {
+	/// Every object in the schema needs `"additionalProperties": false`.
+	JsonSchema { schema: Value },
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -193,50 +412,106 @@ pub struct ApiMessage {
 	pub content: ApiMessageContent,
 }
 
-impl From<llms::Input> for ApiMessage {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => ApiMessage {
-				role: role.into(),
-				content: ApiMessageContent::Text(content),
-			},
-			llms::Input::ToolCall {
-				id, name, input, ..
-			} => ApiMessage {
-				role: ApiRole::Assistant,
-				content: ApiMessageContent::Blocks(vec![
-					ApiContentBlock::ToolUse { id, name, input },
-				]),
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiMessage {
-				role: ApiRole::User,
-				content: ApiMessageContent::Blocks(vec![
-					ApiContentBlock::ToolResult {
-						tool_use_id: id,
-						content: output,
-					},
-				]),
-			},
+impl ApiMessage {
+	/// Encodes the history, merging consecutive turns of the same role since
+	/// tool results and further user text have to share a message.
+	pub fn from_history(input: &[llms::Input]) -> Vec<Self> {
+		let mut messages: Vec<ApiMessage> = Vec::new();
+
+		for turn in history::turns(input) {
+			let (role, blocks) = match turn {
+				// merged into the system prompt
+				Turn::System(_) => continue,
+				Turn::User(parts) => (
+					ApiRole::User,
+					parts
+						.into_iter()
+						.map(|part| match part {
+							UserPart::Text(text) => ApiContentBlock::Text {
+								text,
+								cache_control: None,
+							},
+							UserPart::Image(image) => ApiContentBlock::Image {
+								source: image.into(),
+								cache_control: None,
+							},
+							UserPart::Document { data, mime } => {
+								ApiContentBlock::Document {
+									source: ApiImageSource::document(
+										data, mime,
+									),
+									cache_control: None,
+								}
+							}
+						})
+						.collect::<Vec<_>>(),
+				),
+				Turn::Assistant(parts) => {
+					(ApiRole::Assistant, ApiContentBlock::assistant_turn(parts))
+				}
+				Turn::ToolOutputs(outputs) => (
+					ApiRole::User,
+					outputs
+						.into_iter()
+						.map(|ToolOutput { id, output }| {
+							ApiContentBlock::ToolResult {
+								tool_use_id: id,
+								content: output,
+								cache_control: None,
+							}
+						})
+						.collect(),
+				),
+			};
+
+			match messages.last_mut() {
+				Some(ApiMessage {
+					role: last,
+					content: ApiMessageContent::Blocks(last_blocks),
+				}) if *last == role => last_blocks.extend(blocks),
+				_ => messages.push(ApiMessage {
+					role,
+					content: ApiMessageContent::Blocks(blocks),
+				}),
+			}
+		}
+
+		messages
+	}
+
+	/// Sets a cache breakpoint on the last block that takes one, so the
+	/// next request with the same history reads it from the cache.
+	pub fn cache_breakpoint(messages: &mut [Self]) {
+		let Some(message) = messages.last_mut() else {
+			return;
+		};
+
+		match &mut message.content {
+			ApiMessageContent::Text(text) => {
+				message.content =
+					ApiMessageContent::Blocks(vec![ApiContentBlock::Text {
+						text: std::mem::take(text),
+						cache_control: Some(CacheControl::Ephemeral),
+					}]);
+			}
+			ApiMessageContent::Blocks(blocks) => {
+				if let Some(cache_control) =
+					blocks.iter_mut().rev().find_map(|b| b.cache_control())
+				{
+					*cache_control = Some(CacheControl::Ephemeral);
+				}
+			}
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum ApiRole {
 	User,
 	Assistant,
 }
 
-impl From<llms::Role> for ApiRole {
-	fn from(role: llms::Role) -> Self {
-		match role {
-			llms::Role::User => ApiRole::User,
-			llms::Role::Assistant => ApiRole::Assistant,
-		}
-	}
-}
-
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
 pub enum ApiMessageContent {
@@ -244,21 +519,155 @@ pub enum ApiMessageContent {
 	Blocks(Vec<ApiContentBlock>),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ApiContentBlock {
 	Text {
 		text: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cache_control: Option<CacheControl>,
 	},
 	ToolUse {
 		id: String,
 		name: String,
 		input: Value,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cache_control: Option<CacheControl>,
 	},
 	ToolResult {
 		tool_use_id: String,
 		content: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cache_control: Option<CacheControl>,
+	},
+	Image {
+		source: ApiImageSource,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cache_control: Option<CacheControl>,
 	},
+	Document {
+		source: ApiImageSource,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		cache_control: Option<CacheControl>,
+	},
+	/// Has to be sent back verbatim with the tool results of the turn.
+	Thinking { thinking: String, signature: String },
+	/// Thinking flagged by the safety systems, encrypted.
+	RedactedThinking { data: String },
+}
+
+/// Marks the end of a cached prefix, a cache breakpoint.
+///
+/// Anthropic caches everything before it, tools, then the system prompt,
+/// then the messages, and allows at most 4 per request.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CacheControl {
+	/// Kept for 5 minutes after the last read.
+	Ephemeral,
+}
+
+/// Also the source of documents.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiImageSource {
+	Base64 { media_type: String, data: String },
+	Url { url: String },
+}
+
+impl ApiImageSource {
+	fn document(data: DocumentSource, mime: String) -> Self {
+		match data {
+			DocumentSource::Url(url) => Self::Url { url },
+			DocumentSource::Base64(data) => Self::Base64 {
+				media_type: mime,
+				data,
+			},
+			DocumentSource::Reader(reader) => Self::Base64 {
+				media_type: mime,
+				data: reader.placeholder(),
+			},
+		}
+	}
+}
+
+impl From<ImageSource> for ApiImageSource {
+	fn from(image: ImageSource) -> Self {
+		match image {
+			ImageSource::Url(url) => Self::Url { url },
+			ImageSource::Base64 { data, mime_type } => Self::Base64 {
+				media_type: mime_type,
+				data,
+			},
+			ImageSource::Reader { reader, mime_type } => Self::Base64 {
+				media_type: mime_type,
+				data: reader.placeholder(),
+			},
+		}
+	}
+}
+
+impl ApiContentBlock {
+	fn is_thinking(&self) -> bool {
+		matches!(self, Self::Thinking { .. } | Self::RedactedThinking { .. })
+	}
+
+	/// `None` for thinking blocks, which can't be breakpoints.
+	fn cache_control(&mut self) -> Option<&mut Option<CacheControl>> {
+		match self {
+			Self::Text { cache_control, .. }
+			| Self::ToolUse { cache_control, .. }
+			| Self::ToolResult { cache_control, .. }
+			| Self::Image { cache_control, .. }
+			| Self::Document { cache_control, .. } => Some(cache_control),
+			Self::Thinking { .. } | Self::RedactedThinking { .. } => None,
+		}
+	}
+
+	/// Encodes an assistant turn, restoring the thinking blocks stored in
+	/// the tool call `context` in front, where Anthropic expects them.
+	fn assistant_turn(parts: Vec<AssistantPart>) -> Vec<Self> {
+		let mut thinking = Vec::new();
+		let mut blocks = Vec::new();
+
+		for part in parts {
+			match part {
+				// empty text blocks are rejected
+				AssistantPart::Text(text) if text.is_empty() => {}
+				AssistantPart::Text(text) => {
+					blocks.push(ApiContentBlock::Text {
+						text,
+						cache_control: None,
+					})
+				}
+				AssistantPart::ToolCall {
+					id,
+					name,
+					input,
+					context,
+				} => {
+					// the context might come from another provider
+					if let Some(restored) = context.and_then(|context| {
+						serde_json::from_str::<Vec<Self>>(&context).ok()
+					}) {
+						thinking.extend(
+							restored.into_iter().filter(Self::is_thinking),
+						);
+					}
+
+					blocks.push(ApiContentBlock::ToolUse {
+						id,
+						name,
+						input,
+						cache_control: None,
+					});
+				}
+			}
+		}
+
+		thinking.extend(blocks);
+		thinking
+	}
 }
 
 #[derive(Debug, Serialize)]
@@ -268,6 +677,9 @@ pub struct ApiTool {
 	/// Full JSON Schema object sent verbatim as `input_schema`.
 	/// Anthropic requires at minimum `{ "type": "object", "properties": {} }`.
 	pub input_schema: Value,
+	/// See [`llms::Tool::cache`].
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub cache_control: Option<CacheControl>,
 }
 
 impl From<llms::Tool> for ApiTool {
@@ -276,25 +688,52 @@ impl From<llms::Tool> for ApiTool {
 			name: tool.name,
 			description: tool.description,
 			input_schema: tool.parameters.unwrap_or_else(default_parameters),
+			cache_control: tool.cache.then_some(CacheControl::Ephemeral),
+		}
+	}
+}
+
+/// `tool_choice`, forcing a tool isn't supported together with thinking.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+	None,
+	/// At least one tool call.
+	Any,
+	Tool {
+		name: String,
+	},
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => Some(ToolChoice::None),
+			llms::ToolChoice::Required => Some(ToolChoice::Any),
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Tool { name }),
 		}
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum AnthropicModel {
 	Fable5,
 	Opus4_8,
 	Sonnet5,
 	Haiku4_5,
+	/// Any other model id, see [`llms::Model::Custom`].
+	Custom(String),
 }
 
 impl AnthropicModel {
-	pub fn as_str(&self) -> &'static str {
+	pub fn as_str(&self) -> &str {
 		match self {
 			AnthropicModel::Fable5 => "claude-fable-5",
 			AnthropicModel::Opus4_8 => "claude-opus-4-8",
 			AnthropicModel::Sonnet5 => "claude-sonnet-5",
 			AnthropicModel::Haiku4_5 => "claude-haiku-4-5",
+			AnthropicModel::Custom(id) => id,
 		}
 	}
 }
@@ -336,7 +775,12 @@ pub struct MessageStartData {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct MessageStartUsage {
+	/// Only the input after the last cache breakpoint.
 	pub input_tokens: u32,
+	#[serde(default)]
+	pub cache_creation_input_tokens: u32,
+	#[serde(default)]
+	pub cache_read_input_tokens: u32,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -349,14 +793,17 @@ pub enum ContentBlockStartData {
 		id: String,
 		name: String,
 	},
-	/// Adaptive-thinking content block. We don't surface reasoning text to
-	/// callers, so the fields are deserialized but ignored.
+	/// Adaptive-thinking content block, the text ends up in
+	/// [`llms::Response::reasoning`].
 	Thinking {
 		#[serde(default)]
 		thinking: String,
 		#[serde(default)]
 		signature: String,
 	},
+	RedactedThinking {
+		data: String,
+	},
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -368,11 +815,11 @@ pub enum ContentDelta {
 	InputJsonDelta {
 		partial_json: String,
 	},
-	/// Streaming delta for a `thinking` content block. Ignored.
+	/// Streaming delta for a `thinking` content block.
 	ThinkingDelta {
 		thinking: String,
 	},
-	/// Streaming signature for a `thinking` block. Ignored.
+	/// Streaming signature for a `thinking` block.
 	SignatureDelta {
 		signature: String,
 	},
@@ -401,11 +848,32 @@ pub enum AnthropicError {
 	#[error("Invalid LLM response: {0}")]
 	InvalidLlmResponse(String),
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("API error: {error_type}: {message}")]
 	ApiError { error_type: String, message: String },
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for AnthropicError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<AnthropicError> for LlmsError {
@@ -414,18 +882,32 @@ impl From<AnthropicError> for LlmsError {
 			AnthropicError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::Anthropic,
+				request_id: None,
 			},
-			AnthropicError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			AnthropicError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::Anthropic,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			AnthropicError::ApiError {
 				error_type,
 				message,
-			} => LlmsError::Response {
-				status: StatusCode::OK,
-				body: format!("{error_type}: {message}"),
-			},
+			} => LlmsError::api_event(
+				llms::ProviderKind::Anthropic,
+				&error_type,
+				message,
+				None,
+			),
 			AnthropicError::ReqwestError(e) => LlmsError::Reqwest(e),
+			AnthropicError::Credential(e) => LlmsError::Credential(e),
 		}
 	}
 }
@@ -439,40 +921,35 @@ enum BlockAccumulator {
 		name: String,
 		input_json: String,
 	},
-	/// Thinking blocks are accumulated as a placeholder so block indices
-	/// stay aligned with what Anthropic emits, but the content is dropped
-	/// when building the final response.
-	Thinking,
+	/// Collected into [`llms::Response::reasoning`] and, together with the
+	/// signature, into the `context` of the following tool call so it can be
+	/// sent back.
+	Thinking {
+		thinking: String,
+		signature: String,
+	},
+	RedactedThinking {
+		data: String,
+	},
 }
 
 pub struct ResponseStream {
 	inner: SseResponse,
-	/// Content blocks accumulated in arrival order (Anthropic always sends
-	/// them sequentially, so index == position in this Vec).
-	blocks: Vec<BlockAccumulator>,
-	/// Token usage accumulated across `message_start` (input) and
-	/// `message_delta` (output) events. `None` until the first event with a
-	/// `usage` payload arrives.
-	usage: Option<llms::Usage>,
-	done: bool,
+	acc: MessageAccumulator,
 }
 
 impl std::fmt::Debug for ResponseStream {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("ResponseStream")
-			.field("done", &self.done)
+			.field("done", &self.acc.done)
 			.finish()
 	}
 }
 
 impl ResponseStream {
-	fn new(inner: SseResponse) -> Self {
-		Self {
-			inner,
-			blocks: Vec::new(),
-			usage: None,
-			done: false,
-		}
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
 	}
 
 	async fn next_event(&mut self) -> Option<Result<Event, SseError>> {
@@ -484,16 +961,96 @@ impl ResponseStream {
 			other => other,
 		}
 	}
+}
+
+impl LlmResponseStream for ResponseStream {
+	async fn next(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		while !self.acc.done {
+			let ev = match self.next_event().await {
+				Some(Ok(ev)) => ev,
+				Some(Err(e)) => return Some(Err(e.into())),
+				None => return None,
+			};
 
-	fn build_response(&mut self) -> Result<llms::Response, AnthropicError> {
+			let request_id = self.inner.request_id();
+			if let Some(ev) = self.acc.push(ev, request_id) {
+				return Some(ev);
+			}
+		}
+
+		None
+	}
+}
+
+/// Builds the response from the events of a messages stream, also used
+/// for the Claude models on Bedrock.
+pub(crate) struct MessageAccumulator {
+	/// Reported as the provider of errors.
+	provider: llms::ProviderKind,
+	/// Content blocks accumulated in arrival order (Anthropic always sends
+	/// them sequentially, so index == position in this Vec).
+	blocks: Vec<BlockAccumulator>,
+	/// Token usage accumulated across `message_start` (input) and
+	/// `message_delta` (output) events. `None` until the first event with a
+	/// `usage` payload arrives.
+	usage: Option<llms::Usage>,
+	/// From the `message_delta` event.
+	stop_reason: Option<String>,
+	/// Set after `message_stop` or an error, later events are ignored.
+	pub done: bool,
+}
+
+impl MessageAccumulator {
+	pub fn new(provider: llms::ProviderKind) -> Self {
+		Self {
+			provider,
+			blocks: Vec::new(),
+			usage: None,
+			stop_reason: None,
+			done: false,
+		}
+	}
+
+	fn invalid(&self, msg: String) -> LlmsError {
+		LlmsError::Response {
+			status: StatusCode::OK,
+			body: msg,
+			provider: self.provider,
+			request_id: None,
+		}
+	}
+
+	fn build_response(&mut self) -> Result<llms::Response, LlmsError> {
 		let mut output = Vec::new();
-		for block in self.blocks.drain(..) {
+		let mut reasoning = Vec::new();
+		// thinking blocks not yet attached to a tool call
+		let mut thinking = Vec::new();
+		for block in std::mem::take(&mut self.blocks) {
 			match block {
 				BlockAccumulator::Text { text } if !text.is_empty() => {
 					output.push(llms::Output::Text { content: text });
 				}
 				BlockAccumulator::Text { .. } => {}
-				BlockAccumulator::Thinking => {}
+				BlockAccumulator::Thinking {
+					thinking: text,
+					signature,
+				} => {
+					if !text.is_empty() {
+						reasoning.push(text.clone());
+						output.push(llms::Output::Reasoning {
+							content: text.clone(),
+						});
+					}
+					thinking.push(ApiContentBlock::Thinking {
+						thinking: text,
+						signature,
+					});
+				}
+				BlockAccumulator::RedactedThinking { data } => {
+					thinking.push(ApiContentBlock::RedactedThinking { data });
+				}
 				BlockAccumulator::ToolUse {
 					id,
 					name,
@@ -503,138 +1060,331 @@ impl ResponseStream {
 						Value::Object(Default::default())
 					} else {
 						serde_json::from_str(&input_json).map_err(|e| {
-							AnthropicError::InvalidLlmResponse(format!(
+							self.invalid(format!(
 								"invalid tool input JSON for '{name}': {e}"
 							))
 						})?
 					};
 
+					let context = if thinking.is_empty() {
+						None
+					} else {
+						let blocks = std::mem::take(&mut thinking);
+						Some(serde_json::to_string(&blocks).map_err(|e| {
+							self.invalid(format!(
+								"failed to serialize thinking blocks: {e}"
+							))
+						})?)
+					};
+
 					output.push(llms::Output::ToolCall {
 						id,
 						name,
 						input,
-						context: None,
+						context,
 					});
 				}
 			}
 		}
-		let usage = self.usage.take().ok_or_else(|| {
-			AnthropicError::InvalidLlmResponse(
-				"missing usage in response".into(),
-			)
-		})?;
+		let usage = self
+			.usage
+			.take()
+			.ok_or_else(|| self.invalid("missing usage in response".into()))?;
+
+		let finish_reason =
+			self.stop_reason.take().map(|reason| match reason.as_str() {
+				"end_turn" | "stop_sequence" => llms::FinishReason::Stop,
+				"max_tokens" | "model_context_window_exceeded" => {
+					llms::FinishReason::MaxTokens
+				}
+				"tool_use" => llms::FinishReason::ToolCalls,
+				"refusal" => llms::FinishReason::ContentFilter,
+				_ => llms::FinishReason::Other(reason),
+			});
 
-		Ok(llms::Response { output, usage })
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason,
+			reasoning: Some(reasoning.join("\n\n")).filter(|r| !r.is_empty()),
+			..Default::default()
+		})
 	}
-}
 
-impl LlmResponseStream for ResponseStream {
-	async fn next(
+	/// Handles the next event, returns what to emit for it, if anything.
+	pub fn push(
 		&mut self,
+		ev: Event,
+		request_id: Option<&str>,
 	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
 		if self.done {
 			return None;
 		}
 
-		loop {
-			let ev = match self.next_event().await {
-				Some(Ok(ev)) => ev,
-				Some(Err(e)) => return Some(Err(e.into())),
-				None => return None,
-			};
-
-			match ev {
-				Event::MessageStart { message } => {
-					if let Some(usage) = message.usage {
-						self.usage
-							.get_or_insert_with(llms::Usage::default)
-							.input_tokens = usage.input_tokens;
-					}
-					continue;
+		match ev {
+			Event::MessageStart { message } => {
+				if let Some(usage) = message.usage {
+					let acc =
+						self.usage.get_or_insert_with(llms::Usage::default);
+					acc.input_tokens = usage
+						.input_tokens
+						.saturating_add(usage.cache_creation_input_tokens)
+						.saturating_add(usage.cache_read_input_tokens);
+					acc.cache_read_tokens = usage.cache_read_input_tokens;
+					acc.cache_write_tokens = usage.cache_creation_input_tokens;
 				}
-				Event::MessageDelta { usage, .. } => {
-					if let Some(usage) = usage {
-						self.usage
-							.get_or_insert_with(llms::Usage::default)
-							.output_tokens = usage.output_tokens;
-					}
-					continue;
+				None
+			}
+			Event::MessageDelta { delta, usage } => {
+				if delta.stop_reason.is_some() {
+					self.stop_reason = delta.stop_reason;
 				}
-				Event::ContentBlockStart { content_block, .. } => {
-					let block = match content_block {
-						ContentBlockStartData::Text { text } => {
-							BlockAccumulator::Text { text }
-						}
-						ContentBlockStartData::ToolUse { id, name } => {
-							BlockAccumulator::ToolUse {
+				if let Some(usage) = usage {
+					self.usage
+						.get_or_insert_with(llms::Usage::default)
+						.output_tokens = usage.output_tokens;
+				}
+				None
+			}
+			Event::ContentBlockStart { content_block, .. } => {
+				let block = match content_block {
+					ContentBlockStartData::Text { text } => {
+						BlockAccumulator::Text { text }
+					}
+					ContentBlockStartData::ToolUse { id, name } => {
+						self.blocks.push(BlockAccumulator::ToolUse {
+							id: id.clone(),
+							name: name.clone(),
+							input_json: String::new(),
+						});
+						return Some(Ok(
+							llms::LlmResponseEvent::ToolCallStarted {
 								id,
 								name,
-								input_json: String::new(),
-							}
-						}
-						ContentBlockStartData::Thinking { .. } => {
-							BlockAccumulator::Thinking
-						}
-					};
+							},
+						));
+					}
+					ContentBlockStartData::Thinking {
+						thinking,
+						signature,
+					} => BlockAccumulator::Thinking {
+						thinking,
+						signature,
+					},
+					ContentBlockStartData::RedactedThinking { data } => {
+						BlockAccumulator::RedactedThinking { data }
+					}
+				};
 
-					self.blocks.push(block);
-					continue;
-				}
-				Event::ContentBlockDelta { index, delta } => {
-					let acc = self.blocks.get_mut(index as usize).expect(
-						"received delta for non-existent content block",
-					);
-
-					match (delta, acc) {
-						(
-							ContentDelta::TextDelta { text },
-							BlockAccumulator::Text { text: acc },
-						) => {
-							acc.push_str(&text);
-
-							return Some(Ok(
-								llms::LlmResponseEvent::TextDelta {
-									content: text,
-								},
-							));
+				self.blocks.push(block);
+				None
+			}
+			Event::ContentBlockDelta { index, delta } => {
+				let acc = self
+					.blocks
+					.get_mut(index as usize)
+					.expect("received delta for non-existent content block");
+
+				match (delta, acc) {
+					(
+						ContentDelta::TextDelta { text },
+						BlockAccumulator::Text { text: acc },
+					) => {
+						acc.push_str(&text);
+
+						Some(Ok(llms::LlmResponseEvent::TextDelta {
+							content: text,
+						}))
+					}
+					(
+						ContentDelta::InputJsonDelta { partial_json },
+						BlockAccumulator::ToolUse { id, input_json, .. },
+					) => {
+						input_json.push_str(&partial_json);
+						// the first delta is usually empty
+						if partial_json.is_empty() {
+							return None;
 						}
-						(
-							ContentDelta::InputJsonDelta { partial_json },
-							BlockAccumulator::ToolUse { input_json, .. },
-						) => {
-							input_json.push_str(&partial_json);
-							continue;
+
+						Some(Ok(
+							llms::LlmResponseEvent::ToolCallArgumentsDelta {
+								id: id.clone(),
+								delta: partial_json,
+							},
+						))
+					}
+					(
+						ContentDelta::ThinkingDelta { thinking },
+						BlockAccumulator::Thinking { thinking: acc, .. },
+					) => {
+						acc.push_str(&thinking);
+						if thinking.is_empty() {
+							return None;
 						}
-						(
-							ContentDelta::ThinkingDelta { .. }
-							| ContentDelta::SignatureDelta { .. },
-							BlockAccumulator::Thinking,
-						) => continue,
-						_ => unreachable!(
-							"received delta of wrong type for content block"
-						),
+
+						Some(Ok(llms::LlmResponseEvent::ReasoningDelta {
+							content: thinking,
+						}))
 					}
+					(
+						ContentDelta::SignatureDelta { signature },
+						BlockAccumulator::Thinking { signature: acc, .. },
+					) => {
+						acc.push_str(&signature);
+						None
+					}
+					_ => unreachable!(
+						"received delta of wrong type for content block"
+					),
 				}
-				Event::MessageStop => {
-					self.done = true;
-					let response = self
-						.build_response()
-						.map(llms::LlmResponseEvent::Completed)
-						.map_err(Into::into);
-					return Some(response);
-				}
-				Event::Error { error } => {
-					self.done = true;
-					return Some(Err(LlmsError::Response {
-						status: StatusCode::OK,
-						body: format!(
-							"{}: {}",
-							error.error_type, error.message
-						),
-					}));
-				}
-				_ => continue,
 			}
+			Event::MessageStop => {
+				self.done = true;
+				Some(
+					self.build_response()
+						.map(llms::LlmResponseEvent::Completed),
+				)
+			}
+			Event::Error { error } => {
+				self.done = true;
+				Some(Err(LlmsError::api_event(
+					self.provider,
+					&error.error_type,
+					error.message,
+					request_id.map(Into::into),
+				)))
+			}
+			_ => None,
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::llms::history::tests::interleaved;
+
+	fn encode(input: &[llms::Input]) -> Value {
+		serde_json::to_value(ApiMessage::from_history(input)).unwrap()
+	}
+
+	#[test]
+	fn merges_tool_results_with_the_next_user_turn() {
+		assert_eq!(
+			encode(&interleaved()),
+			json!([
+				{
+					"role": "user",
+					"content": [
+						{ "type": "text", "text": "Weather in Bern and Zurich?" }
+					]
+				},
+				{
+					"role": "assistant",
+					"content": [
+						{ "type": "text", "text": "Checking." },
+						{
+							"type": "tool_use",
+							"id": "call_1",
+							"name": "weather",
+							"input": { "city": "Bern" }
+						},
+						{
+							"type": "tool_use",
+							"id": "call_2",
+							"name": "weather",
+							"input": { "city": "Zurich" }
+						}
+					]
+				},
+				{
+					"role": "user",
+					"content": [
+						{
+							"type": "tool_result",
+							"tool_use_id": "call_1",
+							"content": "sunny"
+						},
+						{
+							"type": "tool_result",
+							"tool_use_id": "call_2",
+							"content": "rainy"
+						},
+						{ "type": "text", "text": "And here?" },
+						{
+							"type": "image",
+							"source": {
+								"type": "url",
+								"url": "https://example.com/a.png"
+							}
+						}
+					]
+				},
+				{
+					"role": "assistant",
+					"content": [{ "type": "text", "text": "Cloudy." }]
+				}
+			])
+		);
+	}
+
+	#[test]
+	fn restores_thinking_in_front_of_the_turn() {
+		let context = json!([
+			{ "type": "thinking", "thinking": "hmm", "signature": "sig" },
+			{ "type": "text", "text": "dropped" }
+		]);
+		let input = [
+			llms::Input::Text {
+				role: llms::Role::Assistant,
+				content: "Checking.".into(),
+			},
+			llms::Input::ToolCall {
+				id: "call_1".into(),
+				name: "weather".into(),
+				input: json!({}),
+				context: Some(context.to_string()),
+			},
+			llms::Input::ToolCall {
+				id: "call_2".into(),
+				name: "weather".into(),
+				input: json!({}),
+				// from another provider
+				context: Some("opaque".into()),
+			},
+		];
+		assert_eq!(
+			encode(&input),
+			json!([{
+				"role": "assistant",
+				"content": [
+					{ "type": "thinking", "thinking": "hmm", "signature": "sig" },
+					{ "type": "text", "text": "Checking." },
+					{
+						"type": "tool_use",
+						"id": "call_1",
+						"name": "weather",
+						"input": {}
+					},
+					{
+						"type": "tool_use",
+						"id": "call_2",
+						"name": "weather",
+						"input": {}
+					}
+				]
+			}])
+		);
+	}
+
+	#[test]
+	fn cache_breakpoint_marks_the_last_block() {
+		let mut messages = ApiMessage::from_history(&interleaved()[..7]);
+		ApiMessage::cache_breakpoint(&mut messages);
+		let last = &serde_json::to_value(&messages).unwrap()[2]["content"];
+		assert!(last[0].get("cache_control").is_none());
+		assert_eq!(last[1]["cache_control"], json!({ "type": "ephemeral" }));
+	}
+}
@@ -0,0 +1,66 @@
+use futures::Stream;
+use serde::Deserialize;
+
+use super::{ANTHROPIC_VERSION, Anthropic, AnthropicError};
+use crate::utils::error_response;
+use crate::utils::paginate::{Page, paginate};
+
+/// A model returned by [`Anthropic::list_models`].
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelInfo {
+	/// e.g. `"claude-sonnet-4-5-20250929"`.
+	pub id: String,
+	pub display_name: String,
+	/// RFC 3339 timestamp of the release.
+	pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+	data: Vec<ModelInfo>,
+	#[serde(default)]
+	has_more: bool,
+	last_id: Option<String>,
+}
+
+impl Anthropic {
+	/// Lists the available models, newest first. The next page is fetched
+	/// while the stream is polled.
+	pub fn list_models(
+		&self,
+	) -> impl Stream<Item = Result<ModelInfo, AnthropicError>> + '_ {
+		paginate(move |after| self.models_page(after))
+	}
+
+	async fn models_page(
+		&self,
+		after: Option<String>,
+	) -> Result<Page<ModelInfo>, AnthropicError> {
+		let api_key = self.api_key.get().await?;
+
+		let mut req = self
+			.client
+			.get(self.url("models"))
+			.header("x-api-key", &*api_key)
+			.header("anthropic-version", ANTHROPIC_VERSION)
+			.build()?;
+		req.url_mut().query_pairs_mut().append_pair("limit", "1000");
+		if let Some(after) = &after {
+			req.url_mut()
+				.query_pairs_mut()
+				.append_pair("after_id", after);
+		}
+
+		let resp = self.client.execute(req).await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let list: ModelList = resp.json().await?;
+		Ok(Page {
+			items: list.data,
+			next: list.last_id.filter(|_| list.has_more),
+		})
+	}
+}
@@ -0,0 +1,178 @@
+use std::time::SystemTime;
+
+use futures::Stream;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use super::{Admin, AnthropicError};
+use crate::utils::paginate::{Page, paginate};
+use crate::utils::time::rfc3339;
+
+/// Time range and grouping of [`Admin::usage_report`] and
+/// [`Admin::cost_report`].
+#[derive(Debug, Clone)]
+pub struct UsageQuery {
+	pub start: SystemTime,
+	/// `None` means until now.
+	pub end: Option<SystemTime>,
+	/// Costs are only reported per day.
+	pub bucket_width: BucketWidth,
+	/// Fields to split the results by, e.g. `"model"` or `"workspace_id"`.
+	pub group_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BucketWidth {
+	Minute,
+	Hour,
+	#[default]
+	Day,
+}
+
+impl BucketWidth {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Minute => "1m",
+			Self::Hour => "1h",
+			Self::Day => "1d",
+		}
+	}
+}
+
+impl UsageQuery {
+	/// Daily buckets from `start` until now.
+	pub fn new(start: SystemTime) -> Self {
+		Self {
+			start,
+			end: None,
+			bucket_width: BucketWidth::Day,
+			group_by: Vec::new(),
+		}
+	}
+
+	pub fn end(mut self, end: SystemTime) -> Self {
+		self.end = Some(end);
+		self
+	}
+
+	pub fn bucket_width(mut self, width: BucketWidth) -> Self {
+		self.bucket_width = width;
+		self
+	}
+
+	pub fn group_by(mut self, field: impl Into<String>) -> Self {
+		self.group_by.push(field.into());
+		self
+	}
+}
+
+/// The results of one time bucket.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bucket<T> {
+	/// RFC 3339 timestamp.
+	pub starting_at: String,
+	/// RFC 3339 timestamp.
+	pub ending_at: String,
+	/// One entry per group, a single one without [`UsageQuery::group_by`].
+	pub results: Vec<T>,
+}
+
+/// Token usage of the Messages API.
+///
+/// The grouping fields are `None` unless grouped by them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessagesUsage {
+	pub uncached_input_tokens: u64,
+	#[serde(default)]
+	pub cache_read_input_tokens: u64,
+	#[serde(default)]
+	pub cache_creation: CacheCreation,
+	pub output_tokens: u64,
+	pub model: Option<String>,
+	pub workspace_id: Option<String>,
+	pub api_key_id: Option<String>,
+	pub service_tier: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CacheCreation {
+	#[serde(default)]
+	pub ephemeral_5m_input_tokens: u64,
+	#[serde(default)]
+	pub ephemeral_1h_input_tokens: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cost {
+	/// Decimal string in the lowest unit of the currency, e.g. cents.
+	pub amount: String,
+	/// e.g. `"USD"`.
+	pub currency: String,
+	/// e.g. which model and token type the cost is for.
+	pub description: Option<String>,
+	pub model: Option<String>,
+	pub workspace_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BucketPage<T> {
+	data: Vec<Bucket<T>>,
+	#[serde(default)]
+	has_more: bool,
+	next_page: Option<String>,
+}
+
+impl Admin {
+	/// Historical token usage of the organization, the next page is fetched
+	/// while the stream is polled.
+	pub fn usage_report<'a>(
+		&'a self,
+		query: &'a UsageQuery,
+	) -> impl Stream<Item = Result<Bucket<MessagesUsage>, AnthropicError>> + 'a
+	{
+		paginate(move |page| {
+			self.buckets_page("usage_report/messages", query, page)
+		})
+	}
+
+	/// Historical spend of the organization in daily buckets, the next page
+	/// is fetched while the stream is polled.
+	pub fn cost_report<'a>(
+		&'a self,
+		query: &'a UsageQuery,
+	) -> impl Stream<Item = Result<Bucket<Cost>, AnthropicError>> + 'a {
+		paginate(move |page| self.buckets_page("cost_report", query, page))
+	}
+
+	async fn buckets_page<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		query: &UsageQuery,
+		page: Option<String>,
+	) -> Result<Page<Bucket<T>>, AnthropicError> {
+		let mut url = self.url(path);
+		{
+			let mut pairs = url.query_pairs_mut();
+			pairs.append_pair("starting_at", &rfc3339(query.start));
+			// the cost report only has daily buckets
+			if path != "cost_report" {
+				pairs.append_pair("bucket_width", query.bucket_width.as_str());
+			}
+			if let Some(end) = query.end {
+				pairs.append_pair("ending_at", &rfc3339(end));
+			}
+			for field in &query.group_by {
+				pairs.append_pair("group_by[]", field);
+			}
+			if let Some(page) = &page {
+				pairs.append_pair("page", page);
+			}
+		}
+
+		let page: BucketPage<T> = self.send(self.client.get(url)).await?;
+		Ok(Page {
+			items: page.data,
+			next: page.next_page.filter(|_| page.has_more),
+		})
+	}
+}
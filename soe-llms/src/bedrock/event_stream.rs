@@ -0,0 +1,274 @@
+use std::time::Duration;
+
+use bytes::{Buf as _, Bytes, BytesMut};
+use futures::{StreamExt as _, stream::BoxStream};
+
+use super::BedrockError;
+
+/// Prelude and message checksum.
+const OVERHEAD: usize = 16;
+/// Larger messages are rejected before reading them.
+const MAX_MESSAGE_LEN: usize = 16 * 1024 * 1024;
+
+/// A message of the [AWS event stream encoding](https://docs.aws.amazon.com/transcribe/latest/dg/streaming-setting-up.html#streaming-event-stream),
+/// with the string headers.
+#[derive(Debug)]
+pub(super) struct Message {
+	headers: Vec<(String, String)>,
+	pub payload: Bytes,
+}
+
+impl Message {
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers
+			.iter()
+			.find(|(n, _)| n == name)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+/// Reads the messages from the response body as they're polled.
+pub(super) struct EventStream {
+	body: BoxStream<'static, reqwest::Result<Bytes>>,
+	buf: BytesMut,
+	read_timeout: Option<Duration>,
+}
+
+impl EventStream {
+	/// Reading fails with [`BedrockError::ReadTimeout`] once no chunk of the
+	/// body arrived within `read_timeout`.
+	pub fn new(
+		body: BoxStream<'static, reqwest::Result<Bytes>>,
+		read_timeout: Option<Duration>,
+	) -> Self {
+		Self {
+			body,
+			buf: BytesMut::new(),
+			read_timeout,
+		}
+	}
+
+	/// Returns `None` once the body ended after a complete message.
+	///
+	/// # Cancel safety
+	///
+	/// This method is cancellation safe, partially received messages are
+	/// kept.
+	pub async fn next(&mut self) -> Option<Result<Message, BedrockError>> {
+		loop {
+			if self.buf.len() >= 4 {
+				let len = u32::from_be_bytes(self.buf[..4].try_into().unwrap())
+					as usize;
+				if !(OVERHEAD..=MAX_MESSAGE_LEN).contains(&len) {
+					return Some(Err(invalid(format!(
+						"invalid message length {len}"
+					))));
+				}
+
+				if self.buf.len() >= len {
+					let message = self.buf.split_to(len).freeze();
+					return Some(decode(message));
+				}
+			}
+
+			let chunk = match self.read_timeout {
+				Some(limit) => {
+					match tokio::time::timeout(limit, self.body.next()).await {
+						Ok(chunk) => chunk,
+						Err(_) => {
+							return Some(Err(BedrockError::ReadTimeout(limit)));
+						}
+					}
+				}
+				None => self.body.next().await,
+			};
+			match chunk {
+				Some(Ok(chunk)) => self.buf.extend_from_slice(&chunk),
+				Some(Err(e)) => return Some(Err(e.into())),
+				None if self.buf.is_empty() => return None,
+				None => {
+					self.buf.clear();
+					return Some(Err(invalid("truncated message".into())));
+				}
+			}
+		}
+	}
+}
+
+fn invalid(msg: String) -> BedrockError {
+	BedrockError::InvalidLlmResponse(msg)
+}
+
+/// Decodes a whole message, checking both checksums.
+fn decode(mut message: Bytes) -> Result<Message, BedrockError> {
+	let len = message.len();
+	let crc = |bytes: &[u8]| crc32fast::hash(bytes);
+	let message_crc =
+		u32::from_be_bytes(message[len - 4..].try_into().unwrap());
+	if crc(&message[..len - 4]) != message_crc {
+		return Err(invalid("message checksum mismatch".into()));
+	}
+	let prelude_crc = u32::from_be_bytes(message[8..12].try_into().unwrap());
+	if crc(&message[..8]) != prelude_crc {
+		return Err(invalid("prelude checksum mismatch".into()));
+	}
+
+	message.advance(4);
+	let headers_len = message.get_u32() as usize;
+	message.advance(4);
+	if headers_len > len - OVERHEAD {
+		return Err(invalid(format!("invalid headers length {headers_len}")));
+	}
+
+	let mut headers_buf = message.split_to(headers_len);
+	let payload = message.split_to(len - OVERHEAD - headers_len);
+
+	let mut headers = Vec::new();
+	while headers_buf.has_remaining() {
+		let (name, value) = decode_header(&mut headers_buf)
+			.ok_or_else(|| invalid("invalid header".into()))?;
+		if let Some(value) = value {
+			headers.push((name, value));
+		}
+	}
+
+	Ok(Message { headers, payload })
+}
+
+/// Only string values are returned, the others are skipped.
+fn decode_header(buf: &mut Bytes) -> Option<(String, Option<String>)> {
+	let take = |buf: &mut Bytes, n: usize| {
+		(buf.remaining() >= n).then(|| buf.split_to(n))
+	};
+
+	let name_len = take(buf, 1)?[0] as usize;
+	let name = String::from_utf8(take(buf, name_len)?.to_vec()).ok()?;
+
+	let value = match take(buf, 1)?[0] {
+		// true and false
+		0 | 1 => None,
+		2 => take(buf, 1).map(|_| None)?,
+		3 => take(buf, 2).map(|_| None)?,
+		4 => take(buf, 4).map(|_| None)?,
+		// long and timestamp
+		5 | 8 => take(buf, 8).map(|_| None)?,
+		// byte array and string
+		kind @ (6 | 7) => {
+			let len = take(buf, 2)?.get_u16() as usize;
+			let value = take(buf, len)?;
+			match kind {
+				7 => Some(String::from_utf8(value.to_vec()).ok()?),
+				_ => None,
+			}
+		}
+		// uuid
+		9 => take(buf, 16).map(|_| None)?,
+		_ => return None,
+	};
+
+	Some((name, value))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+	use futures::stream;
+
+	use super::*;
+
+	/// A message with string headers and valid checksums.
+	pub(in crate::bedrock) fn encode(
+		headers: &[(&str, &str)],
+		payload: &[u8],
+	) -> Vec<u8> {
+		let mut encoded_headers = Vec::new();
+		for (name, value) in headers {
+			encoded_headers.push(name.len() as u8);
+			encoded_headers.extend_from_slice(name.as_bytes());
+			encoded_headers.push(7);
+			encoded_headers
+				.extend_from_slice(&(value.len() as u16).to_be_bytes());
+			encoded_headers.extend_from_slice(value.as_bytes());
+		}
+
+		let len = OVERHEAD + encoded_headers.len() + payload.len();
+		let mut message = Vec::new();
+		message.extend_from_slice(&(len as u32).to_be_bytes());
+		message
+			.extend_from_slice(&(encoded_headers.len() as u32).to_be_bytes());
+		message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+		message.extend_from_slice(&encoded_headers);
+		message.extend_from_slice(payload);
+		message.extend_from_slice(&crc32fast::hash(&message).to_be_bytes());
+		message
+	}
+
+	fn events(chunks: Vec<Vec<u8>>) -> EventStream {
+		let body = stream::iter(chunks.into_iter().map(|c| Ok(c.into())));
+		EventStream::new(body.boxed(), None)
+	}
+
+	#[tokio::test]
+	async fn decodes_messages_split_across_chunks() {
+		let message = encode(
+			&[(":event-type", "chunk"), (":message-type", "event")],
+			b"{\"bytes\":\"e30=\"}",
+		);
+		let (a, b) = message.split_at(10);
+		let mut events = events(vec![a.to_vec(), [b, &message].concat()]);
+
+		for _ in 0..2 {
+			let message = events.next().await.unwrap().unwrap();
+			assert_eq!(message.header(":event-type"), Some("chunk"));
+			assert_eq!(message.header(":message-type"), Some("event"));
+			assert_eq!(&message.payload[..], b"{\"bytes\":\"e30=\"}");
+		}
+		assert!(events.next().await.is_none());
+	}
+
+	#[tokio::test]
+	async fn rejects_checksum_mismatches() {
+		let message = encode(&[(":event-type", "chunk")], b"{}");
+
+		let mut payload = message.clone();
+		let at = payload.len() - 5;
+		payload[at] ^= 1;
+		assert!(matches!(
+			events(vec![payload]).next().await,
+			Some(Err(BedrockError::InvalidLlmResponse(msg)))
+				if msg == "message checksum mismatch"
+		));
+
+		// a wrong prelude checksum with a matching message checksum
+		let mut prelude = message[..message.len() - 4].to_vec();
+		prelude[8] ^= 1;
+		let crc = crc32fast::hash(&prelude);
+		prelude.extend_from_slice(&crc.to_be_bytes());
+		assert!(matches!(
+			events(vec![prelude]).next().await,
+			Some(Err(BedrockError::InvalidLlmResponse(msg)))
+				if msg == "prelude checksum mismatch"
+		));
+
+		let truncated = message[..message.len() - 1].to_vec();
+		assert!(matches!(
+			events(vec![truncated]).next().await,
+			Some(Err(BedrockError::InvalidLlmResponse(msg)))
+				if msg == "truncated message"
+		));
+	}
+
+	#[tokio::test]
+	async fn read_timeout_keeps_the_partial_message() {
+		let limit = Duration::from_millis(50);
+		let body = stream::iter([Ok(Bytes::from_static(&[0, 0]))])
+			.chain(stream::pending())
+			.boxed();
+		let mut events = EventStream::new(body, Some(limit));
+
+		assert!(matches!(
+			events.next().await,
+			Some(Err(BedrockError::ReadTimeout(l))) if l == limit
+		));
+		assert_eq!(&events.buf[..], [0, 0]);
+	}
+}
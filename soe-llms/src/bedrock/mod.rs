@@ -0,0 +1,687 @@
+mod event_stream;
+mod sigv4;
+
+use std::{fmt, time::Duration, time::SystemTime};
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use futures::StreamExt as _;
+use reqwest::{Client, StatusCode, Url};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::trace;
+
+use self::{
+	event_stream::{EventStream, Message},
+	sigv4::{Signer, uri_encode},
+};
+use crate::{
+	anthropic::{self, AnthropicModel, MessageAccumulator},
+	llms::{
+		self, LlmProvider, LlmResponseStream, LlmsError, TranscriptTee, media,
+	},
+	mistral::{self, ChunkAccumulator, MistralModel},
+	utils::{ErrorResponse, error_response, extra::WithExtra, request_id},
+};
+
+/// Sent in the body of the Claude requests instead of a header.
+const ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+/// The name the requests are signed for, also for the runtime endpoints.
+const SERVICE: &str = "bedrock";
+
+/// The runtime endpoint of `region`, the default of [`Bedrock::base_url`].
+pub fn default_base_url(region: &str) -> String {
+	format!("https://bedrock-runtime.{region}.amazonaws.com")
+}
+
+/// The keys of an IAM user or, with a session token, temporary ones of a
+/// role.
+#[derive(Clone)]
+pub struct AwsCredentials {
+	pub access_key_id: String,
+	pub secret_access_key: String,
+	pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+	pub fn new(
+		access_key_id: impl Into<String>,
+		secret_access_key: impl Into<String>,
+	) -> Self {
+		Self {
+			access_key_id: access_key_id.into(),
+			secret_access_key: secret_access_key.into(),
+			session_token: None,
+		}
+	}
+
+	pub fn session_token(mut self, token: impl Into<String>) -> Self {
+		self.session_token = Some(token.into());
+		self
+	}
+}
+
+impl fmt::Debug for AwsCredentials {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("AwsCredentials")
+			.field("access_key_id", &self.access_key_id)
+			.field("secret_access_key", &"***")
+			.field("session_token", &self.session_token.as_ref().map(|_| "***"))
+			.finish()
+	}
+}
+
+/// The models on Amazon Bedrock, through `invoke-with-response-stream`.
+///
+/// Requests go in the format of the model's provider, which is taken from
+/// the model id, see [`ModelFamily::from_id`].
+#[derive(Clone)]
+pub struct Bedrock {
+	pub client: Client,
+	pub credentials: AwsCredentials,
+	/// E.g. `"us-east-1"`, the requests are signed for it.
+	pub region: String,
+	/// Prefix of the runtime endpoints, defaults to
+	/// [`default_base_url`] of the region.
+	pub base_url: String,
+}
+
+impl Bedrock {
+	pub fn new(region: impl Into<String>, credentials: AwsCredentials) -> Self {
+		Self::with_client(region, credentials, Client::new())
+	}
+
+	pub fn with_client(
+		region: impl Into<String>,
+		credentials: AwsCredentials,
+		client: Client,
+	) -> Self {
+		let region = region.into();
+		Self {
+			client,
+			credentials,
+			base_url: default_base_url(&region),
+			region,
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> Result<Url, BedrockError> {
+		let url = format!("{}/{path}", self.base_url.trim_end_matches('/'));
+		Url::parse(&url).map_err(|e| BedrockError::InvalidUrl(e.to_string()))
+	}
+
+	fn signer(&self) -> Signer<'_> {
+		Signer {
+			credentials: &self.credentials,
+			region: &self.region,
+			service: SERVICE,
+		}
+	}
+
+	/// Lists the foundation models of the region, the cheapest
+	/// authenticated call.
+	///
+	/// Used to check that the api is reachable and the credentials are
+	/// valid. Goes to the control plane of the region, not to
+	/// [`Bedrock::base_url`].
+	pub async fn ping(&self) -> Result<(), BedrockError> {
+		let url = format!(
+			"https://bedrock.{}.amazonaws.com/foundation-models",
+			self.region
+		);
+		let url = Url::parse(&url)
+			.map_err(|e| BedrockError::InvalidUrl(e.to_string()))?;
+
+		let headers =
+			self.signer().sign("GET", &url, &[], &[], SystemTime::now());
+		let mut builder = self.client.get(url);
+		for (name, value) in headers {
+			builder = builder.header(name, value);
+		}
+
+		let resp = builder.send().await?;
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+
+	pub async fn request(
+		&self,
+		req: &Request,
+	) -> Result<ResponseStream, BedrockError> {
+		let (model_id, body, headers, transcript, read_timeout) = match req {
+			Request::Anthropic(req) => {
+				let mut api_req = req.api_req();
+				api_req.anthropic_version = Some(ANTHROPIC_VERSION);
+				let body = serde_json::to_vec(&WithExtra {
+					body: &api_req,
+					extra: &req.extra,
+				})?;
+				(
+					req.model_id(),
+					body,
+					&req.headers,
+					&req.transcript,
+					req.read_timeout,
+				)
+			}
+			Request::Mistral(req) => {
+				let body = serde_json::to_vec(&WithExtra {
+					body: &req.api_req(),
+					extra: &req.extra,
+				})?;
+				(
+					req.model_id(),
+					body,
+					&req.headers,
+					&req.transcript,
+					req.read_timeout,
+				)
+			}
+		};
+
+		trace!("{:?}", String::from_utf8_lossy(&body));
+		// signing needs the whole body
+		let body = media::read_body(body).await?;
+
+		let url = self.url(&format!(
+			"model/{}/invoke-with-response-stream",
+			uri_encode(model_id)
+		))?;
+
+		let content = [
+			("accept", "application/vnd.amazon.eventstream"),
+			("content-type", "application/json"),
+		];
+		let signed = self.signer().sign(
+			"POST",
+			&url,
+			&content,
+			&body,
+			SystemTime::now(),
+		);
+
+		let mut builder = self.client.post(url).headers(headers.clone());
+		for (name, value) in content {
+			builder = builder.header(name, value);
+		}
+		for (name, value) in signed {
+			builder = builder.header(name, value);
+		}
+
+		let resp = builder.body(body).send().await?;
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let tee = transcript
+			.as_ref()
+			.map(|t| t.start(&resp, &self.credentials.secret_access_key));
+
+		Ok(ResponseStream {
+			request_id: request_id(resp.headers()),
+			inner: EventStream::new(resp.bytes_stream().boxed(), read_timeout),
+			tee,
+			format: match req {
+				Request::Anthropic(_) => Format::Anthropic(
+					MessageAccumulator::new(llms::ProviderKind::Bedrock),
+				),
+				Request::Mistral(_) => Format::Mistral {
+					acc: ChunkAccumulator::new(llms::ProviderKind::Bedrock),
+					tool_calls: 0,
+				},
+			},
+		})
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
+	}
+}
+
+impl fmt::Debug for Bedrock {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Bedrock")
+			.field("region", &self.region)
+			.field("credentials", &self.credentials)
+			.finish()
+	}
+}
+
+impl LlmProvider for Bedrock {
+	type Stream = ResponseStream;
+
+	async fn request(
+		&self,
+		req: &llms::Request,
+	) -> Result<Self::Stream, LlmsError> {
+		let id = match &req.model {
+			llms::Model::Bedrock(id) | llms::Model::Custom { id, .. } => id,
+			m => unreachable!("unsupported model: {m:?}"),
+		};
+
+		let req = match ModelFamily::from_id(id) {
+			Some(ModelFamily::Anthropic) => {
+				Request::Anthropic(anthropic::Request::from_llms(
+					req,
+					AnthropicModel::Custom(id.clone()),
+				))
+			}
+			Some(ModelFamily::Mistral) => {
+				Request::Mistral(mistral::Request::from_llms(
+					req,
+					MistralModel::Custom(id.clone()),
+				))
+			}
+			None => return Err(BedrockError::UnknownModel(id.clone()).into()),
+		};
+
+		self.request(&req).await.map_err(Into::into)
+	}
+}
+
+/// A request in the format of the model's provider, the model id is taken
+/// from it.
+#[derive(Debug)]
+pub enum Request {
+	/// The messages format of the Claude models, without `model` and
+	/// `stream`.
+	Anthropic(anthropic::Request),
+	/// The chat completion format of the newer Mistral models, e.g.
+	/// Mistral Large 2.
+	Mistral(mistral::Request),
+}
+
+/// Whose request format a model on Bedrock takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ModelFamily {
+	Anthropic,
+	Mistral,
+}
+
+impl ModelFamily {
+	/// From the provider in the model id, with or without the region prefix
+	/// of an inference profile, e.g.
+	/// `"us.anthropic.claude-sonnet-4-5-20250929-v1:0"` or
+	/// `"mistral.mistral-large-2407-v1:0"`.
+	///
+	/// Inference profile ARNs are recognized by the id they end in,
+	/// `None` for application inference profiles, which don't name the
+	/// model, and models of other providers.
+	pub fn from_id(id: &str) -> Option<Self> {
+		let name = id.rsplit('/').next().unwrap_or(id);
+		name.split('.').find_map(|part| match part {
+			"anthropic" => Some(Self::Anthropic),
+			"mistral" => Some(Self::Mistral),
+			_ => None,
+		})
+	}
+}
+
+/// The message of an exception, in the body or the stream.
+#[derive(Debug, Deserialize)]
+struct ExceptionBody {
+	#[serde(alias = "Message")]
+	message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkPayload {
+	/// The provider's own event, base64 encoded JSON.
+	bytes: String,
+}
+
+/// Sent with the last chunk of every model.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InvocationMetrics {
+	input_token_count: u32,
+	output_token_count: u32,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BedrockError {
+	#[error("Invalid LLM response: {0}")]
+	InvalidLlmResponse(String),
+	#[error("Response error: status {status}, body {body}")]
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
+	/// An exception reported in the stream, e.g. `throttlingException`.
+	#[error("API error: {error_type}: {message}")]
+	ApiError { error_type: String, message: String },
+	/// The request format of the model isn't known, see
+	/// [`ModelFamily::from_id`].
+	#[error("Unknown model family: {0}")]
+	UnknownModel(String),
+	#[error("Invalid url: {0}")]
+	InvalidUrl(String),
+	#[error("JSON error: {0}")]
+	Json(#[from] serde_json::Error),
+	#[error("Reqwest error: {0}")]
+	ReqwestError(#[from] reqwest::Error),
+	/// Reading a [`llms::MediaReader`] into the body failed.
+	#[error("IO error: {0}")]
+	Io(#[from] std::io::Error),
+	/// Nothing arrived within [`llms::Timeouts::read`].
+	#[error("No data received within {0:?}")]
+	ReadTimeout(Duration),
+}
+
+impl From<ErrorResponse> for BedrockError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
+}
+
+impl From<BedrockError> for LlmsError {
+	fn from(e: BedrockError) -> Self {
+		match e {
+			BedrockError::InvalidLlmResponse(msg) => LlmsError::Response {
+				status: StatusCode::OK,
+				body: msg,
+				provider: llms::ProviderKind::Bedrock,
+				request_id: None,
+			},
+			BedrockError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::Bedrock,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
+			BedrockError::ApiError {
+				error_type,
+				message,
+			} => LlmsError::api_event(
+				llms::ProviderKind::Bedrock,
+				&error_type,
+				message,
+				None,
+			),
+			e @ (BedrockError::UnknownModel(_)
+			| BedrockError::InvalidUrl(_)) => {
+				LlmsError::LlmNotConfigured(format!("Bedrock: {e}"))
+			}
+			BedrockError::Json(e) => LlmsError::Json(e),
+			BedrockError::ReqwestError(e) => LlmsError::Reqwest(e),
+			BedrockError::Io(e) => LlmsError::Io(e),
+			BedrockError::ReadTimeout(limit) => LlmsError::ReadTimeout(limit),
+		}
+	}
+}
+
+enum Format {
+	Anthropic(MessageAccumulator),
+	Mistral {
+		acc: ChunkAccumulator,
+		/// Tool calls seen so far, indexes the ones sent without an index.
+		tool_calls: usize,
+	},
+}
+
+pub struct ResponseStream {
+	inner: EventStream,
+	request_id: Option<String>,
+	/// Gets the decoded events, one per line.
+	tee: Option<TranscriptTee>,
+	format: Format,
+}
+
+impl std::fmt::Debug for ResponseStream {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ResponseStream")
+			.field("done", &self.is_done())
+			.finish()
+	}
+}
+
+impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.request_id.as_deref()
+	}
+
+	fn is_done(&self) -> bool {
+		match &self.format {
+			Format::Anthropic(acc) => acc.done,
+			Format::Mistral { acc, .. } => acc.done,
+		}
+	}
+
+	fn fail(&mut self, e: BedrockError) -> LlmsError {
+		match &mut self.format {
+			Format::Anthropic(acc) => acc.done = true,
+			Format::Mistral { acc, .. } => acc.done = true,
+		}
+		LlmsError::from(e).with_request_id(self.request_id.as_deref())
+	}
+
+	/// The provider's event in a `chunk` message.
+	fn payload(&mut self, message: Message) -> Result<Bytes, BedrockError> {
+		if let Some(kind @ ("exception" | "error")) =
+			message.header(":message-type")
+		{
+			let error_type = message
+				.header(":exception-type")
+				.or_else(|| message.header(":error-code"))
+				.unwrap_or(kind)
+				.to_string();
+			let message =
+				serde_json::from_slice::<ExceptionBody>(&message.payload)
+					.map(|body| body.message)
+					.unwrap_or_else(|_| {
+						String::from_utf8_lossy(&message.payload).into()
+					});
+			return Err(BedrockError::ApiError {
+				error_type,
+				message,
+			});
+		}
+
+		let chunk: ChunkPayload = serde_json::from_slice(&message.payload)?;
+		let bytes = BASE64_STANDARD.decode(chunk.bytes).map_err(|e| {
+			BedrockError::InvalidLlmResponse(format!("invalid chunk: {e}"))
+		})?;
+
+		if let Some(tee) = &mut self.tee {
+			let mut line = bytes.clone();
+			line.push(b'\n');
+			tee.on_chunk(&line.into());
+		}
+
+		Ok(bytes.into())
+	}
+}
+
+impl LlmResponseStream for ResponseStream {
+	async fn next(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		loop {
+			if let Format::Mistral { acc, .. } = &mut self.format
+				&& let Some(ev) = acc.next_pending()
+			{
+				return Some(ev);
+			}
+			if self.is_done() {
+				return None;
+			}
+
+			let message = match self.inner.next().await {
+				Some(Ok(message)) => message,
+				Some(Err(e)) => return Some(Err(self.fail(e))),
+				None => {
+					if let Format::Mistral { acc, .. } = &mut self.format {
+						return acc.finish();
+					}
+					return Some(Err(self.fail(
+						BedrockError::InvalidLlmResponse(
+							"stream ended before message_stop".into(),
+						),
+					)));
+				}
+			};
+
+			if message
+				.header(":event-type")
+				.is_some_and(|ty| ty != "chunk")
+			{
+				continue;
+			}
+
+			let payload = match self.payload(message) {
+				Ok(payload) => payload,
+				Err(e) => return Some(Err(self.fail(e))),
+			};
+
+			let request_id = self.request_id.as_deref();
+			match &mut self.format {
+				Format::Anthropic(acc) => {
+					let ev: anthropic::Event =
+						match serde_json::from_slice(&payload) {
+							Ok(ev) => ev,
+							Err(e) => return Some(Err(self.fail(e.into()))),
+						};
+					trace!("bedrock anthropic event: {ev:?}");
+
+					if let Some(ev) = acc.push(ev, request_id) {
+						return Some(ev);
+					}
+				}
+				Format::Mistral { acc, tool_calls } => {
+					let (chunk, metrics) =
+						match mistral_chunk(&payload, tool_calls) {
+							Ok(chunk) => chunk,
+							Err(e) => return Some(Err(self.fail(e))),
+						};
+					trace!("bedrock mistral chunk: {chunk:?}");
+
+					let usage = metrics.map(|metrics| llms::Usage {
+						input_tokens: metrics.input_token_count,
+						output_tokens: metrics.output_token_count,
+						..Default::default()
+					});
+					acc.push(chunk, request_id);
+					if acc.usage.is_none() {
+						acc.usage = usage;
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Parses a chunk of a Mistral model, which sends the delta as `message`,
+/// the finish reason as `stop_reason` and tool calls without an index.
+fn mistral_chunk(
+	payload: &[u8],
+	tool_calls: &mut usize,
+) -> Result<(mistral::Chunk, Option<InvocationMetrics>), BedrockError> {
+	let mut chunk: Value = serde_json::from_slice(payload)?;
+
+	let metrics = chunk
+		.get("amazon-bedrock-invocationMetrics")
+		.cloned()
+		.and_then(|metrics| serde_json::from_value(metrics).ok());
+
+	for choice in chunk
+		.get_mut("choices")
+		.and_then(Value::as_array_mut)
+		.into_iter()
+		.flatten()
+	{
+		let Some(choice) = choice.as_object_mut() else {
+			continue;
+		};
+		if let Some(message) = choice.remove("message") {
+			choice.entry("delta").or_insert(message);
+		}
+		if let Some(reason) = choice.remove("stop_reason") {
+			choice.entry("finish_reason").or_insert(reason);
+		}
+
+		let calls = choice
+			.get_mut("delta")
+			.and_then(|delta| delta.get_mut("tool_calls"))
+			.and_then(Value::as_array_mut);
+		for call in calls.into_iter().flatten() {
+			let Some(call) = call.as_object_mut() else {
+				continue;
+			};
+			if let Some(index) = call.get("index").and_then(Value::as_u64) {
+				*tool_calls = (*tool_calls).max(index as usize + 1);
+				continue;
+			}
+			// a new call comes with its id, the rest continues the last
+			if call.contains_key("id") {
+				*tool_calls += 1;
+			}
+			call.insert("index".into(), tool_calls.saturating_sub(1).into());
+		}
+	}
+
+	Ok((serde_json::from_value(chunk)?, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::stream;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn stream_ending_before_message_stop_fails() {
+		let ping = BASE64_STANDARD.encode(r#"{"type":"ping"}"#);
+		let message = event_stream::tests::encode(
+			&[(":event-type", "chunk"), (":message-type", "event")],
+			format!(r#"{{"bytes":"{ping}"}}"#).as_bytes(),
+		);
+		let body = stream::iter([Ok(message.into())]).boxed();
+		let mut stream = ResponseStream {
+			inner: EventStream::new(body, None),
+			request_id: None,
+			tee: None,
+			format: Format::Anthropic(MessageAccumulator::new(
+				llms::ProviderKind::Bedrock,
+			)),
+		};
+
+		assert!(matches!(
+			stream.next().await,
+			Some(Err(LlmsError::Response { body, .. }))
+				if body == "stream ended before message_stop"
+		));
+		assert!(stream.next().await.is_none());
+	}
+}
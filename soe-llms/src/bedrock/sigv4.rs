@@ -0,0 +1,264 @@
+use std::time::SystemTime;
+
+use hmac::{Hmac, Mac as _};
+use reqwest::Url;
+use sha2::{Digest as _, Sha256};
+
+use super::AwsCredentials;
+use crate::utils::time::rfc3339;
+
+/// Signs requests with [AWS Signature Version 4](https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html).
+pub(super) struct Signer<'a> {
+	pub credentials: &'a AwsCredentials,
+	pub region: &'a str,
+	pub service: &'a str,
+}
+
+impl Signer<'_> {
+	/// The headers to send along, `headers` are signed as well and have to
+	/// be sent unchanged. The host is taken from `url`.
+	pub fn sign(
+		&self,
+		method: &str,
+		url: &Url,
+		headers: &[(&str, &str)],
+		body: &[u8],
+		time: SystemTime,
+	) -> Vec<(&'static str, String)> {
+		// 2025-01-02T03:04:05.678Z to 20250102T030405Z
+		let amz_date =
+			format!("{}Z", rfc3339(time)[..19].replace(['-', ':'], ""));
+		let date = &amz_date[..8];
+
+		let mut signed = vec![("x-amz-date", amz_date.clone())];
+		if let Some(token) = &self.credentials.session_token {
+			signed.push(("x-amz-security-token", token.clone()));
+		}
+
+		let host = match url.port() {
+			Some(port) => format!("{}:{port}", url.host_str().unwrap_or("")),
+			None => url.host_str().unwrap_or("").into(),
+		};
+		let mut canonical: Vec<(String, String)> = signed
+			.iter()
+			.map(|(name, value)| (name.to_string(), value.clone()))
+			.chain([("host".into(), host)])
+			.chain(headers.iter().map(|(name, value)| {
+				(name.to_ascii_lowercase(), value.trim().into())
+			}))
+			.collect();
+		canonical.sort();
+
+		let signed_headers = canonical
+			.iter()
+			.map(|(name, _)| name.as_str())
+			.collect::<Vec<_>>()
+			.join(";");
+		let canonical_headers: String = canonical
+			.iter()
+			.map(|(name, value)| format!("{name}:{value}\n"))
+			.collect();
+
+		let canonical_request = format!(
+			"{method}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{}",
+			canonical_uri(url),
+			canonical_query(url),
+			hex(&Sha256::digest(body)),
+		);
+
+		let scope =
+			format!("{date}/{}/{}/aws4_request", self.region, self.service);
+		let string_to_sign = format!(
+			"AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+			hex(&Sha256::digest(canonical_request.as_bytes())),
+		);
+
+		let key = [self.region, self.service, "aws4_request"].iter().fold(
+			hmac(
+				format!("AWS4{}", self.credentials.secret_access_key)
+					.as_bytes(),
+				date.as_bytes(),
+			),
+			|key, part| hmac(&key, part.as_bytes()),
+		);
+		let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+		signed.push((
+			"authorization",
+			format!(
+				"AWS4-HMAC-SHA256 Credential={}/{scope}, \
+				 SignedHeaders={signed_headers}, Signature={signature}",
+				self.credentials.access_key_id
+			),
+		));
+		signed
+	}
+}
+
+/// Encodes every segment of the already encoded path once more, every
+/// service but S3 signs the path double encoded.
+fn canonical_uri(url: &Url) -> String {
+	match url.path() {
+		"" => "/".into(),
+		path => path
+			.split('/')
+			.map(uri_encode)
+			.collect::<Vec<_>>()
+			.join("/"),
+	}
+}
+
+fn canonical_query(url: &Url) -> String {
+	let mut pairs: Vec<_> = url
+		.query_pairs()
+		.map(|(key, value)| (uri_encode(&key), uri_encode(&value)))
+		.collect();
+	pairs.sort();
+
+	pairs
+		.iter()
+		.map(|(key, value)| format!("{key}={value}"))
+		.collect::<Vec<_>>()
+		.join("&")
+}
+
+/// Percent encodes everything but the unreserved characters.
+pub(super) fn uri_encode(s: &str) -> String {
+	s.bytes()
+		.map(|b| match b {
+			b'A'..=b'Z'
+			| b'a'..=b'z'
+			| b'0'..=b'9'
+			| b'-'
+			| b'.'
+			| b'_'
+			| b'~' => (b as char).to_string(),
+			b => format!("%{b:02X}"),
+		})
+		.collect()
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+	let mut mac =
+		Hmac::<Sha256>::new_from_slice(key).expect("hmac takes any key size");
+	mac.update(data);
+	mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, UNIX_EPOCH};
+
+	use super::*;
+
+	/// With the credentials, scope and time of the AWS Signature Version 4
+	/// test suite.
+	fn sign(
+		credentials: &AwsCredentials,
+		method: &str,
+		url: &str,
+	) -> Vec<(&'static str, String)> {
+		let signer = Signer {
+			credentials,
+			region: "us-east-1",
+			service: "service",
+		};
+		// 2015-08-30T12:36:00Z
+		let time = UNIX_EPOCH + Duration::from_secs(1_440_938_160);
+		signer.sign(method, &url.parse().unwrap(), &[], &[], time)
+	}
+
+	fn credentials() -> AwsCredentials {
+		AwsCredentials::new(
+			"AKIDEXAMPLE",
+			"wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+		)
+	}
+
+	fn header<'a>(
+		headers: &'a [(&'static str, String)],
+		name: &str,
+	) -> Option<&'a str> {
+		headers
+			.iter()
+			.find(|(n, _)| *n == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	fn authorization(signed_headers: &str, signature: &str) -> String {
+		format!(
+			"AWS4-HMAC-SHA256 \
+			 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+			 SignedHeaders={signed_headers}, Signature={signature}"
+		)
+	}
+
+	#[test]
+	fn get_vanilla() {
+		let headers =
+			sign(&credentials(), "GET", "https://example.amazonaws.com/");
+
+		assert_eq!(header(&headers, "x-amz-date"), Some("20150830T123600Z"));
+		assert_eq!(
+			header(&headers, "authorization").unwrap(),
+			authorization(
+				"host;x-amz-date",
+				"5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+			)
+		);
+	}
+
+	#[test]
+	fn post_vanilla() {
+		let headers =
+			sign(&credentials(), "POST", "https://example.amazonaws.com/");
+
+		assert_eq!(
+			header(&headers, "authorization").unwrap(),
+			authorization(
+				"host;x-amz-date",
+				"5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+			)
+		);
+	}
+
+	#[test]
+	fn session_token_is_signed() {
+		let token = "AQoDYXdzEPT//////////wEXAMPLEtc764bNrC9SAPBSM22wDOk4x4HIZ8j4FZTwdQWLWsKWHGBuFqwAeMicRXmxfpSPfIeoIYRqTflfKD8YUuwthAx7mSEI/qkPpKPi/kMcGdQrmGdeehM4IC1NtBmUpp2wUE8phUZampKsburEDy0KPkyQDYwT7WZ0wq5VSXDvp75YU9HFvlRd8Tx6q6fE8YQcHNVXAkiY9q6d+xo0rKwT38xVqr7ZD0u0iPPkUL64lIZbqBAz+scqKmlzm8FDrypNC9Yjc8fPOLn9FX9KSYvKTr4rvx3iSIlTJabIQwj2ICCR/oLxBA==";
+		let credentials = AwsCredentials {
+			session_token: Some(token.into()),
+			..credentials()
+		};
+		let headers =
+			sign(&credentials, "POST", "https://example.amazonaws.com/");
+
+		assert_eq!(header(&headers, "x-amz-security-token"), Some(token));
+		assert_eq!(
+			header(&headers, "authorization").unwrap(),
+			authorization(
+				"host;x-amz-date;x-amz-security-token",
+				"85d96828115b5dc0cfc3bd16ad9e210dd772bbebba041836c64533a82be05ead"
+			)
+		);
+	}
+
+	#[test]
+	fn model_ids_are_encoded_twice() {
+		// how the provider builds the path of a model id
+		let path =
+			format!("/model/{}/invoke", uri_encode("anthropic.claude-v1:0"));
+		let url: Url = format!("https://example.amazonaws.com{path}")
+			.parse()
+			.unwrap();
+
+		assert_eq!(url.path(), "/model/anthropic.claude-v1%3A0/invoke");
+		assert_eq!(
+			canonical_uri(&url),
+			"/model/anthropic.claude-v1%253A0/invoke"
+		);
+	}
+}
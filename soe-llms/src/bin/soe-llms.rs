@@ -0,0 +1,294 @@
+//! Small cli to smoke test providers and reproduce bug reports.
+//!
+//! ```text
+//! soe-llms [OPTIONS] [PROMPT]...
+//! ```
+//!
+//! Keys are read from the environment, see [`LlmsConfig::from_env`], or a
+//! config file with `--config`. Without a prompt it is read from stdin.
+
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::{env, fmt};
+
+use serde_json::json;
+use soe_llms::{
+	Conversation, Input, Llms, LlmsConfig, Model, ReasoningEffort, Request,
+	ResponseEvent, Role, Tool, Transcript,
+};
+
+const USAGE: &str = "\
+Usage: soe-llms [OPTIONS] [PROMPT]...
+
+Options:
+  -m, --model <MODEL>          Model to use, e.g. Gpt5_6Luna,
+                               openrouter/<id>, openai-compat/<id> or
+                               bedrock/<id>
+                               [default: Gpt5_6Luna]
+  -i, --instructions <TEXT>    System instructions
+  -r, --reasoning <EFFORT>     low, medium or high
+  -c, --config <PATH>          Config file, the environment still
+                               overrides it
+      --transcript <PATH>      Append the raw SSE stream to the file
+      --snapshot <ID>          Dated model id to send instead
+      --tool-echo              Offer an echo tool and answer its calls
+      --list-models            Print the known models and exit
+  -h, --help                   Print this help";
+
+/// Rounds of tool calls answered in `--tool-echo` mode.
+const MAX_TOOL_ROUNDS: usize = 8;
+
+#[derive(Debug)]
+struct Args {
+	model: Model,
+	instructions: String,
+	reasoning: Option<ReasoningEffort>,
+	config: Option<String>,
+	transcript: Option<String>,
+	snapshot: Option<String>,
+	tool_echo: bool,
+	prompt: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl<E: fmt::Display> From<E> for Error {
+	fn from(e: E) -> Self {
+		Self(e.to_string())
+	}
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+	tracing_subscriber::fmt()
+		.with_env_filter(env::var("RUST_LOG").unwrap_or_else(|_| "warn".into()))
+		.with_writer(io::stderr)
+		.init();
+
+	let args = match parse_args(env::args().skip(1)) {
+		Ok(Some(args)) => args,
+		Ok(None) => return ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("{}\n\n{USAGE}", e.0);
+			return ExitCode::from(2);
+		}
+	};
+
+	match run(args).await {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			eprintln!("error: {}", e.0);
+			ExitCode::FAILURE
+		}
+	}
+}
+
+/// Returns `None` if the program should exit without a request.
+fn parse_args(
+	mut raw: impl Iterator<Item = String>,
+) -> Result<Option<Args>, Error> {
+	let mut args = Args {
+		model: Model::Gpt5_6Luna,
+		instructions: String::new(),
+		reasoning: None,
+		config: None,
+		transcript: None,
+		snapshot: None,
+		tool_echo: false,
+		prompt: Vec::new(),
+	};
+
+	while let Some(arg) = raw.next() {
+		let mut value = |name: &str| {
+			raw.next()
+				.ok_or_else(|| Error(format!("{name} requires a value")))
+		};
+
+		match arg.as_str() {
+			"-m" | "--model" => args.model = parse_model(&value(&arg)?)?,
+			"-i" | "--instructions" => args.instructions = value(&arg)?,
+			"-r" | "--reasoning" => {
+				args.reasoning = Some(match value(&arg)?.as_str() {
+					"low" => ReasoningEffort::Low,
+					"medium" => ReasoningEffort::Medium,
+					"high" => ReasoningEffort::High,
+					other => {
+						return Err(Error(format!(
+							"unknown reasoning effort {other:?}"
+						)));
+					}
+				})
+			}
+			"-c" | "--config" => args.config = Some(value(&arg)?),
+			"--transcript" => args.transcript = Some(value(&arg)?),
+			"--snapshot" => args.snapshot = Some(value(&arg)?),
+			"--tool-echo" => args.tool_echo = true,
+			"--list-models" => {
+				for model in Model::ALL {
+					if !matches!(model, Model::OpenRouter(_)) {
+						println!("{model:?}");
+					}
+				}
+				println!("openrouter/<id>");
+				println!("openai-compat/<id>");
+				println!("bedrock/<id>");
+				return Ok(None);
+			}
+			"-h" | "--help" => {
+				println!("{USAGE}");
+				return Ok(None);
+			}
+			"--" => args.prompt.extend(raw.by_ref()),
+			flag if flag.starts_with('-') && flag.len() > 1 => {
+				return Err(Error(format!("unknown option {flag}")));
+			}
+			_ => args.prompt.push(arg),
+		}
+	}
+
+	Ok(Some(args))
+}
+
+fn parse_model(name: &str) -> Result<Model, Error> {
+	if let Some(id) = name.strip_prefix("openrouter/") {
+		return Ok(Model::OpenRouter(Some(id.into())));
+	}
+	if let Some(id) = name.strip_prefix("openai-compat/") {
+		return Ok(Model::OpenAiCompat(id.into()));
+	}
+	if let Some(id) = name.strip_prefix("bedrock/") {
+		return Ok(Model::Bedrock(id.into()));
+	}
+
+	Model::ALL
+		.iter()
+		.find(|model| format!("{model:?}").eq_ignore_ascii_case(name))
+		.cloned()
+		.ok_or_else(|| {
+			Error(format!(
+				"unknown model {name:?}, see --list-models for all of them"
+			))
+		})
+}
+
+async fn run(args: Args) -> Result<(), Error> {
+	let config = match &args.config {
+		Some(path) => LlmsConfig::from_path(path)?.with_env()?,
+		None => LlmsConfig::from_env()?,
+	};
+	let llms = Llms::try_new(config)?;
+
+	let prompt = if args.prompt.is_empty() {
+		let mut prompt = String::new();
+		io::stdin().read_to_string(&mut prompt)?;
+		prompt
+	} else {
+		args.prompt.join(" ")
+	};
+
+	let mut req = Request {
+		input: vec![Input::Text {
+			role: Role::User,
+			content: prompt,
+		}],
+		instructions: args.instructions,
+		model: args.model,
+		user_id: "soe-llms-cli".into(),
+		tools: vec![],
+		tool_choice: Default::default(),
+		reasoning_effort: args.reasoning,
+		response_format: Default::default(),
+		params: Default::default(),
+		cache: false,
+		extra: Default::default(),
+		headers: Default::default(),
+		timeouts: Default::default(),
+		transcript: args.transcript.map(Transcript::file).transpose()?,
+		snapshot: args.snapshot,
+	};
+
+	if args.tool_echo {
+		req.tools.push(Tool {
+			name: "echo".into(),
+			description: "Returns the given text unchanged.".into(),
+			parameters: Some(json!({
+				"type": "object",
+				"properties": {
+					"text": { "type": "string" },
+				},
+				"required": ["text"],
+			})),
+			strict: true,
+			cache: false,
+		});
+	}
+
+	let mut conversation = Conversation::new(req);
+
+	for _ in 0..=MAX_TOOL_ROUNDS {
+		let mut stream = llms.request(conversation.request()).await?;
+
+		let mut stdout = io::stdout();
+		while let Some(event) = stream.next().await {
+			if let ResponseEvent::TextDelta { content } = event? {
+				write!(stdout, "{content}")?;
+				stdout.flush()?;
+			}
+		}
+		writeln!(stdout)?;
+
+		let stats = stream.stats();
+		let resp = stream.into_response().ok_or("missing response")?;
+
+		if let Some(reasoning) = &resp.reasoning {
+			eprintln!("reasoning: {reasoning}");
+		}
+
+		let cost = match resp.cost_estimate() {
+			Some(cost) => format!("${:.6}", cost.total()),
+			None => "unknown".into(),
+		};
+		let usage = resp.usage;
+		eprintln!(
+			"usage: {} input ({} cache read, {} cache write), {} output \
+			({} reasoning), {} total; cost {cost}",
+			usage.input_tokens,
+			usage.cache_read_tokens,
+			usage.cache_write_tokens,
+			usage.output_tokens,
+			usage.reasoning_tokens,
+			usage.total_tokens(),
+		);
+		if let Some(stats) = stats {
+			eprintln!(
+				"time to first token {:?}, {:?} total, {:.1} tokens/s",
+				stats.time_to_first_token,
+				stats.duration,
+				stats.output_tokens_per_sec
+			);
+		}
+
+		conversation.push_response(&resp);
+
+		let calls = conversation.pending_tool_calls().to_vec();
+		if calls.is_empty() {
+			return Ok(());
+		}
+
+		for call in calls {
+			eprintln!("tool call {}: {}", call.name, call.input);
+			let output = call
+				.input
+				.get("text")
+				.and_then(|text| text.as_str())
+				.map(String::from)
+				.unwrap_or_else(|| call.input.to_string());
+			conversation.push_tool_output(&call.id, output);
+		}
+	}
+
+	Err(Error(format!(
+		"still calling tools after {MAX_TOOL_ROUNDS} rounds"
+	)))
+}
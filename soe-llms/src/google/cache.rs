@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use super::{
+	ApiContent, ApiSystemInstruction, ApiTool, GeminiModel, Google, GoogleError,
+};
+use crate::utils::{compress::json_body, error_response};
+
+/// Content to cache with [`Google::create_cached_content`].
+///
+/// Gemini only accepts caches above a model specific minimum size (a few
+/// thousand tokens), cached tokens are billed at a reduced rate when
+/// referenced through [`Request::cached_content`](super::Request).
+#[derive(Debug)]
+pub struct CachedContentRequest {
+	/// The cache can only be used with the model it was created for.
+	pub model: GeminiModel,
+	pub contents: Vec<ApiContent>,
+	pub system_instruction: Option<String>,
+	pub tools: Vec<ApiTool>,
+	/// How long the cache lives. `None` uses Gemini's default of one hour.
+	pub ttl: Option<Duration>,
+	pub display_name: Option<String>,
+}
+
+/// Metadata of a created cache.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+	/// Resource name, e.g. `"cachedContents/abc-123"`. Pass this as
+	/// [`Request::cached_content`](super::Request).
+	pub name: String,
+	pub model: String,
+	#[serde(default)]
+	pub display_name: Option<String>,
+	/// RFC 3339 timestamp after which the cache is deleted.
+	pub expire_time: String,
+	#[serde(default)]
+	pub usage_metadata: Option<CachedContentUsage>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsage {
+	/// Number of tokens stored in the cache.
+	#[serde(default)]
+	pub total_token_count: u32,
+}
+
+fn serialize_ttl<S: Serializer>(
+	ttl: &Option<Duration>,
+	s: S,
+) -> Result<S::Ok, S::Error> {
+	match ttl {
+		Some(ttl) => s.serialize_str(&format!("{}s", ttl.as_secs())),
+		None => s.serialize_none(),
+	}
+}
+
+impl Google {
+	/// Creates a cache from a system instruction, tools and contents, for
+	/// example a large document that gets referenced in many requests.
+	pub async fn create_cached_content(
+		&self,
+		req: &CachedContentRequest,
+	) -> Result<CachedContent, GoogleError> {
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct ApiReq<'a> {
+			model: String,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			contents: &'a Vec<ApiContent>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			system_instruction: Option<ApiSystemInstruction>,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			tools: &'a Vec<ApiTool>,
+			#[serde(
+				skip_serializing_if = "Option::is_none",
+				serialize_with = "serialize_ttl"
+			)]
+			ttl: Option<Duration>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			display_name: Option<&'a str>,
+		}
+
+		self.gemini_only("Cached contents")?;
+
+		let api_req = ApiReq {
+			model: format!("models/{}", req.model.as_str()),
+			contents: &req.contents,
+			system_instruction: req
+				.system_instruction
+				.as_deref()
+				.map(ApiSystemInstruction::text),
+			tools: &req.tools,
+			ttl: req.ttl,
+			display_name: req.display_name.as_deref(),
+		};
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.post(self.url("cachedContents")), &api_key);
+		let resp = json_body(resp, &api_req, self.compression).send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json().await?)
+	}
+
+	/// Extends or shortens the lifetime of a cache, counted from now.
+	pub async fn update_cached_content_ttl(
+		&self,
+		name: &str,
+		ttl: Duration,
+	) -> Result<CachedContent, GoogleError> {
+		#[derive(Debug, Serialize)]
+		struct ApiReq {
+			#[serde(serialize_with = "serialize_ttl")]
+			ttl: Option<Duration>,
+		}
+
+		self.gemini_only("Cached contents")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(
+				self.client
+					.patch(self.url(&format!("{name}?updateMask=ttl"))),
+				&api_key,
+			)
+			.json(&ApiReq { ttl: Some(ttl) })
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json().await?)
+	}
+
+	/// Deletes a cache before it expires.
+	pub async fn delete_cached_content(
+		&self,
+		name: &str,
+	) -> Result<(), GoogleError> {
+		self.gemini_only("Cached contents")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.delete(self.url(name)), &api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+}
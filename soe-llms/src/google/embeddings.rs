@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use super::{ApiPart, Google, GoogleError};
+use crate::llms::{self, EmbeddingProvider, LlmsError};
+use crate::utils::error_response;
+
+/// Most requests `batchEmbedContents` accepts per call.
+const MAX_BATCH: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct EmbedRequest {
+	pub model: EmbeddingModel,
+	pub input: Vec<String>,
+	pub task_type: Option<TaskType>,
+	/// `outputDimensionality`. `None` returns the full embedding.
+	pub output_dimensionality: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EmbeddingModel {
+	GeminiEmbedding001,
+	TextEmbedding004,
+}
+
+impl EmbeddingModel {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			EmbeddingModel::GeminiEmbedding001 => "gemini-embedding-001",
+			EmbeddingModel::TextEmbedding004 => "text-embedding-004",
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TaskType {
+	RetrievalQuery,
+	RetrievalDocument,
+	SemanticSimilarity,
+	Classification,
+	Clustering,
+	QuestionAnswering,
+	FactVerification,
+	CodeRetrievalQuery,
+}
+
+impl From<llms::EmbeddingTaskType> for TaskType {
+	fn from(task_type: llms::EmbeddingTaskType) -> Self {
+		use llms::EmbeddingTaskType as T;
+
+		match task_type {
+			T::RetrievalQuery => TaskType::RetrievalQuery,
+			T::RetrievalDocument => TaskType::RetrievalDocument,
+			T::SemanticSimilarity => TaskType::SemanticSimilarity,
+			T::Classification => TaskType::Classification,
+			T::Clustering => TaskType::Clustering,
+			T::QuestionAnswering => TaskType::QuestionAnswering,
+			T::FactVerification => TaskType::FactVerification,
+			T::CodeRetrievalQuery => TaskType::CodeRetrievalQuery,
+		}
+	}
+}
+
+impl Google {
+	/// Embeds the texts with `batchEmbedContents`, one call per 100 texts,
+	/// the most it accepts.
+	pub async fn embed(
+		&self,
+		req: &EmbedRequest,
+	) -> Result<Vec<Vec<f32>>, GoogleError> {
+		self.gemini_only("Embeddings")?;
+
+		let mut embeddings = Vec::with_capacity(req.input.len());
+		for batch in req.input.chunks(MAX_BATCH) {
+			embeddings.extend(self.embed_batch(req, batch).await?);
+		}
+		Ok(embeddings)
+	}
+
+	async fn embed_batch(
+		&self,
+		req: &EmbedRequest,
+		input: &[String],
+	) -> Result<Vec<Vec<f32>>, GoogleError> {
+		#[derive(Debug, Serialize)]
+		struct ApiReq<'a> {
+			requests: Vec<ApiEmbedReq<'a>>,
+		}
+
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct ApiEmbedReq<'a> {
+			model: &'a str,
+			content: ApiEmbedContent,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			task_type: Option<TaskType>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			output_dimensionality: Option<u32>,
+		}
+
+		#[derive(Debug, Serialize)]
+		struct ApiEmbedContent {
+			parts: Vec<ApiPart>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct ApiResp {
+			#[serde(default)]
+			embeddings: Vec<ApiEmbedding>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct ApiEmbedding {
+			values: Vec<f32>,
+		}
+
+		let model = format!("models/{}", req.model.as_str());
+
+		let api_req = ApiReq {
+			requests: input
+				.iter()
+				.map(|text| ApiEmbedReq {
+					model: &model,
+					content: ApiEmbedContent {
+						parts: vec![ApiPart::Text { text: text.clone() }],
+					},
+					task_type: req.task_type,
+					output_dimensionality: req.output_dimensionality,
+				})
+				.collect(),
+		};
+
+		let url = self
+			.url(&format!("models/{}:batchEmbedContents", req.model.as_str()));
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.post(url), &api_key)
+			.json(&api_req)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let resp: ApiResp = resp.json().await?;
+
+		if resp.embeddings.len() != input.len() {
+			return Err(GoogleError::InvalidLlmResponse(format!(
+				"expected {} embeddings, got {}",
+				input.len(),
+				resp.embeddings.len()
+			)));
+		}
+
+		Ok(resp.embeddings.into_iter().map(|e| e.values).collect())
+	}
+}
+
+impl EmbeddingProvider for Google {
+	async fn embed(
+		&self,
+		req: &llms::EmbeddingRequest,
+	) -> Result<llms::EmbeddingResponse, LlmsError> {
+		let model = match req.model {
+			llms::EmbeddingModel::GeminiEmbedding001 => {
+				EmbeddingModel::GeminiEmbedding001
+			}
+			llms::EmbeddingModel::TextEmbedding004 => {
+				EmbeddingModel::TextEmbedding004
+			}
+		};
+
+		let embeddings = self
+			.embed(&EmbedRequest {
+				model,
+				input: req.input.clone(),
+				task_type: req.task_type.map(Into::into),
+				output_dimensionality: req.dimensions,
+			})
+			.await?;
+
+		Ok(llms::EmbeddingResponse { embeddings })
+	}
+}
@@ -0,0 +1,271 @@
+use bytes::Bytes;
+use futures::Stream;
+use reqwest::{Body, header::CONTENT_LENGTH};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt as _};
+use tokio_util::io::ReaderStream;
+
+use super::{ApiPart, Google, GoogleError};
+use crate::utils::error_response;
+use crate::utils::paginate::{Page, paginate};
+
+/// Raw payload size above which [`Google::media_part`] uploads through the
+/// File API instead of inlining.
+///
+/// Gemini caps the whole request at 20 MB and base64 inflates the payload
+/// by a third, this leaves some room for the rest of the request.
+pub const INLINE_DATA_LIMIT: usize = 14 * 1024 * 1024;
+
+/// A file uploaded through the Gemini File API.
+///
+/// Uploaded files are kept for 48 hours.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct File {
+	/// Resource name, e.g. `"files/abc-123"`.
+	pub name: String,
+	/// Uri to reference the file in an [`ApiPart::FileData`] part.
+	pub uri: String,
+	pub mime_type: String,
+	#[serde(default)]
+	pub display_name: Option<String>,
+	#[serde(default)]
+	pub state: FileState,
+}
+
+/// Processing state of an uploaded file. Videos need to be processed before
+/// they can be used in a request, poll [`Google::get_file`] until the state
+/// is [`FileState::Active`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FileState {
+	#[default]
+	StateUnspecified,
+	Processing,
+	Active,
+	Failed,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileResponse {
+	file: File,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FileList {
+	#[serde(default)]
+	files: Vec<File>,
+	next_page_token: Option<String>,
+}
+
+impl Google {
+	/// Uploads `data` through the File API using the resumable upload
+	/// protocol.
+	pub async fn upload_file(
+		&self,
+		data: Bytes,
+		mime_type: &str,
+		display_name: Option<&str>,
+	) -> Result<File, GoogleError> {
+		let len = data.len() as u64;
+		self.upload_body(data.into(), len, mime_type, display_name)
+			.await
+	}
+
+	/// Uploads `len` bytes read from `reader` through the File API. The data
+	/// is streamed while uploading, so the size doesn't affect memory usage.
+	pub async fn upload_file_reader(
+		&self,
+		reader: impl AsyncRead + Send + 'static,
+		len: u64,
+		mime_type: &str,
+		display_name: Option<&str>,
+	) -> Result<File, GoogleError> {
+		let body = Body::wrap_stream(ReaderStream::new(reader.take(len)));
+		self.upload_body(body, len, mime_type, display_name).await
+	}
+
+	async fn upload_body(
+		&self,
+		body: Body,
+		len: u64,
+		mime_type: &str,
+		display_name: Option<&str>,
+	) -> Result<File, GoogleError> {
+		#[derive(Debug, Serialize)]
+		struct StartReq<'a> {
+			file: StartFile<'a>,
+		}
+
+		#[derive(Debug, Serialize)]
+		struct StartFile<'a> {
+			#[serde(skip_serializing_if = "Option::is_none")]
+			display_name: Option<&'a str>,
+		}
+
+		self.gemini_only("Files")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.post(self.upload_url()), &api_key)
+			.header("X-Goog-Upload-Protocol", "resumable")
+			.header("X-Goog-Upload-Command", "start")
+			.header("X-Goog-Upload-Header-Content-Length", len)
+			.header("X-Goog-Upload-Header-Content-Type", mime_type)
+			.json(&StartReq {
+				file: StartFile { display_name },
+			})
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let upload_url = resp
+			.headers()
+			.get("x-goog-upload-url")
+			.and_then(|v| v.to_str().ok())
+			.ok_or_else(|| {
+				GoogleError::InvalidLlmResponse(
+					"missing x-goog-upload-url header".into(),
+				)
+			})?
+			.to_string();
+
+		let resp = self
+			.client
+			.post(&upload_url)
+			.header(CONTENT_LENGTH, len)
+			.header("X-Goog-Upload-Offset", 0)
+			.header("X-Goog-Upload-Command", "upload, finalize")
+			.body(body)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json::<FileResponse>().await?.file)
+	}
+
+	/// Fetches the metadata of an uploaded file by its resource name
+	/// (`"files/…"`).
+	pub async fn get_file(&self, name: &str) -> Result<File, GoogleError> {
+		self.gemini_only("Files")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.get(self.url(name)), &api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json().await?)
+	}
+
+	/// Lists all files that haven't expired yet, the next page is fetched
+	/// while the stream is polled.
+	pub fn list_files(
+		&self,
+	) -> impl Stream<Item = Result<File, GoogleError>> + '_ {
+		paginate(move |token| self.files_page(token))
+	}
+
+	async fn files_page(
+		&self,
+		token: Option<String>,
+	) -> Result<Page<File>, GoogleError> {
+		self.gemini_only("Files")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let mut req = self
+			.authorize(self.client.get(self.url("files")), &api_key)
+			.build()?;
+		req.url_mut()
+			.query_pairs_mut()
+			.append_pair("pageSize", "100");
+		if let Some(token) = &token {
+			req.url_mut()
+				.query_pairs_mut()
+				.append_pair("pageToken", token);
+		}
+
+		let resp = self.client.execute(req).await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let list: FileList = resp.json().await?;
+		Ok(Page {
+			items: list.files,
+			next: list.next_page_token,
+		})
+	}
+
+	/// Deletes an uploaded file before it expires.
+	pub async fn delete_file(&self, name: &str) -> Result<(), GoogleError> {
+		self.gemini_only("Files")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.delete(self.url(name)), &api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+
+	/// Creates a part for the given media, inlining it when it is smaller
+	/// than [`INLINE_DATA_LIMIT`] and uploading it through the File API
+	/// otherwise.
+	pub async fn media_part(
+		&self,
+		data: Bytes,
+		mime_type: &str,
+	) -> Result<ApiPart, GoogleError> {
+		if data.len() <= INLINE_DATA_LIMIT {
+			return Ok(ApiPart::inline_data(mime_type, &data));
+		}
+
+		let file = self.upload_file(data, mime_type, None).await?;
+		Ok(ApiPart::file_data(file.mime_type, file.uri))
+	}
+
+	/// Like [`Google::media_part`] but reads `len` bytes from `reader`.
+	///
+	/// Media within [`INLINE_DATA_LIMIT`] is read into memory and inlined,
+	/// larger media is streamed to the File API. Inline data has to be
+	/// base64 encoded into the json body, so it can't be streamed.
+	pub async fn media_part_reader(
+		&self,
+		reader: impl AsyncRead + Send + Unpin + 'static,
+		len: u64,
+		mime_type: &str,
+	) -> Result<ApiPart, GoogleError> {
+		if len > INLINE_DATA_LIMIT as u64 {
+			let file = self
+				.upload_file_reader(reader, len, mime_type, None)
+				.await?;
+			return Ok(ApiPart::file_data(file.mime_type, file.uri));
+		}
+
+		let mut data = Vec::with_capacity(len as usize);
+		reader.take(len).read_to_end(&mut data).await?;
+		Ok(ApiPart::inline_data(mime_type, &data))
+	}
+}
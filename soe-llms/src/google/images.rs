@@ -0,0 +1,283 @@
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+
+use super::{ApiBlob, Google, GoogleError};
+use crate::llms::{self, ImageProvider, LlmsError};
+use crate::utils::error_response;
+
+#[derive(Debug, Clone)]
+pub struct ImageRequest {
+	pub model: ImageModel,
+	pub prompt: String,
+	/// Number of images. Imagen generates them in a single request, Gemini
+	/// image models are called once per image.
+	pub count: u32,
+	/// e.g. `"16:9"`. `None` uses the model's default.
+	pub aspect_ratio: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ImageModel {
+	Imagen4,
+	Imagen4Ultra,
+	Imagen4Fast,
+	GeminiFlashImage,
+}
+
+impl ImageModel {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ImageModel::Imagen4 => "imagen-4.0-generate-001",
+			ImageModel::Imagen4Ultra => "imagen-4.0-ultra-generate-001",
+			ImageModel::Imagen4Fast => "imagen-4.0-fast-generate-001",
+			ImageModel::GeminiFlashImage => "gemini-2.5-flash-image",
+		}
+	}
+}
+
+impl Google {
+	/// Generates images with Imagen (`:predict`) or a Gemini image model
+	/// (`:generateContent` with an image response modality).
+	pub async fn generate_images(
+		&self,
+		req: &ImageRequest,
+	) -> Result<Vec<llms::GeneratedImage>, GoogleError> {
+		match req.model {
+			ImageModel::GeminiFlashImage => {
+				let images = try_join_all(
+					(0..req.count.max(1)).map(|_| self.gemini_image(req)),
+				)
+				.await?;
+				Ok(images.into_iter().flatten().collect())
+			}
+			_ => self.imagen(req).await,
+		}
+	}
+
+	async fn imagen(
+		&self,
+		req: &ImageRequest,
+	) -> Result<Vec<llms::GeneratedImage>, GoogleError> {
+		#[derive(Debug, Serialize)]
+		struct ApiReq<'a> {
+			instances: [Instance<'a>; 1],
+			parameters: Parameters<'a>,
+		}
+
+		#[derive(Debug, Serialize)]
+		struct Instance<'a> {
+			prompt: &'a str,
+		}
+
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Parameters<'a> {
+			sample_count: u32,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			aspect_ratio: Option<&'a str>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct ApiResp {
+			#[serde(default)]
+			predictions: Vec<Prediction>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Prediction {
+			bytes_base64_encoded: Option<String>,
+			mime_type: Option<String>,
+			/// Set instead of the image when it was filtered.
+			rai_filtered_reason: Option<String>,
+		}
+
+		let api_req = ApiReq {
+			instances: [Instance {
+				prompt: &req.prompt,
+			}],
+			parameters: Parameters {
+				sample_count: req.count.max(1),
+				aspect_ratio: req.aspect_ratio.as_deref(),
+			},
+		};
+
+		let url = self.url(&format!("models/{}:predict", req.model.as_str()));
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.post(url), &api_key)
+			.json(&api_req)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let resp: ApiResp = resp.json().await?;
+
+		let mut images = Vec::with_capacity(resp.predictions.len());
+		let mut filtered = None;
+		for prediction in resp.predictions {
+			match prediction.bytes_base64_encoded {
+				Some(data) => images.push(decode_image(
+					&data,
+					prediction.mime_type.as_deref().unwrap_or("image/png"),
+				)?),
+				None => filtered = prediction.rai_filtered_reason,
+			}
+		}
+
+		match (images.is_empty(), filtered) {
+			(true, Some(reason)) => Err(GoogleError::OutputBlocked(reason)),
+			(true, None) => Err(GoogleError::NoOutput),
+			_ => Ok(images),
+		}
+	}
+
+	async fn gemini_image(
+		&self,
+		req: &ImageRequest,
+	) -> Result<Vec<llms::GeneratedImage>, GoogleError> {
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct ApiReq<'a> {
+			contents: [Content<'a>; 1],
+			generation_config: GenerationConfig<'a>,
+		}
+
+		#[derive(Debug, Serialize)]
+		struct Content<'a> {
+			role: &'static str,
+			parts: [TextPart<'a>; 1],
+		}
+
+		#[derive(Debug, Serialize)]
+		struct TextPart<'a> {
+			text: &'a str,
+		}
+
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct GenerationConfig<'a> {
+			response_modalities: [&'static str; 1],
+			#[serde(skip_serializing_if = "Option::is_none")]
+			image_config: Option<ImageConfig<'a>>,
+		}
+
+		#[derive(Debug, Serialize)]
+		#[serde(rename_all = "camelCase")]
+		struct ImageConfig<'a> {
+			aspect_ratio: &'a str,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct ApiResp {
+			#[serde(default)]
+			candidates: Vec<Candidate>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct Candidate {
+			content: Option<CandidateContent>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		struct CandidateContent {
+			#[serde(default)]
+			parts: Vec<Part>,
+		}
+
+		#[derive(Debug, Deserialize)]
+		#[serde(rename_all = "camelCase")]
+		struct Part {
+			inline_data: Option<ApiBlob>,
+		}
+
+		let api_req = ApiReq {
+			contents: [Content {
+				role: "user",
+				parts: [TextPart { text: &req.prompt }],
+			}],
+			generation_config: GenerationConfig {
+				response_modalities: ["IMAGE"],
+				image_config: req
+					.aspect_ratio
+					.as_deref()
+					.map(|aspect_ratio| ImageConfig { aspect_ratio }),
+			},
+		};
+
+		let url =
+			self.url(&format!("models/{}:generateContent", req.model.as_str()));
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.authorize(self.client.post(url), &api_key)
+			.json(&api_req)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let resp: ApiResp = resp.json().await?;
+
+		let images = resp
+			.candidates
+			.into_iter()
+			.filter_map(|c| c.content)
+			.flat_map(|c| c.parts)
+			.filter_map(|p| p.inline_data)
+			.map(|blob| decode_image(&blob.data, &blob.mime_type))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		if images.is_empty() {
+			return Err(GoogleError::NoOutput);
+		}
+
+		Ok(images)
+	}
+}
+
+pub(super) fn decode_image(
+	data: &str,
+	mime_type: &str,
+) -> Result<llms::GeneratedImage, GoogleError> {
+	let data = BASE64_STANDARD.decode(data).map_err(|e| {
+		GoogleError::InvalidLlmResponse(format!("invalid image data: {e}"))
+	})?;
+
+	Ok(llms::GeneratedImage {
+		data: data.into(),
+		mime_type: mime_type.to_string(),
+	})
+}
+
+impl ImageProvider for Google {
+	async fn generate_images(
+		&self,
+		req: &llms::ImageRequest,
+	) -> Result<llms::ImageResponse, LlmsError> {
+		let model = match req.model {
+			llms::ImageModel::Imagen4 => ImageModel::Imagen4,
+			llms::ImageModel::Imagen4Ultra => ImageModel::Imagen4Ultra,
+			llms::ImageModel::Imagen4Fast => ImageModel::Imagen4Fast,
+			llms::ImageModel::GeminiFlashImage => ImageModel::GeminiFlashImage,
+		};
+
+		let images = self
+			.generate_images(&ImageRequest {
+				model,
+				prompt: req.prompt.clone(),
+				count: req.count,
+				aspect_ratio: req.aspect_ratio.map(|r| r.as_str().to_string()),
+			})
+			.await?;
+
+		Ok(llms::ImageResponse { images })
+	}
+}
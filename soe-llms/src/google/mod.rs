@@ -1,33 +1,157 @@
-use std::fmt;
+mod cache;
+mod embeddings;
+mod files;
+mod images;
+mod models;
+mod schema;
+mod vertex;
 
-use reqwest::{Client, StatusCode};
+pub use cache::{CachedContent, CachedContentRequest};
+pub use embeddings::{EmbedRequest, EmbeddingModel, TaskType};
+pub use files::{File, FileState, INLINE_DATA_LIMIT};
+pub use images::{ImageModel, ImageRequest};
+pub use models::ModelInfo;
+pub use schema::{SchemaError, to_gemini_schema};
+pub use vertex::{METADATA_TOKEN_URL, MetadataToken, Vertex};
+
+use std::{collections::VecDeque, fmt, io, time::Duration};
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use reqwest::{Client, RequestBuilder, StatusCode, Url, header::HeaderMap};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tracing::trace;
+use serde_json::{Map, Value};
+use tracing::{debug, trace};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, DocumentSource,
+		ImageSource, LlmProvider, LlmResponseStream, LlmsError, Transcript,
+		history::{self, AssistantPart, ToolOutput, Turn, UserPart},
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
-const BASE_URL: &str =
-	"https://generativelanguage.googleapis.com/v1beta/models";
+/// Default of [`Google::base_url`].
+pub const DEFAULT_BASE_URL: &str =
+	"https://generativelanguage.googleapis.com/v1beta";
 
 #[derive(Clone)]
 pub struct Google {
 	pub client: Client,
-	pub api_key: String,
+	/// The api key, or on Vertex AI an OAuth access token.
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/google/v1"`.
+	pub base_url: String,
+	/// Sends the requests to Vertex AI, see [`Google::vertex`].
+	pub vertex: Option<Vertex>,
 }
 
 impl Google {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+			vertex: None,
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	/// Sends the requests to Vertex AI instead of the Gemini API, with
+	/// [`Google::api_key`] as the OAuth access token, e.g. a
+	/// [`MetadataToken`].
+	///
+	/// Sets [`Google::base_url`] to [`Vertex::base_url`], set it afterwards
+	/// to go through a Private Service Connect endpoint. Vertex AI only
+	/// serves the content generation and image requests, the files, cached
+	/// contents, embeddings and models endpoints are Gemini API only and
+	/// fail with [`GoogleError::VertexUnsupported`].
+	pub fn vertex(mut self, vertex: Vertex) -> Self {
+		self.base_url = vertex.base_url();
+		self.vertex = Some(vertex);
+		self
+	}
+
+	/// Fails with [`GoogleError::VertexUnsupported`] on Vertex AI, before
+	/// anything is sent.
+	fn gemini_only(&self, endpoint: &'static str) -> Result<(), GoogleError> {
+		match self.vertex {
+			Some(_) => Err(GoogleError::VertexUnsupported(endpoint)),
+			None => Ok(()),
+		}
+	}
+
+	/// Adds the api key, or on Vertex AI the access token.
+	fn authorize(&self, builder: RequestBuilder, key: &str) -> RequestBuilder {
+		match self.vertex {
+			Some(_) => builder.bearer_auth(key),
+			None => builder.header("x-goog-api-key", key),
+		}
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	/// Uploads go to the same path below `/upload`, e.g.
+	/// `https://generativelanguage.googleapis.com/upload/v1beta/files`.
+	fn upload_url(&self) -> String {
+		match Url::parse(&self.base_url) {
+			Ok(mut url) => {
+				let path = url.path().trim_end_matches('/').to_string();
+				url.set_path(&format!("/upload{path}/files"));
+				url.into()
+			}
+			// fails when sending
+			Err(_) => self.url("files"),
+		}
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid. On
+	/// Vertex AI the location is fetched instead.
+	pub async fn ping(&self) -> Result<(), GoogleError> {
+		let api_key = self.api_key.get().await?;
+
+		let url = match self.vertex {
+			// `…/locations/{location}/publishers/google`
+			Some(_) => self
+				.base_url
+				.trim_end_matches('/')
+				.trim_end_matches("/publishers/google")
+				.to_string(),
+			None => self.url("models"),
+		};
+		let resp = self
+			.authorize(self.client.get(url), &api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
 		}
+
+		Ok(())
 	}
 
 	pub async fn request(
@@ -43,66 +167,110 @@ impl Google {
 			#[serde(skip_serializing_if = "Vec::is_empty")]
 			tools: &'a Vec<ApiTool>,
 			#[serde(skip_serializing_if = "Option::is_none")]
-			generation_config: Option<GenerationConfig>,
+			tool_config: Option<ToolConfig<'a>>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			generation_config: Option<GenerationConfig<'a>>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			cached_content: Option<&'a str>,
 		}
 
 		#[derive(Debug, Serialize)]
-		struct ApiSystemInstruction {
-			parts: Vec<ApiPart>,
+		#[serde(rename_all = "camelCase")]
+		struct ToolConfig<'a> {
+			function_calling_config: &'a FunctionCallingConfig,
 		}
 
-		let system_instruction =
-			req.system_instruction.as_deref().map(|text| {
-				ApiSystemInstruction {
-					parts: vec![ApiPart::Text {
-						text: text.to_string(),
-					}],
-				}
-			});
+		let system_instruction = req
+			.system_instruction
+			.as_deref()
+			.map(ApiSystemInstruction::text);
 
+		let generation_config = GenerationConfig {
+			thinking_config: Some(ThinkingConfig {
+				thinking_level: req.thinking_level,
+				thinking_budget: req.thinking_budget,
+				include_thoughts: req.include_thoughts,
+			})
+			.filter(|c| !c.is_empty()),
+			response_mime_type: req.response_mime_type.as_deref(),
+			response_schema: req.response_schema.as_ref(),
+			response_modalities: &req.response_modalities,
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_output_tokens: req.max_output_tokens,
+			stop_sequences: &req.stop_sequences,
+		};
 		let generation_config =
-			req.thinking_level.map(|level| GenerationConfig {
-				thinking_config: ThinkingConfig {
-					thinking_level: level,
-				},
-			});
+			Some(generation_config).filter(|c| !c.is_empty());
 
 		let api_req = ApiReq {
 			contents: &req.contents,
 			system_instruction,
 			tools: &req.tools,
+			tool_config: req
+				.function_calling
+				.as_ref()
+				.filter(|_| !req.tools.is_empty())
+				.map(|config| ToolConfig {
+					function_calling_config: config,
+				}),
 			generation_config,
+			cached_content: req.cached_content.as_deref(),
 		};
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let url = format!(
-			"{}/{}:streamGenerateContent?alt=sse",
-			BASE_URL,
-			req.model.as_str(),
-		);
+		let url = self.url(&format!(
+			"models/{}:streamGenerateContent?alt=sse",
+			req.snapshot.as_deref().unwrap_or(req.model.as_str()),
+		));
 
-		let resp = self
-			.client
-			.post(&url)
-			.header("x-goog-api-key", &self.api_key)
-			.json(&api_req)
-			.send()
-			.await?;
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
+			.authorize(self.client.post(&url), &api_key)
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(GoogleError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream::new(SseResponse::new(
+			resp,
+			req.transcript.as_ref(),
+			&api_key,
+			req.read_timeout,
+		)))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
 	}
 }
 
 impl fmt::Debug for Google {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		f.debug_struct("Google").field("api_key", &"***").finish()
+		f.debug_struct("Google")
+			.field("api_key", &"***")
+			.field("vertex", &self.vertex)
+			.finish()
 	}
 }
 
@@ -117,27 +285,72 @@ impl LlmProvider for Google {
 			llms::Model::GeminiPro3_1 => GeminiModel::Pro3_1,
 			llms::Model::GeminiFlash3_5 => GeminiModel::Flash3_5,
 			llms::Model::GeminiFlash3_1Lite => GeminiModel::Flash3_1Lite,
+			llms::Model::GeminiFlashImage => GeminiModel::FlashImage,
+			llms::Model::Custom { id, .. } => GeminiModel::Custom(id.clone()),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
-		let system_instruction = if req.instructions.is_empty() {
-			None
-		} else {
-			Some(req.instructions.clone())
+		let system_instruction =
+			history::instructions(&req.instructions, &req.input);
+
+		let thinking_level = match (&model, req.reasoning_effort) {
+			// the image model doesn't think
+			(GeminiModel::FlashImage, Some(_)) => {
+				debug!("reasoning_effort is ignored for Gemini Flash Image");
+				None
+			}
+			(_, effort) => effort.map(|e| match e {
+				llms::ReasoningEffort::Low => ThinkingLevel::Low,
+				llms::ReasoningEffort::Medium => ThinkingLevel::Medium,
+				llms::ReasoningEffort::High => ThinkingLevel::High,
+			}),
 		};
 
-		let thinking_level = req.reasoning_effort.map(|e| match e {
-			llms::ReasoningEffort::Low => ThinkingLevel::Low,
-			llms::ReasoningEffort::Medium => ThinkingLevel::Medium,
-			llms::ReasoningEffort::High => ThinkingLevel::High,
-		});
+		let (response_mime_type, response_schema) = match &req.response_format {
+			llms::ResponseFormat::Text => (None, None),
+			llms::ResponseFormat::JsonObject => {
+				(Some(JSON_MIME_TYPE.to_string()), None)
+			}
+			llms::ResponseFormat::JsonSchema { schema, .. } => (
+				Some(JSON_MIME_TYPE.to_string()),
+				Some(to_gemini_schema(schema).map_err(GoogleError::from)?),
+			),
+		};
 
 		self.request(&Request {
-			contents: req.input.iter().cloned().map(Into::into).collect(),
+			contents: ApiContent::from_history(&req.input),
+			response_modalities: match model {
+				GeminiModel::FlashImage => {
+					vec![Modality::Text, Modality::Image]
+				}
+				_ => vec![],
+			},
 			model,
+			snapshot: req.snapshot.clone(),
 			system_instruction,
-			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tools: req
+				.tools
+				.iter()
+				.cloned()
+				.map(ApiTool::try_from)
+				.collect::<Result<_, _>>()
+				.map_err(GoogleError::from)?,
+			function_calling: req.tool_choice.clone().into(),
 			thinking_level,
+			thinking_budget: None,
+			// like the reasoning summaries of OpenAI
+			include_thoughts: thinking_level.is_some(),
+			response_mime_type,
+			response_schema,
+			cached_content: None,
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_output_tokens: req.params.max_tokens,
+			stop_sequences: req.params.stop.clone().unwrap_or_default(),
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
 		})
 		.await
 		.map_err(Into::into)
@@ -148,23 +361,129 @@ impl LlmProvider for Google {
 pub struct Request {
 	pub contents: Vec<ApiContent>,
 	pub model: GeminiModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	pub snapshot: Option<String>,
 	pub system_instruction: Option<String>,
 	pub tools: Vec<ApiTool>,
+	/// `toolConfig.functionCallingConfig`, `None` leaves it to the model.
+	pub function_calling: Option<FunctionCallingConfig>,
 	/// `thinkingConfig.thinkingLevel`. `None` omits the field (Gemini
 	/// defaults to dynamic thinking).
 	pub thinking_level: Option<ThinkingLevel>,
+	/// `thinkingConfig.thinkingBudget`, the thinking tokens at most. `0`
+	/// turns thinking off where the model allows it, `-1` is dynamic.
+	/// Gemini 3 models prefer [`Request::thinking_level`], setting both is
+	/// rejected.
+	pub thinking_budget: Option<i32>,
+	/// `thinkingConfig.includeThoughts`, streams summaries of the thoughts
+	/// as [`llms::Output::Reasoning`].
+	pub include_thoughts: bool,
+	/// `generationConfig.responseMimeType`, e.g. `"application/json"`.
+	pub response_mime_type: Option<String>,
+	/// `generationConfig.responseSchema`. Must already be in Gemini's
+	/// OpenAPI-style subset, see [`to_gemini_schema`]. Requires
+	/// `response_mime_type` to be set to `"application/json"`.
+	pub response_schema: Option<Value>,
+	/// `generationConfig.responseModalities`, empty only returns text.
+	/// Image models need [`Modality::Image`].
+	pub response_modalities: Vec<Modality>,
+	/// Name of a cache created with [`Google::create_cached_content`]
+	/// (`"cachedContents/…"`). The cached system instruction and tools
+	/// can't be repeated in the request, leave them empty when setting this.
+	pub cached_content: Option<String>,
+	/// `generationConfig.temperature`.
+	pub temperature: Option<f32>,
+	/// `generationConfig.topP`.
+	pub top_p: Option<f32>,
+	/// `generationConfig.maxOutputTokens`.
+	pub max_output_tokens: Option<u32>,
+	/// `generationConfig.stopSequences`.
+	pub stop_sequences: Vec<String>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
 }
 
+#[derive(Debug, Serialize)]
+struct ApiSystemInstruction {
+	parts: Vec<ApiPart>,
+}
+
+impl ApiSystemInstruction {
+	fn text(text: &str) -> Self {
+		Self {
+			parts: vec![ApiPart::Text {
+				text: text.to_string(),
+			}],
+		}
+	}
+}
+
+const JSON_MIME_TYPE: &str = "application/json";
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-struct GenerationConfig {
-	thinking_config: ThinkingConfig,
+struct GenerationConfig<'a> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	thinking_config: Option<ThinkingConfig>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	response_mime_type: Option<&'a str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	response_schema: Option<&'a Value>,
+	#[serde(skip_serializing_if = "<[_]>::is_empty")]
+	response_modalities: &'a [Modality],
+	#[serde(skip_serializing_if = "Option::is_none")]
+	temperature: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	top_p: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	max_output_tokens: Option<u32>,
+	#[serde(skip_serializing_if = "<[_]>::is_empty")]
+	stop_sequences: &'a [String],
+}
+
+impl GenerationConfig<'_> {
+	fn is_empty(&self) -> bool {
+		self.thinking_config.is_none()
+			&& self.response_mime_type.is_none()
+			&& self.response_schema.is_none()
+			&& self.response_modalities.is_empty()
+			&& self.temperature.is_none()
+			&& self.top_p.is_none()
+			&& self.max_output_tokens.is_none()
+			&& self.stop_sequences.is_empty()
+	}
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Modality {
+	Text,
+	Image,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ThinkingConfig {
-	thinking_level: ThinkingLevel,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	thinking_level: Option<ThinkingLevel>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	thinking_budget: Option<i32>,
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	include_thoughts: bool,
+}
+
+impl ThinkingConfig {
+	fn is_empty(&self) -> bool {
+		self.thinking_level.is_none()
+			&& self.thinking_budget.is_none()
+			&& !self.include_thoughts
+	}
 }
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -182,53 +501,84 @@ pub enum ApiRole {
 	Model,
 }
 
-impl From<llms::Role> for ApiRole {
-	fn from(role: llms::Role) -> Self {
-		match role {
-			llms::Role::User => ApiRole::User,
-			llms::Role::Assistant => ApiRole::Model,
-		}
-	}
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiContent {
 	pub role: ApiRole,
 	pub parts: Vec<ApiPart>,
 }
 
-impl From<llms::Input> for ApiContent {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => ApiContent {
-				role: role.into(),
-				parts: vec![ApiPart::Text { text: content }],
-			},
-			llms::Input::ToolCall {
-				name,
-				input,
-				context,
-				..
-			} => ApiContent {
-				role: ApiRole::Model,
-				parts: vec![ApiPart::FunctionCall {
-					function_call: ApiFunctionCall { name, args: input },
-					thought_signature: context,
-				}],
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiContent {
-				role: ApiRole::User,
-				parts: vec![ApiPart::FunctionResponse {
-					function_response: ApiFunctionResponse {
-						// Gemini identifies responses by function name.
-						// Output::ToolCall sets id == name, so the id here
-						// is already the function name.
-						name: id,
-						response: serde_json::json!({ "output": output }),
-					},
-				}],
-			},
+impl ApiContent {
+	/// Encodes the history, merging consecutive turns of the same role so
+	/// parallel function calls and their responses share a content.
+	pub fn from_history(input: &[llms::Input]) -> Vec<Self> {
+		let mut contents: Vec<ApiContent> = Vec::new();
+
+		for turn in history::turns(input) {
+			let (role, parts) = match turn {
+				// merged into the system prompt
+				Turn::System(_) => continue,
+				Turn::User(parts) => (
+					ApiRole::User,
+					parts
+						.into_iter()
+						.map(|part| match part {
+							UserPart::Text(text) => ApiPart::Text { text },
+							UserPart::Image(image) => image.into(),
+							UserPart::Document { data, mime } => {
+								ApiPart::document(data, mime)
+							}
+						})
+						.collect::<Vec<_>>(),
+				),
+				Turn::Assistant(parts) => (
+					ApiRole::Model,
+					parts
+						.into_iter()
+						.map(|part| match part {
+							AssistantPart::Text(text) => ApiPart::Text { text },
+							AssistantPart::ToolCall {
+								name,
+								input,
+								context,
+								..
+							} => ApiPart::FunctionCall {
+								function_call: ApiFunctionCall {
+									name,
+									args: input,
+								},
+								thought_signature: context,
+							},
+						})
+						.collect(),
+				),
+				Turn::ToolOutputs(outputs) => (
+					ApiRole::User,
+					outputs
+						.into_iter()
+						.map(|ToolOutput { id, output }| {
+							ApiPart::FunctionResponse {
+								function_response: ApiFunctionResponse {
+									// Gemini identifies responses by function
+									// name. Output::ToolCall sets id == name, so
+									// the id here is already the function name.
+									name: id,
+									response: serde_json::json!({
+										"output": output
+									}),
+								},
+							}
+						})
+						.collect(),
+				),
+			};
+
+			match contents.last_mut() {
+				Some(last) if last.role == role => last.parts.extend(parts),
+				_ => contents.push(ApiContent { role, parts }),
+			}
 		}
+
+		contents
 	}
 }
 
@@ -249,6 +599,116 @@ pub enum ApiPart {
 	FunctionResponse {
 		function_response: ApiFunctionResponse,
 	},
+	/// Media sent inline as base64. Limited to [`INLINE_DATA_LIMIT`], larger
+	/// payloads have to go through the File API, see
+	/// [`Google::media_part`].
+	InlineData {
+		inline_data: ApiBlob,
+	},
+	/// Media referenced by the uri of a file uploaded through the File API
+	/// or of a YouTube video.
+	FileData {
+		file_data: ApiFileData,
+	},
+}
+
+impl From<ImageSource> for ApiPart {
+	fn from(image: ImageSource) -> Self {
+		match image {
+			ImageSource::Url(file_uri) => ApiPart::FileData {
+				file_data: ApiFileData {
+					mime_type: None,
+					file_uri,
+				},
+			},
+			ImageSource::Base64 { data, mime_type } => ApiPart::InlineData {
+				inline_data: ApiBlob { mime_type, data },
+			},
+			ImageSource::Reader { reader, mime_type } => ApiPart::InlineData {
+				inline_data: ApiBlob {
+					mime_type,
+					data: reader.placeholder(),
+				},
+			},
+		}
+	}
+}
+
+impl ApiPart {
+	fn document(data: DocumentSource, mime: String) -> Self {
+		match data {
+			DocumentSource::Url(file_uri) => ApiPart::FileData {
+				file_data: ApiFileData {
+					mime_type: Some(mime),
+					file_uri,
+				},
+			},
+			DocumentSource::Base64(data) => ApiPart::InlineData {
+				inline_data: ApiBlob {
+					mime_type: mime,
+					data,
+				},
+			},
+			DocumentSource::Reader(reader) => ApiPart::InlineData {
+				inline_data: ApiBlob {
+					mime_type: mime,
+					data: reader.placeholder(),
+				},
+			},
+		}
+	}
+
+	/// Creates an [`ApiPart::InlineData`] part, base64 encoding `data`.
+	pub fn inline_data(mime_type: impl Into<String>, data: &[u8]) -> Self {
+		ApiPart::InlineData {
+			inline_data: ApiBlob {
+				mime_type: mime_type.into(),
+				data: BASE64_STANDARD.encode(data),
+			},
+		}
+	}
+
+	/// Creates an [`ApiPart::FileData`] part referencing an uploaded file.
+	pub fn file_data(
+		mime_type: impl Into<String>,
+		file_uri: impl Into<String>,
+	) -> Self {
+		ApiPart::FileData {
+			file_data: ApiFileData {
+				mime_type: Some(mime_type.into()),
+				file_uri: file_uri.into(),
+			},
+		}
+	}
+
+	/// Creates an [`ApiPart::FileData`] part for a public YouTube video, e.g.
+	/// `"https://www.youtube.com/watch?v=…"`, so questions about it can be
+	/// asked by link.
+	pub fn youtube(url: impl Into<String>) -> Self {
+		ApiPart::FileData {
+			file_data: ApiFileData {
+				mime_type: None,
+				file_uri: url.into(),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiBlob {
+	pub mime_type: String,
+	/// Base64 encoded bytes.
+	pub data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiFileData {
+	/// Not needed for YouTube urls.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub mime_type: Option<String>,
+	pub file_uri: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -266,45 +726,110 @@ pub struct ApiFunctionResponse {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ApiTool {
-	pub function_declarations: Vec<ApiFunctionDeclaration>,
+#[serde(rename_all_fields = "camelCase")]
+#[serde(untagged)]
+pub enum ApiTool {
+	FunctionDeclarations {
+		function_declarations: Vec<ApiFunctionDeclaration>,
+	},
+	/// Lets the model write and run Python code in a sandbox. The code and
+	/// its result are returned as [`llms::Output::CodeExecution`] and
+	/// [`llms::Output::CodeExecutionResult`].
+	CodeExecution { code_execution: ApiCodeExecution },
+}
+
+impl ApiTool {
+	pub fn code_execution() -> Self {
+		ApiTool::CodeExecution {
+			code_execution: ApiCodeExecution {},
+		}
+	}
 }
 
+/// The code execution tool takes no configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApiCodeExecution {}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ApiFunctionDeclaration {
 	pub name: String,
 	pub description: String,
-	/// JSON Schema object describing the function's parameters.
-	/// Must be `{ "type": "object", "properties": { … } }`.
+	/// Schema of the function's parameters in the subset Gemini accepts,
+	/// see [`to_gemini_schema`]. Must be
+	/// `{ "type": "OBJECT", "properties": { … } }`.
 	pub parameters: Value,
 }
 
-impl From<llms::Tool> for ApiTool {
-	fn from(tool: llms::Tool) -> Self {
-		ApiTool {
+impl TryFrom<llms::Tool> for ApiTool {
+	type Error = SchemaError;
+
+	fn try_from(tool: llms::Tool) -> Result<Self, SchemaError> {
+		let parameters = tool.parameters.unwrap_or_else(default_parameters);
+
+		Ok(ApiTool::FunctionDeclarations {
 			function_declarations: vec![ApiFunctionDeclaration {
 				name: tool.name,
 				description: tool.description,
-				parameters: tool.parameters.unwrap_or_else(default_parameters),
+				parameters: to_gemini_schema(&parameters)?,
 			}],
-		}
+		})
+	}
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCallingConfig {
+	pub mode: FunctionCallingMode,
+	/// Limits [`FunctionCallingMode::Any`] to these functions.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub allowed_function_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FunctionCallingMode {
+	Auto,
+	/// At least one function call.
+	Any,
+	None,
+}
+
+impl From<llms::ToolChoice> for Option<FunctionCallingConfig> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		let (mode, allowed_function_names) = match choice {
+			llms::ToolChoice::Auto => return None,
+			llms::ToolChoice::None => (FunctionCallingMode::None, vec![]),
+			llms::ToolChoice::Required => (FunctionCallingMode::Any, vec![]),
+			llms::ToolChoice::Tool(name) => {
+				(FunctionCallingMode::Any, vec![name])
+			}
+		};
+
+		Some(FunctionCallingConfig {
+			mode,
+			allowed_function_names,
+		})
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum GeminiModel {
 	Pro3_1,
 	Flash3_5,
 	Flash3_1Lite,
+	FlashImage,
+	/// Any other model id, see [`llms::Model::Custom`].
+	Custom(String),
 }
 
 impl GeminiModel {
-	pub fn as_str(&self) -> &'static str {
+	pub fn as_str(&self) -> &str {
 		match self {
 			GeminiModel::Pro3_1 => "gemini-3.1-pro-preview",
 			GeminiModel::Flash3_5 => "gemini-3.5-flash",
 			GeminiModel::Flash3_1Lite => "gemini-3.1-flash-lite-preview",
+			GeminiModel::FlashImage => "gemini-2.5-flash-image",
+			GeminiModel::Custom(id) => id,
 		}
 	}
 }
@@ -319,6 +844,10 @@ pub struct StreamChunk {
 	/// non-`None` value seen is authoritative for the whole response.
 	#[serde(default, rename = "usageMetadata")]
 	pub usage_metadata: Option<UsageMetadata>,
+	/// Set when the prompt itself was blocked, in which case there are no
+	/// candidates.
+	#[serde(default, rename = "promptFeedback")]
+	pub prompt_feedback: Option<PromptFeedback>,
 	pub error: Option<ApiErrorBody>,
 }
 
@@ -327,15 +856,120 @@ pub struct StreamChunk {
 pub struct UsageMetadata {
 	#[serde(default)]
 	pub prompt_token_count: u32,
+	/// Without the thoughts.
 	#[serde(default)]
 	pub candidates_token_count: u32,
 	#[serde(default)]
+	pub thoughts_token_count: u32,
+	/// Part of `prompt_token_count` served from a cached content.
+	#[serde(default)]
+	pub cached_content_token_count: u32,
+	#[serde(default)]
 	pub total_token_count: u32,
+	/// `prompt_token_count` split by modality.
+	#[serde(default)]
+	pub prompt_tokens_details: Vec<ModalityTokenCount>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ModalityTokenCount {
+	/// e.g. `"TEXT"` or `"AUDIO"`.
+	pub modality: String,
+	#[serde(default)]
+	pub token_count: u32,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Candidate {
 	pub content: Option<CandidateContent>,
+	/// Only present on the last chunk of the candidate.
+	#[serde(default)]
+	pub finish_reason: Option<ApiFinishReason>,
+	#[serde(default)]
+	pub safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptFeedback {
+	#[serde(default)]
+	pub block_reason: Option<String>,
+	#[serde(default)]
+	pub safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SafetyRating {
+	/// e.g. `"HARM_CATEGORY_DANGEROUS_CONTENT"`.
+	pub category: String,
+	/// e.g. `"NEGLIGIBLE"` or `"HIGH"`.
+	pub probability: String,
+	/// Whether this category caused the content to be blocked.
+	#[serde(default)]
+	pub blocked: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiFinishReason {
+	Stop,
+	MaxTokens,
+	Safety,
+	Recitation,
+	Language,
+	Blocklist,
+	ProhibitedContent,
+	Spii,
+	ImageSafety,
+	MalformedFunctionCall,
+	UnexpectedToolCall,
+	TooManyToolCalls,
+	#[serde(other)]
+	Other,
+}
+
+impl ApiFinishReason {
+	/// Whether the candidate was stopped by one of Gemini's content filters.
+	pub fn is_filtered(&self) -> bool {
+		matches!(
+			self,
+			ApiFinishReason::Safety
+				| ApiFinishReason::Recitation
+				| ApiFinishReason::Blocklist
+				| ApiFinishReason::ProhibitedContent
+				| ApiFinishReason::Spii
+				| ApiFinishReason::ImageSafety
+		)
+	}
+}
+
+impl From<&ApiFinishReason> for llms::FinishReason {
+	fn from(reason: &ApiFinishReason) -> Self {
+		match reason {
+			ApiFinishReason::Stop => llms::FinishReason::Stop,
+			ApiFinishReason::MaxTokens => llms::FinishReason::MaxTokens,
+			r if r.is_filtered() => llms::FinishReason::ContentFilter,
+			r => llms::FinishReason::Other(format!("{r:?}")),
+		}
+	}
+}
+
+/// Formats a blocking reason together with the categories that caused it.
+fn block_message(reason: &str, ratings: &[SafetyRating]) -> String {
+	let categories: Vec<&str> = ratings
+		.iter()
+		.filter(|r| r.blocked)
+		.map(|r| r.category.as_str())
+		.collect();
+
+	if categories.is_empty() {
+		reason.to_string()
+	} else {
+		format!("{reason} ({})", categories.join(", "))
+	}
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -360,11 +994,61 @@ pub enum CandidatePart {
 		/// corresponding tool response has been added to the history.
 		thought_signature: Option<String>,
 	},
+	ExecutableCode {
+		executable_code: ExecutableCode,
+	},
+	CodeExecutionResult {
+		code_execution_result: CodeExecutionResult,
+	},
+	/// Generated images of image models.
+	InlineData {
+		inline_data: ApiBlob,
+	},
 	Text {
 		text: String,
+		/// A summary of the thoughts, only sent with
+		/// [`Request::include_thoughts`].
+		#[serde(default)]
+		thought: bool,
 	},
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExecutableCode {
+	pub language: String,
+	pub code: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CodeExecutionResult {
+	pub outcome: Outcome,
+	#[serde(default)]
+	pub output: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Outcome {
+	OutcomeUnspecified,
+	OutcomeOk,
+	OutcomeFailed,
+	OutcomeDeadlineExceeded,
+}
+
+impl From<Outcome> for llms::CodeExecutionOutcome {
+	fn from(outcome: Outcome) -> Self {
+		match outcome {
+			Outcome::OutcomeOk => llms::CodeExecutionOutcome::Ok,
+			Outcome::OutcomeDeadlineExceeded => {
+				llms::CodeExecutionOutcome::DeadlineExceeded
+			}
+			Outcome::OutcomeUnspecified | Outcome::OutcomeFailed => {
+				llms::CodeExecutionOutcome::Failed
+			}
+		}
+	}
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ApiErrorBody {
 	pub code: Option<u32>,
@@ -378,12 +1062,44 @@ pub enum GoogleError {
 	InvalidLlmResponse(String),
 	#[error("No output in response")]
 	NoOutput,
+	#[error("Prompt blocked: {0}")]
+	PromptBlocked(String),
+	#[error("Output blocked: {0}")]
+	OutputBlocked(String),
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("API error {code}: {message}")]
 	ApiError { code: u32, message: String },
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+	#[error("Schema error: {0}")]
+	Schema(#[from] SchemaError),
+	/// The endpoint is Gemini API only, see [`Google::vertex`].
+	#[error("{0} are not available on Vertex AI")]
+	VertexUnsupported(&'static str),
+}
+
+impl From<ErrorResponse> for GoogleError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<GoogleError> for LlmsError {
@@ -392,34 +1108,78 @@ impl From<GoogleError> for LlmsError {
 			GoogleError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::Google,
+				request_id: None,
 			},
 			GoogleError::NoOutput => LlmsError::Response {
 				status: StatusCode::OK,
 				body: "no output in response".into(),
+				provider: llms::ProviderKind::Google,
+				request_id: None,
+			},
+			GoogleError::PromptBlocked(reason) => LlmsError::ContentFiltered {
+				reason: format!("prompt blocked: {reason}"),
 			},
-			GoogleError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
+			GoogleError::OutputBlocked(reason) => {
+				LlmsError::ContentFiltered { reason }
 			}
-			GoogleError::ApiError { code, message } => LlmsError::Response {
-				status: StatusCode::from_u16(code as u16)
+			GoogleError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::Google,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
+			GoogleError::ApiError { code, message } => LlmsError::api(
+				llms::ProviderKind::Google,
+				StatusCode::from_u16(code as u16)
 					.unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
-				body: message,
-			},
+				&message,
+				None,
+				None,
+			),
 			GoogleError::ReqwestError(e) => LlmsError::Reqwest(e),
+			GoogleError::Credential(e) => LlmsError::Credential(e),
+			GoogleError::Io(e) => LlmsError::Io(e),
+			GoogleError::Schema(e) => LlmsError::InvalidSchema(e.to_string()),
+			e @ GoogleError::VertexUnsupported(_) => {
+				LlmsError::LlmNotConfigured(format!("Google: {e}"))
+			}
 		}
 	}
 }
 
 pub struct ResponseStream {
 	inner: SseResponse,
-	/// Accumulated text across all content deltas. `None` until the first
+	/// Text accumulated since the last non-text part. `None` until the next
 	/// non-empty content delta arrives.
 	text_acc: Option<String>,
-	tool_calls: Vec<llms::Output>,
+	/// Thought summaries accumulated since the last other part, like
+	/// `text_acc`.
+	thought_acc: Option<String>,
+	/// Every flushed thought summary, for [`llms::Response::reasoning`].
+	reasoning: Vec<String>,
+	/// Outputs in arrival order. Pending text is flushed into this whenever
+	/// a non-text part arrives, so text around code execution stays in
+	/// order.
+	output: Vec<llms::Output>,
 	/// Latest token usage seen on a chunk. Gemini's `usageMetadata` is
 	/// cumulative, so the value from the last chunk that carries it wins.
 	/// `None` until the first chunk with `usageMetadata` arrives.
 	usage: Option<llms::Usage>,
+	/// Finish reason of the first candidate, together with the message
+	/// describing why it was blocked if it was filtered.
+	finish_reason: Option<(ApiFinishReason, String)>,
+	/// Formatted `promptFeedback.blockReason`, if the prompt was blocked.
+	prompt_blocked: Option<String>,
+	/// Events of the last chunk not returned yet, a chunk can hold text and
+	/// several function calls.
+	pending: VecDeque<llms::LlmResponseEvent>,
 	done: bool,
 }
 
@@ -432,12 +1192,22 @@ impl std::fmt::Debug for ResponseStream {
 }
 
 impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
 	fn new(inner: SseResponse) -> Self {
 		Self {
 			inner,
 			text_acc: None,
-			tool_calls: Vec::new(),
+			thought_acc: None,
+			reasoning: Vec::new(),
+			output: Vec::new(),
 			usage: None,
+			finish_reason: None,
+			prompt_blocked: None,
+			pending: VecDeque::new(),
 			done: false,
 		}
 	}
@@ -452,25 +1222,73 @@ impl ResponseStream {
 		}
 	}
 
+	fn flush_text(&mut self) {
+		if let Some(text) = self.text_acc.take() {
+			self.output.push(llms::Output::Text { content: text });
+		}
+	}
+
+	fn flush_thoughts(&mut self) {
+		if let Some(thought) = self.thought_acc.take() {
+			self.reasoning.push(thought.clone());
+			self.output
+				.push(llms::Output::Reasoning { content: thought });
+		}
+	}
+
+	fn push_output(&mut self, output: llms::Output) {
+		self.flush_thoughts();
+		self.flush_text();
+		self.output.push(output);
+	}
+
 	fn build_response(&mut self) -> Result<llms::Response, GoogleError> {
-		let mut output: Vec<llms::Output> =
-			Vec::with_capacity(self.tool_calls.len() + 1 /* text */);
+		self.flush_thoughts();
+		self.flush_text();
+		let output = std::mem::take(&mut self.output);
 
-		if let Some(text) = self.text_acc.take() {
-			output.push(llms::Output::Text { content: text });
+		if let Some(reason) = self.prompt_blocked.take() {
+			return Err(GoogleError::PromptBlocked(reason));
 		}
 
-		output.append(&mut self.tool_calls);
+		let finish_reason = self.finish_reason.take();
 
 		if output.is_empty() {
-			return Err(GoogleError::NoOutput);
+			match &finish_reason {
+				Some((reason, message)) if reason.is_filtered() => {
+					return Err(GoogleError::OutputBlocked(message.clone()));
+				}
+				// Thinking can use up the whole budget before any output is
+				// produced, let the caller see that it was truncated.
+				Some((ApiFinishReason::MaxTokens, _)) => {}
+				_ => return Err(GoogleError::NoOutput),
+			}
 		}
 
+		let has_tool_calls = output
+			.iter()
+			.any(|o| matches!(o, llms::Output::ToolCall { .. }));
+		let finish_reason = finish_reason.map(|(reason, _)| match reason {
+			// Gemini reports STOP after function calls.
+			ApiFinishReason::Stop if has_tool_calls => {
+				llms::FinishReason::ToolCalls
+			}
+			reason => (&reason).into(),
+		});
+
 		let usage = self.usage.take().ok_or_else(|| {
 			GoogleError::InvalidLlmResponse("missing usage in response".into())
 		})?;
 
-		Ok(llms::Response { output, usage })
+		let reasoning = std::mem::take(&mut self.reasoning).join("\n\n");
+
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason,
+			reasoning: Some(reasoning).filter(|r| !r.is_empty()),
+			..Default::default()
+		})
 	}
 }
 
@@ -483,6 +1301,10 @@ impl LlmResponseStream for ResponseStream {
 		}
 
 		loop {
+			if let Some(ev) = self.pending.pop_front() {
+				return Some(Ok(ev));
+			}
+
 			let chunk = match self.next_chunk().await {
 				Some(Ok(c)) => c,
 				Some(Err(e)) => return Some(Err(e.into())),
@@ -499,7 +1321,18 @@ impl LlmResponseStream for ResponseStream {
 			if let Some(usage) = chunk.usage_metadata {
 				self.usage = Some(llms::Usage {
 					input_tokens: usage.prompt_token_count,
-					output_tokens: usage.candidates_token_count,
+					output_tokens: usage
+						.candidates_token_count
+						.saturating_add(usage.thoughts_token_count),
+					cache_read_tokens: usage.cached_content_token_count,
+					reasoning_tokens: usage.thoughts_token_count,
+					audio_input_tokens: usage
+						.prompt_tokens_details
+						.iter()
+						.filter(|details| details.modality == "AUDIO")
+						.map(|details| details.token_count)
+						.sum(),
+					..Default::default()
 				});
 			}
 
@@ -512,17 +1345,56 @@ impl LlmResponseStream for ResponseStream {
 				.into()));
 			}
 
+			if let Some(feedback) = chunk.prompt_feedback
+				&& let Some(reason) = feedback.block_reason
+			{
+				self.prompt_blocked =
+					Some(block_message(&reason, &feedback.safety_ratings));
+			}
+
 			// We only ever inspect the first candidate.
 			let Some(candidate) = chunk.candidates.into_iter().next() else {
 				continue;
 			};
 
+			if let Some(reason) = candidate.finish_reason {
+				let message = block_message(
+					&format!("{reason:?}"),
+					&candidate.safety_ratings,
+				);
+				self.finish_reason = Some((reason, message));
+			}
+
 			let mut text_delta: Option<String> = None;
 
 			if let Some(content) = candidate.content {
 				for part in content.parts {
 					match part {
-						CandidatePart::Text { text } if !text.is_empty() => {
+						CandidatePart::Text {
+							text,
+							thought: true,
+						} if !text.is_empty() => {
+							self.flush_text();
+							if let Some(text) = text_delta.take() {
+								self.pending.push_back(
+									llms::LlmResponseEvent::TextDelta {
+										content: text,
+									},
+								);
+							}
+							self.thought_acc
+								.get_or_insert_with(String::new)
+								.push_str(&text);
+							self.pending.push_back(
+								llms::LlmResponseEvent::ReasoningDelta {
+									content: text,
+								},
+							);
+						}
+						CandidatePart::Text { text, .. }
+							if !text.is_empty() =>
+						{
+							self.flush_thoughts();
 							self.text_acc
 								.get_or_insert_with(String::new)
 								.push_str(&text);
@@ -535,7 +1407,27 @@ impl LlmResponseStream for ResponseStream {
 							function_call,
 							thought_signature,
 						} => {
-							self.tool_calls.push(llms::Output::ToolCall {
+							if let Some(text) = text_delta.take() {
+								self.pending.push_back(
+									llms::LlmResponseEvent::TextDelta {
+										content: text,
+									},
+								);
+							}
+							// calls arrive complete, the arguments are a
+							// single delta
+							self.pending.extend([
+								llms::LlmResponseEvent::ToolCallStarted {
+									id: function_call.name.clone(),
+									name: function_call.name.clone(),
+								},
+								llms::LlmResponseEvent::ToolCallArgumentsDelta {
+									id: function_call.name.clone(),
+									delta: function_call.args.to_string(),
+								},
+							]);
+
+							self.push_output(llms::Output::ToolCall {
 								// Gemini has no separate opaque call id.
 								// We use the function name for both fields so
 								// that ToolCallOutput can round-trip via id.
@@ -545,15 +1437,183 @@ impl LlmResponseStream for ResponseStream {
 								context: thought_signature,
 							});
 						}
+						CandidatePart::ExecutableCode { executable_code } => {
+							self.push_output(llms::Output::CodeExecution {
+								language: executable_code.language,
+								code: executable_code.code,
+							});
+						}
+						CandidatePart::InlineData { inline_data } => {
+							let image = match images::decode_image(
+								&inline_data.data,
+								&inline_data.mime_type,
+							) {
+								Ok(image) => image,
+								Err(e) => return Some(Err(e.into())),
+							};
+							self.push_output(llms::Output::Image {
+								data: image.data,
+								mime_type: image.mime_type,
+							});
+						}
+						CandidatePart::CodeExecutionResult {
+							code_execution_result,
+						} => {
+							self.push_output(
+								llms::Output::CodeExecutionResult {
+									outcome: code_execution_result
+										.outcome
+										.into(),
+									output: code_execution_result.output,
+								},
+							);
+						}
 					}
 				}
 			}
 
 			if let Some(text) = text_delta {
-				return Some(Ok(llms::LlmResponseEvent::TextDelta {
+				self.pending.push_back(llms::LlmResponseEvent::TextDelta {
 					content: text,
-				}));
+				});
 			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use std::pin::pin;
+
+	use futures::StreamExt as _;
+
+	use super::*;
+	use crate::llms::{ImageSource, Input, Role};
+
+	#[tokio::test]
+	async fn gemini_only_endpoints_fail_on_vertex() {
+		// nothing listens there, a sent request fails differently
+		let google = Google::new("token")
+			.vertex(Vertex::new("project", "europe-west4"))
+			.base_url("http://127.0.0.1:9");
+		let unsupported =
+			|e: GoogleError| matches!(e, GoogleError::VertexUnsupported(_));
+
+		let embed = EmbedRequest {
+			model: EmbeddingModel::GeminiEmbedding001,
+			input: vec!["a".into()],
+			task_type: None,
+			output_dimensionality: None,
+		};
+		assert!(unsupported(google.embed(&embed).await.unwrap_err()));
+		let upload = google.upload_file("a".into(), "text/plain", None);
+		assert!(unsupported(upload.await.unwrap_err()));
+		assert!(unsupported(google.get_file("files/a").await.unwrap_err()));
+		let file = pin!(google.list_files()).next().await.unwrap();
+		assert!(unsupported(file.unwrap_err()));
+		let delete = google.delete_cached_content("cachedContents/a");
+		assert!(unsupported(delete.await.unwrap_err()));
+		let model = pin!(google.list_models()).next().await.unwrap();
+		assert!(unsupported(model.unwrap_err()));
+	}
+
+	#[test]
+	fn merges_turns_and_answers_calls_by_name() {
+		// Gemini tool calls come back with the function name as id
+		let input = [
+			Input::Text {
+				role: Role::System,
+				content: "Be brief.".into(),
+			},
+			Input::Text {
+				role: Role::User,
+				content: "Weather and time in Bern?".into(),
+			},
+			Input::Text {
+				role: Role::Assistant,
+				content: "Checking.".into(),
+			},
+			Input::ToolCall {
+				id: "weather".into(),
+				name: "weather".into(),
+				input: json!({ "city": "Bern" }),
+				context: Some("sig".into()),
+			},
+			Input::ToolCall {
+				id: "time".into(),
+				name: "time".into(),
+				input: json!({ "city": "Bern" }),
+				context: None,
+			},
+			Input::ToolCallOutput {
+				id: "weather".into(),
+				output: "sunny".into(),
+			},
+			Input::ToolCallOutput {
+				id: "time".into(),
+				output: "noon".into(),
+			},
+			Input::Text {
+				role: Role::User,
+				content: "And here?".into(),
+			},
+			Input::Image {
+				data: ImageSource::Url("https://example.com/a.png".into()),
+			},
+		];
+
+		assert_eq!(
+			serde_json::to_value(ApiContent::from_history(&input)).unwrap(),
+			json!([
+				{
+					"role": "user",
+					"parts": [{ "text": "Weather and time in Bern?" }]
+				},
+				{
+					"role": "model",
+					"parts": [
+						{ "text": "Checking." },
+						{
+							"functionCall": {
+								"name": "weather",
+								"args": { "city": "Bern" }
+							},
+							"thoughtSignature": "sig"
+						},
+						{
+							"functionCall": {
+								"name": "time",
+								"args": { "city": "Bern" }
+							}
+						}
+					]
+				},
+				{
+					"role": "user",
+					"parts": [
+						{
+							"functionResponse": {
+								"name": "weather",
+								"response": { "output": "sunny" }
+							}
+						},
+						{
+							"functionResponse": {
+								"name": "time",
+								"response": { "output": "noon" }
+							}
+						},
+						{ "text": "And here?" },
+						{
+							"fileData": {
+								"fileUri": "https://example.com/a.png"
+							}
+						}
+					]
+				}
+			])
+		);
+	}
+}
@@ -0,0 +1,76 @@
+use futures::Stream;
+use serde::Deserialize;
+
+use super::{Google, GoogleError};
+use crate::utils::error_response;
+use crate::utils::paginate::{Page, paginate};
+
+/// A model returned by [`Google::list_models`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+	/// Resource name, e.g. `"models/gemini-2.5-flash"`.
+	pub name: String,
+	#[serde(default)]
+	pub display_name: String,
+	#[serde(default)]
+	pub description: String,
+	#[serde(default)]
+	pub input_token_limit: u32,
+	#[serde(default)]
+	pub output_token_limit: u32,
+	/// e.g. `"generateContent"` or `"embedContent"`.
+	#[serde(default)]
+	pub supported_generation_methods: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ModelList {
+	#[serde(default)]
+	models: Vec<ModelInfo>,
+	next_page_token: Option<String>,
+}
+
+impl Google {
+	/// Lists the available models, the next page is fetched while the
+	/// stream is polled.
+	pub fn list_models(
+		&self,
+	) -> impl Stream<Item = Result<ModelInfo, GoogleError>> + '_ {
+		paginate(move |token| self.models_page(token))
+	}
+
+	async fn models_page(
+		&self,
+		token: Option<String>,
+	) -> Result<Page<ModelInfo>, GoogleError> {
+		self.gemini_only("Models")?;
+
+		let api_key = self.api_key.get().await?;
+
+		let mut req = self
+			.authorize(self.client.get(self.url("models")), &api_key)
+			.build()?;
+		req.url_mut()
+			.query_pairs_mut()
+			.append_pair("pageSize", "1000");
+		if let Some(token) = &token {
+			req.url_mut()
+				.query_pairs_mut()
+				.append_pair("pageToken", token);
+		}
+
+		let resp = self.client.execute(req).await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let list: ModelList = resp.json().await?;
+		Ok(Page {
+			items: list.models,
+			next: list.next_page_token,
+		})
+	}
+}
@@ -0,0 +1,428 @@
+use serde_json::{Map, Value};
+
+/// Keywords understood by Gemini's OpenAPI-style `Schema` object. Every
+/// other JSON Schema keyword is dropped during translation.
+const SUPPORTED_KEYWORDS: &[&str] = &[
+	"type",
+	"title",
+	"description",
+	"nullable",
+	"enum",
+	"maxItems",
+	"minItems",
+	"required",
+	"minProperties",
+	"maxProperties",
+	"minLength",
+	"maxLength",
+	"pattern",
+	"example",
+	"propertyOrdering",
+	"default",
+	"minimum",
+	"maximum",
+];
+
+/// Values of `format` Gemini accepts, others are dropped.
+const SUPPORTED_FORMATS: &[&str] =
+	&["enum", "date-time", "int32", "int64", "float", "double"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+	/// Only local references (`#/$defs/...`) can be resolved.
+	#[error("Unresolvable $ref {0:?}")]
+	UnresolvedRef(String),
+	/// Gemini schemas can't reference themselves, so recursive types can't
+	/// be expressed.
+	#[error("Recursive $ref {0:?} is not supported by Gemini")]
+	RecursiveRef(String),
+}
+
+/// Translates a standard JSON Schema into the subset Gemini accepts as
+/// `responseSchema` or function `parameters`.
+///
+/// - `type` is upper-cased and multiple types become an `anyOf`.
+/// - A `"null"` type, `anyOf` variant or `enum` value makes the rest
+///   `nullable`, Gemini has no null type.
+/// - `const` becomes a single-value `enum`, `examples` an `example`. Enums
+///   are always strings, other values are converted.
+/// - Local `$ref`s are inlined, recursive ones fail.
+/// - `oneOf` becomes an `anyOf`, `allOf` is merged into a single schema.
+/// - Unsupported keywords (`additionalProperties`, `$schema`, …) and
+///   formats are dropped.
+pub fn to_gemini_schema(schema: &Value) -> Result<Value, SchemaError> {
+	translate(schema, schema, &mut Vec::new())
+}
+
+fn translate(
+	schema: &Value,
+	root: &Value,
+	refs: &mut Vec<String>,
+) -> Result<Value, SchemaError> {
+	let Value::Object(obj) = schema else {
+		return Ok(schema.clone());
+	};
+
+	let mut out = Map::new();
+	let mut nullable = false;
+
+	// siblings of $ref override what it points to
+	if let Some(Value::String(reference)) = obj.get("$ref") {
+		if refs.contains(reference) {
+			return Err(SchemaError::RecursiveRef(reference.clone()));
+		}
+
+		let target = reference
+			.strip_prefix('#')
+			.and_then(|pointer| root.pointer(pointer))
+			.ok_or_else(|| SchemaError::UnresolvedRef(reference.clone()))?;
+
+		refs.push(reference.clone());
+		let resolved = translate(target, root, refs)?;
+		refs.pop();
+
+		if let Value::Object(resolved) = resolved {
+			out = resolved;
+		}
+	}
+
+	for (key, value) in obj {
+		match key.as_str() {
+			"type" => nullable |= translate_type(value, &mut out),
+			"nullable" => nullable |= value.as_bool() == Some(true),
+			"properties" => {
+				let mut props = Map::new();
+				if let Value::Object(value) = value {
+					for (k, v) in value {
+						props.insert(k.clone(), translate(v, root, refs)?);
+					}
+				}
+				out.insert(key.clone(), Value::Object(props));
+			}
+			"items" => {
+				out.insert(key.clone(), translate(value, root, refs)?);
+			}
+			"anyOf" | "oneOf" => {
+				let mut variants = Vec::new();
+				for variant in value.as_array().into_iter().flatten() {
+					if variant.get("type").and_then(Value::as_str)
+						== Some("null")
+					{
+						nullable = true;
+						continue;
+					}
+					variants.push(translate(variant, root, refs)?);
+				}
+				// e.g. the anyOf of an optional field
+				if let [Value::Object(variant)] = variants.as_mut_slice() {
+					merge(&mut out, std::mem::take(variant));
+				} else {
+					out.insert("anyOf".into(), Value::Array(variants));
+				}
+			}
+			"allOf" => {
+				for part in value.as_array().into_iter().flatten() {
+					if let Value::Object(part) = translate(part, root, refs)? {
+						merge(&mut out, part);
+					}
+				}
+			}
+			"const" => {
+				out.insert("enum".into(), Value::Array(vec![value.clone()]));
+			}
+			"examples" => {
+				if let Some(example) = value.as_array().and_then(|e| e.first())
+				{
+					out.insert("example".into(), example.clone());
+				}
+			}
+			"format"
+				if value.as_str().is_some_and(|format| {
+					SUPPORTED_FORMATS.contains(&format)
+				}) =>
+			{
+				out.insert(key.clone(), value.clone());
+			}
+			k if SUPPORTED_KEYWORDS.contains(&k) => {
+				out.insert(key.clone(), value.clone());
+			}
+			_ => {}
+		}
+	}
+
+	if let Some(Value::Array(values)) = out.get_mut("enum") {
+		nullable |= values.contains(&Value::Null);
+		*values = values
+			.drain(..)
+			.filter(|value| !value.is_null())
+			.map(|value| match value {
+				Value::String(_) => value,
+				value => Value::String(value.to_string()),
+			})
+			.collect();
+		out.insert("type".into(), "STRING".into());
+	}
+
+	if nullable {
+		make_nullable(&mut out);
+	}
+
+	Ok(Value::Object(out))
+}
+
+/// Sets `nullable`, which Gemini only accepts next to a `type`, so an
+/// `anyOf` passes it on to its variants.
+fn make_nullable(out: &mut Map<String, Value>) {
+	if out.contains_key("type") {
+		out.insert("nullable".into(), Value::Bool(true));
+	} else if let Some(Value::Array(variants)) = out.get_mut("anyOf") {
+		for variant in variants {
+			if let Value::Object(variant) = variant {
+				make_nullable(variant);
+			}
+		}
+	}
+}
+
+/// Merges an `allOf` part, combining `properties` and `required`.
+fn merge(out: &mut Map<String, Value>, part: Map<String, Value>) {
+	for (key, value) in part {
+		match (key.as_str(), out.get_mut(&key), value) {
+			("properties", Some(Value::Object(props)), Value::Object(more)) => {
+				props.extend(more);
+			}
+			("required", Some(Value::Array(required)), Value::Array(more)) => {
+				for name in more {
+					if !required.contains(&name) {
+						required.push(name);
+					}
+				}
+			}
+			(_, _, value) => {
+				out.insert(key, value);
+			}
+		}
+	}
+}
+
+/// Returns whether one of the types is `"null"`.
+fn translate_type(value: &Value, out: &mut Map<String, Value>) -> bool {
+	let types: Vec<&str> = match value {
+		Value::String(ty) => vec![ty.as_str()],
+		Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+		_ => return false,
+	};
+	let nullable = types.contains(&"null");
+
+	let mut types: Vec<Value> = types
+		.into_iter()
+		.filter(|ty| *ty != "null")
+		.map(|ty| Value::String(ty.to_uppercase()))
+		.collect();
+
+	match types.len() {
+		0 => {}
+		1 => {
+			out.insert("type".into(), types.remove(0));
+		}
+		_ => {
+			let variants = types
+				.into_iter()
+				.map(|ty| {
+					let mut variant = Map::new();
+					variant.insert("type".into(), ty);
+					Value::Object(variant)
+				})
+				.collect();
+			out.insert("anyOf".into(), Value::Array(variants));
+		}
+	}
+
+	nullable
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn translates_schemas() {
+		let cases = [
+			(
+				"$ref inlining",
+				json!({
+					"type": "object",
+					"properties": {
+						"home": { "$ref": "#/$defs/address" },
+						"work": {
+							"$ref": "#/$defs/address",
+							"description": "Office"
+						}
+					},
+					"$defs": {
+						"address": {
+							"type": "object",
+							"properties": { "city": { "type": "string" } }
+						}
+					}
+				}),
+				json!({
+					"type": "OBJECT",
+					"properties": {
+						"home": {
+							"type": "OBJECT",
+							"properties": { "city": { "type": "STRING" } }
+						},
+						"work": {
+							"type": "OBJECT",
+							"properties": { "city": { "type": "STRING" } },
+							"description": "Office"
+						}
+					}
+				}),
+			),
+			(
+				"allOf merge",
+				json!({
+					"allOf": [
+						{
+							"type": "object",
+							"properties": { "a": { "type": "string" } },
+							"required": ["a"]
+						},
+						{
+							"properties": { "b": { "type": "integer" } },
+							"required": ["a", "b"]
+						}
+					]
+				}),
+				json!({
+					"type": "OBJECT",
+					"properties": {
+						"a": { "type": "STRING" },
+						"b": { "type": "INTEGER" }
+					},
+					"required": ["a", "b"]
+				}),
+			),
+			(
+				"oneOf to anyOf",
+				json!({ "oneOf": [{ "type": "string" }, { "type": "integer" }] }),
+				json!({ "anyOf": [{ "type": "STRING" }, { "type": "INTEGER" }] }),
+			),
+			(
+				"optional anyOf",
+				json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] }),
+				json!({ "type": "STRING", "nullable": true }),
+			),
+			(
+				"nullable anyOf",
+				json!({
+					"anyOf": [
+						{ "type": "string" },
+						{ "type": "integer" },
+						{ "type": "null" }
+					]
+				}),
+				json!({
+					"anyOf": [
+						{ "type": "STRING", "nullable": true },
+						{ "type": "INTEGER", "nullable": true }
+					]
+				}),
+			),
+			(
+				"type array to nullable",
+				json!({ "type": ["string", "null"] }),
+				json!({ "type": "STRING", "nullable": true }),
+			),
+			(
+				"type array to anyOf",
+				json!({ "type": ["string", "number", "null"] }),
+				json!({
+					"anyOf": [
+						{ "type": "STRING", "nullable": true },
+						{ "type": "NUMBER", "nullable": true }
+					]
+				}),
+			),
+			("only null", json!({ "type": "null" }), json!({})),
+			(
+				"format filtering",
+				json!({
+					"type": "object",
+					"properties": {
+						"at": { "type": "string", "format": "date-time" },
+						"mail": { "type": "string", "format": "email" }
+					}
+				}),
+				json!({
+					"type": "OBJECT",
+					"properties": {
+						"at": { "type": "STRING", "format": "date-time" },
+						"mail": { "type": "STRING" }
+					}
+				}),
+			),
+			(
+				"string const",
+				json!({ "const": "on" }),
+				json!({ "type": "STRING", "enum": ["on"] }),
+			),
+			(
+				"integer const",
+				json!({ "type": "integer", "const": 5 }),
+				json!({ "type": "STRING", "enum": ["5"] }),
+			),
+			(
+				"nullable enum",
+				json!({ "enum": ["a", 1, null] }),
+				json!({ "type": "STRING", "enum": ["a", "1"], "nullable": true }),
+			),
+			(
+				"unsupported keywords",
+				json!({
+					"$schema": "https://json-schema.org/draft/2020-12/schema",
+					"type": "object",
+					"additionalProperties": false,
+					"examples": [{}]
+				}),
+				json!({ "type": "OBJECT", "example": {} }),
+			),
+		];
+
+		for (name, schema, expected) in cases {
+			assert_eq!(to_gemini_schema(&schema).unwrap(), expected, "{name}");
+		}
+	}
+
+	#[test]
+	fn rejects_recursive_and_unresolved_refs() {
+		let recursive = json!({
+			"$ref": "#/$defs/node",
+			"$defs": {
+				"node": {
+					"type": "object",
+					"properties": {
+						"children": {
+							"type": "array",
+							"items": { "$ref": "#/$defs/node" }
+						}
+					}
+				}
+			}
+		});
+		assert!(matches!(
+			to_gemini_schema(&recursive),
+			Err(SchemaError::RecursiveRef(r)) if r == "#/$defs/node"
+		));
+
+		let remote = json!({ "$ref": "https://example.com/schema.json" });
+		assert!(matches!(
+			to_gemini_schema(&remote),
+			Err(SchemaError::UnresolvedRef(_))
+		));
+	}
+}
@@ -0,0 +1,134 @@
+use std::time::{Duration, Instant};
+
+use futures::{FutureExt as _, future::BoxFuture};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use crate::llms::{CredentialError, CredentialProvider};
+
+/// Default of [`MetadataToken::url`].
+pub const METADATA_TOKEN_URL: &str = "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Tokens are refreshed this long before they expire.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// The Google Cloud project and location Vertex AI requests go to, see
+/// [`Google::vertex`](super::Google::vertex).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vertex {
+	pub project: String,
+	/// A region like `"europe-west4"`, or `"global"`.
+	pub location: String,
+}
+
+impl Vertex {
+	pub fn new(
+		project: impl Into<String>,
+		location: impl Into<String>,
+	) -> Self {
+		Self {
+			project: project.into(),
+			location: location.into(),
+		}
+	}
+
+	/// The endpoint prefix of the Gemini models, e.g.
+	/// `https://europe-west4-aiplatform.googleapis.com/v1/projects/my-project/locations/europe-west4/publishers/google`.
+	pub fn base_url(&self) -> String {
+		let host = match self.location.as_str() {
+			"global" => "aiplatform.googleapis.com".into(),
+			location => format!("{location}-aiplatform.googleapis.com"),
+		};
+		format!(
+			"https://{host}/v1/projects/{}/locations/{}/publishers/google",
+			self.project, self.location
+		)
+	}
+}
+
+/// Fetches the access token of the service account attached to the
+/// workload from the metadata server, on Cloud Run, GKE or Compute Engine.
+///
+/// The token is cached until shortly before it expires.
+///
+/// ```no_run
+/// # use soe_llms::{Llms, LlmsConfig, ProviderKind, google::MetadataToken};
+/// let config = LlmsConfig::new()
+///     .google_vertex("my-project", "europe-west4")
+///     .credentials(ProviderKind::Google, MetadataToken::new());
+/// ```
+pub struct MetadataToken {
+	client: Client,
+	url: String,
+	token: Mutex<Option<(String, Instant)>>,
+}
+
+impl MetadataToken {
+	pub fn new() -> Self {
+		Self::with_client(Client::new())
+	}
+
+	pub fn with_client(client: Client) -> Self {
+		Self {
+			client,
+			url: METADATA_TOKEN_URL.into(),
+			token: Mutex::new(None),
+		}
+	}
+
+	/// Where the token is fetched from, e.g. to request scopes with
+	/// `"…/token?scopes=…"` or to use an emulator.
+	pub fn url(mut self, url: impl Into<String>) -> Self {
+		self.url = url.into();
+		self
+	}
+
+	async fn fetch(&self) -> Result<(String, Instant), CredentialError> {
+		#[derive(Deserialize)]
+		struct Token {
+			access_token: String,
+			expires_in: u64,
+		}
+
+		let requested = Instant::now();
+		let token: Token = self
+			.client
+			.get(&self.url)
+			.header("Metadata-Flavor", "Google")
+			.send()
+			.await
+			.and_then(|resp| resp.error_for_status())
+			.map_err(CredentialError::new)?
+			.json()
+			.await
+			.map_err(CredentialError::new)?;
+
+		let expires = requested + Duration::from_secs(token.expires_in);
+		Ok((token.access_token, expires))
+	}
+}
+
+impl Default for MetadataToken {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl CredentialProvider for MetadataToken {
+	fn credential(&self) -> BoxFuture<'_, Result<String, CredentialError>> {
+		async move {
+			let mut cached = self.token.lock().await;
+			if let Some((token, expires)) = &*cached
+				&& Instant::now() + REFRESH_MARGIN < *expires
+			{
+				return Ok(token.clone());
+			}
+
+			let (token, expires) = self.fetch().await?;
+			*cached = Some((token.clone(), expires));
+			Ok(token)
+		}
+		.boxed()
+	}
+}
@@ -1,8 +1,12 @@
 pub mod anthropic;
+pub mod bedrock;
 pub mod google;
 mod llms;
 pub mod mistral;
+#[cfg(feature = "test-utils")]
+pub mod mock;
 pub mod openai;
+pub mod openai_compat;
 pub mod openrouter;
 pub mod publicai;
 mod utils;
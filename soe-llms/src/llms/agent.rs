@@ -0,0 +1,301 @@
+use std::fmt;
+use std::sync::Arc;
+
+use futures::future::{self, BoxFuture, FutureExt as _};
+#[cfg(feature = "schemars")]
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{
+	Conversation, GenerationParams, Llms, LlmsError, Model, PendingToolCall,
+	ReasoningEffort, Request, Response, ResponseEvent, Tool,
+};
+
+type Handler = Arc<dyn Fn(Value) -> BoxFuture<'static, String> + Send + Sync>;
+
+/// Answers a prompt with the help of tools, sending requests and running
+/// the tool calls until the model answers without calling any.
+///
+/// ```no_run
+/// # use serde_json::{Value, json};
+/// # use soe_llms::{Agent, Llms, Model, Tool};
+/// # async fn run(llms: Llms) -> Result<(), soe_llms::LlmsError> {
+/// let agent = Agent::new(llms, Model::ClaudeSonnet5)
+///     .instructions("You are a weather assistant.")
+///     .tool(
+///         Tool {
+///             name: "weather".into(),
+///             description: "The current weather in a city.".into(),
+///             parameters: Some(json!({
+///                 "type": "object",
+///                 "properties": { "city": { "type": "string" } },
+///                 "required": ["city"],
+///             })),
+///             strict: true,
+///             cache: false,
+///         },
+///         |input: Value| async move {
+///             Ok::<_, String>(format!("Sunny in {}", input["city"]))
+///         },
+///     );
+///
+/// let output = agent.run("What's the weather in Zurich?").await?;
+/// println!("{}", output.response.text());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Agent {
+	llms: Llms,
+	pub model: Model,
+	pub instructions: String,
+	pub user_id: String,
+	pub reasoning_effort: Option<ReasoningEffort>,
+	pub params: GenerationParams,
+	/// Requests sent at most per run, fails with
+	/// [`LlmsError::MaxIterations`] if the model still calls tools after
+	/// the last one.
+	pub max_iterations: usize,
+	tools: Vec<(Tool, Handler)>,
+}
+
+/// Emitted while an [`Agent`] runs, see [`Agent::run_with`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum AgentEvent {
+	/// An event of the response to the current request.
+	Response(ResponseEvent),
+	/// The model called a tool, its handler is running.
+	ToolCall(PendingToolCall),
+	/// A tool handler returned, the output is sent with the next request.
+	ToolOutput { id: String, output: String },
+}
+
+#[derive(Debug)]
+pub struct AgentOutput {
+	/// The final response, without tool calls.
+	pub response: Response,
+	/// The whole conversation, e.g. to ask a follow-up question with
+	/// [`Agent::run_conversation`].
+	pub conversation: Conversation,
+	/// Requests sent.
+	pub iterations: usize,
+}
+
+impl Agent {
+	pub fn new(llms: Llms, model: Model) -> Self {
+		Self {
+			llms,
+			model,
+			instructions: String::new(),
+			user_id: String::new(),
+			reasoning_effort: None,
+			params: GenerationParams::default(),
+			max_iterations: 10,
+			tools: Vec::new(),
+		}
+	}
+
+	pub fn instructions(mut self, instructions: impl Into<String>) -> Self {
+		self.instructions = instructions.into();
+		self
+	}
+
+	pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+		self.user_id = user_id.into();
+		self
+	}
+
+	pub fn reasoning_effort(mut self, effort: ReasoningEffort) -> Self {
+		self.reasoning_effort = Some(effort);
+		self
+	}
+
+	pub fn params(mut self, params: GenerationParams) -> Self {
+		self.params = params;
+		self
+	}
+
+	pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+		self.max_iterations = max_iterations;
+		self
+	}
+
+	/// Offers `tool` to the model, its calls are answered by `handler` with
+	/// the call's input.
+	///
+	/// An error is sent to the model as the output, so it can retry or
+	/// answer without the tool. The handlers of parallel calls run
+	/// concurrently.
+	pub fn tool<F, Fut, E>(mut self, tool: Tool, handler: F) -> Self
+	where
+		F: Fn(Value) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<String, E>> + Send + 'static,
+		E: fmt::Display,
+	{
+		let handler: Handler = Arc::new(move |input| {
+			handler(input)
+				.map(|output| output.unwrap_or_else(|e| format!("Error: {e}")))
+				.boxed()
+		});
+		self.tools.push((tool, handler));
+		self
+	}
+
+	/// Offers a [`Tool::typed`] taking `T`, see [`Agent::tool`]. Inputs
+	/// that don't deserialize into `T` are answered with the error.
+	#[cfg(feature = "schemars")]
+	pub fn typed_tool<T, F, Fut, E>(
+		self,
+		name: impl Into<String>,
+		description: impl Into<String>,
+		handler: F,
+	) -> Self
+	where
+		T: schemars::JsonSchema + DeserializeOwned + Send + 'static,
+		F: Fn(T) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<String, E>> + Send + 'static,
+		E: fmt::Display,
+	{
+		let handler = Arc::new(handler);
+		self.tool(Tool::typed::<T>(name, description), move |input| {
+			let handler = handler.clone();
+			async move {
+				let input = T::deserialize(input)
+					.map_err(|e| format!("invalid input: {e}"))?;
+				handler(input).await.map_err(|e| e.to_string())
+			}
+		})
+	}
+
+	/// The first request of a run, without any input.
+	pub fn request(&self) -> Request {
+		Request {
+			input: vec![],
+			instructions: self.instructions.clone(),
+			model: self.model.clone(),
+			user_id: self.user_id.clone(),
+			tools: self.tools.iter().map(|(tool, _)| tool.clone()).collect(),
+			tool_choice: Default::default(),
+			reasoning_effort: self.reasoning_effort,
+			response_format: Default::default(),
+			params: self.params.clone(),
+			cache: false,
+			extra: Default::default(),
+			headers: Default::default(),
+			timeouts: Default::default(),
+			transcript: None,
+			snapshot: None,
+		}
+	}
+
+	pub async fn run(
+		&self,
+		prompt: impl Into<String>,
+	) -> Result<AgentOutput, LlmsError> {
+		self.run_with(prompt, |_| {}).await
+	}
+
+	/// Like [`Agent::run`], calling `on_event` as the responses stream in
+	/// and the tools run.
+	pub async fn run_with(
+		&self,
+		prompt: impl Into<String>,
+		on_event: impl FnMut(AgentEvent),
+	) -> Result<AgentOutput, LlmsError> {
+		let mut conversation = Conversation::new(self.request());
+		conversation.push_user(prompt);
+
+		let (response, iterations) =
+			self.run_conversation(&mut conversation, on_event).await?;
+
+		Ok(AgentOutput {
+			response,
+			conversation,
+			iterations,
+		})
+	}
+
+	/// Continues `conversation` until the model answers without calling
+	/// tools, returns the final response and the number of requests sent.
+	///
+	/// The conversation's request is sent as is, so it should offer the
+	/// agent's tools, see [`Agent::request`]. On failure the conversation
+	/// holds everything up to the failed request, with all tool calls
+	/// answered.
+	pub async fn run_conversation(
+		&self,
+		conversation: &mut Conversation,
+		mut on_event: impl FnMut(AgentEvent),
+	) -> Result<(Response, usize), LlmsError> {
+		for iteration in 1..=self.max_iterations {
+			let mut stream = self.llms.request(conversation.request()).await?;
+			while let Some(ev) = stream.next().await {
+				on_event(AgentEvent::Response(ev?));
+			}
+			let response =
+				stream.into_response().ok_or(LlmsError::Cancelled)?;
+			conversation.push_response(&response);
+
+			let calls = conversation.pending_tool_calls().to_vec();
+			if calls.is_empty() {
+				return Ok((response, iteration));
+			}
+
+			for call in &calls {
+				on_event(AgentEvent::ToolCall(call.clone()));
+			}
+			let outputs =
+				future::join_all(calls.iter().map(|call| self.call(call)))
+					.await;
+
+			for (call, output) in calls.into_iter().zip(outputs) {
+				on_event(AgentEvent::ToolOutput {
+					id: call.id.clone(),
+					output: output.clone(),
+				});
+				conversation.push_tool_output(&call.id, output);
+			}
+		}
+
+		Err(LlmsError::MaxIterations(self.max_iterations))
+	}
+
+	fn call(&self, call: &PendingToolCall) -> BoxFuture<'static, String> {
+		let handler = self
+			.tools
+			.iter()
+			.find(|(tool, _)| tool.name == call.name)
+			.map(|(_, handler)| handler);
+
+		match handler {
+			Some(handler) => handler(call.input.clone()),
+			None => future::ready(format!(
+				"Error: there is no tool named {:?}",
+				call.name
+			))
+			.boxed(),
+		}
+	}
+}
+
+impl fmt::Debug for Agent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Agent")
+			.field("model", &self.model)
+			.field("instructions", &self.instructions)
+			.field("user_id", &self.user_id)
+			.field("reasoning_effort", &self.reasoning_effort)
+			.field("params", &self.params)
+			.field("max_iterations", &self.max_iterations)
+			.field(
+				"tools",
+				&self
+					.tools
+					.iter()
+					.map(|(tool, _)| &tool.name)
+					.collect::<Vec<_>>(),
+			)
+			.finish()
+	}
+}
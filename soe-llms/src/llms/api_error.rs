@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use super::{LlmsError, ProviderKind};
+
+/// Why a provider rejected a request, see [`LlmsError::Api`].
+///
+/// Derived from the status and the error codes in the body, the codes
+/// differ per provider, e.g. Anthropic's `overloaded_error` or OpenAI's
+/// `insufficient_quota`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ApiErrorKind {
+	/// Too many requests or tokens per time unit.
+	RateLimited,
+	/// The account is out of credits or over its spending limit, retrying
+	/// won't help until that changes.
+	QuotaExceeded,
+	/// The prompt and the requested output don't fit into the model's
+	/// context window.
+	ContextLengthExceeded,
+	InvalidRequest,
+	/// The api key is missing, invalid or lacks the permission.
+	AuthFailed,
+	/// Unknown model or resource.
+	NotFound,
+	/// The provider is temporarily out of capacity.
+	Overloaded,
+	/// An internal error of the provider.
+	Server,
+	Other,
+}
+
+impl ApiErrorKind {
+	/// Returns `true` if the same request may succeed later.
+	pub fn is_retryable(self) -> bool {
+		matches!(self, Self::RateLimited | Self::Overloaded | Self::Server)
+	}
+
+	/// `codes` are the error type and code fields of the body, `status` is
+	/// `200` for errors reported in the stream.
+	fn classify(status: StatusCode, codes: &[&str], message: &str) -> Self {
+		let message = message.to_ascii_lowercase();
+		let code = |names: &[&str]| {
+			codes
+				.iter()
+				.any(|c| names.iter().any(|n| c.eq_ignore_ascii_case(n)))
+		};
+
+		if code(&["insufficient_quota", "billing_hard_limit_reached"])
+			|| status == StatusCode::PAYMENT_REQUIRED
+			|| message.contains("credit balance")
+		{
+			Self::QuotaExceeded
+		} else if code(&["context_length_exceeded", "string_above_max_length"])
+			|| message.contains("context length")
+			|| message.contains("context window")
+			|| message.contains("prompt is too long")
+			|| message.contains("exceeds the maximum number of tokens")
+			|| message.contains("input is too long")
+		{
+			Self::ContextLengthExceeded
+		} else if code(&[
+			"rate_limit_error",
+			"rate_limit_exceeded",
+			"RESOURCE_EXHAUSTED",
+			"ThrottlingException",
+		]) || status == StatusCode::TOO_MANY_REQUESTS
+		{
+			Self::RateLimited
+		} else if code(&[
+			"overloaded_error",
+			"UNAVAILABLE",
+			"ServiceUnavailableException",
+			"ModelNotReadyException",
+		]) || status.as_u16() == 529
+			|| status == StatusCode::SERVICE_UNAVAILABLE
+		{
+			Self::Overloaded
+		} else if code(&[
+			"authentication_error",
+			"permission_error",
+			"invalid_api_key",
+			"UNAUTHENTICATED",
+			"PERMISSION_DENIED",
+			"AccessDeniedException",
+			"UnrecognizedClientException",
+		]) || matches!(
+			status,
+			StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+		) {
+			Self::AuthFailed
+		} else if code(&[
+			"not_found_error",
+			"model_not_found",
+			"NOT_FOUND",
+			"ResourceNotFoundException",
+		]) || status == StatusCode::NOT_FOUND
+		{
+			Self::NotFound
+		} else if code(&[
+			"invalid_request_error",
+			"invalid_request",
+			"INVALID_ARGUMENT",
+			"FAILED_PRECONDITION",
+			"ValidationException",
+		]) || matches!(
+			status,
+			StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY
+		) {
+			Self::InvalidRequest
+		} else if code(&[
+			"api_error",
+			"server_error",
+			"INTERNAL",
+			"InternalServerException",
+			"ModelStreamErrorException",
+		]) || status.is_server_error()
+		{
+			Self::Server
+		} else {
+			Self::Other
+		}
+	}
+}
+
+impl LlmsError {
+	/// Classifies an error response, `body` is either the provider's error
+	/// JSON or, for errors reported in the stream, just the message.
+	pub(crate) fn api(
+		provider: ProviderKind,
+		status: StatusCode,
+		body: &str,
+		request_id: Option<String>,
+		retry_after: Option<Duration>,
+	) -> Self {
+		let value: Value = serde_json::from_str(body).unwrap_or_default();
+		// most nest the details in an `error` object, Mistral and vLLM
+		// sometimes put them at the top level
+		let error = match value.get("error") {
+			Some(error @ Value::Object(_)) => error,
+			_ => &value,
+		};
+
+		let codes: Vec<&str> = ["type", "code", "status"]
+			.iter()
+			.filter_map(|key| error.get(key).and_then(Value::as_str))
+			.collect();
+		let message = ["message", "detail"]
+			.iter()
+			.find_map(|key| error.get(key).and_then(Value::as_str))
+			.or_else(|| value.get("error").and_then(Value::as_str))
+			.unwrap_or(body)
+			.to_string();
+
+		let kind = ApiErrorKind::classify(status, &codes, &message);
+
+		Self::Api {
+			kind,
+			provider,
+			message,
+			retryable: kind.is_retryable(),
+			status,
+			request_id,
+			retry_after,
+		}
+	}
+
+	/// An error the provider reported in the stream, with its error type
+	/// or code.
+	pub(crate) fn api_event(
+		provider: ProviderKind,
+		code: &str,
+		message: String,
+		request_id: Option<String>,
+	) -> Self {
+		let kind = ApiErrorKind::classify(StatusCode::OK, &[code], &message);
+
+		Self::Api {
+			kind,
+			provider,
+			message,
+			retryable: kind.is_retryable(),
+			status: StatusCode::OK,
+			request_id,
+			retry_after: None,
+		}
+	}
+}
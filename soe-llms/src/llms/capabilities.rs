@@ -0,0 +1,336 @@
+use tracing::warn;
+
+use super::{Input, LlmsError, Model, Request, ResponseFormat, ToolChoice};
+
+/// A request feature a model might not support, see
+/// [`LlmsError::Unsupported`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Feature {
+	Tools,
+	StructuredOutput,
+	/// [`GenerationParams::stop`](super::GenerationParams::stop).
+	StopSequences,
+	/// [`Input::Image`].
+	Vision,
+	/// [`Input::Document`].
+	Documents,
+}
+
+/// A request feature that was changed in lenient mode, see
+/// [`LlmsConfig::lenient`](super::LlmsConfig::lenient).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Degradation {
+	/// The feature was removed from the request.
+	Stripped(Feature),
+	/// The feature was emulated through the prompt, the provider doesn't
+	/// enforce it.
+	Emulated(Feature),
+}
+
+/// What a model supports, see [`Model::capabilities`].
+///
+/// Like [`Model::context_window`] these are taken from each provider's
+/// public documentation. For [`Model::OpenRouter`] and
+/// [`Model::OpenAiCompat`] they are only conservative defaults since the
+/// real capabilities depend on whichever model is behind them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+	/// Supports [`Tool`](super::Tool)s.
+	pub tools: bool,
+	/// Can return multiple tool calls in a single response.
+	pub parallel_tools: bool,
+	/// Accepts [`Input::Image`]s.
+	pub vision: bool,
+	/// Accepts [`Input::Document`]s, e.g. PDFs.
+	pub documents: bool,
+	/// Accepts audio input.
+	pub audio: bool,
+	/// Supports [`ResponseFormat::JsonSchema`](super::ResponseFormat).
+	pub structured_output: bool,
+	/// Reasons before answering.
+	pub reasoning: bool,
+	/// Supports [`GenerationParams::stop`](super::GenerationParams::stop).
+	pub stop_sequences: bool,
+	/// Same as [`Model::context_window`].
+	pub max_context: u32,
+}
+
+impl Model {
+	/// What the model supports.
+	///
+	/// The id of a [`Model::Bedrock`] or [`Model::Custom`] isn't known, it is
+	/// assumed to be like a representative model: the provider's current
+	/// flagship, or on Bedrock the flagship of the id's
+	/// [`ModelFamily`](crate::bedrock::ModelFamily). Its capabilities and
+	/// [`Model::context_window`] may be wrong for the actual model.
+	pub fn capabilities(&self) -> Capabilities {
+		let max_context = self.context_window();
+
+		match self {
+			Model::Gpt5_6Sol | Model::Gpt5_6Terra | Model::Gpt5_6Luna => {
+				Capabilities {
+					tools: true,
+					parallel_tools: true,
+					vision: true,
+					documents: true,
+					audio: false,
+					structured_output: true,
+					reasoning: true,
+					stop_sequences: false,
+					max_context,
+				}
+			}
+
+			Model::ClaudeFable5
+			| Model::ClaudeOpus4_8
+			| Model::ClaudeSonnet5 => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: true,
+				audio: false,
+				structured_output: true,
+				reasoning: true,
+				stop_sequences: true,
+				max_context,
+			},
+			// Haiku 4.5 doesn't support adaptive thinking.
+			Model::ClaudeHaiku4_5 => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: true,
+				audio: false,
+				structured_output: true,
+				reasoning: false,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::GeminiPro3_1
+			| Model::GeminiFlash3_5
+			| Model::GeminiFlash3_1Lite => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: true,
+				audio: true,
+				structured_output: true,
+				reasoning: true,
+				stop_sequences: true,
+				max_context,
+			},
+			Model::GeminiFlashImage => Capabilities {
+				tools: false,
+				parallel_tools: false,
+				vision: true,
+				documents: false,
+				audio: false,
+				structured_output: false,
+				reasoning: false,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::Grok4_5 => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: false,
+				audio: false,
+				structured_output: true,
+				reasoning: true,
+				stop_sequences: false,
+				max_context,
+			},
+
+			Model::MistralLarge
+			| Model::MistralMedium
+			| Model::MistralSmall
+			| Model::Ministral14b => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: false,
+				audio: false,
+				structured_output: true,
+				reasoning: false,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::Apertus8bInstruct => Capabilities {
+				tools: false,
+				parallel_tools: false,
+				vision: false,
+				documents: false,
+				audio: false,
+				structured_output: false,
+				reasoning: false,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::OpenRouter(_) => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: false,
+				documents: false,
+				audio: false,
+				structured_output: true,
+				reasoning: true,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::OpenAiCompat(_) => Capabilities {
+				tools: true,
+				parallel_tools: false,
+				vision: false,
+				documents: false,
+				audio: false,
+				structured_output: true,
+				reasoning: false,
+				stop_sequences: true,
+				max_context,
+			},
+
+			Model::Custom { provider, .. } => {
+				Model::representative(*provider).capabilities()
+			}
+			Model::Bedrock(id) => {
+				Model::bedrock_representative(id).capabilities()
+			}
+
+			Model::Alias(_) => Capabilities {
+				tools: false,
+				parallel_tools: false,
+				vision: false,
+				documents: false,
+				audio: false,
+				structured_output: false,
+				reasoning: false,
+				stop_sequences: false,
+				max_context,
+			},
+
+			// accepts everything, so nothing gets degraded
+			#[cfg(feature = "test-utils")]
+			Model::Mock => Capabilities {
+				tools: true,
+				parallel_tools: true,
+				vision: true,
+				documents: true,
+				audio: true,
+				structured_output: true,
+				reasoning: true,
+				stop_sequences: true,
+				max_context,
+			},
+		}
+	}
+}
+
+impl Request {
+	/// Checks the request against the capabilities of its model.
+	///
+	/// Called by [`Llms::request`](super::Llms::request) before anything is
+	/// sent, so unsupported features fail with a descriptive
+	/// [`LlmsError::Unsupported`] instead of an opaque provider error.
+	pub fn validate(&self) -> Result<(), LlmsError> {
+		let caps = self.model.capabilities();
+
+		let unsupported = |feature| LlmsError::Unsupported {
+			feature,
+			model: self.model.clone(),
+		};
+
+		if !self.tools.is_empty() && !caps.tools {
+			return Err(unsupported(Feature::Tools));
+		}
+
+		if !matches!(self.response_format, ResponseFormat::Text)
+			&& !caps.structured_output
+		{
+			return Err(unsupported(Feature::StructuredOutput));
+		}
+
+		if self.params.stop.is_some() && !caps.stop_sequences {
+			return Err(unsupported(Feature::StopSequences));
+		}
+
+		if self.has_images() && !caps.vision {
+			return Err(unsupported(Feature::Vision));
+		}
+
+		if self.has_documents() && !caps.documents {
+			return Err(unsupported(Feature::Documents));
+		}
+
+		Ok(())
+	}
+
+	/// Strips or emulates every feature [`Request::validate`] would reject.
+	pub(crate) fn degrade(&self) -> (Request, Vec<Degradation>) {
+		let caps = self.model.capabilities();
+		let mut req = self.clone();
+		let mut degraded = Vec::new();
+
+		if !req.tools.is_empty() && !caps.tools {
+			req.tools.clear();
+			req.tool_choice = ToolChoice::Auto;
+			degraded.push(Degradation::Stripped(Feature::Tools));
+		}
+
+		if req.params.stop.is_some() && !caps.stop_sequences {
+			req.params.stop = None;
+			degraded.push(Degradation::Stripped(Feature::StopSequences));
+		}
+
+		if req.has_images() && !caps.vision {
+			req.input
+				.retain(|input| !matches!(input, Input::Image { .. }));
+			degraded.push(Degradation::Stripped(Feature::Vision));
+		}
+
+		if req.has_documents() && !caps.documents {
+			req.input
+				.retain(|input| !matches!(input, Input::Document { .. }));
+			degraded.push(Degradation::Stripped(Feature::Documents));
+		}
+
+		if !matches!(req.response_format, ResponseFormat::Text)
+			&& !caps.structured_output
+		{
+			let format = std::mem::take(&mut req.response_format);
+			if let Some(instruction) = format.instruction() {
+				if !req.instructions.is_empty() {
+					req.instructions.push_str("\n\n");
+				}
+				req.instructions.push_str(&instruction);
+			}
+			degraded.push(Degradation::Emulated(Feature::StructuredOutput));
+		}
+
+		for degradation in &degraded {
+			warn!("{degradation:?} for {:?}", self.model);
+		}
+
+		(req, degraded)
+	}
+
+	fn has_images(&self) -> bool {
+		self.input
+			.iter()
+			.any(|input| matches!(input, Input::Image { .. }))
+	}
+
+	pub(super) fn has_documents(&self) -> bool {
+		self.input
+			.iter()
+			.any(|input| matches!(input, Input::Document { .. }))
+	}
+}
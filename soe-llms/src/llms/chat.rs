@@ -0,0 +1,314 @@
+//! The message format of the chat completions API, shared by the providers
+//! speaking it: xAI, Mistral, OpenRouter, PublicAI and OpenAI compatible
+//! servers.
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+	Input, LlmResponseEvent,
+	history::{self, AssistantPart, ToolOutput, Turn, UserPart},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "role", rename_all = "lowercase")]
+pub enum ApiMessage {
+	System {
+		content: String,
+	},
+	User {
+		content: ApiUserContent,
+	},
+	Assistant {
+		#[serde(skip_serializing_if = "Option::is_none")]
+		content: Option<String>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		tool_calls: Option<Vec<ApiToolCall>>,
+	},
+	Tool {
+		tool_call_id: String,
+		content: String,
+	},
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ApiUserContent {
+	Text(String),
+	Parts(Vec<ApiContentPart>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiContentPart {
+	Text { text: String },
+	ImageUrl { image_url: ApiImageUrl },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiImageUrl {
+	/// A `data:` url for base64 images.
+	pub url: String,
+}
+
+impl ApiMessage {
+	/// Every text of a user turn is its own message, images can only be
+	/// sent as parts of one message with the texts around them.
+	fn user_turn(parts: Vec<UserPart>) -> Vec<Self> {
+		if !parts.iter().any(UserPart::is_image) {
+			return parts
+				.into_iter()
+				.filter_map(|part| match part {
+					UserPart::Text(text) => Some(ApiMessage::User {
+						content: ApiUserContent::Text(text),
+					}),
+					UserPart::Image(_) | UserPart::Document { .. } => None,
+				})
+				.collect();
+		}
+
+		let content = parts
+			.into_iter()
+			.filter_map(|part| match part {
+				UserPart::Text(text) => Some(ApiContentPart::Text { text }),
+				UserPart::Image(image) => Some(ApiContentPart::ImageUrl {
+					image_url: ApiImageUrl {
+						url: image.to_url(),
+					},
+				}),
+				// rejected by Request::validate
+				UserPart::Document { .. } => None,
+			})
+			.collect();
+		vec![ApiMessage::User {
+			content: ApiUserContent::Parts(content),
+		}]
+	}
+
+	/// Encodes the history, an assistant turn becomes a single message with
+	/// all of its tool calls, followed by one tool message per output.
+	pub fn from_history(input: &[Input]) -> Vec<Self> {
+		let mut messages = Vec::new();
+
+		for turn in history::turns(input) {
+			match turn {
+				Turn::System(content) => {
+					messages.push(ApiMessage::System { content })
+				}
+				Turn::User(parts) => messages.extend(Self::user_turn(parts)),
+				Turn::Assistant(parts) => {
+					let content = history::assistant_text(&parts);
+					let tool_calls: Vec<_> = parts
+						.into_iter()
+						.filter_map(|part| match part {
+							AssistantPart::ToolCall {
+								id, name, input, ..
+							} => Some(ApiToolCall {
+								id,
+								kind: "function".into(),
+								function: ApiToolCallFunction {
+									name,
+									arguments: input.to_string(),
+								},
+							}),
+							AssistantPart::Text(_) => None,
+						})
+						.collect();
+
+					messages.push(ApiMessage::Assistant {
+						content,
+						tool_calls: Some(tool_calls).filter(|c| !c.is_empty()),
+					});
+				}
+				Turn::ToolOutputs(outputs) => {
+					messages.extend(outputs.into_iter().map(
+						|ToolOutput { id, output }| ApiMessage::Tool {
+							tool_call_id: id,
+							content: output,
+						},
+					))
+				}
+			}
+		}
+
+		messages
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToolCall {
+	pub id: String,
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub function: ApiToolCallFunction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToolCallFunction {
+	pub name: String,
+	/// JSON-encoded arguments string.
+	pub arguments: String,
+}
+
+/// `delta.audio` of a streamed chunk, only sent for audio output.
+#[derive(Debug, Deserialize)]
+pub struct ApiAudioDelta {
+	/// Base64 encoded audio.
+	pub data: Option<String>,
+	/// The next piece of the spoken text.
+	pub transcript: Option<String>,
+}
+
+impl ApiAudioDelta {
+	/// The events of the delta, the transcript first.
+	pub(crate) fn into_events(self) -> impl Iterator<Item = LlmResponseEvent> {
+		let transcript = self
+			.transcript
+			.filter(|t| !t.is_empty())
+			.map(|content| LlmResponseEvent::TranscriptDelta { content });
+		let audio = self
+			.data
+			.filter(|d| !d.is_empty())
+			.map(|data| LlmResponseEvent::AudioDelta { data });
+		transcript.into_iter().chain(audio)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+	use crate::llms::{ImageSource, Role};
+
+	fn encode(input: &[Input]) -> serde_json::Value {
+		serde_json::to_value(ApiMessage::from_history(input)).unwrap()
+	}
+
+	#[test]
+	fn encodes_interleaved_history() {
+		assert_eq!(
+			encode(&history::tests::interleaved()),
+			json!([
+				{ "role": "system", "content": "Be brief." },
+				{ "role": "user", "content": "Weather in Bern and Zurich?" },
+				{
+					"role": "assistant",
+					"content": "Checking.",
+					"tool_calls": [
+						{
+							"id": "call_1",
+							"type": "function",
+							"function": {
+								"name": "weather",
+								"arguments": "{\"city\":\"Bern\"}"
+							}
+						},
+						{
+							"id": "call_2",
+							"type": "function",
+							"function": {
+								"name": "weather",
+								"arguments": "{\"city\":\"Zurich\"}"
+							}
+						}
+					]
+				},
+				{ "role": "tool", "tool_call_id": "call_1", "content": "sunny" },
+				{ "role": "tool", "tool_call_id": "call_2", "content": "rainy" },
+				{
+					"role": "user",
+					"content": [
+						{ "type": "text", "text": "And here?" },
+						{
+							"type": "image_url",
+							"image_url": { "url": "https://example.com/a.png" }
+						}
+					]
+				},
+				{ "role": "assistant", "content": "Cloudy." }
+			])
+		);
+	}
+
+	#[test]
+	fn user_texts_without_images_stay_separate_messages() {
+		let input = [
+			Input::Text {
+				role: Role::User,
+				content: "a".into(),
+			},
+			Input::Text {
+				role: Role::User,
+				content: "b".into(),
+			},
+		];
+		assert_eq!(
+			encode(&input),
+			json!([
+				{ "role": "user", "content": "a" },
+				{ "role": "user", "content": "b" }
+			])
+		);
+	}
+
+	#[test]
+	fn base64_images_become_data_urls() {
+		let input = [Input::Image {
+			data: ImageSource::Base64 {
+				data: "iVBORw0K".into(),
+				mime_type: "image/png".into(),
+			},
+		}];
+		assert_eq!(
+			encode(&input),
+			json!([{
+				"role": "user",
+				"content": [{
+					"type": "image_url",
+					"image_url": { "url": "data:image/png;base64,iVBORw0K" }
+				}]
+			}])
+		);
+	}
+
+	#[test]
+	fn tool_calls_without_text_have_no_content() {
+		let input = &history::tests::interleaved()[3..4];
+		assert_eq!(
+			encode(input),
+			json!([{
+				"role": "assistant",
+				"tool_calls": [{
+					"id": "call_1",
+					"type": "function",
+					"function": {
+						"name": "weather",
+						"arguments": "{\"city\":\"Bern\"}"
+					}
+				}]
+			}])
+		);
+	}
+
+	#[test]
+	fn audio_deltas_stream_the_transcript_first() {
+		let delta: ApiAudioDelta = serde_json::from_value(json!({
+			"id": "audio_1",
+			"data": "UklGR",
+			"transcript": "Hello"
+		}))
+		.unwrap();
+		let events: Vec<_> = delta.into_events().collect();
+		assert!(matches!(
+			events.as_slice(),
+			[
+				LlmResponseEvent::TranscriptDelta { content },
+				LlmResponseEvent::AudioDelta { data },
+			] if content == "Hello" && data == "UklGR"
+		));
+
+		let delta: ApiAudioDelta =
+			serde_json::from_value(json!({ "transcript": "" })).unwrap();
+		assert_eq!(delta.into_events().count(), 0);
+	}
+}
@@ -0,0 +1,346 @@
+//! Token-aware text splitting, e.g. for embeddings or
+//! [`Llms::map_reduce`](super::Llms::map_reduce).
+//!
+//! Token counts are estimated at about four characters per token, which is
+//! good enough for budgeting but not for billing.
+
+use std::ops::Range;
+
+const CHARS_PER_TOKEN: usize = 4;
+
+pub fn estimate_tokens(text: &str) -> u32 {
+	(text.len() / CHARS_PER_TOKEN)
+		.try_into()
+		.unwrap_or(u32::MAX)
+}
+
+/// Where [`Splitter`] prefers to end a chunk. If no boundary of the kind
+/// fits, the next finer one is used, down to whitespace and finally any
+/// character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Boundary {
+	/// Blank lines, then sentences.
+	#[default]
+	Paragraph,
+	Sentence,
+	/// Headings, then blank lines, then sentences. Code fences are only
+	/// split if a single one doesn't fit.
+	Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+	Heading,
+	Paragraph,
+	Sentence,
+	Whitespace,
+}
+
+/// Splits text into chunks of at most about [`Splitter::max_tokens`].
+///
+/// Chunks are trimmed slices of the input, consecutive chunks share about
+/// [`Splitter::overlap_tokens`] so context at the edges isn't lost.
+#[derive(Debug, Clone, Copy)]
+pub struct Splitter {
+	pub max_tokens: u32,
+	/// Capped at half of `max_tokens`.
+	pub overlap_tokens: u32,
+	pub boundary: Boundary,
+}
+
+impl Splitter {
+	pub fn new(max_tokens: u32) -> Self {
+		Self {
+			max_tokens,
+			overlap_tokens: 0,
+			boundary: Boundary::default(),
+		}
+	}
+
+	pub fn overlap_tokens(mut self, overlap_tokens: u32) -> Self {
+		self.overlap_tokens = overlap_tokens;
+		self
+	}
+
+	pub fn boundary(mut self, boundary: Boundary) -> Self {
+		self.boundary = boundary;
+		self
+	}
+
+	pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+		let max_len = (self.max_tokens as usize)
+			.saturating_mul(CHARS_PER_TOKEN)
+			.max(1);
+		let overlap_len = (self.overlap_tokens as usize)
+			.saturating_mul(CHARS_PER_TOKEN)
+			.min(max_len / 2);
+
+		let levels: &[Level] = match self.boundary {
+			Boundary::Paragraph => {
+				&[Level::Paragraph, Level::Sentence, Level::Whitespace]
+			}
+			Boundary::Sentence => &[Level::Sentence, Level::Whitespace],
+			Boundary::Markdown => &[
+				Level::Heading,
+				Level::Paragraph,
+				Level::Sentence,
+				Level::Whitespace,
+			],
+		};
+		let fences = match self.boundary {
+			Boundary::Markdown => code_fences(text),
+			_ => Vec::new(),
+		};
+
+		let mut chunks = Vec::new();
+		let mut start = 0;
+		// the end of the previous chunk, the next one has to go further
+		let mut prev_end = 0;
+
+		loop {
+			let rest = &text[start..];
+			if rest.len() <= max_len {
+				push_trimmed(&mut chunks, rest);
+				break;
+			}
+
+			let window = &rest[..rest.floor_char_boundary(max_len)];
+			let min = prev_end - start;
+			let end = levels
+				.iter()
+				.find_map(|&level| {
+					cuts(window, level).filter(|&cut| cut > min).find(|&cut| {
+						level == Level::Whitespace
+							|| !in_fence(&fences, start + cut)
+					})
+				})
+				// a single char can be longer than max_len
+				.unwrap_or_else(|| {
+					window.len().max(rest.ceil_char_boundary(1))
+				}) + start;
+
+			push_trimmed(&mut chunks, &text[start..end]);
+			start = overlap_start(text, start, end, overlap_len);
+			prev_end = end;
+		}
+
+		chunks
+	}
+}
+
+fn push_trimmed<'a>(chunks: &mut Vec<&'a str>, chunk: &'a str) {
+	let chunk = chunk.trim();
+	if !chunk.is_empty() {
+		chunks.push(chunk);
+	}
+}
+
+/// Possible chunk ends in `window`, from the back.
+fn cuts(window: &str, level: Level) -> Box<dyn Iterator<Item = usize> + '_> {
+	match level {
+		Level::Heading => {
+			Box::new(window.rmatch_indices("\n#").map(|(i, _)| i + 1))
+		}
+		Level::Paragraph => {
+			Box::new(window.rmatch_indices("\n\n").map(|(i, _)| i + 2))
+		}
+		Level::Sentence => Box::new(
+			window
+				.rmatch_indices(['.', '!', '?'])
+				.map(|(i, _)| i + 1)
+				.filter(|&i| window[i..].starts_with(char::is_whitespace)),
+		),
+		Level::Whitespace => {
+			Box::new(window.rmatch_indices(char::is_whitespace).map(|(i, _)| i))
+		}
+	}
+}
+
+/// Start of the next chunk, about `overlap_len` before `end` at a word
+/// boundary.
+fn overlap_start(
+	text: &str,
+	start: usize,
+	end: usize,
+	overlap_len: usize,
+) -> usize {
+	if overlap_len == 0 {
+		return end;
+	}
+
+	let back =
+		text.ceil_char_boundary(end.saturating_sub(overlap_len).max(start));
+	match text[back..end].find(char::is_whitespace) {
+		Some(i) if back + i > start => back + i,
+		_ => end,
+	}
+}
+
+/// Byte ranges of fenced code blocks, an unclosed fence runs until the end.
+fn code_fences(text: &str) -> Vec<Range<usize>> {
+	let mut fences = Vec::new();
+	let mut open = None;
+	let mut offset = 0;
+
+	for line in text.split_inclusive('\n') {
+		if line.trim_start().starts_with("```") {
+			match open.take() {
+				Some(start) => fences.push(start..offset + line.len()),
+				None => open = Some(offset),
+			}
+		}
+		offset += line.len();
+	}
+
+	if let Some(start) = open {
+		fences.push(start..text.len());
+	}
+
+	fences
+}
+
+fn in_fence(fences: &[Range<usize>], pos: usize) -> bool {
+	fences
+		.iter()
+		.any(|fence| fence.start < pos && pos < fence.end)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Byte offset of `chunk` in `text`, chunks are slices of the input.
+	fn offset(text: &str, chunk: &str) -> usize {
+		chunk.as_ptr() as usize - text.as_ptr() as usize
+	}
+
+	fn words(text: &str) -> Vec<&str> {
+		text.split_whitespace().collect()
+	}
+
+	#[test]
+	fn short_text_is_one_trimmed_chunk() {
+		let chunks = Splitter::new(100).split("  Hello world.\n");
+		assert_eq!(chunks, ["Hello world."]);
+		assert!(Splitter::new(100).split(" \n ").is_empty());
+	}
+
+	#[test]
+	fn prefers_paragraphs_then_sentences() {
+		let text = "One two three.\n\nFour five. Six seven eight nine ten.";
+		// 32 chars per chunk
+		let chunks = Splitter::new(8).split(text);
+		assert_eq!(
+			chunks,
+			["One two three.", "Four five.", "Six seven eight nine ten."]
+		);
+	}
+
+	#[test]
+	fn non_ascii_text_is_cut_at_char_boundaries() {
+		let text = "äöü ß€ 😀😀 ".repeat(20);
+		let splitter = Splitter::new(3);
+		let chunks = splitter.split(&text);
+
+		assert!(chunks.len() > 1);
+		for chunk in &chunks {
+			assert!(chunk.len() <= 12, "{chunk:?}");
+		}
+		assert_eq!(words(&chunks.join(" ")), words(&text));
+	}
+
+	#[test]
+	fn overlong_word_is_cut_anywhere() {
+		let word = "a".repeat(50);
+		let chunks = Splitter::new(2).split(&word);
+
+		assert_eq!(chunks.len(), 7);
+		assert!(chunks.iter().all(|chunk| chunk.len() <= 8));
+		assert_eq!(chunks.concat(), word);
+	}
+
+	#[test]
+	fn char_longer_than_max_is_its_own_chunk() {
+		let chunks = Splitter::new(0).split("😀😀");
+		assert_eq!(chunks, ["😀", "😀"]);
+	}
+
+	#[test]
+	fn overlap_repeats_the_end_of_the_previous_chunk() {
+		let text = (0..40).map(|i| format!("w{i:02}")).collect::<Vec<_>>();
+		let text = text.join(" ");
+		let chunks = Splitter::new(5).overlap_tokens(2).split(&text);
+
+		assert_eq!(chunks[..2], ["w00 w01 w02 w03 w04", "w03 w04 w05 w06"]);
+		for pair in chunks.windows(2) {
+			let prev_end = offset(&text, pair[0]) + pair[0].len();
+			let start = offset(&text, pair[1]);
+			assert!(start < prev_end && prev_end - start <= 8, "{pair:?}");
+		}
+		assert!(chunks.last().unwrap().ends_with("w39"));
+	}
+
+	#[test]
+	fn overlap_of_half_or_more_still_progresses() {
+		let text = "lorem ipsum dolor sit amet ".repeat(30);
+		let half = Splitter::new(8).overlap_tokens(4).split(&text);
+
+		let mut prev = None;
+		for chunk in &half {
+			assert!(chunk.len() <= 32);
+			let start = offset(&text, chunk);
+			assert!(prev.is_none_or(|prev| start > prev), "{half:?}");
+			prev = Some(start);
+		}
+		assert!(half.last().unwrap().ends_with("amet"));
+
+		// capped at half of max_tokens
+		for overlap in [5, 8, 100] {
+			let chunks = Splitter::new(8).overlap_tokens(overlap).split(&text);
+			assert_eq!(chunks, half, "{overlap}");
+		}
+	}
+
+	#[test]
+	fn overlap_without_whitespace_still_progresses() {
+		let word = "a".repeat(50);
+		let chunks = Splitter::new(2).overlap_tokens(1).split(&word);
+		assert_eq!(chunks.concat(), word);
+	}
+
+	#[test]
+	fn markdown_splits_at_headings() {
+		let text = "# One\nSome text here.\n# Two\nMore text here.";
+		let chunks = Splitter::new(6).boundary(Boundary::Markdown).split(text);
+		assert_eq!(
+			chunks,
+			["# One\nSome text here.", "# Two\nMore text here."]
+		);
+	}
+
+	#[test]
+	fn markdown_keeps_fences_together() {
+		let text = "Intro text.\n\n```\nlet a = 1.\n\nlet b = 2.\n```\n\nEnd.";
+		let chunks = Splitter::new(11).boundary(Boundary::Markdown).split(text);
+		assert_eq!(
+			chunks,
+			["Intro text.", "```\nlet a = 1.\n\nlet b = 2.\n```\n\nEnd."]
+		);
+	}
+
+	#[test]
+	fn unclosed_fence_runs_until_the_end() {
+		let fences = code_fences("a\n```\nb\n\nc");
+		assert_eq!(fences.len(), 1);
+		assert_eq!(fences[0], 2..10);
+
+		let text = "Intro.\n\n```\nfn a() {}\n\nfn b() {}\n\nfn c() {}";
+		let chunks = Splitter::new(6).boundary(Boundary::Markdown).split(text);
+
+		// no paragraph cut inside the fence, only whitespace ones
+		assert_eq!(chunks[0], "Intro.");
+		assert!(chunks.iter().skip(1).all(|chunk| chunk.len() <= 24));
+		assert_eq!(words(&chunks.join(" ")), words(text));
+	}
+}
@@ -0,0 +1,947 @@
+use std::{
+	collections::HashMap,
+	env, fs, io,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use reqwest::{Certificate, Identity};
+use serde::Deserialize;
+
+use super::{
+	Compression, FallbackPolicy, HttpConfig, LlmsConfig, Model, Price,
+	ProviderKind, RateLimit, RetryPolicy, Timeouts,
+};
+use crate::{bedrock::AwsCredentials, openrouter::ProviderPreferences};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+	#[error("JSON error: {0}")]
+	Json(#[from] serde_json::Error),
+	#[cfg(feature = "toml")]
+	#[error("TOML error: {0}")]
+	Toml(#[from] toml::de::Error),
+	#[error("Invalid certificate {path:?}: {error}")]
+	Certificate {
+		path: PathBuf,
+		error: reqwest::Error,
+	},
+	#[error("Unsupported config format: {0}")]
+	UnsupportedFormat(String),
+	/// None of the api key variables is set, see [`LlmsConfig::from_env`].
+	#[error("No api key found, set one of {0:?}")]
+	MissingEnv(Vec<&'static str>),
+	/// These variables are set but empty or not valid unicode.
+	#[error("Invalid environment variables {0:?}")]
+	InvalidEnv(Vec<&'static str>),
+}
+
+/// Api key variables per provider, the first one set wins.
+const ENV_API_KEYS: &[(ProviderKind, &[&str])] = &[
+	(ProviderKind::OpenAi, &["OPENAI_API_KEY"]),
+	(ProviderKind::Anthropic, &["ANTHROPIC_API_KEY"]),
+	(ProviderKind::Google, &["GEMINI_API_KEY", "GOOGLE_API_KEY"]),
+	(ProviderKind::XAi, &["XAI_API_KEY"]),
+	(ProviderKind::Mistral, &["MISTRAL_API_KEY"]),
+	(ProviderKind::PublicAi, &["PUBLICAI_API_KEY"]),
+	(ProviderKind::OpenRouter, &["OPENROUTER_API_KEY"]),
+	(ProviderKind::OpenAiCompat, &["OPENAI_COMPAT_API_KEY"]),
+];
+
+/// Base url variables per provider, the first one set wins. The OpenAI
+/// compatible server is configured by [`ENV_OPENAI_COMPAT_BASE_URL`].
+const ENV_BASE_URLS: &[(ProviderKind, &[&str])] = &[
+	(ProviderKind::OpenAi, &["OPENAI_BASE_URL"]),
+	(ProviderKind::Anthropic, &["ANTHROPIC_BASE_URL"]),
+	(
+		ProviderKind::Google,
+		&["GEMINI_BASE_URL", "GOOGLE_BASE_URL"],
+	),
+	(ProviderKind::XAi, &["XAI_BASE_URL"]),
+	(ProviderKind::Mistral, &["MISTRAL_BASE_URL"]),
+	(ProviderKind::PublicAi, &["PUBLICAI_BASE_URL"]),
+	(ProviderKind::OpenRouter, &["OPENROUTER_BASE_URL"]),
+	(ProviderKind::Bedrock, &["AWS_ENDPOINT_URL_BEDROCK_RUNTIME"]),
+];
+
+const ENV_LENIENT: &str = "LLMS_LENIENT";
+const ENV_OPENAI_ORGANIZATION: &str = "OPENAI_ORG_ID";
+const ENV_OPENAI_PROJECT: &str = "OPENAI_PROJECT_ID";
+const ENV_OPENAI_COMPAT_BASE_URL: &str = "OPENAI_COMPAT_BASE_URL";
+const ENV_AWS_REGION: &[&str] = &["AWS_REGION", "AWS_DEFAULT_REGION"];
+const ENV_AWS_ACCESS_KEY_ID: &str = "AWS_ACCESS_KEY_ID";
+const ENV_AWS_SECRET_ACCESS_KEY: &str = "AWS_SECRET_ACCESS_KEY";
+const ENV_AWS_SESSION_TOKEN: &str = "AWS_SESSION_TOKEN";
+
+/// The layout of a config file, see [`LlmsConfig::from_path`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+	#[serde(default)]
+	lenient: bool,
+	#[serde(default)]
+	http: FileHttp,
+	#[serde(default)]
+	timeouts: FileTimeouts,
+	retry: Option<FileRetry>,
+	fallback: Option<FileFallback>,
+	#[serde(default)]
+	aliases: HashMap<String, Model>,
+	#[serde(default)]
+	prices: Vec<FilePrice>,
+	openai: Option<FileOpenAi>,
+	anthropic: Option<FileProvider>,
+	google: Option<FileProvider>,
+	xai: Option<FileProvider>,
+	mistral: Option<FileProvider>,
+	publicai: Option<FileProvider>,
+	openrouter: Option<FileOpenRouter>,
+	openai_compat: Option<FileOpenAiCompat>,
+	bedrock: Option<FileBedrock>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileProvider {
+	api_key: String,
+	base_url: Option<String>,
+	http: Option<FileHttp>,
+	rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOpenAi {
+	api_key: String,
+	organization: Option<String>,
+	project: Option<String>,
+	base_url: Option<String>,
+	http: Option<FileHttp>,
+	rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOpenRouter {
+	api_key: String,
+	app_url: Option<String>,
+	app_name: Option<String>,
+	provider: Option<ProviderPreferences>,
+	base_url: Option<String>,
+	http: Option<FileHttp>,
+	rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOpenAiCompat {
+	base_url: String,
+	api_key: Option<String>,
+	http: Option<FileHttp>,
+	rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileBedrock {
+	region: String,
+	access_key_id: String,
+	secret_access_key: String,
+	session_token: Option<String>,
+	base_url: Option<String>,
+	http: Option<FileHttp>,
+	rate_limit: Option<RateLimit>,
+}
+
+/// See [`Timeouts`], in seconds.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileTimeouts {
+	read: Option<u64>,
+	total: Option<u64>,
+}
+
+/// See [`RetryPolicy`], the backoffs in seconds, fractions allowed.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileRetry {
+	max_retries: u32,
+	initial_backoff: Option<f64>,
+	max_backoff: Option<f64>,
+	max_retry_after: Option<f64>,
+}
+
+/// See [`FallbackPolicy`], `attempt_timeout` in seconds.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileFallback {
+	models: Vec<Model>,
+	attempt_timeout: Option<u64>,
+}
+
+/// A [`Price`] of `model`, in USD per million tokens.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FilePrice {
+	model: Model,
+	input: f64,
+	output: f64,
+	cached_input: Option<f64>,
+	cache_write: Option<f64>,
+}
+
+/// Durations are given in seconds, certificates as paths to PEM files.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileHttp {
+	#[cfg(unix)]
+	unix_socket: Option<PathBuf>,
+	connect_timeout: Option<u64>,
+	read_timeout: Option<u64>,
+	timeout: Option<u64>,
+	pool_max_idle_per_host: Option<usize>,
+	pool_idle_timeout: Option<u64>,
+	http2_keep_alive_interval: Option<u64>,
+	tcp_nodelay: Option<bool>,
+	identity: Option<PathBuf>,
+	#[serde(default)]
+	root_certificates: Vec<PathBuf>,
+	#[serde(default)]
+	accept_encoding: Vec<Compression>,
+	request_compression: Option<Compression>,
+}
+
+impl LlmsConfig {
+	/// Reads the config from a `.json` or, with the `toml` feature, a
+	/// `.toml` file.
+	///
+	/// Every provider has its own table with an `api_key`, optionally a
+	/// `base_url` and an `http` table, which replaces the top level `http`
+	/// table for that provider:
+	///
+	/// `timeouts` sets [`LlmsConfig::timeouts`] in seconds, `retry` the
+	/// [`LlmsConfig::retry`] policy with backoffs in seconds and `fallback`
+	/// the [`LlmsConfig::fallback`] policy. `aliases` maps names to models
+	/// and `prices` overrides [`Model::price`], see [`LlmsConfig::alias`]
+	/// and [`LlmsConfig::price`]. Models are given by their variant name,
+	/// e.g. `"Gpt5_6Luna"` or `{ OpenAiCompat = "qwen3:8b" }`. Every
+	/// provider table also takes a [`LlmsConfig::rate_limit`].
+	///
+	/// ```toml
+	/// lenient = true
+	///
+	/// [timeouts]
+	/// read = 60
+	/// total = 600
+	///
+	/// [retry]
+	/// max_retries = 3
+	/// initial_backoff = 0.5
+	/// max_backoff = 30
+	///
+	/// [fallback]
+	/// models = ["ClaudeSonnet5", "GeminiFlash3_5"]
+	/// attempt_timeout = 20
+	///
+	/// [aliases]
+	/// fast = "Gpt5_6Luna"
+	/// local = { OpenAiCompat = "qwen3:8b" }
+	///
+	/// [[prices]]
+	/// model = { OpenAiCompat = "qwen3:8b" }
+	/// input = 0.0
+	/// output = 0.0
+	///
+	/// [http]
+	/// connect_timeout = 5
+	/// pool_idle_timeout = 90
+	/// tcp_nodelay = true
+	///
+	/// [openai]
+	/// api_key = "sk-..."
+	/// organization = "org-..."
+	/// project = "proj_..."
+	/// rate_limit = { requests_per_minute = 500, max_concurrent = 20 }
+	///
+	/// [mistral]
+	/// api_key = "..."
+	/// base_url = "https://llm-proxy.internal/mistral/v1"
+	/// http = { identity = "/etc/llms/client.pem" }
+	///
+	/// [openrouter]
+	/// api_key = "sk-or-..."
+	/// app_url = "https://example.com"
+	/// app_name = "Example"
+	/// provider = { order = ["anthropic"], allow_fallbacks = false }
+	///
+	/// [openai_compat]
+	/// base_url = "http://localhost:11434/v1"
+	///
+	/// [bedrock]
+	/// region = "us-east-1"
+	/// access_key_id = "AKIA..."
+	/// secret_access_key = "..."
+	/// ```
+	pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+		let path = path.as_ref();
+		let content = fs::read_to_string(path)?;
+
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("json") => Self::from_json(&content),
+			#[cfg(feature = "toml")]
+			Some("toml") => Self::from_toml(&content),
+			ext => Err(ConfigError::UnsupportedFormat(
+				ext.unwrap_or_default().into(),
+			)),
+		}
+	}
+
+	/// Parses the config from json, see [`LlmsConfig::from_path`].
+	pub fn from_json(json: &str) -> Result<Self, ConfigError> {
+		serde_json::from_str::<FileConfig>(json)?.try_into()
+	}
+
+	/// Parses the config from toml, see [`LlmsConfig::from_path`].
+	#[cfg(feature = "toml")]
+	pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+		toml::from_str::<FileConfig>(toml)?.try_into()
+	}
+
+	/// Reads the config from environment variables, failing if not a single
+	/// api key is set.
+	///
+	/// | Provider   | Api key                                 | Base url                                  |
+	/// |------------|-----------------------------------------|-------------------------------------------|
+	/// | OpenAI     | `OPENAI_API_KEY`                        | `OPENAI_BASE_URL`                         |
+	/// | Anthropic  | `ANTHROPIC_API_KEY`                     | `ANTHROPIC_BASE_URL`                      |
+	/// | Google     | `GEMINI_API_KEY`, then `GOOGLE_API_KEY` | `GEMINI_BASE_URL`, then `GOOGLE_BASE_URL` |
+	/// | xAI        | `XAI_API_KEY`                           | `XAI_BASE_URL`                            |
+	/// | Mistral    | `MISTRAL_API_KEY`                       | `MISTRAL_BASE_URL`                        |
+	/// | PublicAI   | `PUBLICAI_API_KEY`                      | `PUBLICAI_BASE_URL`                       |
+	/// | OpenRouter | `OPENROUTER_API_KEY`                    | `OPENROUTER_BASE_URL`                     |
+	///
+	/// Where a provider has two variables the first one set wins. A base
+	/// url replaces the provider's default and one from a config file, see
+	/// [`LlmsConfig::base_url`], it doesn't configure the provider without
+	/// an api key.
+	///
+	/// An OpenAI compatible server is configured through
+	/// `OPENAI_COMPAT_BASE_URL`, see [`LlmsConfig::openai_compat`],
+	/// `OPENAI_COMPAT_API_KEY` is only needed if it checks the key.
+	///
+	/// Bedrock is configured if `AWS_REGION` or `AWS_DEFAULT_REGION`,
+	/// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` are set, see
+	/// [`LlmsConfig::bedrock`], `AWS_SESSION_TOKEN` for temporary
+	/// credentials and `AWS_ENDPOINT_URL_BEDROCK_RUNTIME` for another
+	/// endpoint.
+	///
+	/// [`LlmsConfig::lenient`] is read from `LLMS_LENIENT` (`true` or
+	/// `false`), [`LlmsConfig::openai_organization`] and
+	/// [`LlmsConfig::openai_project`] from `OPENAI_ORG_ID` and
+	/// `OPENAI_PROJECT_ID`.
+	pub fn from_env() -> Result<Self, ConfigError> {
+		Self::from_vars(|var| env::var(var))
+	}
+
+	fn from_vars(lookup: impl Fn(&str) -> EnvVar) -> Result<Self, ConfigError> {
+		let config = Self::new().with_vars(lookup)?;
+
+		let any_key = ENV_API_KEYS
+			.iter()
+			.any(|(kind, _)| config.api_key(*kind).is_some())
+			|| config.base_urls.contains_key(&ProviderKind::OpenAiCompat)
+			|| config.bedrock_credentials.is_some();
+		if !any_key {
+			return Err(ConfigError::MissingEnv(
+				ENV_API_KEYS
+					.iter()
+					.flat_map(|(_, vars)| *vars)
+					.copied()
+					.chain([ENV_OPENAI_COMPAT_BASE_URL, ENV_AWS_ACCESS_KEY_ID])
+					.collect(),
+			));
+		}
+
+		Ok(config)
+	}
+
+	/// Overrides the config with every variable [`LlmsConfig::from_env`]
+	/// reads that is set.
+	///
+	/// The precedence is config file, then environment, then builder calls
+	/// made afterwards:
+	/// `LlmsConfig::from_path(path)?.with_env()?.openai(key)`.
+	///
+	/// Fails with every variable that is set but empty or not valid
+	/// unicode.
+	pub fn with_env(self) -> Result<Self, ConfigError> {
+		self.with_vars(|var| env::var(var))
+	}
+
+	/// [`LlmsConfig::with_env`] with the variables from `lookup`.
+	fn with_vars(
+		mut self,
+		lookup: impl Fn(&str) -> EnvVar,
+	) -> Result<Self, ConfigError> {
+		let mut invalid = Vec::new();
+
+		for (kind, vars) in ENV_API_KEYS {
+			let key = vars
+				.iter()
+				.find_map(|var| read_env(&lookup, var, &mut invalid));
+
+			if let Some(key) = key {
+				self.set_api_key(*kind, Some(key));
+			}
+		}
+
+		match read_env(&lookup, ENV_LENIENT, &mut invalid).as_deref() {
+			Some("true") => self.lenient = true,
+			Some("false") => self.lenient = false,
+			Some(_) => invalid.push(ENV_LENIENT),
+			None => {}
+		}
+
+		if let Some(id) =
+			read_env(&lookup, ENV_OPENAI_ORGANIZATION, &mut invalid)
+		{
+			self.openai_organization = Some(id);
+		}
+		if let Some(id) = read_env(&lookup, ENV_OPENAI_PROJECT, &mut invalid) {
+			self.openai_project = Some(id);
+		}
+		if let Some(url) =
+			read_env(&lookup, ENV_OPENAI_COMPAT_BASE_URL, &mut invalid)
+		{
+			self = self.openai_compat(url);
+		}
+
+		for (kind, vars) in ENV_BASE_URLS {
+			let url = vars
+				.iter()
+				.find_map(|var| read_env(&lookup, var, &mut invalid));
+
+			if let Some(url) = url {
+				self = self.base_url(*kind, url);
+			}
+		}
+
+		let region = ENV_AWS_REGION
+			.iter()
+			.find_map(|var| read_env(&lookup, var, &mut invalid));
+		let access_key_id =
+			read_env(&lookup, ENV_AWS_ACCESS_KEY_ID, &mut invalid);
+		let secret_access_key =
+			read_env(&lookup, ENV_AWS_SECRET_ACCESS_KEY, &mut invalid);
+		let session_token =
+			read_env(&lookup, ENV_AWS_SESSION_TOKEN, &mut invalid);
+		if let (Some(region), Some(access_key_id), Some(secret_access_key)) =
+			(region, access_key_id, secret_access_key)
+		{
+			let credentials = AwsCredentials {
+				access_key_id,
+				secret_access_key,
+				session_token,
+			};
+			self = self.bedrock(region, credentials);
+		}
+
+		if !invalid.is_empty() {
+			return Err(ConfigError::InvalidEnv(invalid));
+		}
+
+		Ok(self)
+	}
+
+	pub(super) fn api_key(&self, kind: ProviderKind) -> Option<&String> {
+		match kind {
+			ProviderKind::OpenAi => self.openai_api_key.as_ref(),
+			ProviderKind::Anthropic => self.anthropic_api_key.as_ref(),
+			ProviderKind::Google => self.google_api_key.as_ref(),
+			ProviderKind::XAi => self.xai_api_key.as_ref(),
+			ProviderKind::Mistral => self.mistral_api_key.as_ref(),
+			ProviderKind::PublicAi => self.publicai_api_key.as_ref(),
+			ProviderKind::OpenRouter => self.openrouter_api_key.as_ref(),
+			ProviderKind::OpenAiCompat => self.openai_compat_api_key.as_ref(),
+			// signed with the credentials instead
+			ProviderKind::Bedrock => None,
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => None,
+		}
+	}
+
+	fn set_api_key(&mut self, kind: ProviderKind, key: Option<String>) {
+		match kind {
+			ProviderKind::OpenAi => self.openai_api_key = key,
+			ProviderKind::Anthropic => self.anthropic_api_key = key,
+			ProviderKind::Google => self.google_api_key = key,
+			ProviderKind::XAi => self.xai_api_key = key,
+			ProviderKind::Mistral => self.mistral_api_key = key,
+			ProviderKind::PublicAi => self.publicai_api_key = key,
+			ProviderKind::OpenRouter => self.openrouter_api_key = key,
+			ProviderKind::OpenAiCompat => self.openai_compat_api_key = key,
+			ProviderKind::Bedrock => {}
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => {}
+		}
+	}
+}
+
+/// The result of looking up an environment variable, like [`env::var`].
+type EnvVar = Result<String, env::VarError>;
+
+/// Returns `None` if the variable isn't set, empty and non unicode values
+/// are pushed to `invalid`.
+fn read_env(
+	lookup: impl Fn(&str) -> EnvVar,
+	var: &'static str,
+	invalid: &mut Vec<&'static str>,
+) -> Option<String> {
+	match lookup(var) {
+		Ok(value) if !value.trim().is_empty() => Some(value),
+		Ok(_) | Err(env::VarError::NotUnicode(_)) => {
+			invalid.push(var);
+			None
+		}
+		Err(env::VarError::NotPresent) => None,
+	}
+}
+
+impl TryFrom<FileConfig> for LlmsConfig {
+	type Error = ConfigError;
+
+	fn try_from(file: FileConfig) -> Result<Self, ConfigError> {
+		let mut config = LlmsConfig::new()
+			.lenient(file.lenient)
+			.timeouts(Timeouts {
+				read: file.timeouts.read.map(Duration::from_secs),
+				total: file.timeouts.total.map(Duration::from_secs),
+			})
+			.http(file.http.try_into()?);
+
+		if let Some(retry) = file.retry {
+			let mut policy = RetryPolicy::new(retry.max_retries);
+			if let Some(secs) = retry.initial_backoff {
+				policy.initial_backoff = Duration::from_secs_f64(secs);
+			}
+			if let Some(secs) = retry.max_backoff {
+				policy.max_backoff = Duration::from_secs_f64(secs);
+			}
+			if let Some(secs) = retry.max_retry_after {
+				policy.max_retry_after = Duration::from_secs_f64(secs);
+			}
+			config = config.retry(policy);
+		}
+
+		if let Some(fallback) = file.fallback {
+			let mut policy = FallbackPolicy::new(fallback.models);
+			policy.attempt_timeout =
+				fallback.attempt_timeout.map(Duration::from_secs);
+			config = config.fallback(policy);
+		}
+
+		for (name, model) in file.aliases {
+			config = config.alias(name, model);
+		}
+
+		for price in file.prices {
+			config = config.price(
+				price.model,
+				Price {
+					input: price.input,
+					output: price.output,
+					cached_input: price.cached_input,
+					cache_write: price.cache_write,
+				},
+			);
+		}
+
+		let openai = file.openai.map(|openai| {
+			config.openai_organization = openai.organization;
+			config.openai_project = openai.project;
+			FileProvider {
+				api_key: openai.api_key,
+				base_url: openai.base_url,
+				http: openai.http,
+				rate_limit: openai.rate_limit,
+			}
+		});
+
+		let openrouter = file.openrouter.map(|openrouter| {
+			config.openrouter_app_url = openrouter.app_url;
+			config.openrouter_app_name = openrouter.app_name;
+			config.openrouter_provider = openrouter.provider;
+			FileProvider {
+				api_key: openrouter.api_key,
+				base_url: openrouter.base_url,
+				http: openrouter.http,
+				rate_limit: openrouter.rate_limit,
+			}
+		});
+
+		let providers = [
+			(ProviderKind::OpenAi, openai),
+			(ProviderKind::Anthropic, file.anthropic),
+			(ProviderKind::Google, file.google),
+			(ProviderKind::XAi, file.xai),
+			(ProviderKind::Mistral, file.mistral),
+			(ProviderKind::PublicAi, file.publicai),
+			(ProviderKind::OpenRouter, openrouter),
+		];
+
+		if let Some(compat) = file.openai_compat {
+			config = config
+				.openai_compat(compat.base_url)
+				.openai_compat_api_key(compat.api_key);
+			if let Some(http) = compat.http {
+				config = config.provider_http(
+					ProviderKind::OpenAiCompat,
+					http.try_into()?,
+				);
+			}
+			if let Some(limit) = compat.rate_limit {
+				config = config.rate_limit(ProviderKind::OpenAiCompat, limit);
+			}
+		}
+
+		if let Some(bedrock) = file.bedrock {
+			let credentials = AwsCredentials {
+				access_key_id: bedrock.access_key_id,
+				secret_access_key: bedrock.secret_access_key,
+				session_token: bedrock.session_token,
+			};
+			config = config.bedrock(bedrock.region, credentials);
+			if let Some(url) = bedrock.base_url {
+				config = config.base_url(ProviderKind::Bedrock, url);
+			}
+			if let Some(http) = bedrock.http {
+				config = config
+					.provider_http(ProviderKind::Bedrock, http.try_into()?);
+			}
+			if let Some(limit) = bedrock.rate_limit {
+				config = config.rate_limit(ProviderKind::Bedrock, limit);
+			}
+		}
+
+		for (kind, provider) in providers {
+			let Some(provider) = provider else {
+				continue;
+			};
+
+			config.set_api_key(kind, Some(provider.api_key));
+
+			if let Some(url) = provider.base_url {
+				config = config.base_url(kind, url);
+			}
+
+			if let Some(http) = provider.http {
+				config = config.provider_http(kind, http.try_into()?);
+			}
+
+			if let Some(limit) = provider.rate_limit {
+				config = config.rate_limit(kind, limit);
+			}
+		}
+
+		Ok(config)
+	}
+}
+
+impl TryFrom<FileHttp> for HttpConfig {
+	type Error = ConfigError;
+
+	fn try_from(file: FileHttp) -> Result<Self, ConfigError> {
+		let mut http = HttpConfig::new();
+
+		#[cfg(unix)]
+		{
+			http.unix_socket = file.unix_socket;
+		}
+
+		http.connect_timeout = file.connect_timeout.map(Duration::from_secs);
+		http.read_timeout = file.read_timeout.map(Duration::from_secs);
+		http.timeout = file.timeout.map(Duration::from_secs);
+		http.pool_max_idle_per_host = file.pool_max_idle_per_host;
+		http.pool_idle_timeout =
+			file.pool_idle_timeout.map(Duration::from_secs);
+		http.http2_keep_alive_interval =
+			file.http2_keep_alive_interval.map(Duration::from_secs);
+		http.tcp_nodelay = file.tcp_nodelay;
+		http.accept_encoding = file.accept_encoding;
+		http.request_compression = file.request_compression;
+
+		if let Some(path) = file.identity {
+			let pem = fs::read(&path)?;
+			http.identity =
+				Some(Identity::from_pem(&pem).map_err(|error| {
+					ConfigError::Certificate { path, error }
+				})?);
+		}
+
+		for path in file.root_certificates {
+			let pem = fs::read(&path)?;
+			http.root_certificates.push(
+				Certificate::from_pem(&pem).map_err(|error| {
+					ConfigError::Certificate { path, error }
+				})?,
+			);
+		}
+
+		Ok(http)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Looks the variables up in `vars` instead of the process environment.
+	fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> EnvVar {
+		let vars: HashMap<String, String> = vars
+			.iter()
+			.map(|(var, value)| (var.to_string(), value.to_string()))
+			.collect();
+		move |var| vars.get(var).cloned().ok_or(env::VarError::NotPresent)
+	}
+
+	const JSON: &str = r#"{
+		"lenient": true,
+		"timeouts": { "read": 60, "total": 600 },
+		"retry": { "max_retries": 3, "initial_backoff": 0.5 },
+		"fallback": { "models": ["ClaudeSonnet5"], "attempt_timeout": 20 },
+		"aliases": { "local": { "OpenAiCompat": "qwen3:8b" } },
+		"prices": [
+			{ "model": "ClaudeSonnet5", "input": 1.0, "output": 2.0 }
+		],
+		"http": { "connect_timeout": 5 },
+		"openai": {
+			"api_key": "sk-file",
+			"organization": "org-file",
+			"base_url": "https://file.example.com/v1",
+			"rate_limit": { "requests_per_minute": 500 }
+		},
+		"google": { "api_key": "file-google" },
+		"openai_compat": { "base_url": "http://localhost:11434/v1" },
+		"bedrock": {
+			"region": "us-east-1",
+			"access_key_id": "AKIA",
+			"secret_access_key": "secret"
+		}
+	}"#;
+
+	fn assert_full(config: &LlmsConfig) {
+		assert!(config.lenient);
+		assert_eq!(config.timeouts.read, Some(Duration::from_secs(60)));
+		assert_eq!(config.timeouts.total, Some(Duration::from_secs(600)));
+
+		let retry = config.retry.as_ref().unwrap();
+		assert_eq!(retry.max_retries, 3);
+		assert_eq!(retry.initial_backoff, Duration::from_millis(500));
+
+		let fallback = config.fallback.as_ref().unwrap();
+		assert_eq!(fallback.models, [Model::ClaudeSonnet5]);
+		assert_eq!(fallback.attempt_timeout, Some(Duration::from_secs(20)));
+
+		assert_eq!(
+			config.aliases["local"],
+			Model::OpenAiCompat("qwen3:8b".into())
+		);
+		assert_eq!(config.prices[&Model::ClaudeSonnet5].output, 2.0);
+		assert_eq!(config.http.connect_timeout, Some(Duration::from_secs(5)));
+
+		assert_eq!(config.openai_api_key.as_deref(), Some("sk-file"));
+		assert_eq!(config.openai_organization.as_deref(), Some("org-file"));
+		assert_eq!(
+			config.base_urls[&ProviderKind::OpenAi],
+			"https://file.example.com/v1"
+		);
+		assert_eq!(
+			config.rate_limits[&ProviderKind::OpenAi].requests_per_minute,
+			Some(500)
+		);
+		assert_eq!(config.google_api_key.as_deref(), Some("file-google"));
+		assert_eq!(
+			config.base_urls[&ProviderKind::OpenAiCompat],
+			"http://localhost:11434/v1"
+		);
+		assert_eq!(config.bedrock_region.as_deref(), Some("us-east-1"));
+	}
+
+	#[test]
+	fn parses_a_full_json_file() {
+		assert_full(&LlmsConfig::from_json(JSON).unwrap());
+	}
+
+	#[cfg(feature = "toml")]
+	#[test]
+	fn parses_a_full_toml_file() {
+		let toml = r#"
+			lenient = true
+			aliases = { local = { OpenAiCompat = "qwen3:8b" } }
+
+			[timeouts]
+			read = 60
+			total = 600
+
+			[retry]
+			max_retries = 3
+			initial_backoff = 0.5
+
+			[fallback]
+			models = ["ClaudeSonnet5"]
+			attempt_timeout = 20
+
+			[[prices]]
+			model = "ClaudeSonnet5"
+			input = 1.0
+			output = 2.0
+
+			[http]
+			connect_timeout = 5
+
+			[openai]
+			api_key = "sk-file"
+			organization = "org-file"
+			base_url = "https://file.example.com/v1"
+			rate_limit = { requests_per_minute = 500 }
+
+			[google]
+			api_key = "file-google"
+
+			[openai_compat]
+			base_url = "http://localhost:11434/v1"
+
+			[bedrock]
+			region = "us-east-1"
+			access_key_id = "AKIA"
+			secret_access_key = "secret"
+		"#;
+		assert_full(&LlmsConfig::from_toml(toml).unwrap());
+
+		let err = LlmsConfig::from_toml("[openai\napi_key = 1").unwrap_err();
+		assert!(matches!(err, ConfigError::Toml(_)));
+	}
+
+	#[test]
+	fn rejects_bad_files() {
+		let err = LlmsConfig::from_json(r#"{ "openai": {} }"#).unwrap_err();
+		assert!(matches!(err, ConfigError::Json(_)), "{err}");
+
+		let err = LlmsConfig::from_json("{ lenient: true").unwrap_err();
+		assert!(matches!(err, ConfigError::Json(_)), "{err}");
+
+		let path = env::temp_dir()
+			.join(format!("soe-llms-config-test-{}.yaml", std::process::id()));
+		fs::write(&path, "lenient: true").unwrap();
+		let err = LlmsConfig::from_path(&path).unwrap_err();
+		fs::remove_file(&path).unwrap();
+		assert!(
+			matches!(err, ConfigError::UnsupportedFormat(ext) if ext == "yaml")
+		);
+	}
+
+	#[test]
+	fn rejects_unknown_fields() {
+		for json in [
+			r#"{ "lenint": true }"#,
+			r#"{ "openai": { "api_key": "sk", "apikey": "sk" } }"#,
+			r#"{ "http": { "conect_timeout": 5 } }"#,
+			r#"{ "retry": { "max_retries": 1, "backoff": 1 } }"#,
+		] {
+			let err = LlmsConfig::from_json(json).unwrap_err();
+			assert!(err.to_string().contains("unknown field"), "{err}");
+		}
+	}
+
+	#[test]
+	fn env_overrides_the_file() {
+		let path = env::temp_dir()
+			.join(format!("soe-llms-config-test-{}.json", std::process::id()));
+		fs::write(&path, JSON).unwrap();
+		let config = LlmsConfig::from_path(&path);
+		fs::remove_file(&path).unwrap();
+
+		let config = config
+			.unwrap()
+			.with_vars(vars(&[
+				("OPENAI_API_KEY", "sk-env"),
+				("OPENAI_BASE_URL", "https://env.example.com/v1"),
+				("LLMS_LENIENT", "false"),
+			]))
+			.unwrap();
+		assert_eq!(config.openai_api_key.as_deref(), Some("sk-env"));
+		assert_eq!(
+			config.base_urls[&ProviderKind::OpenAi],
+			"https://env.example.com/v1"
+		);
+		assert!(!config.lenient);
+		// not set in the environment, so kept from the file
+		assert_eq!(config.openai_organization.as_deref(), Some("org-file"));
+		assert_eq!(config.google_api_key.as_deref(), Some("file-google"));
+
+		// builder calls made afterwards win
+		let config = config.openai("sk-builder".to_string());
+		assert_eq!(config.openai_api_key.as_deref(), Some("sk-builder"));
+	}
+
+	#[test]
+	fn gemini_variables_win_over_google_ones() {
+		let config = LlmsConfig::from_vars(vars(&[
+			("GOOGLE_API_KEY", "google"),
+			("GEMINI_API_KEY", "gemini"),
+			("GOOGLE_BASE_URL", "https://google.example.com"),
+			("GEMINI_BASE_URL", "https://gemini.example.com"),
+		]))
+		.unwrap();
+		assert_eq!(config.google_api_key.as_deref(), Some("gemini"));
+		assert_eq!(
+			config.base_urls[&ProviderKind::Google],
+			"https://gemini.example.com"
+		);
+
+		let config =
+			LlmsConfig::from_vars(vars(&[("GOOGLE_API_KEY", "google")]))
+				.unwrap();
+		assert_eq!(config.google_api_key.as_deref(), Some("google"));
+	}
+
+	#[test]
+	fn lists_every_invalid_variable() {
+		let err = LlmsConfig::new()
+			.with_vars(vars(&[
+				("OPENAI_API_KEY", "sk"),
+				("ANTHROPIC_API_KEY", ""),
+				("LLMS_LENIENT", "yes"),
+				("AWS_REGION", "  "),
+			]))
+			.unwrap_err();
+		let ConfigError::InvalidEnv(invalid) = err else {
+			panic!("{err}");
+		};
+		assert_eq!(
+			invalid,
+			["ANTHROPIC_API_KEY", "LLMS_LENIENT", "AWS_REGION"]
+		);
+	}
+
+	#[test]
+	fn requires_an_api_key_in_the_environment() {
+		let err = LlmsConfig::from_vars(vars(&[("LLMS_LENIENT", "true")]))
+			.unwrap_err();
+		let ConfigError::MissingEnv(missing) = err else {
+			panic!("{err}");
+		};
+		assert!(missing.contains(&"GEMINI_API_KEY"));
+		assert!(missing.contains(&"AWS_ACCESS_KEY_ID"));
+
+		let config = LlmsConfig::from_vars(vars(&[
+			("AWS_DEFAULT_REGION", "eu-west-1"),
+			("AWS_ACCESS_KEY_ID", "AKIA"),
+			("AWS_SECRET_ACCESS_KEY", "secret"),
+		]))
+		.unwrap();
+		assert_eq!(config.bedrock_region.as_deref(), Some("eu-west-1"));
+	}
+}
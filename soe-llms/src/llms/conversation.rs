@@ -0,0 +1,175 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{
+	DocumentSource, ImageSource, Input, Llms, LlmsError, Output, Request,
+	Response, Role,
+};
+
+/// The history of a multi-turn conversation, e.g. an agent loop, built
+/// into the next [`Request`].
+///
+/// Responses are appended with their tool calls, which are then answered
+/// by id. The contexts the providers need back are kept and the parallel
+/// calls of a turn stay together, see [`Request::extend_from_response`].
+///
+/// ```no_run
+/// # use soe_llms::{Conversation, Llms, Request};
+/// # fn run_tool(name: &str, input: &serde_json::Value) -> String { todo!() }
+/// # async fn run(llms: Llms, req: Request) -> Result<(), soe_llms::LlmsError> {
+/// let mut conversation = Conversation::new(req);
+/// conversation.push_user("What's the weather in Zurich?");
+///
+/// loop {
+///     let resp = conversation.send(&llms).await?;
+///     if conversation.pending_tool_calls().is_empty() {
+///         println!("{}", resp.text());
+///         break;
+///     }
+///
+///     for call in conversation.pending_tool_calls().to_vec() {
+///         let output = run_tool(&call.name, &call.input);
+///         conversation.push_tool_output(&call.id, output);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Conversation {
+	request: Request,
+	pending: Vec<PendingToolCall>,
+}
+
+/// A tool call of the last response without an output yet, see
+/// [`Conversation::push_tool_output`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingToolCall {
+	pub id: String,
+	pub name: String,
+	pub input: Value,
+}
+
+impl PendingToolCall {
+	pub fn parse_input<T: DeserializeOwned>(
+		&self,
+	) -> Result<T, serde_json::Error> {
+		T::deserialize(&self.input)
+	}
+}
+
+impl Conversation {
+	/// Starts from `req`, whose input is the history so far. Everything
+	/// besides the input is sent unchanged with every request.
+	pub fn new(req: Request) -> Self {
+		Self {
+			request: req,
+			pending: Vec::new(),
+		}
+	}
+
+	pub fn push_user(&mut self, content: impl Into<String>) {
+		self.push(Input::Text {
+			role: Role::User,
+			content: content.into(),
+		});
+	}
+
+	pub fn push_image(&mut self, data: ImageSource) {
+		self.push(Input::Image { data });
+	}
+
+	/// Appends a document from the user, e.g. a PDF with
+	/// `"application/pdf"`.
+	pub fn push_document(
+		&mut self,
+		data: DocumentSource,
+		mime: impl Into<String>,
+	) {
+		self.push(Input::Document {
+			data,
+			mime: mime.into(),
+		});
+	}
+
+	/// Appends `input` as is, see [`Conversation::push_response`] and
+	/// [`Conversation::push_tool_output`] for responses and tool outputs.
+	pub fn push(&mut self, input: Input) {
+		self.request.input.push(input);
+	}
+
+	/// Appends the outputs of `resp`, its tool calls are pending until
+	/// they were answered.
+	///
+	/// Calls still pending from the previous response stay pending, most
+	/// providers reject the next request until all of them are answered.
+	pub fn push_response(&mut self, resp: &Response) {
+		self.request.extend_from_response(resp);
+		self.pending.extend(resp.output.iter().filter_map(
+			|output| match output {
+				Output::ToolCall {
+					id, name, input, ..
+				} => Some(PendingToolCall {
+					id: id.clone(),
+					name: name.clone(),
+					input: input.clone(),
+				}),
+				_ => None,
+			},
+		));
+	}
+
+	/// Answers the pending tool call with this id. Returns `false` and
+	/// drops the output if no call with this id is pending.
+	///
+	/// If several calls share an id, e.g. Google names them after the
+	/// function, they're answered in the order they were made.
+	pub fn push_tool_output(
+		&mut self,
+		id: &str,
+		output: impl Into<String>,
+	) -> bool {
+		let Some(pos) = self.pending.iter().position(|call| call.id == id)
+		else {
+			return false;
+		};
+		self.pending.remove(pos);
+
+		self.push(Input::ToolCallOutput {
+			id: id.into(),
+			output: output.into(),
+		});
+		true
+	}
+
+	/// The tool calls of the responses that weren't answered yet.
+	pub fn pending_tool_calls(&self) -> &[PendingToolCall] {
+		&self.pending
+	}
+
+	/// The next request, with the whole history as its input.
+	pub fn request(&self) -> &Request {
+		&self.request
+	}
+
+	/// E.g. to switch the model or the tools between turns.
+	pub fn request_mut(&mut self) -> &mut Request {
+		&mut self.request
+	}
+
+	/// The whole history.
+	pub fn history(&self) -> &[Input] {
+		&self.request.input
+	}
+
+	pub fn into_request(self) -> Request {
+		self.request
+	}
+
+	/// Sends the next request, waits for the response and appends it.
+	pub async fn send(&mut self, llms: &Llms) -> Result<Response, LlmsError> {
+		let resp = llms.request_blocking(&self.request).await?;
+		self.push_response(&resp);
+		Ok(resp)
+	}
+}
@@ -0,0 +1,68 @@
+use std::{borrow::Cow, error::Error, fmt, sync::Arc};
+
+use futures::future::BoxFuture;
+
+/// Fetches api keys or tokens at request time, e.g. from a vault or an
+/// OAuth token endpoint.
+///
+/// [`CredentialProvider::credential`] is called before every request, so
+/// implementations should cache the credential and only refresh it once it
+/// is about to expire.
+pub trait CredentialProvider: Send + Sync + 'static {
+	fn credential(&self) -> BoxFuture<'_, Result<String, CredentialError>>;
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct CredentialError(pub Box<dyn Error + Send + Sync>);
+
+impl CredentialError {
+	pub fn new(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+		Self(error.into())
+	}
+}
+
+/// The api key a provider authenticates with, either fixed or fetched
+/// through a [`CredentialProvider`].
+#[derive(Clone)]
+pub enum ApiKey {
+	Static(String),
+	Provider(Arc<dyn CredentialProvider>),
+}
+
+impl ApiKey {
+	pub fn provider(provider: impl CredentialProvider) -> Self {
+		Self::Provider(Arc::new(provider))
+	}
+
+	/// Returns the current key, fetching it from the provider if needed.
+	pub async fn get(&self) -> Result<Cow<'_, str>, CredentialError> {
+		match self {
+			Self::Static(key) => Ok(Cow::Borrowed(key)),
+			Self::Provider(provider) => {
+				provider.credential().await.map(Cow::Owned)
+			}
+		}
+	}
+}
+
+impl From<String> for ApiKey {
+	fn from(key: String) -> Self {
+		Self::Static(key)
+	}
+}
+
+impl From<&str> for ApiKey {
+	fn from(key: &str) -> Self {
+		Self::Static(key.into())
+	}
+}
+
+impl fmt::Debug for ApiKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Static(_) => f.write_str("ApiKey::Static(..)"),
+			Self::Provider(_) => f.write_str("ApiKey::Provider(..)"),
+		}
+	}
+}
@@ -0,0 +1,49 @@
+use std::borrow::Cow;
+
+use super::{GenerationParams, Request, Timeouts};
+
+/// Defaults for requests to a provider or model, see
+/// [`LlmsConfig::provider_defaults`](super::LlmsConfig::provider_defaults)
+/// and [`LlmsConfig::model_defaults`](super::LlmsConfig::model_defaults).
+#[derive(Debug, Clone, Default)]
+pub struct RequestDefaults {
+	/// Every parameter the request leaves at `None`.
+	pub params: GenerationParams,
+	/// Used if the request's instructions are empty.
+	pub instructions: Option<String>,
+	/// Every limit the request leaves at `None`.
+	pub timeouts: Timeouts,
+	/// Used if the request doesn't pin a snapshot, only sensible in
+	/// [`LlmsConfig::model_defaults`](super::LlmsConfig::model_defaults).
+	pub snapshot: Option<String>,
+}
+
+impl Request {
+	/// Fills everything the request leaves unset from `defaults`, the
+	/// first entry taking precedence.
+	pub(crate) fn with_defaults<'a>(
+		&'a self,
+		defaults: &[&RequestDefaults],
+	) -> Cow<'a, Request> {
+		if defaults.is_empty() {
+			return Cow::Borrowed(self);
+		}
+
+		let mut req = self.clone();
+		for defaults in defaults {
+			req.params = req.params.or(defaults.params.clone());
+			req.timeouts = req.timeouts.or(defaults.timeouts);
+			if req.snapshot.is_none() {
+				req.snapshot = defaults.snapshot.clone();
+			}
+
+			if req.instructions.is_empty()
+				&& let Some(instructions) = &defaults.instructions
+			{
+				req.instructions = instructions.clone();
+			}
+		}
+
+		Cow::Owned(req)
+	}
+}
@@ -0,0 +1,40 @@
+/// A request to embed one or more texts, see
+/// [`Llms::embed`](super::Llms::embed).
+#[derive(Debug, Clone)]
+pub struct EmbeddingRequest {
+	pub model: EmbeddingModel,
+	/// Texts to embed, one embedding is returned per text in the same order.
+	pub input: Vec<String>,
+	/// What the embeddings will be used for, lets the model optimize them.
+	/// Ignored by models that don't support it.
+	pub task_type: Option<EmbeddingTaskType>,
+	/// Truncate the embeddings to this many dimensions. `None` uses the
+	/// model's full size.
+	pub dimensions: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmbeddingModel {
+	GeminiEmbedding001,
+	TextEmbedding004,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EmbeddingTaskType {
+	RetrievalQuery,
+	RetrievalDocument,
+	SemanticSimilarity,
+	Classification,
+	Clustering,
+	QuestionAnswering,
+	FactVerification,
+	CodeRetrievalQuery,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct EmbeddingResponse {
+	pub embeddings: Vec<Vec<f32>>,
+}
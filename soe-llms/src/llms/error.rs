@@ -1,18 +1,110 @@
 use std::io;
+use std::time::Duration;
 
 use reqwest::StatusCode;
 
+use super::{ApiErrorKind, CredentialError, Feature, Model, ProviderKind};
+
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum LlmsError {
 	#[error("Llm not configured: {0}")]
 	LlmNotConfigured(String),
+	/// A [`Model::Alias`] that doesn't point to any model.
+	#[error("Unknown model alias: {0}")]
+	UnknownAlias(String),
+	/// No candidate of a [`Router`](super::Router) fits the request.
+	#[error("No model matches the request")]
+	NoMatchingModel,
+	/// The request uses a feature the model doesn't support. Returned before
+	/// anything is sent to the provider.
+	#[error("{feature:?} is not supported by {model:?}")]
+	Unsupported { feature: Feature, model: Model },
+	/// A tool name the provider doesn't accept. In lenient mode invalid
+	/// names are renamed instead, see [`LlmsConfig::lenient`].
+	///
+	/// [`LlmsConfig::lenient`]: super::LlmsConfig::lenient
+	#[error("Invalid tool name {name:?}: {reason}")]
+	InvalidToolName { name: String, reason: String },
+	/// A tool or response schema the provider can't express.
+	#[error("Invalid schema: {0}")]
+	InvalidSchema(String),
 	#[error("JSON deserialization error: {0}")]
 	Json(#[from] serde_json::Error),
-	#[error("Response error: status {status}, body {body}")]
-	Response { status: StatusCode, body: String },
+	/// The provider rejected the request, classified from the status and
+	/// the error in the body.
+	#[error("{kind:?} error from {provider:?}: {message}")]
+	Api {
+		kind: ApiErrorKind,
+		provider: ProviderKind,
+		message: String,
+		/// Same as [`ApiErrorKind::is_retryable`].
+		retryable: bool,
+		/// `200` if the error was reported in the stream.
+		status: StatusCode,
+		/// The provider's id for the request, from headers like
+		/// `request-id` or `x-request-id`. `None` if it didn't return one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait before retrying, from the
+		/// `Retry-After` header.
+		retry_after: Option<Duration>,
+	},
+	/// A response that couldn't be understood.
+	#[error("Response error from {provider:?}: status {status}, body {body}")]
+	Response {
+		status: StatusCode,
+		body: String,
+		provider: ProviderKind,
+		/// The provider's id for the request, from headers like
+		/// `request-id` or `x-request-id`. `None` if it didn't return one.
+		request_id: Option<String>,
+	},
+	/// The provider withheld the output because of its content filters, or
+	/// refused to process the prompt at all.
+	#[error("Content filtered: {reason}")]
+	ContentFiltered { reason: String },
+	/// Nothing arrived within [`Timeouts::read`](super::Timeouts::read).
+	#[error("No response data for {0:?}")]
+	ReadTimeout(Duration),
+	/// The response didn't complete within
+	/// [`Timeouts::total`](super::Timeouts::total).
+	#[error("Request didn't complete within {0:?}")]
+	Timeout(Duration),
+	/// A fallback attempt didn't start within
+	/// [`FallbackPolicy::attempt_timeout`](super::FallbackPolicy::attempt_timeout).
+	#[error("No response event within {0:?}")]
+	AttemptTimeout(Duration),
+	/// The stream was cancelled, see
+	/// [`ResponseStream::cancel`](super::ResponseStream::cancel).
+	#[error("Request was cancelled")]
+	Cancelled,
+	/// An [`Agent`](super::Agent) still called tools after
+	/// [`Agent::max_iterations`](super::Agent::max_iterations) requests.
+	#[error("Still calling tools after {0} iterations")]
+	MaxIterations(usize),
+	/// A [`Sampling`](super::Sampling) without samples, or a reducer of
+	/// [`Llms::self_consistency_with`](super::Llms::self_consistency_with)
+	/// that picked no candidate.
+	#[error("Invalid sampling: {0}")]
+	InvalidSampling(String),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
 	#[error("Reqwest error: {0}")]
 	Reqwest(#[from] reqwest::Error),
 	#[error("IO error: {0}")]
 	Io(#[from] io::Error),
 }
+
+impl LlmsError {
+	/// Sets the request id of a [`LlmsError::Response`] or
+	/// [`LlmsError::Api`] that was only noticed in the stream, after the
+	/// headers were received.
+	pub(crate) fn with_request_id(mut self, id: Option<&str>) -> Self {
+		if let Self::Response { request_id, .. } | Self::Api { request_id, .. } =
+			&mut self && request_id.is_none()
+		{
+			*request_id = id.map(Into::into);
+		}
+		self
+	}
+}
@@ -0,0 +1,114 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{Input, Llms, LlmsError, Model, Request, ResponseFormat, Role};
+
+/// Grades answers with a judge model against a rubric, see [`Llms::judge`].
+///
+/// Meant for regression-testing prompt changes: run the same cases before
+/// and after a change, on every provider, and compare the scores.
+#[derive(Debug, Clone)]
+pub struct Judge {
+	/// Needs to support structured output, unless [`LlmsConfig::lenient`]
+	/// is set.
+	///
+	/// [`LlmsConfig::lenient`]: super::LlmsConfig::lenient
+	pub model: Model,
+	/// What a good answer looks like and how points are awarded.
+	pub rubric: String,
+	/// Scores range from 0 to this.
+	pub max_score: u32,
+}
+
+/// The judgement of a single answer.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Verdict {
+	/// Why the judge gave this score.
+	pub reasoning: String,
+	/// Clamped to [`Judge::max_score`].
+	pub score: u32,
+}
+
+impl Judge {
+	pub fn new(model: Model, rubric: impl Into<String>) -> Self {
+		Self {
+			model,
+			rubric: rubric.into(),
+			max_score: 10,
+		}
+	}
+
+	pub fn max_score(mut self, max_score: u32) -> Self {
+		self.max_score = max_score;
+		self
+	}
+
+	fn request(&self, question: &str, answer: &str) -> Request {
+		let instructions = format!(
+			"You are an impartial judge grading an answer to a question. \
+			Grade it strictly according to the rubric, give a short \
+			reasoning and then a score from 0 to {}.\n\n\
+			Rubric:\n{}",
+			self.max_score, self.rubric
+		);
+
+		Request {
+			input: vec![Input::Text {
+				role: Role::User,
+				content: format!(
+					"<question>\n{question}\n</question>\n\n\
+					<answer>\n{answer}\n</answer>"
+				),
+			}],
+			instructions,
+			model: self.model.clone(),
+			user_id: String::new(),
+			tools: vec![],
+			tool_choice: Default::default(),
+			reasoning_effort: None,
+			response_format: ResponseFormat::JsonSchema {
+				name: "verdict".into(),
+				schema: json!({
+					"type": "object",
+					"properties": {
+						"reasoning": { "type": "string" },
+						"score": {
+							"type": "integer",
+							"minimum": 0,
+							"maximum": self.max_score,
+						},
+					},
+					"required": ["reasoning", "score"],
+					"additionalProperties": false,
+				}),
+				strict: true,
+			},
+			params: Default::default(),
+			cache: false,
+			extra: Default::default(),
+			headers: Default::default(),
+			timeouts: Default::default(),
+			transcript: None,
+			snapshot: None,
+		}
+	}
+}
+
+impl Llms {
+	/// Asks the judge model to grade `answer` to `question`.
+	pub async fn judge(
+		&self,
+		judge: &Judge,
+		question: &str,
+		answer: &str,
+	) -> Result<Verdict, LlmsError> {
+		let resp = self
+			.request_blocking(&judge.request(question, answer))
+			.await?;
+
+		let mut verdict: Verdict = serde_json::from_str(resp.text().trim())?;
+		verdict.score = verdict.score.min(judge.max_score);
+
+		Ok(verdict)
+	}
+}
@@ -0,0 +1,79 @@
+use super::Model;
+
+/// Splits requests to a [`Model::Alias`] between several models, see
+/// [`Llms::set_experiment`](super::Llms::set_experiment).
+///
+/// Each request is assigned to an arm by hashing the alias together with
+/// [`Request::user_id`](super::Request::user_id), so a user always gets
+/// the same arm as long as the arms don't change.
+#[derive(Debug, Clone, Default)]
+pub struct Experiment {
+	pub arms: Vec<Arm>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Arm {
+	pub name: String,
+	pub model: Model,
+	/// Share of the requests relative to the other arms.
+	pub weight: u32,
+}
+
+/// Which arm of an experiment a response came from, see
+/// [`Response::experiment`](super::Response::experiment).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentArm {
+	/// The alias the experiment is registered under.
+	pub experiment: String,
+	pub arm: String,
+}
+
+impl Experiment {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn arm(
+		mut self,
+		name: impl Into<String>,
+		model: Model,
+		weight: u32,
+	) -> Self {
+		self.arms.push(Arm {
+			name: name.into(),
+			model,
+			weight,
+		});
+		self
+	}
+
+	/// Returns the arm for `user_id`, `None` if there are no arms or all
+	/// weights are zero.
+	pub fn assign(&self, experiment: &str, user_id: &str) -> Option<&Arm> {
+		let total: u64 = self.arms.iter().map(|arm| arm.weight as u64).sum();
+		if total == 0 {
+			return None;
+		}
+
+		let mut point = fnv1a(&[experiment, "/", user_id]) % total;
+		self.arms.iter().find(|arm| {
+			let weight = arm.weight as u64;
+			if point < weight {
+				return true;
+			}
+			point -= weight;
+			false
+		})
+	}
+}
+
+/// FNV-1a, unlike the std hashers it is guaranteed to stay the same across
+/// Rust versions.
+fn fnv1a(parts: &[&str]) -> u64 {
+	parts
+		.iter()
+		.flat_map(|part| part.bytes())
+		.fold(0xcbf29ce484222325, |hash, byte| {
+			(hash ^ byte as u64).wrapping_mul(0x100000001b3)
+		})
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{LlmsError, Request, Response, ResponseEvent, Usage};
+
+/// Receives a [`ExportRecord`] for every request made through
+/// [`Llms`](super::Llms), see [`Llms::add_exporter`](super::Llms::add_exporter).
+///
+/// Unlike a [`Hook`](super::Hook) the record is only handed over once the
+/// request is over and contains everything about it, so an exporter
+/// doesn't have to correlate callbacks of concurrent requests.
+///
+/// Exporters are called synchronously from the stream, anything slow like
+/// sending the record should happen elsewhere, see [`LangfuseExporter`].
+///
+/// [`LangfuseExporter`]: super::LangfuseExporter
+pub trait Exporter: Send + Sync + 'static {
+	fn export(&self, record: &ExportRecord);
+}
+
+/// Everything about a single request, see [`Exporter`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExportRecord {
+	/// The request as sent to the provider, after defaults, tool output
+	/// limits and lenient degradation were applied.
+	pub request: Request,
+	/// The events returned by
+	/// [`ResponseStream::next`](super::ResponseStream::next) in order.
+	pub events: Vec<ResponseEvent>,
+	pub outcome: ExportOutcome,
+	pub started_at: SystemTime,
+	/// Time from sending the request until the first text delta, `None` if
+	/// there was none.
+	pub time_to_first_token: Option<Duration>,
+	/// Time from sending the request until it completed, failed or the
+	/// stream was dropped.
+	pub latency: Duration,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ExportOutcome {
+	Completed(Box<Response>),
+	/// The request or the stream failed, with the error message.
+	Failed(String),
+	/// The stream was dropped or cancelled before the response completed.
+	Cancelled,
+}
+
+impl ExportRecord {
+	/// The usage of the response, `None` unless it completed.
+	pub fn usage(&self) -> Option<Usage> {
+		match &self.outcome {
+			ExportOutcome::Completed(resp) => Some(resp.usage),
+			_ => None,
+		}
+	}
+
+	pub fn ended_at(&self) -> SystemTime {
+		self.started_at + self.latency
+	}
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Exporters(Vec<Arc<dyn Exporter>>);
+
+impl Exporters {
+	pub fn push(&mut self, exporter: impl Exporter) {
+		self.0.push(Arc::new(exporter));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Starts recording a request that is about to be sent.
+	pub fn record(&self, request: Request) -> Recorder {
+		Recorder {
+			exporters: self.clone(),
+			request: Some(request),
+			events: Vec::new(),
+			started_at: SystemTime::now(),
+			start: Instant::now(),
+			time_to_first_token: None,
+		}
+	}
+}
+
+impl fmt::Debug for Exporters {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Exporters")
+			.field("len", &self.0.len())
+			.finish()
+	}
+}
+
+/// Collects the record of a request, exports it as
+/// [`ExportOutcome::Cancelled`] if dropped before it completed or failed.
+#[derive(Debug)]
+pub(crate) struct Recorder {
+	exporters: Exporters,
+	/// `None` once exported.
+	request: Option<Request>,
+	events: Vec<ResponseEvent>,
+	started_at: SystemTime,
+	start: Instant,
+	time_to_first_token: Option<Duration>,
+}
+
+impl Recorder {
+	pub fn on_event(&mut self, event: &ResponseEvent) {
+		if self.time_to_first_token.is_none()
+			&& matches!(event, ResponseEvent::TextDelta { .. })
+		{
+			self.time_to_first_token = Some(self.start.elapsed());
+		}
+		self.events.push(event.clone());
+	}
+
+	pub fn on_complete(&mut self, resp: &Response) {
+		self.export(ExportOutcome::Completed(Box::new(resp.clone())));
+	}
+
+	pub fn on_error(&mut self, error: &LlmsError) {
+		self.export(ExportOutcome::Failed(error.to_string()));
+	}
+
+	pub fn on_cancel(&mut self) {
+		self.export(ExportOutcome::Cancelled);
+	}
+
+	fn export(&mut self, outcome: ExportOutcome) {
+		let Some(request) = self.request.take() else {
+			return;
+		};
+
+		let record = ExportRecord {
+			request,
+			events: std::mem::take(&mut self.events),
+			outcome,
+			started_at: self.started_at,
+			time_to_first_token: self.time_to_first_token,
+			latency: self.start.elapsed(),
+		};
+
+		self.exporters.0.iter().for_each(|e| e.export(&record));
+	}
+}
+
+impl Drop for Recorder {
+	fn drop(&mut self) {
+		self.export(ExportOutcome::Cancelled);
+	}
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use super::{LlmsError, Model, retry::is_transient};
+
+/// Other models to try when a request fails before its response started,
+/// see [`LlmsConfig::fallback`](super::LlmsConfig::fallback).
+///
+/// The request's own model is tried first, including the retries of the
+/// [`RetryPolicy`](super::RetryPolicy). If it fails with a rate limit, an
+/// overloaded or failing provider, a connection error or a timeout, the
+/// `models` are tried in order, skipping the request's model and the ones
+/// whose provider isn't configured. Other errors and the last model's
+/// error are returned.
+///
+/// Since some providers report being overloaded as the first event, every
+/// attempt but the last one without an
+/// [`attempt_timeout`](FallbackPolicy::attempt_timeout) waits for its
+/// first event before [`Llms::request`](super::Llms::request) returns.
+/// [`Request::snapshot`](super::Request::snapshot) is only sent to the
+/// request's own model, [`Response::fallback`](super::Response::fallback)
+/// tells which model answered instead.
+#[derive(Debug, Clone, Default)]
+pub struct FallbackPolicy {
+	pub models: Vec<Model>,
+	/// Longest wait of an attempt for its first event, then it fails with
+	/// [`LlmsError::AttemptTimeout`] and the next model is tried.
+	pub attempt_timeout: Option<Duration>,
+}
+
+impl FallbackPolicy {
+	pub fn new(models: Vec<Model>) -> Self {
+		Self {
+			models,
+			attempt_timeout: None,
+		}
+	}
+
+	pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+		self.attempt_timeout = Some(timeout);
+		self
+	}
+
+	/// Whether the next model is tried after `error`.
+	pub(crate) fn falls_back(error: &LlmsError) -> bool {
+		is_transient(error)
+			|| matches!(
+				error,
+				LlmsError::ReadTimeout(_)
+					| LlmsError::Timeout(_)
+					| LlmsError::AttemptTimeout(_)
+			)
+	}
+}
@@ -0,0 +1,105 @@
+use std::{
+	future::Future,
+	time::{Duration, Instant},
+};
+
+use super::{ApiErrorKind, Llms, LlmsError, ProviderKind};
+
+/// The result of checking a single provider, see [`Llms::health_check`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct HealthCheck {
+	pub provider: ProviderKind,
+	/// How long the check took, including failed ones.
+	pub latency: Duration,
+	pub result: Result<(), LlmsError>,
+}
+
+impl HealthCheck {
+	pub fn is_healthy(&self) -> bool {
+		self.result.is_ok()
+	}
+
+	/// Returns `true` if the provider was reachable but rejected the api
+	/// key.
+	pub fn is_auth_error(&self) -> bool {
+		matches!(
+			self.result,
+			Err(LlmsError::Api {
+				kind: ApiErrorKind::AuthFailed,
+				..
+			})
+		)
+	}
+}
+
+impl Llms {
+	/// Checks every configured provider concurrently with the cheapest
+	/// authenticated call it offers, usually listing its models.
+	///
+	/// Nothing is generated, so this doesn't cost any tokens.
+	pub async fn health_check(&self) -> Vec<HealthCheck> {
+		use ProviderKind as P;
+
+		let p = &self.inner;
+		let checks = futures::join!(
+			check(P::OpenAi, p.open_ai.as_ref().map(|p| p.ping())),
+			check(P::Anthropic, p.anthropic.as_ref().map(|p| p.ping())),
+			check(P::Google, p.google.as_ref().map(|p| p.ping())),
+			check(P::XAi, p.xai.as_ref().map(|p| p.ping())),
+			check(P::Mistral, p.mistral.as_ref().map(|p| p.ping())),
+			check(P::PublicAi, p.publicai.as_ref().map(|p| p.ping())),
+			check(P::OpenRouter, p.openrouter.as_ref().map(|p| p.ping())),
+			check(P::OpenAiCompat, p.openai_compat.as_ref().map(|p| p.ping())),
+			check(P::Bedrock, p.bedrock.as_ref().map(|p| p.ping())),
+		);
+
+		[
+			checks.0, checks.1, checks.2, checks.3, checks.4, checks.5,
+			checks.6, checks.7, checks.8,
+		]
+		.into_iter()
+		.flatten()
+		.collect()
+	}
+
+	/// Verifies the api key of every configured provider, meant to be called
+	/// once at startup so a misconfigured deployment fails immediately.
+	///
+	/// Uses the same calls as [`Llms::health_check`]. Returns every provider
+	/// that couldn't be verified, either because the key was rejected or the
+	/// provider wasn't reachable.
+	pub async fn validate_keys(
+		&self,
+	) -> Result<(), Vec<(ProviderKind, LlmsError)>> {
+		let failed: Vec<_> = self
+			.health_check()
+			.await
+			.into_iter()
+			.filter_map(|check| Some((check.provider, check.result.err()?)))
+			.collect();
+
+		if failed.is_empty() {
+			Ok(())
+		} else {
+			Err(failed)
+		}
+	}
+}
+
+async fn check<E>(
+	provider: ProviderKind,
+	ping: Option<impl Future<Output = Result<(), E>>>,
+) -> Option<HealthCheck>
+where
+	E: Into<LlmsError>,
+{
+	let start = Instant::now();
+	let result = ping?.await.map_err(Into::into);
+
+	Some(HealthCheck {
+		provider,
+		latency: start.elapsed(),
+		result,
+	})
+}
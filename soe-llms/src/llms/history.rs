@@ -0,0 +1,362 @@
+//! Groups the flat [`Input`] list into the turns providers expect, so every
+//! provider encodes the same history the same way.
+//!
+//! - consecutive user texts, images and documents form one user turn
+//! - assistant texts and tool calls form one assistant turn, a provider
+//!   response with text and several parallel tool calls stays together
+//! - consecutive tool outputs form one turn, answering the tool calls of
+//!   the preceding assistant turn
+//! - every system text is its own turn, providers without system messages
+//!   merge them into the instructions, see [`instructions`]
+
+use serde_json::Value;
+
+use super::{DocumentSource, ImageSource, Input, Role};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Turn {
+	System(String),
+	User(Vec<UserPart>),
+	Assistant(Vec<AssistantPart>),
+	ToolOutputs(Vec<ToolOutput>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum UserPart {
+	Text(String),
+	Image(ImageSource),
+	Document { data: DocumentSource, mime: String },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum AssistantPart {
+	Text(String),
+	ToolCall {
+		id: String,
+		name: String,
+		input: Value,
+		context: Option<String>,
+	},
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ToolOutput {
+	pub id: String,
+	pub output: String,
+}
+
+impl UserPart {
+	pub fn is_image(&self) -> bool {
+		matches!(self, Self::Image(_))
+	}
+}
+
+impl AssistantPart {
+	pub fn text(&self) -> Option<&str> {
+		match self {
+			Self::Text(text) => Some(text),
+			Self::ToolCall { .. } => None,
+		}
+	}
+}
+
+/// Returns the text parts of an assistant turn joined, `None` if there
+/// are none.
+pub(crate) fn assistant_text(parts: &[AssistantPart]) -> Option<String> {
+	let texts: Vec<_> = parts.iter().filter_map(AssistantPart::text).collect();
+	if texts.is_empty() {
+		None
+	} else {
+		Some(texts.join("\n\n"))
+	}
+}
+
+/// Joins `instructions` with all system texts of the history, `None` if
+/// there are none.
+pub(crate) fn instructions(
+	instructions: &str,
+	input: &[Input],
+) -> Option<String> {
+	let system = input.iter().filter_map(|input| match input {
+		Input::Text {
+			role: Role::System,
+			content,
+		} => Some(content.as_str()),
+		_ => None,
+	});
+
+	let parts: Vec<_> = std::iter::once(instructions)
+		.chain(system)
+		.filter(|text| !text.is_empty())
+		.collect();
+
+	if parts.is_empty() {
+		None
+	} else {
+		Some(parts.join("\n\n"))
+	}
+}
+
+pub(crate) fn turns(input: &[Input]) -> Vec<Turn> {
+	let mut turns = Vec::new();
+
+	for input in input.iter().cloned() {
+		match (turns.last_mut(), input) {
+			(
+				_,
+				Input::Text {
+					role: Role::System,
+					content,
+				},
+			) => turns.push(Turn::System(content)),
+			(
+				Some(Turn::User(parts)),
+				Input::Text {
+					role: Role::User,
+					content,
+				},
+			) => parts.push(UserPart::Text(content)),
+			(
+				_,
+				Input::Text {
+					role: Role::User,
+					content,
+				},
+			) => turns.push(Turn::User(vec![UserPart::Text(content)])),
+			(Some(Turn::User(parts)), Input::Image { data }) => {
+				parts.push(UserPart::Image(data))
+			}
+			(_, Input::Image { data }) => {
+				turns.push(Turn::User(vec![UserPart::Image(data)]))
+			}
+			(Some(Turn::User(parts)), Input::Document { data, mime }) => {
+				parts.push(UserPart::Document { data, mime })
+			}
+			(_, Input::Document { data, mime }) => {
+				turns.push(Turn::User(vec![UserPart::Document { data, mime }]))
+			}
+			(Some(Turn::Assistant(parts)), input @ Input::Text { .. })
+			| (Some(Turn::Assistant(parts)), input @ Input::ToolCall { .. }) => {
+				parts.push(assistant_part(input));
+			}
+			(_, input @ Input::Text { .. })
+			| (_, input @ Input::ToolCall { .. }) => {
+				turns.push(Turn::Assistant(vec![assistant_part(input)]));
+			}
+			(
+				Some(Turn::ToolOutputs(outputs)),
+				Input::ToolCallOutput { id, output },
+			) => {
+				outputs.push(ToolOutput { id, output });
+			}
+			(_, Input::ToolCallOutput { id, output }) => {
+				turns.push(Turn::ToolOutputs(vec![ToolOutput { id, output }]));
+			}
+		}
+	}
+
+	turns
+}
+
+/// Only called for assistant texts and tool calls.
+fn assistant_part(input: Input) -> AssistantPart {
+	match input {
+		Input::Text { content, .. } => AssistantPart::Text(content),
+		Input::ToolCall {
+			id,
+			name,
+			input,
+			context,
+		} => AssistantPart::ToolCall {
+			id,
+			name,
+			input,
+			context,
+		},
+		Input::ToolCallOutput { .. }
+		| Input::Image { .. }
+		| Input::Document { .. } => unreachable!(),
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	fn text(role: Role, content: &str) -> Input {
+		Input::Text {
+			role,
+			content: content.into(),
+		}
+	}
+
+	fn call(id: &str, city: &str) -> Input {
+		Input::ToolCall {
+			id: id.into(),
+			name: "weather".into(),
+			input: json!({ "city": city }),
+			context: None,
+		}
+	}
+
+	fn output(id: &str, output: &str) -> Input {
+		Input::ToolCallOutput {
+			id: id.into(),
+			output: output.into(),
+		}
+	}
+
+	/// Interleaved text, parallel tool calls and their outputs, the shape
+	/// every provider encodes.
+	pub(crate) fn interleaved() -> Vec<Input> {
+		vec![
+			text(Role::System, "Be brief."),
+			text(Role::User, "Weather in Bern and Zurich?"),
+			text(Role::Assistant, "Checking."),
+			call("call_1", "Bern"),
+			call("call_2", "Zurich"),
+			output("call_1", "sunny"),
+			output("call_2", "rainy"),
+			text(Role::User, "And here?"),
+			Input::Image {
+				data: ImageSource::Url("https://example.com/a.png".into()),
+			},
+			text(Role::Assistant, "Cloudy."),
+		]
+	}
+
+	fn render(turns: &[Turn]) -> Vec<String> {
+		turns
+			.iter()
+			.map(|turn| match turn {
+				Turn::System(text) => format!("system: {text}"),
+				Turn::User(parts) => {
+					let parts: Vec<_> = parts
+						.iter()
+						.map(|part| match part {
+							UserPart::Text(text) => text.clone(),
+							UserPart::Image(_) => "<image>".into(),
+							UserPart::Document { mime, .. } => {
+								format!("<{mime}>")
+							}
+						})
+						.collect();
+					format!("user: {}", parts.join(" | "))
+				}
+				Turn::Assistant(parts) => {
+					let parts: Vec<_> = parts
+						.iter()
+						.map(|part| match part {
+							AssistantPart::Text(text) => text.clone(),
+							AssistantPart::ToolCall { id, input, .. } => {
+								format!("{id}({input})")
+							}
+						})
+						.collect();
+					format!("assistant: {}", parts.join(" | "))
+				}
+				Turn::ToolOutputs(outputs) => {
+					let outputs: Vec<_> = outputs
+						.iter()
+						.map(|o| format!("{}={}", o.id, o.output))
+						.collect();
+					format!("tools: {}", outputs.join(" | "))
+				}
+			})
+			.collect()
+	}
+
+	#[test]
+	fn groups_interleaved_history() {
+		assert_eq!(
+			render(&turns(&interleaved())),
+			[
+				"system: Be brief.",
+				"user: Weather in Bern and Zurich?",
+				"assistant: Checking. | call_1({\"city\":\"Bern\"}) \
+				 | call_2({\"city\":\"Zurich\"})",
+				"tools: call_1=sunny | call_2=rainy",
+				"user: And here? | <image>",
+				"assistant: Cloudy.",
+			]
+		);
+	}
+
+	#[test]
+	fn system_text_splits_user_turns() {
+		let input = [
+			text(Role::User, "a"),
+			text(Role::System, "s"),
+			text(Role::User, "b"),
+			text(Role::User, "c"),
+		];
+		assert_eq!(
+			render(&turns(&input)),
+			["user: a", "system: s", "user: b | c"]
+		);
+	}
+
+	#[test]
+	fn documents_join_the_user_turn() {
+		let input = [
+			text(Role::User, "Summarize"),
+			Input::Document {
+				data: DocumentSource::Base64("JVBERi0=".into()),
+				mime: "application/pdf".into(),
+			},
+			text(Role::User, "briefly"),
+		];
+		assert_eq!(
+			render(&turns(&input)),
+			["user: Summarize | <application/pdf> | briefly"]
+		);
+	}
+
+	#[test]
+	fn tool_outputs_after_a_user_text_start_a_turn() {
+		let input = [
+			call("call_1", "Bern"),
+			text(Role::User, "hurry"),
+			output("call_1", "sunny"),
+			call("call_2", "Zurich"),
+		];
+		assert_eq!(
+			render(&turns(&input)),
+			[
+				"assistant: call_1({\"city\":\"Bern\"})",
+				"user: hurry",
+				"tools: call_1=sunny",
+				"assistant: call_2({\"city\":\"Zurich\"})",
+			]
+		);
+	}
+
+	#[test]
+	fn joins_instructions_with_system_texts() {
+		let input = interleaved();
+		assert_eq!(
+			instructions("Answer in German.", &input).as_deref(),
+			Some("Answer in German.\n\nBe brief.")
+		);
+		assert_eq!(instructions("", &input).as_deref(), Some("Be brief."));
+		assert_eq!(instructions("", &input[1..]), None);
+	}
+
+	#[test]
+	fn joins_assistant_texts() {
+		let parts = [
+			AssistantPart::Text("a".into()),
+			AssistantPart::ToolCall {
+				id: "call_1".into(),
+				name: "weather".into(),
+				input: json!({}),
+				context: None,
+			},
+			AssistantPart::Text("b".into()),
+		];
+		assert_eq!(assistant_text(&parts).as_deref(), Some("a\n\nb"));
+		assert_eq!(assistant_text(&parts[1..2]), None);
+	}
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Model, Request, Response, ResponseEvent};
+
+/// Callbacks fired for every request made through [`Llms`](super::Llms),
+/// see [`Llms::add_hook`](super::Llms::add_hook).
+///
+/// All methods default to doing nothing. Hooks are called synchronously
+/// from the stream, so they should return quickly.
+pub trait Hook: Send + Sync + 'static {
+	/// Called right before the request is sent to the provider. In lenient
+	/// mode this is the already degraded request.
+	fn on_request(&self, _req: &Request) {}
+
+	/// Called when the first text delta of a response arrives.
+	fn on_first_token(&self, _model: &Model) {}
+
+	/// Called for every event returned by
+	/// [`ResponseStream::next`](super::ResponseStream::next).
+	fn on_event(&self, _model: &Model, _event: &ResponseEvent) {}
+
+	/// Called once the final response was received.
+	fn on_complete(&self, _model: &Model, _resp: &Response) {}
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks(Vec<Arc<dyn Hook>>);
+
+impl Hooks {
+	pub fn push(&mut self, hook: impl Hook) {
+		self.0.push(Arc::new(hook));
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	pub fn on_request(&self, req: &Request) {
+		self.0.iter().for_each(|h| h.on_request(req));
+	}
+
+	pub fn on_first_token(&self, model: &Model) {
+		self.0.iter().for_each(|h| h.on_first_token(model));
+	}
+
+	pub fn on_event(&self, model: &Model, event: &ResponseEvent) {
+		self.0.iter().for_each(|h| h.on_event(model, event));
+	}
+
+	pub fn on_complete(&self, model: &Model, resp: &Response) {
+		self.0.iter().for_each(|h| h.on_complete(model, resp));
+	}
+}
+
+impl fmt::Debug for Hooks {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Hooks").field("len", &self.0.len()).finish()
+	}
+}
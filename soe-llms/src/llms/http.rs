@@ -0,0 +1,213 @@
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use reqwest::{Certificate, Client, Identity};
+use serde::Deserialize;
+
+use super::LlmsError;
+
+/// Settings for the HTTP client a provider uses, see [`LlmsConfig::http`]
+/// and [`LlmsConfig::provider_http`].
+///
+/// [`LlmsConfig::http`]: super::LlmsConfig::http
+/// [`LlmsConfig::provider_http`]: super::LlmsConfig::provider_http
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct HttpConfig {
+	/// Use this client as is. Every other setting is ignored.
+	///
+	/// This is the escape hatch for custom connectors, proxies or anything
+	/// else [`reqwest::ClientBuilder`] supports.
+	pub client: Option<Client>,
+	/// Connect through this Unix domain socket instead of TCP, as local
+	/// inference daemons commonly expose. The request url is then only used
+	/// for the path and the host header.
+	#[cfg(unix)]
+	pub unix_socket: Option<PathBuf>,
+	/// Limit for establishing a connection, including the TLS handshake.
+	pub connect_timeout: Option<Duration>,
+	/// Limit for every read from a connection, so a stalled stream fails
+	/// even while the total limit is long.
+	pub read_timeout: Option<Duration>,
+	/// Wall-clock limit of every request, from connecting until the body
+	/// was read completely. [`Timeouts`] sets limits per request.
+	///
+	/// [`Timeouts`]: super::Timeouts
+	pub timeout: Option<Duration>,
+	/// Maximum number of idle connections kept per host.
+	pub pool_max_idle_per_host: Option<usize>,
+	/// How long an idle connection is kept in the pool.
+	pub pool_idle_timeout: Option<Duration>,
+	/// Interval of HTTP/2 ping frames keeping a connection alive, also
+	/// while it's idle.
+	pub http2_keep_alive_interval: Option<Duration>,
+	/// Set `TCP_NODELAY` on new connections.
+	pub tcp_nodelay: Option<bool>,
+	/// Client certificate presented to servers requiring mutual TLS.
+	pub identity: Option<Identity>,
+	/// Trust anchors accepted in addition to the system roots, e.g. the CA
+	/// of an internal gateway.
+	pub root_certificates: Vec<Certificate>,
+	/// Encodings announced in `Accept-Encoding` and transparently decoded.
+	/// Empty means responses are never compressed.
+	pub accept_encoding: Vec<Compression>,
+	/// Compress request bodies larger than [`COMPRESSION_MIN_SIZE`].
+	///
+	/// Only enable this if the endpoint accepts a `Content-Encoding`, which
+	/// is mostly the case for self-hosted gateways.
+	pub request_compression: Option<Compression>,
+}
+
+/// Request bodies smaller than this are sent uncompressed, see
+/// [`HttpConfig::request_compression`].
+pub const COMPRESSION_MIN_SIZE: usize = 8 * 1024;
+
+/// A body encoding, available variants depend on the enabled `gzip` and
+/// `zstd` features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Compression {
+	#[cfg(feature = "gzip")]
+	Gzip,
+	#[cfg(feature = "zstd")]
+	Zstd,
+}
+
+impl HttpConfig {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn client(mut self, client: Client) -> Self {
+		self.client = Some(client);
+		self
+	}
+
+	#[cfg(unix)]
+	pub fn unix_socket(mut self, path: impl Into<PathBuf>) -> Self {
+		self.unix_socket = Some(path.into());
+		self
+	}
+
+	pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+		self.connect_timeout = Some(timeout);
+		self
+	}
+
+	pub fn read_timeout(mut self, timeout: Duration) -> Self {
+		self.read_timeout = Some(timeout);
+		self
+	}
+
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+		self.pool_max_idle_per_host = Some(max);
+		self
+	}
+
+	pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+		self.pool_idle_timeout = Some(timeout);
+		self
+	}
+
+	pub fn http2_keep_alive_interval(mut self, interval: Duration) -> Self {
+		self.http2_keep_alive_interval = Some(interval);
+		self
+	}
+
+	pub fn tcp_nodelay(mut self, enabled: bool) -> Self {
+		self.tcp_nodelay = Some(enabled);
+		self
+	}
+
+	pub fn identity(mut self, identity: Identity) -> Self {
+		self.identity = Some(identity);
+		self
+	}
+
+	pub fn add_root_certificate(mut self, cert: Certificate) -> Self {
+		self.root_certificates.push(cert);
+		self
+	}
+
+	pub fn accept_encoding(mut self, encoding: Compression) -> Self {
+		self.accept_encoding.push(encoding);
+		self
+	}
+
+	pub fn request_compression(mut self, compression: Compression) -> Self {
+		self.request_compression = Some(compression);
+		self
+	}
+
+	pub(crate) fn build(&self) -> Result<Client, LlmsError> {
+		if let Some(client) = &self.client {
+			return Ok(client.clone());
+		}
+
+		let mut builder = Client::builder();
+
+		if let Some(timeout) = self.connect_timeout {
+			builder = builder.connect_timeout(timeout);
+		}
+
+		if let Some(timeout) = self.read_timeout {
+			builder = builder.read_timeout(timeout);
+		}
+
+		if let Some(timeout) = self.timeout {
+			builder = builder.timeout(timeout);
+		}
+
+		if let Some(max) = self.pool_max_idle_per_host {
+			builder = builder.pool_max_idle_per_host(max);
+		}
+
+		if let Some(timeout) = self.pool_idle_timeout {
+			builder = builder.pool_idle_timeout(timeout);
+		}
+
+		if let Some(interval) = self.http2_keep_alive_interval {
+			builder = builder
+				.http2_keep_alive_interval(interval)
+				.http2_keep_alive_while_idle(true);
+		}
+
+		if let Some(enabled) = self.tcp_nodelay {
+			builder = builder.tcp_nodelay(enabled);
+		}
+
+		if let Some(identity) = &self.identity {
+			builder = builder.identity(identity.clone());
+		}
+
+		if !self.root_certificates.is_empty() {
+			builder = builder.tls_certs_merge(self.root_certificates.clone());
+		}
+
+		#[cfg(feature = "gzip")]
+		{
+			builder =
+				builder.gzip(self.accept_encoding.contains(&Compression::Gzip));
+		}
+
+		#[cfg(feature = "zstd")]
+		{
+			builder =
+				builder.zstd(self.accept_encoding.contains(&Compression::Zstd));
+		}
+
+		#[cfg(unix)]
+		if let Some(path) = &self.unix_socket {
+			builder = builder.unix_socket(path.clone());
+		}
+
+		Ok(builder.build()?)
+	}
+}
@@ -0,0 +1,64 @@
+use bytes::Bytes;
+
+/// A request to generate images from a text prompt, see
+/// [`Llms::generate_images`](super::Llms::generate_images).
+#[derive(Debug, Clone)]
+pub struct ImageRequest {
+	pub model: ImageModel,
+	pub prompt: String,
+	/// Number of images to generate, 0 is treated as 1. Imagen supports 1
+	/// to 4.
+	pub count: u32,
+	/// `None` uses the model's default, usually square.
+	pub aspect_ratio: Option<AspectRatio>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImageModel {
+	Imagen4,
+	Imagen4Ultra,
+	Imagen4Fast,
+	/// Gemini's native image generation ("nano banana").
+	GeminiFlashImage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AspectRatio {
+	/// 1:1
+	Square,
+	/// 3:4
+	Portrait,
+	/// 4:3
+	Landscape,
+	/// 9:16
+	PortraitTall,
+	/// 16:9
+	LandscapeWide,
+}
+
+impl AspectRatio {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			AspectRatio::Square => "1:1",
+			AspectRatio::Portrait => "3:4",
+			AspectRatio::Landscape => "4:3",
+			AspectRatio::PortraitTall => "9:16",
+			AspectRatio::LandscapeWide => "16:9",
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ImageResponse {
+	pub images: Vec<GeneratedImage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratedImage {
+	/// The raw, already decoded image bytes.
+	pub data: Bytes,
+	/// e.g. `"image/png"`.
+	pub mime_type: String,
+}
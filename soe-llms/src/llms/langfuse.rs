@@ -0,0 +1,294 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::StreamExt;
+use futures::channel::mpsc;
+use reqwest::Client;
+use serde_json::{Value, json};
+use tracing::warn;
+
+use super::{
+	DocumentSource, ExportOutcome, ExportRecord, Exporter, ImageSource, Input,
+	Model, Output, Role,
+};
+use crate::utils::time::rfc3339;
+
+/// Where and how [`LangfuseExporter`] sends its records.
+#[derive(Debug, Clone)]
+pub struct LangfuseConfig {
+	/// e.g. `"https://cloud.langfuse.com"`.
+	pub host: String,
+	pub public_key: String,
+	pub secret_key: String,
+	/// Records sent in one ingestion call at most.
+	pub batch_size: usize,
+	pub client: Client,
+}
+
+impl LangfuseConfig {
+	pub fn new(
+		host: impl Into<String>,
+		public_key: impl Into<String>,
+		secret_key: impl Into<String>,
+	) -> Self {
+		Self {
+			host: host.into(),
+			public_key: public_key.into(),
+			secret_key: secret_key.into(),
+			batch_size: 50,
+			client: Client::new(),
+		}
+	}
+}
+
+/// Reference [`Exporter`] sending every request as a trace with one
+/// generation to the Langfuse ingestion api.
+///
+/// Exporting only queues the record, the returned [`LangfuseWorker`]
+/// sends them and has to be spawned on a runtime.
+///
+/// ```no_run
+/// # use soe_llms::{LangfuseConfig, LangfuseExporter, Llms};
+/// # async fn run(mut llms: Llms) {
+/// let config = LangfuseConfig::new("https://cloud.langfuse.com", "pk", "sk");
+/// let (exporter, worker) = LangfuseExporter::new(config);
+/// tokio::spawn(worker.run());
+/// llms.add_exporter(exporter);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LangfuseExporter {
+	tx: mpsc::UnboundedSender<ExportRecord>,
+}
+
+/// Sends the records queued by a [`LangfuseExporter`].
+#[derive(Debug)]
+pub struct LangfuseWorker {
+	config: LangfuseConfig,
+	rx: mpsc::UnboundedReceiver<ExportRecord>,
+}
+
+impl LangfuseExporter {
+	pub fn new(config: LangfuseConfig) -> (Self, LangfuseWorker) {
+		let (tx, rx) = mpsc::unbounded();
+		(Self { tx }, LangfuseWorker { config, rx })
+	}
+}
+
+impl Exporter for LangfuseExporter {
+	fn export(&self, record: &ExportRecord) {
+		// the worker stopped, nothing left to send to
+		let _ = self.tx.unbounded_send(record.clone());
+	}
+}
+
+impl LangfuseWorker {
+	/// Sends records as they come in until all exporters were dropped.
+	///
+	/// Failed batches are logged and dropped.
+	pub async fn run(self) {
+		let url = format!(
+			"{}/api/public/ingestion",
+			self.config.host.trim_end_matches('/')
+		);
+		let mut batches = self.rx.ready_chunks(self.config.batch_size.max(1));
+
+		while let Some(records) = batches.next().await {
+			let batch: Vec<_> = records.iter().flat_map(events).collect();
+
+			let resp = self
+				.config
+				.client
+				.post(&url)
+				.basic_auth(
+					&self.config.public_key,
+					Some(&self.config.secret_key),
+				)
+				.json(&json!({ "batch": batch }))
+				.send()
+				.await
+				.and_then(|resp| resp.error_for_status());
+
+			if let Err(e) = resp {
+				warn!(
+					"failed to send {} records to langfuse: {e}",
+					records.len()
+				);
+			}
+		}
+	}
+}
+
+/// The trace and generation events of a record.
+fn events(record: &ExportRecord) -> [Value; 2] {
+	let trace_id = new_id();
+	let started_at = rfc3339(record.started_at);
+	let ended_at = rfc3339(record.ended_at());
+	let req = &record.request;
+	let input = input(req.instructions.as_str(), &req.input);
+	let model = match (&req.snapshot, &req.model) {
+		(Some(snapshot), _) => snapshot.clone(),
+		(None, Model::Custom { id, .. }) => id.clone(),
+		(None, model) => format!("{model:?}"),
+	};
+
+	let (output, level, status) = match &record.outcome {
+		ExportOutcome::Completed(resp) => {
+			(output(&resp.output), "DEFAULT", None)
+		}
+		ExportOutcome::Failed(e) => (Value::Null, "ERROR", Some(e.as_str())),
+		ExportOutcome::Cancelled => (Value::Null, "WARNING", Some("cancelled")),
+	};
+
+	let mut generation = json!({
+		"id": new_id(),
+		"traceId": trace_id,
+		"name": "llm-request",
+		"startTime": started_at,
+		"endTime": ended_at,
+		"model": model,
+		"input": input,
+		"output": output,
+		"level": level,
+		"statusMessage": status,
+	});
+	if let Some(ttft) = record.time_to_first_token {
+		generation["completionStartTime"] =
+			rfc3339(record.started_at + ttft).into();
+	}
+	if let Some(usage) = record.usage() {
+		generation["usageDetails"] = json!({
+			"input": usage.input_tokens,
+			"output": usage.output_tokens,
+			"total": usage.total_tokens(),
+			"cache_read_input_tokens": usage.cache_read_tokens,
+			"cache_creation_input_tokens": usage.cache_write_tokens,
+			"reasoning_tokens": usage.reasoning_tokens,
+		});
+	}
+	if let ExportOutcome::Completed(resp) = &record.outcome
+		&& let Some(cost) = resp.cost_estimate()
+	{
+		generation["costDetails"] = json!({
+			"input": cost.input,
+			"cache_read_input_tokens": cost.cached_input,
+			"cache_creation_input_tokens": cost.cache_write,
+			"output": cost.output,
+			"total": cost.total(),
+		});
+	}
+
+	let user_id = Some(req.user_id.as_str()).filter(|id| !id.is_empty());
+
+	[
+		json!({
+			"id": new_id(),
+			"timestamp": started_at,
+			"type": "trace-create",
+			"body": {
+				"id": trace_id,
+				"timestamp": started_at,
+				"name": "llm-request",
+				"userId": user_id,
+				"input": input,
+				"output": output,
+			},
+		}),
+		json!({
+			"id": new_id(),
+			"timestamp": ended_at,
+			"type": "generation-create",
+			"body": generation,
+		}),
+	]
+}
+
+/// The history as chat messages.
+fn input(instructions: &str, input: &[Input]) -> Value {
+	let system = Some(instructions)
+		.filter(|i| !i.is_empty())
+		.map(|i| json!({ "role": "system", "content": i }));
+
+	let messages = input.iter().map(|input| match input {
+		Input::Text { role, content } => {
+			json!({ "role": role_name(*role), "content": content })
+		}
+		Input::ToolCall {
+			id, name, input, ..
+		} => json!({
+			"role": "assistant",
+			"tool_calls": [{ "id": id, "name": name, "arguments": input }],
+		}),
+		Input::ToolCallOutput { id, output } => {
+			json!({ "role": "tool", "tool_call_id": id, "content": output })
+		}
+		// only a placeholder, the data would bloat every trace
+		Input::Image { data } => {
+			let content = match data {
+				ImageSource::Url(url) => format!("[image {url}]"),
+				ImageSource::Base64 { mime_type, .. }
+				| ImageSource::Reader { mime_type, .. } => {
+					format!("[{mime_type} image]")
+				}
+			};
+			json!({ "role": "user", "content": content })
+		}
+		Input::Document { data, mime } => {
+			let content = match data {
+				DocumentSource::Url(url) => format!("[document {url}]"),
+				DocumentSource::Base64(_) | DocumentSource::Reader(_) => {
+					format!("[{mime} document]")
+				}
+			};
+			json!({ "role": "user", "content": content })
+		}
+	});
+
+	system.into_iter().chain(messages).collect()
+}
+
+fn role_name(role: Role) -> &'static str {
+	match role {
+		Role::User => "user",
+		Role::Assistant => "assistant",
+		Role::System => "system",
+	}
+}
+
+fn output(output: &[Output]) -> Value {
+	let mut content = String::new();
+	let mut tool_calls = Vec::new();
+
+	for output in output {
+		match output {
+			Output::Text { content: text } => content.push_str(text),
+			Output::ToolCall {
+				id, name, input, ..
+			} => tool_calls
+				.push(json!({ "id": id, "name": name, "arguments": input })),
+			// not part of the answer
+			Output::Reasoning { .. } => {}
+			// described the same way as when sent back as history
+			other => {
+				if let Input::Text { content: text, .. } =
+					Input::from(other.clone())
+				{
+					content.push_str(&text);
+				}
+			}
+		}
+	}
+
+	json!({ "role": "assistant", "content": content, "tool_calls": tool_calls })
+}
+
+fn new_id() -> String {
+	static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+	let nanos = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_nanos();
+	let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+	format!("{nanos:x}-{:x}-{count:x}", std::process::id())
+}
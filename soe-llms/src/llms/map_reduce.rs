@@ -0,0 +1,162 @@
+use futures::stream::{self, StreamExt as _, TryStreamExt as _};
+
+use super::{
+	GenerationParams, Input, Llms, LlmsError, Model, Request, Response, Role,
+	chunking::Splitter,
+};
+
+/// Processes a document too long for a single request, see
+/// [`Llms::map_reduce`].
+#[derive(Debug, Clone)]
+pub struct MapReduce {
+	pub model: Model,
+	/// Run over every chunk, e.g. "Summarize this part of the report".
+	pub map_instructions: String,
+	/// Run over all map outputs, e.g. "Combine these partial summaries".
+	pub reduce_instructions: String,
+	/// Estimated size of a chunk, leave room for the instructions and the
+	/// output in the model's context.
+	pub chunk_tokens: u32,
+	/// Maximum number of map requests in flight at once.
+	pub concurrency: usize,
+	pub params: GenerationParams,
+	pub user_id: String,
+}
+
+/// Emitted while [`Llms::map_reduce`] runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MapReduceProgress {
+	/// The document was split into this many chunks.
+	Split { chunks: usize },
+	/// The map request over chunk `index` completed.
+	Mapped { index: usize, completed: usize },
+	/// All chunks are mapped, the reduce request was sent.
+	Reducing,
+}
+
+#[derive(Debug)]
+pub struct MapReduceOutput {
+	/// The map output per chunk, in document order.
+	pub partials: Vec<String>,
+	/// The response of the reduce request.
+	pub response: Response,
+}
+
+impl MapReduce {
+	pub fn new(
+		model: Model,
+		map_instructions: impl Into<String>,
+		reduce_instructions: impl Into<String>,
+	) -> Self {
+		Self {
+			model,
+			map_instructions: map_instructions.into(),
+			reduce_instructions: reduce_instructions.into(),
+			chunk_tokens: 8_000,
+			concurrency: 4,
+			params: GenerationParams::default(),
+			user_id: String::new(),
+		}
+	}
+
+	pub fn chunk_tokens(mut self, chunk_tokens: u32) -> Self {
+		self.chunk_tokens = chunk_tokens;
+		self
+	}
+
+	pub fn concurrency(mut self, concurrency: usize) -> Self {
+		self.concurrency = concurrency;
+		self
+	}
+
+	pub fn params(mut self, params: GenerationParams) -> Self {
+		self.params = params;
+		self
+	}
+
+	pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+		self.user_id = user_id.into();
+		self
+	}
+
+	fn request(&self, instructions: &str, content: String) -> Request {
+		Request {
+			input: vec![Input::Text {
+				role: Role::User,
+				content,
+			}],
+			instructions: instructions.into(),
+			model: self.model.clone(),
+			user_id: self.user_id.clone(),
+			tools: vec![],
+			tool_choice: Default::default(),
+			reasoning_effort: None,
+			response_format: Default::default(),
+			params: self.params.clone(),
+			cache: false,
+			extra: Default::default(),
+			headers: Default::default(),
+			timeouts: Default::default(),
+			transcript: None,
+			snapshot: None,
+		}
+	}
+}
+
+impl Llms {
+	/// Splits `document` into chunks of about
+	/// [`MapReduce::chunk_tokens`], runs the map instructions over every
+	/// chunk concurrently and then the reduce instructions over the
+	/// concatenated map outputs.
+	///
+	/// `progress` is called as the steps complete. The first failing
+	/// request aborts the whole run.
+	pub async fn map_reduce(
+		&self,
+		map_reduce: &MapReduce,
+		document: &str,
+		mut progress: impl FnMut(MapReduceProgress),
+	) -> Result<MapReduceOutput, LlmsError> {
+		let chunks = Splitter::new(map_reduce.chunk_tokens).split(document);
+		progress(MapReduceProgress::Split {
+			chunks: chunks.len(),
+		});
+
+		let mut partials = vec![String::new(); chunks.len()];
+		let mut mapped = stream::iter(chunks.into_iter().enumerate())
+			.map(|(index, chunk)| async move {
+				let req = map_reduce
+					.request(&map_reduce.map_instructions, chunk.into());
+				let resp = self.request_blocking(&req).await?;
+				Ok::<_, LlmsError>((index, resp.text()))
+			})
+			.buffer_unordered(map_reduce.concurrency.max(1));
+
+		let mut completed = 0;
+		while let Some((index, text)) = mapped.try_next().await? {
+			completed += 1;
+			partials[index] = text;
+			progress(MapReduceProgress::Mapped { index, completed });
+		}
+
+		progress(MapReduceProgress::Reducing);
+		let content = partials
+			.iter()
+			.enumerate()
+			.map(|(i, partial)| {
+				format!(
+					"<part index=\"{}\">\n{}\n</part>",
+					i + 1,
+					partial.trim()
+				)
+			})
+			.collect::<Vec<_>>()
+			.join("\n\n");
+
+		let req = map_reduce.request(&map_reduce.reduce_instructions, content);
+		let response = self.request_blocking(&req).await?;
+
+		Ok(MapReduceOutput { partials, response })
+	}
+}
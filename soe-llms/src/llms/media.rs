@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, Weak};
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use bytes::Bytes;
+use futures::{Stream, StreamExt as _, TryStreamExt as _, stream};
+use reqwest::Body;
+use tokio::io::AsyncRead;
+use tokio_util::io::ReaderStream;
+
+/// Stands in for the base64 data in the serialized body, followed by the
+/// key of the reader.
+const PLACEHOLDER: &str = "soe-llms-media-";
+/// Hex digits of the id and of the random part.
+const KEY_LEN: usize = 32;
+
+type Reader = Pin<Box<dyn AsyncRead + Send>>;
+
+/// The readers that weren't dropped yet by their key, so the body can be
+/// built from the serialized request.
+static READERS: LazyLock<Mutex<HashMap<String, Weak<Slot>>>> =
+	LazyLock::new(Default::default);
+
+fn readers() -> MutexGuard<'static, HashMap<String, Weak<Slot>>> {
+	match READERS.lock() {
+		Ok(readers) => readers,
+		Err(poisoned) => poisoned.into_inner(),
+	}
+}
+
+/// Media read while the request body is sent, for an
+/// [`ImageSource::Reader`](super::ImageSource::Reader) or a
+/// [`DocumentSource::Reader`](super::DocumentSource::Reader).
+///
+/// The data is base64 encoded chunk by chunk, so memory stays bounded
+/// however large it is. It can only be read once, clones share the
+/// reader and a retry or fallback sent after it was read fails with an
+/// [`LlmsError::Reqwest`](super::LlmsError::Reqwest).
+///
+/// Bodies that have to be complete before sending are read into memory:
+/// Bedrock signs them and [`HttpConfig::request_compression`] isn't
+/// applied to bodies with readers.
+///
+/// [`HttpConfig::request_compression`]: super::HttpConfig::request_compression
+#[derive(Clone)]
+pub struct MediaReader(Arc<Slot>);
+
+struct Slot {
+	key: String,
+	reader: Mutex<Option<Reader>>,
+}
+
+impl MediaReader {
+	pub fn new(reader: impl AsyncRead + Send + 'static) -> Self {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+		// random so that text in the request can't name another reader
+		let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+		let random = RandomState::new().hash_one(id);
+		let key = format!("{id:016x}{random:016x}");
+
+		let slot = Arc::new(Slot {
+			key: key.clone(),
+			reader: Mutex::new(Some(Box::pin(reader))),
+		});
+		readers().insert(key, Arc::downgrade(&slot));
+		Self(slot)
+	}
+
+	/// Serialized instead of the base64 data.
+	pub(crate) fn placeholder(&self) -> String {
+		format!("{PLACEHOLDER}{}", self.0.key)
+	}
+}
+
+impl fmt::Debug for MediaReader {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("MediaReader").finish_non_exhaustive()
+	}
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		readers().remove(&self.key);
+	}
+}
+
+impl Slot {
+	fn take(&self) -> Option<Reader> {
+		match self.reader.lock() {
+			Ok(mut reader) => reader.take(),
+			Err(poisoned) => poisoned.into_inner().take(),
+		}
+	}
+}
+
+enum Part {
+	Json(Bytes),
+	Reader(Arc<Slot>),
+}
+
+/// Splits the serialized body at the placeholders of readers, `None` if
+/// there are none.
+fn split(json: &[u8]) -> Option<Vec<Part>> {
+	let mut parts = Vec::new();
+	let mut start = 0;
+	let mut at = 0;
+
+	while let Some(pos) = find(&json[at..], PLACEHOLDER.as_bytes()) {
+		let key_start = at + pos + PLACEHOLDER.len();
+		at = key_start;
+		let Some(key) = json.get(key_start..key_start + KEY_LEN) else {
+			break;
+		};
+		let slot = std::str::from_utf8(key)
+			.ok()
+			.and_then(|key| readers().get(key)?.upgrade());
+		let Some(slot) = slot else {
+			continue;
+		};
+
+		parts.push(Part::Json(Bytes::copy_from_slice(
+			&json[start..at - PLACEHOLDER.len()],
+		)));
+		parts.push(Part::Reader(slot));
+		at += KEY_LEN;
+		start = at;
+	}
+
+	if parts.is_empty() {
+		return None;
+	}
+	parts.push(Part::Json(Bytes::copy_from_slice(&json[start..])));
+	Some(parts)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parts_stream(
+	parts: Vec<Part>,
+) -> impl Stream<Item = io::Result<Bytes>> + Send + 'static {
+	stream::iter(parts).flat_map(|part| match part {
+		Part::Json(json) => stream::once(async { Ok(json) }).boxed(),
+		Part::Reader(slot) => match slot.take() {
+			Some(reader) => encode(reader).boxed(),
+			None => stream::once(async {
+				Err(io::Error::other("media reader was already read"))
+			})
+			.boxed(),
+		},
+	})
+}
+
+/// The body of a serialized request, streaming the data of its readers.
+/// `None` if it has none.
+pub(crate) fn streaming_body(json: &[u8]) -> Option<Body> {
+	split(json).map(|parts| Body::wrap_stream(parts_stream(parts)))
+}
+
+/// Replaces the placeholders with the data of their readers, for bodies
+/// that have to be complete before sending.
+pub(crate) async fn read_body(json: Vec<u8>) -> io::Result<Vec<u8>> {
+	let Some(parts) = split(&json) else {
+		return Ok(json);
+	};
+
+	parts_stream(parts)
+		.try_fold(
+			Vec::with_capacity(json.len()),
+			|mut body, chunk| async move {
+				body.extend_from_slice(&chunk);
+				Ok(body)
+			},
+		)
+		.await
+}
+
+/// Base64 encodes the reader as it is read, holding back the bytes that
+/// don't fill a group of three until the next chunk.
+fn encode(reader: Reader) -> impl Stream<Item = io::Result<Bytes>> + Send {
+	let chunks = ReaderStream::new(reader);
+	stream::unfold(Some((chunks, Vec::new())), |state| async move {
+		let (mut chunks, mut carry) = state?;
+		match chunks.next().await {
+			Some(Ok(chunk)) => {
+				carry.extend_from_slice(&chunk);
+				let rest = carry.split_off(carry.len() / 3 * 3);
+				let encoded = BASE64_STANDARD.encode(&carry);
+				Some((Ok(encoded.into()), Some((chunks, rest))))
+			}
+			Some(Err(e)) => Some((Err(e), None)),
+			None => {
+				let encoded = BASE64_STANDARD.encode(&carry);
+				Some((Ok(encoded.into()), None))
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	/// Yields the data in chunks of `chunk` bytes.
+	fn reader(data: &[u8], chunk: usize) -> MediaReader {
+		let chunks: Vec<_> = data
+			.chunks(chunk)
+			.map(|c| io::Result::Ok(Bytes::copy_from_slice(c)))
+			.collect();
+		MediaReader::new(tokio_util::io::StreamReader::new(stream::iter(
+			chunks,
+		)))
+	}
+
+	#[tokio::test]
+	async fn encodes_readers_in_place() {
+		let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+		let image = reader(&data, 7);
+		let document = reader(b"%PDF-", 2);
+
+		let body = json!({
+			"image": format!("data:image/png;base64,{}", image.placeholder()),
+			"document": document.placeholder(),
+			"text": "soe-llms-media-but not a reader",
+		});
+		let body = read_body(serde_json::to_vec(&body).unwrap()).await.unwrap();
+
+		let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+		assert_eq!(
+			body["image"],
+			format!("data:image/png;base64,{}", BASE64_STANDARD.encode(&data))
+		);
+		assert_eq!(body["document"], BASE64_STANDARD.encode(b"%PDF-"));
+		assert_eq!(body["text"], "soe-llms-media-but not a reader");
+	}
+
+	#[tokio::test]
+	async fn readers_are_read_once() {
+		let image = reader(b"png", 3);
+		let body = serde_json::to_vec(&json!([image.placeholder()])).unwrap();
+
+		assert_eq!(read_body(body.clone()).await.unwrap(), br#"["cG5n"]"#);
+		assert!(read_body(body).await.is_err());
+	}
+
+	#[test]
+	fn dropped_readers_are_not_resolved() {
+		let image = reader(b"png", 3);
+		let body = serde_json::to_vec(&json!([image.placeholder()])).unwrap();
+		drop(image);
+
+		assert!(split(&body).is_none());
+	}
+}
@@ -1,10 +1,113 @@
+mod agent;
+mod api_error;
+mod capabilities;
+pub(crate) mod chat;
+pub mod chunking;
+mod config;
+mod conversation;
+mod credentials;
+mod defaults;
+mod embeddings;
 pub mod error;
-
+mod eval;
+mod experiment;
+mod export;
+mod fallback;
+mod health;
+pub(crate) mod history;
+mod hooks;
+mod http;
+mod images;
+mod langfuse;
+mod map_reduce;
+pub(crate) mod media;
+pub mod pricing;
+mod rate_limit;
+mod retry;
+mod router;
+mod sampling;
+#[cfg(feature = "schemars")]
+mod schema;
+mod segment;
+mod stats;
+mod timeout;
+mod tool_names;
+mod trace;
+mod transcript;
+mod truncate;
+
+pub use agent::{Agent, AgentEvent, AgentOutput};
+pub use api_error::ApiErrorKind;
+pub use capabilities::{Capabilities, Degradation, Feature};
+pub use chunking::{Boundary, Splitter};
+pub use config::ConfigError;
+pub use conversation::{Conversation, PendingToolCall};
+pub use credentials::{ApiKey, CredentialError, CredentialProvider};
+pub use defaults::RequestDefaults;
+pub use embeddings::{
+	EmbeddingModel, EmbeddingRequest, EmbeddingResponse, EmbeddingTaskType,
+};
 pub use error::LlmsError;
-
-use serde_json::Value;
-
-use crate::{anthropic, google, mistral, openai, openrouter, publicai, xai};
+pub use eval::{Judge, Verdict};
+pub use experiment::{Arm, Experiment, ExperimentArm};
+pub use export::{ExportOutcome, ExportRecord, Exporter};
+pub use fallback::FallbackPolicy;
+pub use health::HealthCheck;
+pub use hooks::Hook;
+pub use http::{COMPRESSION_MIN_SIZE, Compression, HttpConfig};
+pub use images::{
+	AspectRatio, GeneratedImage, ImageModel, ImageRequest, ImageResponse,
+};
+pub use langfuse::{LangfuseConfig, LangfuseExporter, LangfuseWorker};
+pub use map_reduce::{MapReduce, MapReduceOutput, MapReduceProgress};
+pub use media::MediaReader;
+pub use pricing::{Cost, Price};
+pub use rate_limit::RateLimit;
+pub use retry::RetryPolicy;
+pub use router::{CostClass, LatencyClass, Router};
+pub use sampling::{Samples, Sampling};
+#[cfg(feature = "schemars")]
+pub use schema::schema_for;
+pub use segment::{MarkdownSegmenter, Segmenter, WordSegmenter};
+pub use stats::StreamStats;
+pub use timeout::Timeouts;
+pub use trace::TraceHeaders;
+pub use transcript::Transcript;
+pub(crate) use transcript::TranscriptTee;
+pub use truncate::{ToolOutputLimit, Truncation};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::pin::pin;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use bytes::Bytes;
+use futures::future::{self, Either};
+use reqwest::Client;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use export::{Exporters, Recorder};
+use hooks::Hooks;
+use rate_limit::{RateLimitPermit, RateLimiter, reserved_tokens};
+use segment::Segmenters;
+use stats::StatsTracker;
+use timeout::Deadline;
+use tool_names::ToolRenames;
+
+use crate::{
+	anthropic, bedrock, google, mistral, openai, openai_compat, openrouter,
+	publicai, xai,
+};
+
+#[cfg(feature = "test-utils")]
+use crate::mock;
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -13,7 +116,104 @@ pub struct Request {
 	pub model: Model,
 	pub user_id: String,
 	pub tools: Vec<Tool>,
+	/// Ignored without [`Request::tools`].
+	pub tool_choice: ToolChoice,
 	pub reasoning_effort: Option<ReasoningEffort>,
+	pub response_format: ResponseFormat,
+	pub params: GenerationParams,
+	/// Marks the prompt as reused by later requests, for providers that
+	/// only cache when asked to.
+	///
+	/// - Anthropic: cache breakpoints after the instructions and at the end
+	///   of the input, so the next turn of a conversation reads everything
+	///   but its new input from the cache. Writes cost 25% more than
+	///   uncached input, reads a tenth.
+	/// - Others: ignored, they cache long prefixes on their own.
+	///
+	/// The cached part shows up in [`Usage::cache_read_tokens`] and
+	/// [`Usage::cache_write_tokens`].
+	pub cache: bool,
+	/// Provider-specific fields merged into the JSON body, for parameters
+	/// this crate doesn't support yet.
+	///
+	/// Overrides what is already set, objects are merged recursively, so
+	/// `{"generationConfig": {"seed": 1}}` keeps the rest of Gemini's
+	/// generation config. Sent as is to whichever provider the model belongs
+	/// to.
+	pub extra: Map<String, Value>,
+	/// Extra HTTP headers, e.g. for tracing or to enable an `anthropic-beta`
+	/// feature for a single call. They replace headers of the same name the
+	/// provider sets.
+	pub headers: HeaderMap,
+	pub timeouts: Timeouts,
+	/// Copies the raw SSE stream of the response, e.g. to report a provider
+	/// protocol bug. `None` by default.
+	pub transcript: Option<Transcript>,
+	/// A dated model id sent instead of the name the model maps to, e.g.
+	/// `"claude-sonnet-5-20260301"`, so the model doesn't change when the
+	/// provider points the name at a newer snapshot.
+	///
+	/// Usually pinned for every request through
+	/// [`RequestDefaults::snapshot`] in [`LlmsConfig::model_defaults`].
+	/// Ignored for [`Model::OpenRouter`], whose id is sent as is, a
+	/// [`Model::Custom`] id is replaced like any other.
+	pub snapshot: Option<String>,
+}
+
+/// Sampling and length parameters, `None` leaves the provider's default.
+///
+/// Not every model accepts every parameter, reasoning models commonly
+/// reject `temperature` and `top_p`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	/// Maximum number of output tokens, reasoning tokens included.
+	///
+	/// Anthropic requires a limit, it defaults to 8096 or 32768 when
+	/// [`Request::reasoning_effort`] is set.
+	pub max_tokens: Option<u32>,
+	/// Generation stops before the first of these would be output.
+	///
+	/// Providers limit the count, Gemini and Anthropic's docs don't
+	/// guarantee more than 5 work. Not supported by OpenAI's and xAI's
+	/// reasoning models, see [`Capabilities::stop_sequences`].
+	pub stop: Option<Vec<String>>,
+}
+
+impl GenerationParams {
+	/// Fills every parameter not set in `self` from `defaults`.
+	pub fn or(self, defaults: GenerationParams) -> Self {
+		Self {
+			temperature: self.temperature.or(defaults.temperature),
+			top_p: self.top_p.or(defaults.top_p),
+			max_tokens: self.max_tokens.or(defaults.max_tokens),
+			stop: self.stop.or(defaults.stop),
+		}
+	}
+}
+
+/// Whether and which tool the model has to call.
+///
+/// Mapping:
+/// - OpenAI / xAI / Mistral / PublicAI / OpenRouter / OpenAI compatible
+///   servers: `tool_choice`
+/// - Anthropic: `tool_choice`. Forcing a tool doesn't work together with
+///   thinking, so [`Request::reasoning_effort`] has to be `None` for
+///   [`ToolChoice::Required`] and [`ToolChoice::Tool`].
+/// - Google: `toolConfig.functionCallingConfig`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolChoice {
+	/// The model decides whether to call tools.
+	#[default]
+	Auto,
+	/// The model answers without calling tools.
+	None,
+	/// The model calls at least one tool.
+	Required,
+	/// The model calls the tool with this name, e.g. to extract
+	/// structured data through its arguments.
+	Tool(String),
 }
 
 /// Reasoning effort level.
@@ -22,9 +222,12 @@ pub struct Request {
 /// - OpenAI: `reasoning.effort`
 /// - Anthropic: `output_config.effort` via adaptive thinking.
 ///   Not supported on Claude Haiku 4.5 (ignored).
-/// - Google: `thinkingConfig.thinkingLevel`
+/// - Google: `thinkingConfig.thinkingLevel`, thought summaries are
+///   requested with `includeThoughts`. Models without thinking levels
+///   need a [`google::Request::thinking_budget`] instead.
 /// - OpenRouter: `reasoning.effort` (normalized across models; models
 ///   that don't reason ignore it)
+/// - OpenAI compatible servers: `reasoning_effort`
 /// - xAI / Mistral / PublicAi: ignored
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReasoningEffort {
@@ -33,12 +236,70 @@ pub enum ReasoningEffort {
 	High,
 }
 
+/// The format the model should produce its final answer in.
+///
+/// Mapping:
+/// - OpenAI: `text.format`, a strict schema is normalized first, see
+///   [`openai::to_strict_schema`].
+/// - Anthropic: `output_config.format` with the schema. There is no JSON
+///   mode, [`ResponseFormat::JsonObject`] is only asked for in the system
+///   prompt.
+/// - Google: `generationConfig.responseMimeType` plus `responseSchema`.
+///   The schema is translated into Gemini's OpenAPI-style subset, see
+///   [`google::to_gemini_schema`].
+/// - xAI / Mistral / OpenRouter / OpenAI compatible servers:
+///   `response_format` (`json_object` / `json_schema`)
+/// - PublicAI: not supported, [`Llms::request`] rejects it unless
+///   [`LlmsConfig::lenient`] is set, which asks for it in the instructions
+///   instead.
+///
+/// [`openai::to_strict_schema`]: crate::openai::to_strict_schema
+#[derive(Debug, Clone, Default)]
+pub enum ResponseFormat {
+	#[default]
+	Text,
+	/// Any syntactically valid JSON object.
+	JsonObject,
+	/// JSON matching the given JSON Schema.
+	JsonSchema {
+		/// Name of the schema, some providers require one.
+		name: String,
+		schema: Value,
+		/// Ask the provider to strictly enforce the schema where supported.
+		strict: bool,
+	},
+}
+
+impl ResponseFormat {
+	/// Asks for the format in the instructions, for providers that can't
+	/// enforce it.
+	pub(crate) fn instruction(&self) -> Option<String> {
+		match self {
+			Self::Text => None,
+			Self::JsonObject => Some(
+				"Respond only with a valid JSON object, without any \
+				surrounding text or code block."
+					.into(),
+			),
+			Self::JsonSchema { schema, .. } => Some(format!(
+				"Respond only with JSON matching this JSON Schema, without \
+				any surrounding text or code block:\n{schema}"
+			)),
+		}
+	}
+}
+
 #[derive(Debug, Clone)]
 pub enum Input {
 	Text {
 		role: Role,
 		content: String,
 	},
+	/// A call the assistant made. Consecutive calls and the assistant text
+	/// around them are sent as one assistant message with several tool
+	/// calls, so the calls of a parallel turn have to come before their
+	/// [`Input::ToolCallOutput`]s, as [`Request::extend_from_response`]
+	/// adds them.
 	ToolCall {
 		id: String,
 		name: String,
@@ -53,6 +314,111 @@ pub enum Input {
 		id: String,
 		output: String,
 	},
+	/// An image from the user, joining the user texts around it in one
+	/// message.
+	///
+	/// Requires [`Capabilities::vision`], see [`Feature::Vision`]. None of
+	/// the providers accept images from the assistant or in system
+	/// messages, so there is no role.
+	Image {
+		data: ImageSource,
+	},
+	/// A file like a PDF from the user, joining the user texts around it in
+	/// one message.
+	///
+	/// Requires [`Capabilities::documents`], see [`Feature::Documents`].
+	/// Like images, Anthropic and Gemini take no documents from the
+	/// assistant or in system messages, so there is no role.
+	Document {
+		data: DocumentSource,
+		/// e.g. `"application/pdf"`.
+		mime: String,
+	},
+}
+
+/// Where the provider gets an [`Input::Image`] from.
+///
+/// Base64 data is held in memory and encoded into the request body. A
+/// `Reader` is encoded while the body is sent instead, and with Gemini
+/// large media can also be uploaded with
+/// [`google::Google::upload_file_reader`] and passed as a `Url`.
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+	/// Downloaded by the provider. Google only accepts the uris of files
+	/// uploaded through its File API, see [`google::Google::upload_file`].
+	Url(String),
+	Base64 {
+		/// Without a `data:` prefix.
+		data: String,
+		/// e.g. `"image/png"`.
+		mime_type: String,
+	},
+	/// Base64 encoded while the request is sent.
+	Reader {
+		reader: MediaReader,
+		/// e.g. `"image/png"`.
+		mime_type: String,
+	},
+}
+
+impl ImageSource {
+	/// Base64 encodes the raw image bytes.
+	pub fn bytes(data: &[u8], mime_type: impl Into<String>) -> Self {
+		Self::Base64 {
+			data: BASE64_STANDARD.encode(data),
+			mime_type: mime_type.into(),
+		}
+	}
+
+	/// Streams the image from `reader`, see [`MediaReader`].
+	pub fn reader(
+		reader: impl tokio::io::AsyncRead + Send + 'static,
+		mime_type: impl Into<String>,
+	) -> Self {
+		Self::Reader {
+			reader: MediaReader::new(reader),
+			mime_type: mime_type.into(),
+		}
+	}
+
+	/// The url, a `data:` url for base64 images.
+	pub(crate) fn to_url(&self) -> String {
+		match self {
+			Self::Url(url) => url.clone(),
+			Self::Base64 { data, mime_type } => {
+				format!("data:{mime_type};base64,{data}")
+			}
+			Self::Reader { reader, mime_type } => {
+				format!("data:{mime_type};base64,{}", reader.placeholder())
+			}
+		}
+	}
+}
+
+/// Where the provider gets an [`Input::Document`] from.
+///
+/// Buffered or streamed like an [`ImageSource`].
+#[derive(Debug, Clone)]
+pub enum DocumentSource {
+	/// Downloaded by the provider. Google only accepts the uris of files
+	/// uploaded through its File API, see [`google::Google::upload_file`].
+	Url(String),
+	/// Without a `data:` prefix.
+	Base64(String),
+	/// Base64 encoded while the request is sent.
+	Reader(MediaReader),
+}
+
+impl DocumentSource {
+	/// Base64 encodes the raw file bytes.
+	pub fn bytes(data: &[u8]) -> Self {
+		Self::Base64(BASE64_STANDARD.encode(data))
+	}
+
+	/// Streams the document from `reader`, see [`MediaReader`].
+	pub fn reader(reader: impl tokio::io::AsyncRead + Send + 'static) -> Self {
+		Self::Reader(MediaReader::new(reader))
+	}
 }
 
 impl From<Output> for Input {
@@ -73,6 +439,26 @@ impl From<Output> for Input {
 				input,
 				context,
 			},
+			// Code execution happens on the provider side, the model only
+			// needs to see what it ran and what came out of it.
+			Output::CodeExecution { language, code } => Input::Text {
+				role: Role::Assistant,
+				content: format!("```{}\n{code}\n```", language.to_lowercase()),
+			},
+			Output::CodeExecutionResult { outcome, output } => Input::Text {
+				role: Role::Assistant,
+				content: format!("Execution result ({outcome:?}):\n{output}"),
+			},
+			// images can't be sent back as assistant content
+			Output::Image { mime_type, .. } => Input::Text {
+				role: Role::Assistant,
+				content: format!("[generated {mime_type} image]"),
+			},
+			// skipped by push_outputs, only converted when asked for
+			Output::Reasoning { content } => Input::Text {
+				role: Role::Assistant,
+				content,
+			},
 		}
 	}
 }
@@ -81,9 +467,18 @@ impl From<Output> for Input {
 pub enum Role {
 	User,
 	Assistant,
+	/// Instructions given mid-conversation, e.g. when the context changes.
+	///
+	/// Sent as developer messages to OpenAI and as system messages to
+	/// providers supporting them. Anthropic and Google only take a single
+	/// system prompt, there they're appended to
+	/// [`Request::instructions`].
+	System,
 }
 
-#[derive(Debug, Clone)]
+/// Deserializes from the variant name, e.g. `"Gpt5_6Luna"` or
+/// `{ "OpenAiCompat": "qwen3:8b" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
 #[non_exhaustive]
 pub enum Model {
 	Gpt5_6Sol,
@@ -98,6 +493,9 @@ pub enum Model {
 	GeminiPro3_1,
 	GeminiFlash3_5,
 	GeminiFlash3_1Lite,
+	/// Gemini's image generation model, answers with text and
+	/// [`Output::Image`]s.
+	GeminiFlashImage,
 
 	Grok4_5,
 
@@ -110,6 +508,44 @@ pub enum Model {
 	Apertus8bInstruct,
 
 	OpenRouter(Option<String>),
+
+	/// A model of an OpenAI compatible server like Ollama, as named by the
+	/// server, e.g. `"qwen3:8b"`. See [`LlmsConfig::openai_compat`].
+	///
+	/// What the model supports isn't known, its [`Model::capabilities`]
+	/// and [`Model::context_window`] are conservative defaults.
+	OpenAiCompat(String),
+
+	/// A model on Amazon Bedrock by its model or inference profile id, e.g.
+	/// `"us.anthropic.claude-sonnet-4-5-20250929-v1:0"`. See
+	/// [`LlmsConfig::bedrock`].
+	///
+	/// Claude and Mistral models are supported, see
+	/// [`ModelFamily::from_id`](crate::bedrock::ModelFamily::from_id). Its
+	/// limits are assumed, see [`Model::capabilities`].
+	Bedrock(String),
+
+	/// A model not listed here, e.g. a new release or a dated snapshot,
+	/// routed to `provider` with `id` sent as is.
+	///
+	/// Its limits are assumed, see [`Model::capabilities`].
+	Custom {
+		provider: ProviderKind,
+		id: String,
+	},
+
+	/// A name like `"fast"` resolved to a concrete model by [`Llms`] when
+	/// requesting, see [`Llms::set_alias`].
+	///
+	/// Nothing is known about an alias before it is resolved, its
+	/// [`Model::capabilities`] are all unsupported and its
+	/// [`Model::context_window`] is zero.
+	Alias(String),
+
+	/// Answered by the scripted [`mock::Mock`](crate::mock::Mock), see
+	/// [`LlmsConfig::mock`]. Supports every feature.
+	#[cfg(feature = "test-utils")]
+	Mock,
 }
 
 impl Model {
@@ -124,6 +560,7 @@ impl Model {
 		Model::GeminiPro3_1,
 		Model::GeminiFlash3_5,
 		Model::GeminiFlash3_1Lite,
+		Model::GeminiFlashImage,
 		Model::Grok4_5,
 		Model::MistralLarge,
 		Model::MistralMedium,
@@ -133,6 +570,36 @@ impl Model {
 		Model::OpenRouter(None),
 	];
 
+	/// The provider serving this model, `None` for a [`Model::Alias`].
+	pub fn provider(&self) -> Option<ProviderKind> {
+		Some(match self {
+			Model::Gpt5_6Sol | Model::Gpt5_6Terra | Model::Gpt5_6Luna => {
+				ProviderKind::OpenAi
+			}
+			Model::ClaudeFable5
+			| Model::ClaudeOpus4_8
+			| Model::ClaudeSonnet5
+			| Model::ClaudeHaiku4_5 => ProviderKind::Anthropic,
+			Model::GeminiPro3_1
+			| Model::GeminiFlash3_5
+			| Model::GeminiFlash3_1Lite
+			| Model::GeminiFlashImage => ProviderKind::Google,
+			Model::Grok4_5 => ProviderKind::XAi,
+			Model::MistralLarge
+			| Model::MistralMedium
+			| Model::MistralSmall
+			| Model::Ministral14b => ProviderKind::Mistral,
+			Model::Apertus8bInstruct => ProviderKind::PublicAi,
+			Model::OpenRouter(_) => ProviderKind::OpenRouter,
+			Model::OpenAiCompat(_) => ProviderKind::OpenAiCompat,
+			Model::Bedrock(_) => ProviderKind::Bedrock,
+			Model::Custom { provider, .. } => *provider,
+			#[cfg(feature = "test-utils")]
+			Model::Mock => ProviderKind::Mock,
+			Model::Alias(_) => return None,
+		})
+	}
+
 	/// The model's total context window in tokens (input + output combined).
 	///
 	/// Values are taken from each provider's public documentation. Providers
@@ -140,6 +607,9 @@ impl Model {
 	/// as conservative defaults — if you need a stricter or more accurate
 	/// budget, pass your own number directly to [`Usage::remaining_tokens`] /
 	/// [`Usage::fraction_used`].
+	///
+	/// Assumed for a [`Model::Bedrock`] or [`Model::Custom`], see
+	/// [`Model::capabilities`].
 	pub fn context_window(&self) -> u32 {
 		match self {
 			Model::Gpt5_6Sol | Model::Gpt5_6Terra | Model::Gpt5_6Luna => {
@@ -154,6 +624,7 @@ impl Model {
 			Model::GeminiPro3_1
 			| Model::GeminiFlash3_5
 			| Model::GeminiFlash3_1Lite => 1_048_576,
+			Model::GeminiFlashImage => 32_768,
 
 			Model::Grok4_5 => 500_000,
 
@@ -167,6 +638,43 @@ impl Model {
 			// The real context window depends on whichever model OpenRouter
 			// routes to; this is only a conservative default.
 			Model::OpenRouter(_) => 128_000,
+			// Ollama's default context length, servers are often configured
+			// for more.
+			Model::OpenAiCompat(_) => 4_096,
+			Model::Bedrock(id) => {
+				Model::bedrock_representative(id).context_window()
+			}
+			Model::Custom { provider, .. } => {
+				Model::representative(*provider).context_window()
+			}
+			#[cfg(feature = "test-utils")]
+			Model::Mock => 1_000_000,
+			Model::Alias(_) => 0,
+		}
+	}
+
+	/// The model whose limits a [`Model::Custom`] of `provider` assumes.
+	fn representative(provider: ProviderKind) -> Model {
+		match provider {
+			ProviderKind::OpenAi => Model::Gpt5_6Terra,
+			ProviderKind::Anthropic => Model::ClaudeSonnet5,
+			ProviderKind::Google => Model::GeminiPro3_1,
+			ProviderKind::XAi => Model::Grok4_5,
+			ProviderKind::Mistral => Model::MistralLarge,
+			ProviderKind::PublicAi => Model::Apertus8bInstruct,
+			ProviderKind::OpenRouter => Model::OpenRouter(None),
+			ProviderKind::OpenAiCompat => Model::OpenAiCompat(String::new()),
+			ProviderKind::Bedrock => Model::ClaudeSonnet5,
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => Model::Mock,
+		}
+	}
+
+	/// The model whose limits a [`Model::Bedrock`] assumes.
+	fn bedrock_representative(id: &str) -> Model {
+		match bedrock::ModelFamily::from_id(id) {
+			Some(bedrock::ModelFamily::Mistral) => Model::MistralLarge,
+			_ => Model::ClaudeSonnet5,
 		}
 	}
 }
@@ -187,6 +695,22 @@ pub struct Tool {
 	/// ```
 	// None = { "type": "object", "properties": {} }
 	pub parameters: Option<Value>,
+	/// Ask the provider to guarantee arguments matching `parameters`.
+	///
+	/// - OpenAI: `strict: true`, the schema is normalized first, see
+	///   [`openai::to_strict_schema`]. Optional properties become nullable.
+	/// - Others: ignored
+	///
+	/// [`openai::to_strict_schema`]: crate::openai::to_strict_schema
+	pub strict: bool,
+	/// Cache the tool definitions up to and including this one.
+	///
+	/// - Anthropic: a cache breakpoint after the tool, only needed if the
+	///   tools are static but the instructions aren't, since
+	///   [`Request::cache`] covers the tools as well. At most 4 breakpoints
+	///   are allowed per request.
+	/// - Others: ignored
+	pub cache: bool,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -195,10 +719,79 @@ pub struct LlmsConfig {
 	pub openai_api_key: Option<String>,
 	pub anthropic_api_key: Option<String>,
 	pub google_api_key: Option<String>,
+	/// See [`LlmsConfig::google_vertex`].
+	pub google_vertex: Option<google::Vertex>,
 	pub xai_api_key: Option<String>,
 	pub mistral_api_key: Option<String>,
 	pub publicai_api_key: Option<String>,
 	pub openrouter_api_key: Option<String>,
+	/// Only needed if the server checks it, see
+	/// [`LlmsConfig::openai_compat`].
+	pub openai_compat_api_key: Option<String>,
+	/// See [`LlmsConfig::bedrock`].
+	pub bedrock_region: Option<String>,
+	/// See [`LlmsConfig::bedrock`].
+	pub bedrock_credentials: Option<bedrock::AwsCredentials>,
+	/// Answers requests to [`Model::Mock`], see [`LlmsConfig::mock`].
+	#[cfg(feature = "test-utils")]
+	pub mock: Option<mock::Mock>,
+	/// See [`openai::OpenAi::organization`](crate::openai::OpenAi::organization).
+	pub openai_organization: Option<String>,
+	/// See [`openai::OpenAi::project`](crate::openai::OpenAi::project).
+	pub openai_project: Option<String>,
+	/// See [`openrouter::OpenRouter::app_url`](crate::openrouter::OpenRouter::app_url).
+	pub openrouter_app_url: Option<String>,
+	/// See [`openrouter::OpenRouter::app_name`](crate::openrouter::OpenRouter::app_name).
+	pub openrouter_app_name: Option<String>,
+	/// See [`openrouter::OpenRouter::provider`](crate::openrouter::OpenRouter::provider).
+	pub openrouter_provider: Option<openrouter::ProviderPreferences>,
+	/// Strip or emulate unsupported request features instead of failing
+	/// with [`LlmsError::Unsupported`]. What was changed is reported in
+	/// [`Response::degraded`].
+	///
+	/// Tool names the provider doesn't accept are renamed instead of
+	/// failing with [`LlmsError::InvalidToolName`], the tool calls in the
+	/// response carry the original names.
+	pub lenient: bool,
+	/// HTTP client settings used by every provider without an entry in
+	/// [`LlmsConfig::provider_http`]. These providers share one client and
+	/// with it the connection pool.
+	pub http: HttpConfig,
+	/// HTTP client settings for individual providers.
+	pub provider_http: HashMap<ProviderKind, HttpConfig>,
+	/// Endpoint prefixes replacing the providers' own, e.g. for proxies or
+	/// gateways, see [`openai::OpenAi::base_url`].
+	pub base_urls: HashMap<ProviderKind, String>,
+	/// Credential providers, taking precedence over the `*_api_key` fields.
+	pub credentials: HashMap<ProviderKind, ApiKey>,
+	/// Limits of every request, each one the request and its
+	/// [`RequestDefaults`] leave unset. Without any, a stalled stream waits
+	/// forever.
+	pub timeouts: Timeouts,
+	/// Defaults for every request to a provider.
+	pub provider_defaults: HashMap<ProviderKind, RequestDefaults>,
+	/// Defaults for every request to a model, taking precedence over
+	/// [`LlmsConfig::provider_defaults`].
+	pub model_defaults: HashMap<Model, RequestDefaults>,
+	/// Initial [`Model::Alias`] targets, see [`Llms::set_alias`].
+	pub aliases: HashMap<String, Model>,
+	/// Initial price overrides, see [`Llms::set_price`].
+	pub prices: HashMap<Model, Price>,
+	/// Initial experiments, see [`Llms::set_experiment`].
+	pub experiments: HashMap<String, Experiment>,
+	/// Shortens oversized tool outputs before a request is sent.
+	pub tool_output_limit: Option<ToolOutputLimit>,
+	/// Adds trace context headers to every request.
+	pub trace_headers: Option<TraceHeaders>,
+	/// Retries transient failures before the response started, `None`
+	/// fails right away.
+	pub retry: Option<RetryPolicy>,
+	/// Models tried when a request's own model fails, `None` returns the
+	/// error.
+	pub fallback: Option<FallbackPolicy>,
+	/// Limits of the requests to a provider, without one requests are sent
+	/// right away.
+	pub rate_limits: HashMap<ProviderKind, RateLimit>,
 }
 
 impl LlmsConfig {
@@ -211,6 +804,39 @@ impl LlmsConfig {
 		self
 	}
 
+	pub fn openai_organization(
+		mut self,
+		id: impl Into<Option<String>>,
+	) -> Self {
+		self.openai_organization = id.into();
+		self
+	}
+
+	pub fn openai_project(mut self, id: impl Into<Option<String>>) -> Self {
+		self.openai_project = id.into();
+		self
+	}
+
+	/// Attributes the OpenRouter requests to an app, see
+	/// [`openrouter::OpenRouter::app_url`](crate::openrouter::OpenRouter::app_url).
+	pub fn openrouter_app(
+		mut self,
+		url: impl Into<Option<String>>,
+		name: impl Into<Option<String>>,
+	) -> Self {
+		self.openrouter_app_url = url.into();
+		self.openrouter_app_name = name.into();
+		self
+	}
+
+	pub fn openrouter_provider(
+		mut self,
+		provider: impl Into<Option<openrouter::ProviderPreferences>>,
+	) -> Self {
+		self.openrouter_provider = provider.into();
+		self
+	}
+
 	pub fn anthropic(mut self, api_key: impl Into<Option<String>>) -> Self {
 		self.anthropic_api_key = api_key.into();
 		self
@@ -221,6 +847,19 @@ impl LlmsConfig {
 		self
 	}
 
+	/// Sends the Gemini requests to Vertex AI in `project` and `location`,
+	/// see [`google::Google::vertex`]. The Google api key is sent as the
+	/// OAuth access token, usually it comes from
+	/// [`LlmsConfig::credentials`], e.g. a [`google::MetadataToken`].
+	pub fn google_vertex(
+		mut self,
+		project: impl Into<String>,
+		location: impl Into<String>,
+	) -> Self {
+		self.google_vertex = Some(google::Vertex::new(project, location));
+		self
+	}
+
 	pub fn xai(mut self, api_key: impl Into<Option<String>>) -> Self {
 		self.xai_api_key = api_key.into();
 		self
@@ -240,6 +879,242 @@ impl LlmsConfig {
 		self.openrouter_api_key = api_key.into();
 		self
 	}
+
+	/// Configures the OpenAI compatible server at `base_url` for
+	/// [`Model::OpenAiCompat`], e.g. `"http://localhost:11434/v1"` for a
+	/// local Ollama.
+	pub fn openai_compat(mut self, base_url: impl Into<String>) -> Self {
+		self.base_urls
+			.insert(ProviderKind::OpenAiCompat, base_url.into());
+		self
+	}
+
+	pub fn openai_compat_api_key(
+		mut self,
+		api_key: impl Into<Option<String>>,
+	) -> Self {
+		self.openai_compat_api_key = api_key.into();
+		self
+	}
+
+	/// Configures Amazon Bedrock in `region` for [`Model::Bedrock`], the
+	/// requests are signed with `credentials`.
+	///
+	/// [`HttpConfig::request_compression`] doesn't apply, the bodies are
+	/// always sent uncompressed.
+	pub fn bedrock(
+		mut self,
+		region: impl Into<String>,
+		credentials: bedrock::AwsCredentials,
+	) -> Self {
+		self.bedrock_region = Some(region.into());
+		self.bedrock_credentials = Some(credentials);
+		self
+	}
+
+	/// Answers requests to [`Model::Mock`] with the replies queued on
+	/// `mock`, see [`mock::Mock`].
+	#[cfg(feature = "test-utils")]
+	pub fn mock(mut self, mock: mock::Mock) -> Self {
+		self.mock = Some(mock);
+		self
+	}
+
+	pub fn lenient(mut self, lenient: bool) -> Self {
+		self.lenient = lenient;
+		self
+	}
+
+	pub fn http(mut self, http: HttpConfig) -> Self {
+		self.http = http;
+		self
+	}
+
+	/// Sends the requests of every provider without an entry in
+	/// [`LlmsConfig::provider_http`] through `client`, e.g. one also used
+	/// elsewhere in the application. Shorthand for [`HttpConfig::client`].
+	pub fn with_client(mut self, client: Client) -> Self {
+		self.http.client = Some(client);
+		self
+	}
+
+	pub fn provider_http(
+		mut self,
+		kind: ProviderKind,
+		http: HttpConfig,
+	) -> Self {
+		self.provider_http.insert(kind, http);
+		self
+	}
+
+	/// Sends the requests to the provider to `url` instead, e.g.
+	/// `"http://localhost:4000/v1"` for a LiteLLM proxy.
+	pub fn base_url(
+		mut self,
+		kind: ProviderKind,
+		url: impl Into<String>,
+	) -> Self {
+		self.base_urls.insert(kind, url.into());
+		self
+	}
+
+	/// Fetch the api key of the provider through a [`CredentialProvider`]
+	/// at request time. Also configures the provider if no api key is set.
+	pub fn credentials(
+		mut self,
+		kind: ProviderKind,
+		provider: impl CredentialProvider,
+	) -> Self {
+		self.credentials.insert(kind, ApiKey::provider(provider));
+		self
+	}
+
+	pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+		self.timeouts = timeouts;
+		self
+	}
+
+	pub fn provider_defaults(
+		mut self,
+		kind: ProviderKind,
+		defaults: RequestDefaults,
+	) -> Self {
+		self.provider_defaults.insert(kind, defaults);
+		self
+	}
+
+	pub fn model_defaults(
+		mut self,
+		model: Model,
+		defaults: RequestDefaults,
+	) -> Self {
+		self.model_defaults.insert(model, defaults);
+		self
+	}
+
+	pub fn alias(mut self, name: impl Into<String>, model: Model) -> Self {
+		self.aliases.insert(name.into(), model);
+		self
+	}
+
+	pub fn price(mut self, model: Model, price: Price) -> Self {
+		self.prices.insert(model, price);
+		self
+	}
+
+	pub fn experiment(
+		mut self,
+		alias: impl Into<String>,
+		experiment: Experiment,
+	) -> Self {
+		self.experiments.insert(alias.into(), experiment);
+		self
+	}
+
+	pub fn tool_output_limit(mut self, limit: ToolOutputLimit) -> Self {
+		self.tool_output_limit = Some(limit);
+		self
+	}
+
+	pub fn trace_headers(mut self, trace_headers: TraceHeaders) -> Self {
+		self.trace_headers = Some(trace_headers);
+		self
+	}
+
+	pub fn retry(mut self, retry: RetryPolicy) -> Self {
+		self.retry = Some(retry);
+		self
+	}
+
+	pub fn fallback(mut self, fallback: FallbackPolicy) -> Self {
+		self.fallback = Some(fallback);
+		self
+	}
+
+	pub fn rate_limit(mut self, kind: ProviderKind, limit: RateLimit) -> Self {
+		self.rate_limits.insert(kind, limit);
+		self
+	}
+
+	fn key_for(&self, kind: ProviderKind) -> Option<ApiKey> {
+		self.credentials
+			.get(&kind)
+			.cloned()
+			.or_else(|| self.api_key(kind).cloned().map(ApiKey::Static))
+	}
+
+	fn http_for(&self, kind: ProviderKind) -> &HttpConfig {
+		self.provider_http.get(&kind).unwrap_or(&self.http)
+	}
+
+	/// The client of the provider, the one built from [`LlmsConfig::http`]
+	/// is kept in `shared` and reused by every provider without its own
+	/// settings.
+	fn client_for(
+		&self,
+		kind: ProviderKind,
+		shared: &mut Option<Client>,
+	) -> Result<Client, LlmsError> {
+		if let Some(http) = self.provider_http.get(&kind) {
+			return http.build();
+		}
+
+		match shared {
+			Some(client) => Ok(client.clone()),
+			None => Ok(shared.insert(self.http.build()?).clone()),
+		}
+	}
+
+	fn base_url_for(&self, kind: ProviderKind) -> String {
+		if let Some(url) = self.base_urls.get(&kind) {
+			return url.clone();
+		}
+		if kind == ProviderKind::Google
+			&& let Some(vertex) = &self.google_vertex
+		{
+			return vertex.base_url();
+		}
+		if kind == ProviderKind::Bedrock {
+			let region = self.bedrock_region.as_deref().unwrap_or_default();
+			return bedrock::default_base_url(region);
+		}
+
+		match kind {
+			ProviderKind::OpenAi => openai::DEFAULT_BASE_URL,
+			ProviderKind::Anthropic => anthropic::DEFAULT_BASE_URL,
+			ProviderKind::Google => google::DEFAULT_BASE_URL,
+			ProviderKind::XAi => xai::DEFAULT_BASE_URL,
+			ProviderKind::Mistral => mistral::DEFAULT_BASE_URL,
+			ProviderKind::PublicAi => publicai::DEFAULT_BASE_URL,
+			ProviderKind::OpenRouter => openrouter::DEFAULT_BASE_URL,
+			ProviderKind::OpenAiCompat => openai_compat::DEFAULT_BASE_URL,
+			ProviderKind::Bedrock => unreachable!("depends on the region"),
+			// never sends anything
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => "",
+		}
+		.into()
+	}
+}
+
+/// The providers an [`Llms`] can route to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[non_exhaustive]
+pub enum ProviderKind {
+	OpenAi,
+	Anthropic,
+	Google,
+	XAi,
+	Mistral,
+	PublicAi,
+	OpenRouter,
+	/// See [`Model::OpenAiCompat`].
+	OpenAiCompat,
+	/// See [`Model::Bedrock`].
+	Bedrock,
+	/// See [`Model::Mock`].
+	#[cfg(feature = "test-utils")]
+	Mock,
 }
 
 #[derive(Debug, Clone)]
@@ -251,90 +1126,691 @@ struct LlmProviders {
 	mistral: Option<mistral::Mistral>,
 	publicai: Option<publicai::PublicAi>,
 	openrouter: Option<openrouter::OpenRouter>,
+	openai_compat: Option<openai_compat::OpenAiCompat>,
+	bedrock: Option<bedrock::Bedrock>,
+	#[cfg(feature = "test-utils")]
+	mock: Option<mock::Mock>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Llms {
 	inner: LlmProviders,
+	lenient: bool,
+	hooks: Hooks,
+	exporters: Exporters,
+	timeouts: Timeouts,
+	provider_defaults: HashMap<ProviderKind, RequestDefaults>,
+	model_defaults: HashMap<Model, RequestDefaults>,
+	/// Shared between clones, so repointing an alias affects all of them.
+	aliases: Arc<RwLock<HashMap<String, Model>>>,
+	/// Shared between clones like the aliases.
+	prices: Arc<RwLock<HashMap<Model, Price>>>,
+	experiments: Arc<RwLock<HashMap<String, Experiment>>>,
+	tool_output_limit: Option<ToolOutputLimit>,
+	summaries: truncate::Summaries,
+	trace_headers: Option<TraceHeaders>,
+	retry: Option<RetryPolicy>,
+	fallback: Option<FallbackPolicy>,
+	/// Shared between clones, so the limits apply to all of them.
+	rate_limiters: HashMap<ProviderKind, Arc<RateLimiter>>,
 }
 
 impl Llms {
+	/// Creates a new instance from the config.
+	///
+	/// ## Panics
+	/// Panics if an HTTP client can't be built, use [`Llms::try_new`] to
+	/// handle that case.
 	pub fn new(config: LlmsConfig) -> Self {
-		Self {
+		Self::try_new(config).expect("failed to build http client")
+	}
+
+	/// Creates a new instance from the config, failing if an HTTP client
+	/// can't be built from its [`HttpConfig`].
+	pub fn try_new(config: LlmsConfig) -> Result<Self, LlmsError> {
+		use ProviderKind as P;
+
+		let mut shared = None;
+		Ok(Self {
 			inner: LlmProviders {
-				open_ai: config.openai_api_key.map(openai::OpenAi::new),
-				anthropic: config
-					.anthropic_api_key
-					.map(anthropic::Anthropic::new),
-				google: config.google_api_key.map(google::Google::new),
-				xai: config.xai_api_key.map(xai::XAi::new),
-				mistral: config.mistral_api_key.map(mistral::Mistral::new),
-				publicai: config.publicai_api_key.map(publicai::PublicAi::new),
-				openrouter: config
-					.openrouter_api_key
-					.map(openrouter::OpenRouter::new),
+				open_ai: match config.key_for(P::OpenAi) {
+					Some(key) => {
+						let http = config.http_for(P::OpenAi);
+						Some(openai::OpenAi {
+							client: config
+								.client_for(P::OpenAi, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::OpenAi),
+							organization: config.openai_organization.clone(),
+							project: config.openai_project.clone(),
+						})
+					}
+					None => None,
+				},
+				anthropic: match config.key_for(P::Anthropic) {
+					Some(key) => {
+						let http = config.http_for(P::Anthropic);
+						Some(anthropic::Anthropic {
+							client: config
+								.client_for(P::Anthropic, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::Anthropic),
+						})
+					}
+					None => None,
+				},
+				google: match config.key_for(P::Google) {
+					Some(key) => {
+						let http = config.http_for(P::Google);
+						Some(google::Google {
+							client: config
+								.client_for(P::Google, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::Google),
+							vertex: config.google_vertex.clone(),
+						})
+					}
+					None => None,
+				},
+				xai: match config.key_for(P::XAi) {
+					Some(key) => {
+						let http = config.http_for(P::XAi);
+						Some(xai::XAi {
+							client: config.client_for(P::XAi, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::XAi),
+						})
+					}
+					None => None,
+				},
+				mistral: match config.key_for(P::Mistral) {
+					Some(key) => {
+						let http = config.http_for(P::Mistral);
+						Some(mistral::Mistral {
+							client: config
+								.client_for(P::Mistral, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::Mistral),
+						})
+					}
+					None => None,
+				},
+				publicai: match config.key_for(P::PublicAi) {
+					Some(key) => {
+						let http = config.http_for(P::PublicAi);
+						Some(publicai::PublicAi {
+							client: config
+								.client_for(P::PublicAi, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::PublicAi),
+						})
+					}
+					None => None,
+				},
+				openrouter: match config.key_for(P::OpenRouter) {
+					Some(key) => {
+						let http = config.http_for(P::OpenRouter);
+						Some(openrouter::OpenRouter {
+							client: config
+								.client_for(P::OpenRouter, &mut shared)?,
+							api_key: key,
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::OpenRouter),
+							app_url: config.openrouter_app_url.clone(),
+							app_name: config.openrouter_app_name.clone(),
+							provider: config.openrouter_provider.clone(),
+						})
+					}
+					None => None,
+				},
+				// local servers usually don't need a key
+				openai_compat: match (
+					config.key_for(P::OpenAiCompat),
+					config.base_urls.contains_key(&P::OpenAiCompat),
+				) {
+					(None, false) => None,
+					(key, _) => {
+						let http = config.http_for(P::OpenAiCompat);
+						Some(openai_compat::OpenAiCompat {
+							client: config
+								.client_for(P::OpenAiCompat, &mut shared)?,
+							api_key: key.unwrap_or_else(|| {
+								ApiKey::Static(String::new())
+							}),
+							compression: http.request_compression,
+							base_url: config.base_url_for(P::OpenAiCompat),
+						})
+					}
+				},
+				bedrock: match (
+					&config.bedrock_region,
+					&config.bedrock_credentials,
+				) {
+					(Some(region), Some(credentials)) => {
+						Some(bedrock::Bedrock {
+							client: config
+								.client_for(P::Bedrock, &mut shared)?,
+							credentials: credentials.clone(),
+							region: region.clone(),
+							base_url: config.base_url_for(P::Bedrock),
+						})
+					}
+					_ => None,
+				},
+				#[cfg(feature = "test-utils")]
+				mock: config.mock.clone(),
 			},
+			lenient: config.lenient,
+			timeouts: config.timeouts,
+			hooks: Hooks::default(),
+			exporters: Exporters::default(),
+			provider_defaults: config.provider_defaults,
+			model_defaults: config.model_defaults,
+			aliases: Arc::new(RwLock::new(config.aliases)),
+			prices: Arc::new(RwLock::new(config.prices)),
+			experiments: Arc::new(RwLock::new(config.experiments)),
+			tool_output_limit: config.tool_output_limit,
+			summaries: Default::default(),
+			trace_headers: config.trace_headers,
+			retry: config.retry,
+			fallback: config.fallback,
+			rate_limiters: config
+				.rate_limits
+				.into_iter()
+				.map(|(kind, limit)| (kind, Arc::new(RateLimiter::new(limit))))
+				.collect(),
+		})
+	}
+
+	/// Points the alias `name` to `model`, replacing any previous target.
+	///
+	/// Takes effect for every following request, also on clones of this
+	/// instance. `model` can't be an alias itself.
+	pub fn set_alias(&self, name: impl Into<String>, model: Model) {
+		write(&self.aliases).insert(name.into(), model);
+	}
+
+	pub fn remove_alias(&self, name: &str) -> Option<Model> {
+		write(&self.aliases).remove(name)
+	}
+
+	/// Prices the responses of `model` at `price` instead of
+	/// [`Model::price`], e.g. negotiated rates or a [`Model::Custom`].
+	///
+	/// Takes effect for every following request, also on clones of this
+	/// instance.
+	pub fn set_price(&self, model: Model, price: Price) {
+		write(&self.prices).insert(model, price);
+	}
+
+	/// Goes back to [`Model::price`] for `model`.
+	pub fn remove_price(&self, model: &Model) -> Option<Price> {
+		write(&self.prices).remove(model)
+	}
+
+	/// The price responses of `model` are estimated with, see
+	/// [`Response::cost_estimate`].
+	pub fn price(&self, model: &Model) -> Option<Price> {
+		let price = read(&self.prices).get(model).copied();
+		price.or_else(|| model.price())
+	}
+
+	/// Runs an experiment on the alias `name`, requests to it are split
+	/// between the arms of `experiment`. Takes precedence over
+	/// [`Llms::set_alias`] and affects clones of this instance.
+	///
+	/// Responses report their arm in [`Response::experiment`].
+	pub fn set_experiment(
+		&self,
+		name: impl Into<String>,
+		experiment: Experiment,
+	) {
+		write(&self.experiments).insert(name.into(), experiment);
+	}
+
+	pub fn remove_experiment(&self, name: &str) -> Option<Experiment> {
+		write(&self.experiments).remove(name)
+	}
+
+	/// Resolves the alias `name` through the experiments first, then the
+	/// plain aliases.
+	fn resolve_alias(
+		&self,
+		name: &str,
+		user_id: &str,
+	) -> Result<(Model, Option<ExperimentArm>), LlmsError> {
+		let experiments = read(&self.experiments);
+		if let Some(arm) = experiments
+			.get(name)
+			.and_then(|experiment| experiment.assign(name, user_id))
+			&& !matches!(arm.model, Model::Alias(_))
+		{
+			let tag = ExperimentArm {
+				experiment: name.into(),
+				arm: arm.name.clone(),
+			};
+			return Ok((arm.model.clone(), Some(tag)));
+		}
+		drop(experiments);
+
+		self.resolve_model(&Model::Alias(name.into()))
+			.map(|model| (model, None))
+	}
+
+	/// Resolves a [`Model::Alias`], other models are returned unchanged.
+	pub fn resolve_model(&self, model: &Model) -> Result<Model, LlmsError> {
+		let Model::Alias(name) = model else {
+			return Ok(model.clone());
+		};
+
+		match read(&self.aliases).get(name) {
+			Some(Model::Alias(_)) | None => {
+				Err(LlmsError::UnknownAlias(name.clone()))
+			}
+			Some(model) => Ok(model.clone()),
+		}
+	}
+
+	/// Returns `true` if the provider serving `model` is configured.
+	pub fn is_configured(&self, model: &Model) -> bool {
+		let Ok(model) = self.resolve_model(model) else {
+			return false;
+		};
+
+		match model.provider() {
+			Some(ProviderKind::OpenAi) => self.inner.open_ai.is_some(),
+			Some(ProviderKind::Anthropic) => self.inner.anthropic.is_some(),
+			Some(ProviderKind::Google) => self.inner.google.is_some(),
+			Some(ProviderKind::XAi) => self.inner.xai.is_some(),
+			Some(ProviderKind::Mistral) => self.inner.mistral.is_some(),
+			Some(ProviderKind::PublicAi) => self.inner.publicai.is_some(),
+			Some(ProviderKind::OpenRouter) => self.inner.openrouter.is_some(),
+			Some(ProviderKind::OpenAiCompat) => {
+				self.inner.openai_compat.is_some()
+			}
+			Some(ProviderKind::Bedrock) => self.inner.bedrock.is_some(),
+			#[cfg(feature = "test-utils")]
+			Some(ProviderKind::Mock) => self.inner.mock.is_some(),
+			None => false,
 		}
 	}
 
+	/// Registers a [`Hook`] that is called for every following request.
+	pub fn add_hook(&mut self, hook: impl Hook) {
+		self.hooks.push(hook);
+	}
+
+	/// Registers an [`Exporter`] that receives a record of every following
+	/// request.
+	pub fn add_exporter(&mut self, exporter: impl Exporter) {
+		self.exporters.push(exporter);
+	}
+
+	/// Sends the request, falling back to other models if it fails and a
+	/// [`FallbackPolicy`] is configured.
 	pub async fn request(
 		&self,
 		req: &Request,
 	) -> Result<ResponseStream, LlmsError> {
-		match &req.model {
-			Model::Gpt5_6Sol | Model::Gpt5_6Terra | Model::Gpt5_6Luna => {
-				let llm = self.inner.open_ai.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("OpenAI".into())
-				})?;
-				LlmProvider::request(llm, req).await.map(Into::into)
-			}
-			Model::ClaudeFable5
-			| Model::ClaudeOpus4_8
-			| Model::ClaudeSonnet5
-			| Model::ClaudeHaiku4_5 => {
-				let llm = self.inner.anthropic.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("Anthropic".into())
-				})?;
+		let Some(fallback) = &self.fallback else {
+			return self.send(req).await;
+		};
+
+		let models: Vec<_> = fallback
+			.models
+			.iter()
+			.filter(|model| **model != req.model && self.is_configured(model))
+			.collect();
+		let mut models = models.into_iter().peekable();
+		let mut attempt = Cow::Borrowed(req);
+		loop {
+			let last = models.peek().is_none();
+			let e = match self
+				.send_started(&attempt, fallback.attempt_timeout, last)
+				.await
+			{
+				Ok(mut stream) => {
+					if attempt.model != req.model {
+						stream.fallback = Some(attempt.model.clone());
+					}
+					return Ok(stream);
+				}
+				Err(e) => e,
+			};
+
+			let Some(model) = models.next() else {
+				return Err(e);
+			};
+			if !FallbackPolicy::falls_back(&e) {
+				return Err(e);
+			}
+
+			warn!("falling back from {:?} to {model:?}: {e}", attempt.model);
+			attempt = Cow::Owned(Request {
+				model: model.clone(),
+				snapshot: None,
+				..req.clone()
+			});
+		}
+	}
+
+	/// Sends the request and waits for its first event within `timeout`,
+	/// so that errors reported as the first event fail the attempt. The
+	/// `last` attempt isn't waited for without a timeout.
+	async fn send_started(
+		&self,
+		req: &Request,
+		timeout: Option<Duration>,
+		last: bool,
+	) -> Result<ResponseStream, LlmsError> {
+		let started = async {
+			let mut stream = self.send(req).await?;
+			if !last || timeout.is_some() {
+				stream.wait_started().await?;
+			}
+			Ok(stream)
+		};
+
+		match timeout {
+			Some(limit) => tokio::time::timeout(limit, started)
+				.await
+				.unwrap_or(Err(LlmsError::AttemptTimeout(limit))),
+			None => started.await,
+		}
+	}
+
+	/// Sends the request to its own model.
+	async fn send(&self, req: &Request) -> Result<ResponseStream, LlmsError> {
+		let (req, experiment) = match &req.model {
+			Model::Alias(name) => {
+				let (model, experiment) =
+					self.resolve_alias(name, &req.user_id)?;
+				(
+					Cow::Owned(Request {
+						model,
+						..req.clone()
+					}),
+					experiment,
+				)
+			}
+			_ => (Cow::Borrowed(req), None),
+		};
+
+		let defaults: Vec<_> = [
+			self.model_defaults.get(&req.model),
+			req.model
+				.provider()
+				.and_then(|kind| self.provider_defaults.get(&kind)),
+		]
+		.into_iter()
+		.flatten()
+		.collect();
+		let mut req = req.with_defaults(&defaults);
+		let timeouts = req.timeouts.or(self.timeouts);
+		if timeouts != req.timeouts {
+			req.to_mut().timeouts = timeouts;
+		}
+
+		let req = if self.needs_tool_output_limit(&req) {
+			let mut req = req.into_owned();
+			self.limit_tool_outputs(&mut req).await;
+			Cow::Owned(req)
+		} else {
+			req
+		};
+
+		let (req, degraded) = if self.lenient {
+			let (req, degraded) = req.degrade();
+			(Cow::Owned(req), degraded)
+		} else {
+			(req, Vec::new())
+		};
+
+		req.validate()?;
+		let (req, renames) = match req.check_tool_names(self.lenient)? {
+			Some((req, renames)) => (Cow::Owned(req), renames),
+			None => (req, ToolRenames::default()),
+		};
+		let req = self.with_trace_headers(req);
+		self.hooks.on_request(&req);
+
+		let rate_limit = match req
+			.model
+			.provider()
+			.and_then(|kind| self.rate_limiters.get(&kind))
+		{
+			Some(limiter) => Some(limiter.acquire(reserved_tokens(&req)).await),
+			None => None,
+		};
+
+		let started = Instant::now();
+		let mut recorder = (!self.exporters.is_empty())
+			.then(|| self.exporters.record(req.clone().into_owned()));
+		let mut deadline = Deadline::new(req.timeouts, started);
+		let mut attempt = 0;
+		let dispatched = loop {
+			let result = match deadline.read(self.dispatch(&req)).await {
+				// some providers report being overloaded as the first event
+				Ok(Ok(mut stream)) if self.retry.is_some() => {
+					stream.peek_first(&mut deadline).await.map(|()| stream)
+				}
+				Ok(result) => result,
+				Err(e) => break Err(e),
+			};
+			let e = match result {
+				Ok(stream) => break Ok(stream),
+				Err(e) => e,
+			};
+			attempt += 1;
+
+			let delay = self
+				.retry
+				.as_ref()
+				.and_then(|retry| retry.delay(attempt, &e))
+				.filter(|delay| deadline.can_wait(*delay));
+			let Some(delay) = delay else {
+				break Err(e);
+			};
+
+			warn!("retrying {:?} in {delay:?}: {e}", req.model);
+			tokio::time::sleep(delay).await;
+			deadline.restart_read();
+		};
+		let mut stream = match dispatched {
+			Ok(stream) => stream,
+			Err(e) => {
+				if let Some(recorder) = &mut recorder {
+					recorder.on_error(&e);
+				}
+				return Err(e);
+			}
+		};
+		stream.recorder = recorder;
+		stream.stats = StatsTracker::new(started);
+		stream.deadline = deadline;
+		stream.degraded = degraded;
+		stream.experiment = experiment;
+		stream.price = self.price(&req.model);
+		stream.renames = renames;
+		stream.rate_limit = rate_limit;
+		if !self.hooks.is_empty() {
+			stream.hooks = Some(StreamHooks {
+				hooks: self.hooks.clone(),
+				model: req.model.clone(),
+				first_token: true,
+			});
+		}
+		Ok(stream)
+	}
+
+	/// Sends the request and waits for the complete response, for callers
+	/// that don't need the events, e.g. batch jobs.
+	///
+	/// The response is still streamed, so hooks, exporters and
+	/// [`Timeouts`] work the same as with [`Llms::request`]. The providers
+	/// have a `request_blocking` too, e.g.
+	/// [`OpenAi::request_blocking`](openai::OpenAi::request_blocking).
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<Response, LlmsError> {
+		self.request(req).await?.wait().await
+	}
+
+	async fn dispatch(
+		&self,
+		req: &Request,
+	) -> Result<ResponseStream, LlmsError> {
+		let Some(provider) = req.model.provider() else {
+			// resolved in request
+			let Model::Alias(name) = &req.model else {
+				unreachable!("only aliases have no provider")
+			};
+			return Err(LlmsError::UnknownAlias(name.clone()));
+		};
+
+		let not_configured =
+			|name: &str| LlmsError::LlmNotConfigured(name.into());
+		match provider {
+			ProviderKind::OpenAi => {
+				let llm = self
+					.inner
+					.open_ai
+					.as_ref()
+					.ok_or_else(|| not_configured("OpenAI"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
-			Model::GeminiPro3_1
-			| Model::GeminiFlash3_5
-			| Model::GeminiFlash3_1Lite => {
-				let llm = self.inner.google.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("Google".into())
-				})?;
+			ProviderKind::Anthropic => {
+				let llm = self
+					.inner
+					.anthropic
+					.as_ref()
+					.ok_or_else(|| not_configured("Anthropic"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
-			Model::Grok4_5 => {
-				let llm =
-					self.inner.xai.as_ref().ok_or_else(|| {
-						LlmsError::LlmNotConfigured("xAI".into())
-					})?;
+			ProviderKind::Google => {
+				let llm = self
+					.inner
+					.google
+					.as_ref()
+					.ok_or_else(|| not_configured("Google"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
-			Model::MistralLarge
-			| Model::MistralMedium
-			| Model::MistralSmall
-			| Model::Ministral14b => {
-				let llm = self.inner.mistral.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("Mistral".into())
-				})?;
+			ProviderKind::XAi => {
+				let llm = self
+					.inner
+					.xai
+					.as_ref()
+					.ok_or_else(|| not_configured("xAI"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
-			Model::Apertus8bInstruct => {
-				let llm = self.inner.publicai.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("PublicAI".into())
-				})?;
+			ProviderKind::Mistral => {
+				let llm = self
+					.inner
+					.mistral
+					.as_ref()
+					.ok_or_else(|| not_configured("Mistral"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
-			Model::OpenRouter(_) => {
-				let llm = self.inner.openrouter.as_ref().ok_or_else(|| {
-					LlmsError::LlmNotConfigured("OpenRouter".into())
-				})?;
+			ProviderKind::PublicAi => {
+				let llm = self
+					.inner
+					.publicai
+					.as_ref()
+					.ok_or_else(|| not_configured("PublicAI"))?;
+				LlmProvider::request(llm, req).await.map(Into::into)
+			}
+			ProviderKind::OpenRouter => {
+				let llm = self
+					.inner
+					.openrouter
+					.as_ref()
+					.ok_or_else(|| not_configured("OpenRouter"))?;
+				LlmProvider::request(llm, req).await.map(Into::into)
+			}
+			ProviderKind::OpenAiCompat => {
+				let llm = self
+					.inner
+					.openai_compat
+					.as_ref()
+					.ok_or_else(|| not_configured("OpenAI compatible"))?;
+				LlmProvider::request(llm, req).await.map(Into::into)
+			}
+			ProviderKind::Bedrock => {
+				let llm = self
+					.inner
+					.bedrock
+					.as_ref()
+					.ok_or_else(|| not_configured("Bedrock"))?;
+				LlmProvider::request(llm, req).await.map(Into::into)
+			}
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => {
+				let llm = self
+					.inner
+					.mock
+					.as_ref()
+					.ok_or_else(|| not_configured("Mock"))?;
 				LlmProvider::request(llm, req).await.map(Into::into)
 			}
 		}
 	}
+
+	/// Embed texts, returning one embedding per input text.
+	pub async fn embed(
+		&self,
+		req: &EmbeddingRequest,
+	) -> Result<EmbeddingResponse, LlmsError> {
+		match req.model {
+			EmbeddingModel::GeminiEmbedding001
+			| EmbeddingModel::TextEmbedding004 => {
+				let llm = self.inner.google.as_ref().ok_or_else(|| {
+					LlmsError::LlmNotConfigured("Google".into())
+				})?;
+				EmbeddingProvider::embed(llm, req).await
+			}
+		}
+	}
+
+	/// Generate images from a text prompt.
+	pub async fn generate_images(
+		&self,
+		req: &ImageRequest,
+	) -> Result<ImageResponse, LlmsError> {
+		match req.model {
+			ImageModel::Imagen4
+			| ImageModel::Imagen4Ultra
+			| ImageModel::Imagen4Fast
+			| ImageModel::GeminiFlashImage => {
+				let llm = self.inner.google.as_ref().ok_or_else(|| {
+					LlmsError::LlmNotConfigured("Google".into())
+				})?;
+				ImageProvider::generate_images(llm, req).await
+			}
+		}
+	}
+}
+
+/// Like the other locks of the crate these recover from a panic while
+/// held, every write is a single insert or remove.
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+	match lock.read() {
+		Ok(guard) => guard,
+		Err(poisoned) => poisoned.into_inner(),
+	}
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+	match lock.write() {
+		Ok(guard) => guard,
+		Err(poisoned) => poisoned.into_inner(),
+	}
 }
 
 pub(crate) trait LlmProvider {
@@ -343,10 +1819,44 @@ pub(crate) trait LlmProvider {
 	async fn request(&self, req: &Request) -> Result<Self::Stream, LlmsError>;
 }
 
+pub(crate) trait EmbeddingProvider {
+	async fn embed(
+		&self,
+		req: &EmbeddingRequest,
+	) -> Result<EmbeddingResponse, LlmsError>;
+}
+
+pub(crate) trait ImageProvider {
+	async fn generate_images(
+		&self,
+		req: &ImageRequest,
+	) -> Result<ImageResponse, LlmsError>;
+}
+
 pub(crate) trait LlmResponseStream {
 	async fn next(&mut self) -> Option<Result<LlmResponseEvent, LlmsError>>;
 }
 
+/// Drains the stream into the completed response, for the
+/// `request_blocking` methods of the providers.
+pub(crate) async fn drain(
+	mut stream: impl LlmResponseStream,
+) -> Result<Response, LlmsError> {
+	while let Some(event) = stream.next().await {
+		if let LlmResponseEvent::Completed(resp) = event? {
+			return Ok(resp);
+		}
+	}
+	Err(incomplete())
+}
+
+fn incomplete() -> LlmsError {
+	LlmsError::Io(io::Error::new(
+		io::ErrorKind::UnexpectedEof,
+		"stream ended before the response completed",
+	))
+}
+
 #[derive(Debug)]
 pub(crate) enum LlmResponseEvent {
 	/// Note, on some providers the last TextDelta may not be emitted
@@ -354,21 +1864,206 @@ pub(crate) enum LlmResponseEvent {
 	TextDelta {
 		content: String,
 	},
+	ReasoningDelta {
+		content: String,
+	},
+	ToolCallStarted {
+		id: String,
+		name: String,
+	},
+	ToolCallArgumentsDelta {
+		id: String,
+		delta: String,
+	},
+	TranscriptDelta {
+		content: String,
+	},
+	AudioDelta {
+		data: String,
+	},
 	Completed(Response),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ResponseEvent {
 	/// Note, on some providers the last TextDelta may not be emitted
 	/// but returned as part of the final Completed event instead.
 	TextDelta { content: String },
+	/// The next piece of the model's reasoning, see [`Output::Reasoning`].
+	///
+	/// Only returned if the provider streams its reasoning, not passed
+	/// through the segmenters.
+	ReasoningDelta { content: String },
+	/// The model started a tool call, its arguments follow as
+	/// [`ResponseEvent::ToolCallArgumentsDelta`]s.
+	///
+	/// Only meant to show progress, the complete call is part of the
+	/// [`Response::output`] as [`Output::ToolCall`].
+	ToolCallStarted { id: String, name: String },
+	/// The next piece of the JSON arguments of a started tool call, all the
+	/// deltas of a call joined give its arguments.
+	///
+	/// Google returns calls in one piece, they arrive as a single delta.
+	ToolCallArgumentsDelta { id: String, delta: String },
+	/// The next piece of the transcript of the audio the model speaks, to
+	/// render captions in sync with the [`ResponseEvent::AudioDelta`]s.
+	///
+	/// Only returned for audio output, which chat completion models like
+	/// `gpt-4o-audio-preview` stream if `modalities` and `audio` are set in
+	/// [`Request::extra`], on OpenRouter and OpenAI compatible servers. The
+	/// transcript is the text of the [`Response`], it is not passed through
+	/// the segmenters.
+	TranscriptDelta { content: String },
+	/// The next chunk of audio output, base64 encoded in the format
+	/// requested in [`Request::extra`], see
+	/// [`ResponseEvent::TranscriptDelta`].
+	AudioDelta { data: String },
+	/// Nothing arrived for the interval set with
+	/// [`ResponseStream::heartbeat`], e.g. while a reasoning model is
+	/// thinking. Repeated every interval until the next event.
+	Progress {
+		/// Time since the request was sent.
+		elapsed: Duration,
+	},
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 #[non_exhaustive]
 pub struct Response {
 	pub output: Vec<Output>,
 	pub usage: Usage,
+	/// Why the model stopped generating. `None` if the provider didn't
+	/// report a reason.
+	pub finish_reason: Option<FinishReason>,
+	/// Summary of the model's reasoning, `None` if the provider returned
+	/// none. The [`Output::Reasoning`]s joined, streamed as
+	/// [`ResponseEvent::ReasoningDelta`]s.
+	///
+	/// - OpenAI: the reasoning item summaries, only requested if
+	///   [`Request::reasoning_effort`] is set.
+	/// - Anthropic: the text of the thinking blocks.
+	/// - Google: the thought summaries, only requested if
+	///   [`Request::reasoning_effort`] is set.
+	/// - Mistral: the thinking blocks of Magistral models.
+	/// - xAI, Mistral, PublicAI and OpenRouter: the streamed
+	///   `reasoning_content` of backends returning it, e.g. DeepSeek, Qwen
+	///   or vLLM with a reasoning parser.
+	/// - Others: `None`
+	pub reasoning: Option<String>,
+	/// Request features that were stripped or emulated because the model
+	/// doesn't support them. Always empty unless [`LlmsConfig::lenient`]
+	/// is set.
+	pub degraded: Vec<Degradation>,
+	/// The experiment arm the request was assigned to, see
+	/// [`Llms::set_experiment`].
+	pub experiment: Option<ExperimentArm>,
+	/// The model that answered instead of the request's own, see
+	/// [`FallbackPolicy`].
+	pub fallback: Option<Model>,
+	/// The price of the model that answered when the request was sent,
+	/// see [`Llms::price`].
+	pub price: Option<Price>,
+}
+
+impl Response {
+	/// What the response cost at [`Response::price`], `None` if the model
+	/// has no price.
+	pub fn cost_estimate(&self) -> Option<Cost> {
+		self.price.map(|price| price.cost(&self.usage))
+	}
+
+	/// All [`Output::Text`] contents joined together.
+	pub fn text(&self) -> String {
+		self.output
+			.iter()
+			.filter_map(|output| match output {
+				Output::Text { content } => Some(content.as_str()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Converts the output into inputs for the next request, see
+	/// [`Request::extend_from_response`].
+	pub fn into_inputs(self) -> Vec<Input> {
+		let mut inputs = Vec::new();
+		push_outputs(&mut inputs, self.output);
+		inputs
+	}
+}
+
+impl Request {
+	/// Appends the response to the conversation history.
+	///
+	/// Tool calls keep their `context` and order, consecutive text outputs
+	/// (including code execution) are merged into a single assistant turn,
+	/// which is how every provider expects the history.
+	pub fn extend_from_response(&mut self, resp: &Response) {
+		push_outputs(&mut self.input, resp.output.iter().cloned());
+	}
+}
+
+fn push_outputs(
+	inputs: &mut Vec<Input>,
+	outputs: impl IntoIterator<Item = Output>,
+) {
+	let start = inputs.len();
+
+	let outputs = outputs
+		.into_iter()
+		.filter(|output| !matches!(output, Output::Reasoning { .. }));
+
+	for input in outputs.map(Input::from) {
+		match (inputs[start..].last_mut(), input) {
+			(
+				Some(Input::Text {
+					role: Role::Assistant,
+					content,
+				}),
+				Input::Text {
+					role: Role::Assistant,
+					content: next,
+				},
+			) => {
+				content.push_str("\n\n");
+				content.push_str(&next);
+			}
+			(_, input) => inputs.push(input),
+		}
+	}
+}
+
+/// Why the model stopped generating, see [`Response::finish_reason`].
+///
+/// Use it e.g. to continue an answer cut by [`FinishReason::MaxTokens`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FinishReason {
+	/// Natural end of the answer or a stop sequence was hit.
+	Stop,
+	/// The output token limit was reached, the answer is truncated.
+	MaxTokens,
+	/// The model stopped to let the caller run tool calls.
+	ToolCalls,
+	/// The output was cut by a safety or recitation filter.
+	ContentFilter,
+	/// Any other reason, as reported by the provider.
+	Other(String),
+}
+
+impl FinishReason {
+	/// Maps the `finish_reason` of chat completions APIs.
+	pub(crate) fn from_chat(reason: String) -> Self {
+		match reason.as_str() {
+			"stop" => Self::Stop,
+			// Mistral's when the context window is full
+			"length" | "model_length" => Self::MaxTokens,
+			"tool_calls" | "function_call" => Self::ToolCalls,
+			"content_filter" => Self::ContentFilter,
+			_ => Self::Other(reason),
+		}
+	}
 }
 
 /// Token usage reported by the provider for a single request.
@@ -379,8 +2074,30 @@ pub struct Response {
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 #[non_exhaustive]
 pub struct Usage {
+	/// All input tokens, cached ones included.
 	pub input_tokens: u32,
+	/// All output tokens, reasoning included.
 	pub output_tokens: u32,
+	/// Part of `input_tokens` served from the provider's cache, usually
+	/// billed at a discount.
+	///
+	/// Reported by OpenAI, Anthropic, Gemini, xAI and OpenRouter, by
+	/// PublicAI if the backend caches prefixes.
+	pub cache_read_tokens: u32,
+	/// Part of `input_tokens` written to the provider's cache, billed at a
+	/// premium.
+	///
+	/// Only reported by Anthropic.
+	pub cache_write_tokens: u32,
+	/// Part of `output_tokens` spent on reasoning.
+	///
+	/// Reported by OpenAI, Gemini, xAI and OpenRouter.
+	pub reasoning_tokens: u32,
+	/// Part of `input_tokens` that was audio, usually billed at a different
+	/// rate.
+	///
+	/// Reported by Gemini and OpenRouter.
+	pub audio_input_tokens: u32,
 }
 
 impl Usage {
@@ -403,7 +2120,7 @@ impl Usage {
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Output {
 	Text {
 		content: String,
@@ -416,17 +2133,107 @@ pub enum Output {
 		/// [`Input::ToolCall::context`] until the corresponding
 		/// [`Input::ToolCallOutput`] is added. After that it can be discarded.
 		///
-		/// Currently populated by Gemini 3 thinking models (`thoughtSignature`)
-		/// to preserve reasoning state across multi-step tool use within a
-		/// single turn. All other providers set this to `None`.
+		/// Currently populated by Gemini 3 thinking models (`thoughtSignature`),
+		/// OpenAI reasoning models (the encrypted reasoning item) and Claude
+		/// with thinking (the preceding thinking blocks) to preserve reasoning
+		/// state across multi-step tool use within a single turn. All other
+		/// providers set this to `None`.
 		context: Option<String>,
 	},
+	/// Code the model wrote and ran in the provider's sandbox.
+	///
+	/// Currently only produced by Gemini when the code execution tool is
+	/// enabled.
+	CodeExecution {
+		/// Language of the code, e.g. `"PYTHON"`.
+		language: String,
+		code: String,
+	},
+	/// Result of running the preceding [`Output::CodeExecution`].
+	CodeExecutionResult {
+		outcome: CodeExecutionOutcome,
+		/// stdout on success, otherwise stderr or another description.
+		output: String,
+	},
+	/// An image the model generated, e.g. by [`Model::GeminiFlashImage`].
+	Image {
+		/// The raw, already decoded image bytes.
+		data: Bytes,
+		/// e.g. `"image/png"`.
+		mime_type: String,
+	},
+	/// The model's reasoning before the output following it, the same text
+	/// [`Response::reasoning`] joins.
+	///
+	/// Not sent back by [`Request::extend_from_response`], providers that
+	/// need their reasoning state get it through [`Output::ToolCall::context`].
+	Reasoning {
+		content: String,
+	},
 }
 
+impl Output {
+	/// Deserializes the input of a [`Output::ToolCall`], `None` for other
+	/// outputs.
+	pub fn parse_tool_input<T: DeserializeOwned>(
+		&self,
+	) -> Option<Result<T, serde_json::Error>> {
+		match self {
+			Self::ToolCall { input, .. } => Some(T::deserialize(input)),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeExecutionOutcome {
+	Ok,
+	Failed,
+	DeadlineExceeded,
+}
+
+/// A streaming response, see [`Llms::request`].
+///
+/// Nothing runs in the background: events are only read from the
+/// connection while [`ResponseStream::next`] is polled. Dropping the
+/// stream closes the connection, which makes the provider stop
+/// generating, so abandoned responses don't keep costing tokens. The same
+/// holds for the providers' own streams, like
+/// [`anthropic::ResponseStream`]. To stop a stream another task is reading
+/// use [`ResponseStream::cancel_on`].
 #[derive(Debug)]
 pub struct ResponseStream {
-	inner: RespStreamInner,
+	/// `None` once cancelled or failed, which closed the connection.
+	inner: Option<RespStreamInner>,
 	response: Option<Response>,
+	degraded: Vec<Degradation>,
+	hooks: Option<StreamHooks>,
+	recorder: Option<Recorder>,
+	stats: StatsTracker,
+	deadline: Deadline,
+	/// Already received event returned before polling the provider again.
+	buffered: Option<ResponseEvent>,
+	/// The provider's first event, read before the stream was returned to
+	/// retry errors, see [`ResponseStream::peek_first`].
+	peeked: Option<LlmResponseEvent>,
+	experiment: Option<ExperimentArm>,
+	fallback: Option<Model>,
+	price: Option<Price>,
+	segmenters: Segmenters,
+	/// All text returned so far, see [`ResponseStream::text`].
+	text: String,
+	renames: ToolRenames,
+	heartbeat: Option<Duration>,
+	cancel: Option<CancellationToken>,
+	/// Released once the response completed or the stream is dropped.
+	rate_limit: Option<RateLimitPermit>,
+}
+
+#[derive(Debug)]
+struct StreamHooks {
+	hooks: Hooks,
+	model: Model,
+	first_token: bool,
 }
 
 #[derive(Debug)]
@@ -438,13 +2245,87 @@ enum RespStreamInner {
 	Mistral(mistral::ResponseStream),
 	PublicAi(publicai::ResponseStream),
 	OpenRouter(openrouter::ResponseStream),
+	OpenAiCompat(openai_compat::ResponseStream),
+	Bedrock(bedrock::ResponseStream),
+	#[cfg(feature = "test-utils")]
+	Mock(mock::ResponseStream),
 }
 
 impl ResponseStream {
 	fn new(inner: RespStreamInner) -> Self {
 		Self {
-			inner,
+			inner: Some(inner),
 			response: None,
+			degraded: Vec::new(),
+			hooks: None,
+			recorder: None,
+			stats: StatsTracker::new(Instant::now()),
+			deadline: Deadline::new(Timeouts::default(), Instant::now()),
+			buffered: None,
+			peeked: None,
+			experiment: None,
+			fallback: None,
+			price: None,
+			segmenters: Segmenters::default(),
+			text: String::new(),
+			renames: ToolRenames::default(),
+			heartbeat: None,
+			cancel: None,
+			rate_limit: None,
+		}
+	}
+
+	/// Passes the text deltas through `segmenter` before they're returned
+	/// by [`ResponseStream::next`], e.g. a [`MarkdownSegmenter`] or a
+	/// [`WordSegmenter`]. Multiple segmenters are applied in the order they
+	/// were added.
+	pub fn segment(mut self, segmenter: impl Segmenter) -> Self {
+		self.segmenters.push(segmenter);
+		self
+	}
+
+	/// Returns a [`ResponseEvent::Progress`] whenever nothing arrived for
+	/// `interval`, so front-ends can show that the model is still working
+	/// and idle connections to them are kept alive.
+	///
+	/// Progress events are not passed to hooks or exporters. The timer needs
+	/// a tokio runtime.
+	pub fn heartbeat(mut self, interval: Duration) -> Self {
+		self.heartbeat = Some(interval);
+		self
+	}
+
+	/// Cancels the stream once `token` is cancelled, even while
+	/// [`ResponseStream::next`] is awaited, which then returns
+	/// [`LlmsError::Cancelled`]. See [`ResponseStream::cancel`].
+	pub fn cancel_on(mut self, token: CancellationToken) -> Self {
+		self.cancel = Some(token);
+		self
+	}
+
+	/// Closes the connection, the provider stops generating. Does nothing
+	/// if the response already completed.
+	///
+	/// A running OpenAI response is cancelled on the server as well, see
+	/// [`OpenAi::cancel_response`](crate::openai::OpenAi::cancel_response).
+	///
+	/// Exporters receive an [`ExportOutcome::Cancelled`] record, later
+	/// calls to [`ResponseStream::next`] return `None` and
+	/// [`ResponseStream::wait`] returns [`LlmsError::Cancelled`].
+	pub fn cancel(&mut self) {
+		if self.response.is_some() {
+			return;
+		}
+		match self.inner.take() {
+			Some(RespStreamInner::OpenAi(stream)) => stream.cancel(),
+			Some(_) => {}
+			None => return,
+		}
+		self.buffered = None;
+		self.peeked = None;
+		self.rate_limit = None;
+		if let Some(recorder) = &mut self.recorder {
+			recorder.on_cancel();
 		}
 	}
 
@@ -454,35 +2335,252 @@ impl ResponseStream {
 	///
 	/// This method is cancellation safe.
 	pub async fn next(&mut self) -> Option<Result<ResponseEvent, LlmsError>> {
+		let Some(interval) = self.heartbeat else {
+			return self.next_event().await;
+		};
+
+		let ev = {
+			let ev = pin!(self.next_event());
+			let timeout = pin!(tokio::time::sleep(interval));
+			match future::select(ev, timeout).await {
+				Either::Left((ev, _)) => Some(ev),
+				Either::Right(_) => None,
+			}
+		};
+
+		match ev {
+			Some(ev) => ev,
+			None => Some(Ok(ResponseEvent::Progress {
+				elapsed: self.stats.elapsed(),
+			})),
+		}
+	}
+
+	/// The next event from the provider after segmentation.
+	async fn next_event(&mut self) -> Option<Result<ResponseEvent, LlmsError>> {
+		let ev = loop {
+			match self.next_raw().await {
+				Some(Ok(ResponseEvent::TextDelta { content })) => {
+					if let Some(content) = self.segmenters.process(content) {
+						break ResponseEvent::TextDelta { content };
+					}
+				}
+				Some(Ok(ev @ ResponseEvent::ToolCallStarted { .. })) => {
+					// text held back by a segmenter came before the call
+					match self.segmenters.finish() {
+						Some(content) => {
+							self.buffered = Some(ev);
+							break ResponseEvent::TextDelta { content };
+						}
+						None => break ev,
+					}
+				}
+				Some(Ok(
+					ev @ (ResponseEvent::ReasoningDelta { .. }
+					| ResponseEvent::ToolCallArgumentsDelta { .. }
+					| ResponseEvent::TranscriptDelta { .. }
+					| ResponseEvent::AudioDelta { .. }),
+				)) => {
+					break ev;
+				}
+				None => match self.segmenters.finish() {
+					Some(content) => {
+						break ResponseEvent::TextDelta { content };
+					}
+					None => {
+						// the last delta might only be part of the response
+						if let Some(resp) = &self.response {
+							self.text = resp.text();
+						}
+						return None;
+					}
+				},
+				other => return other,
+			}
+		};
+
+		if let ResponseEvent::TextDelta { content } = &ev {
+			self.text.push_str(content);
+		}
+
+		if let Some(h) = &mut self.hooks {
+			if h.first_token {
+				h.first_token = false;
+				h.hooks.on_first_token(&h.model);
+			}
+			h.hooks.on_event(&h.model, &ev);
+		}
+		if let Some(recorder) = &mut self.recorder {
+			recorder.on_event(&ev);
+		}
+		self.stats.on_event(&ev);
+
+		Some(Ok(ev))
+	}
+
+	/// The text of all deltas returned so far, for consumers re-rendering
+	/// the whole message on every event.
+	///
+	/// Once [`ResponseStream::next`] returned `None` this is the complete
+	/// text of the response.
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Timings of the stream, `None` until the response completed.
+	pub fn stats(&self) -> Option<StreamStats> {
+		self.stats.stats()
+	}
+
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
 		use RespStreamInner::*;
 
+		match self.inner.as_ref()? {
+			OpenAi(stream) => stream.request_id(),
+			Anthropic(stream) => stream.request_id(),
+			Google(stream) => stream.request_id(),
+			XAi(stream) => stream.request_id(),
+			Mistral(stream) => stream.request_id(),
+			PublicAi(stream) => stream.request_id(),
+			OpenRouter(stream) => stream.request_id(),
+			OpenAiCompat(stream) => stream.request_id(),
+			Bedrock(stream) => stream.request_id(),
+			#[cfg(feature = "test-utils")]
+			Mock(stream) => stream.request_id(),
+		}
+	}
+
+	/// Waits for the first event, [`ResponseStream::next`] returns it
+	/// afterwards.
+	async fn wait_started(&mut self) -> Result<(), LlmsError> {
+		match self.next_raw().await {
+			Some(Ok(ev)) => self.buffered = Some(ev),
+			Some(Err(e)) => return Err(e),
+			// already completed, the response is stored
+			None => {}
+		}
+		Ok(())
+	}
+
+	/// Reads the provider's first event before anything else is set up, so
+	/// that an error reported as the first event can be retried. It is
+	/// processed like any other event by [`ResponseStream::next`].
+	async fn peek_first(
+		&mut self,
+		deadline: &mut Deadline,
+	) -> Result<(), LlmsError> {
+		let Some(inner) = self.inner.as_mut() else {
+			return Ok(());
+		};
+		match deadline.total(inner.next()).await? {
+			Some(Ok(ev)) => self.peeked = Some(ev),
+			Some(Err(e)) => return Err(e.with_request_id(self.request_id())),
+			// reported once the stream is read
+			None => {}
+		}
+		Ok(())
+	}
+
+	/// The next event before segmentation.
+	async fn next_raw(&mut self) -> Option<Result<ResponseEvent, LlmsError>> {
+		if let Some(ev) = self.buffered.take() {
+			return Some(Ok(ev));
+		}
+
 		loop {
-			let ev = match &mut self.inner {
-				OpenAi(stream) => LlmResponseStream::next(stream).await,
-				Anthropic(stream) => LlmResponseStream::next(stream).await,
-				Google(stream) => LlmResponseStream::next(stream).await,
-				XAi(stream) => LlmResponseStream::next(stream).await,
-				Mistral(stream) => LlmResponseStream::next(stream).await,
-				PublicAi(stream) => LlmResponseStream::next(stream).await,
-				OpenRouter(stream) => LlmResponseStream::next(stream).await,
+			let ev = match self.peeked.take() {
+				Some(ev) => Some(Ok(ev)),
+				None => {
+					let inner = self.inner.as_mut()?;
+					let read = self.deadline.total(inner.next());
+					let read = match &self.cancel {
+						Some(token) => token.run_until_cancelled(read).await,
+						None => Some(read.await),
+					};
+					match read {
+						Some(Ok(ev)) => ev,
+						// a timeout, the stalled connection is closed and the
+						// stream ends after the error
+						Some(Err(e)) => {
+							self.inner = None;
+							Some(Err(e))
+						}
+						None => {
+							self.cancel();
+							return Some(Err(LlmsError::Cancelled));
+						}
+					}
+				}
 			};
 
 			break match ev {
 				Some(Ok(LlmResponseEvent::TextDelta { content })) => {
 					Some(Ok(ResponseEvent::TextDelta { content }))
 				}
-				Some(Ok(LlmResponseEvent::Completed(resp))) => {
+				Some(Ok(LlmResponseEvent::ReasoningDelta { content })) => {
+					Some(Ok(ResponseEvent::ReasoningDelta { content }))
+				}
+				Some(Ok(LlmResponseEvent::TranscriptDelta { content })) => {
+					Some(Ok(ResponseEvent::TranscriptDelta { content }))
+				}
+				Some(Ok(LlmResponseEvent::AudioDelta { data })) => {
+					Some(Ok(ResponseEvent::AudioDelta { data }))
+				}
+				Some(Ok(LlmResponseEvent::ToolCallStarted {
+					mut id,
+					mut name,
+				})) => {
+					self.renames.restore_call(&mut id, &mut name);
+					Some(Ok(ResponseEvent::ToolCallStarted { id, name }))
+				}
+				Some(Ok(LlmResponseEvent::ToolCallArgumentsDelta {
+					mut id,
+					delta,
+				})) => {
+					self.renames.restore_id(&mut id);
+					Some(Ok(ResponseEvent::ToolCallArgumentsDelta {
+						id,
+						delta,
+					}))
+				}
+				Some(Ok(LlmResponseEvent::Completed(mut resp))) => {
+					resp.degraded = std::mem::take(&mut self.degraded);
+					resp.experiment = self.experiment.take();
+					resp.fallback = self.fallback.take();
+					resp.price = self.price.take();
+					self.renames.restore(&mut resp.output);
+					if let Some(h) = &self.hooks {
+						h.hooks.on_complete(&h.model, &resp);
+					}
+					if let Some(recorder) = &mut self.recorder {
+						recorder.on_complete(&resp);
+					}
+					self.stats.on_complete(&resp);
+					if let Some(permit) = self.rate_limit.take() {
+						permit.complete(&resp.usage);
+					}
 					self.response = Some(resp);
 					continue;
 				}
-				Some(Err(e)) => Some(Err(e)),
-				None => {
-					assert!(
-						self.response.is_some(),
-						"response Completed event not received"
-					);
-					None
+				Some(Err(e)) => {
+					let e = e.with_request_id(self.request_id());
+					// providers don't continue after an error
+					self.inner = None;
+					if let Some(recorder) = &mut self.recorder {
+						recorder.on_error(&e);
+					}
+					Some(Err(e))
+				}
+				None if self.response.is_none() => {
+					self.inner = None;
+					let e = incomplete();
+					if let Some(recorder) = &mut self.recorder {
+						recorder.on_error(&e);
+					}
+					Some(Err(e))
 				}
+				None => None,
 			};
 		}
 	}
@@ -500,7 +2598,11 @@ impl ResponseStream {
 			result?;
 		}
 
-		Ok(self.response.take().unwrap())
+		match self.response.take() {
+			Some(resp) => Ok(resp),
+			None if self.inner.is_none() => Err(LlmsError::Cancelled),
+			None => panic!("response was already taken"),
+		}
 	}
 
 	/// Get the final response after the stream has completed.
@@ -511,6 +2613,26 @@ impl ResponseStream {
 	}
 }
 
+impl RespStreamInner {
+	async fn next(&mut self) -> Option<Result<LlmResponseEvent, LlmsError>> {
+		use RespStreamInner::*;
+
+		match self {
+			OpenAi(stream) => LlmResponseStream::next(stream).await,
+			Anthropic(stream) => LlmResponseStream::next(stream).await,
+			Google(stream) => LlmResponseStream::next(stream).await,
+			XAi(stream) => LlmResponseStream::next(stream).await,
+			Mistral(stream) => LlmResponseStream::next(stream).await,
+			PublicAi(stream) => LlmResponseStream::next(stream).await,
+			OpenRouter(stream) => LlmResponseStream::next(stream).await,
+			OpenAiCompat(stream) => LlmResponseStream::next(stream).await,
+			Bedrock(stream) => LlmResponseStream::next(stream).await,
+			#[cfg(feature = "test-utils")]
+			Mock(stream) => LlmResponseStream::next(stream).await,
+		}
+	}
+}
+
 impl From<openai::ResponseStream> for ResponseStream {
 	fn from(stream: openai::ResponseStream) -> Self {
 		Self::new(RespStreamInner::OpenAi(stream))
@@ -552,3 +2674,213 @@ impl From<openrouter::ResponseStream> for ResponseStream {
 		Self::new(RespStreamInner::OpenRouter(stream))
 	}
 }
+
+impl From<openai_compat::ResponseStream> for ResponseStream {
+	fn from(stream: openai_compat::ResponseStream) -> Self {
+		Self::new(RespStreamInner::OpenAiCompat(stream))
+	}
+}
+
+impl From<bedrock::ResponseStream> for ResponseStream {
+	fn from(stream: bedrock::ResponseStream) -> Self {
+		Self::new(RespStreamInner::Bedrock(stream))
+	}
+}
+
+#[cfg(feature = "test-utils")]
+impl From<mock::ResponseStream> for ResponseStream {
+	fn from(stream: mock::ResponseStream) -> Self {
+		Self::new(RespStreamInner::Mock(stream))
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::time::Duration;
+
+	use tokio::{
+		io::{AsyncReadExt as _, AsyncWriteExt as _},
+		net::TcpListener,
+		task::JoinHandle,
+		time::{sleep, timeout},
+	};
+
+	use super::*;
+	use crate::anthropic::{self, Anthropic, AnthropicModel};
+
+	/// The start of an Anthropic response, the rest never comes.
+	const EVENTS: &str = "event: message_start\n\
+		data: {\"type\":\"message_start\",\"message\":{\"id\":\"m\",\"model\":\"claude-sonnet-5\",\"usage\":{\"input_tokens\":1,\"output_tokens\":1}}}\n\n\
+		event: content_block_start\n\
+		data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n\
+		event: content_block_delta\n\
+		data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n";
+
+	/// Answers a single request with [`EVENTS`] and keeps the response
+	/// open. The task finishes once the client closed the connection.
+	async fn endless_server() -> (String, JoinHandle<()>) {
+		sse_server(EVENTS.into(), false).await
+	}
+
+	/// Answers a single request with `events`, ending the response if
+	/// `end` is set.
+	async fn sse_server(events: String, end: bool) -> (String, JoinHandle<()>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+		let server = tokio::spawn(async move {
+			let (mut socket, _) = listener.accept().await.unwrap();
+
+			let mut req = Vec::new();
+			let mut buf = [0; 8192];
+			while !request_complete(&req) {
+				let n = socket.read(&mut buf).await.unwrap();
+				assert!(n > 0, "closed before the request was sent");
+				req.extend_from_slice(&buf[..n]);
+			}
+
+			let head = "HTTP/1.1 200 OK\r\n\
+				content-type: text/event-stream\r\n\
+				transfer-encoding: chunked\r\n\r\n";
+			let chunk = format!("{:x}\r\n{events}\r\n", events.len());
+			socket.write_all(head.as_bytes()).await.unwrap();
+			socket.write_all(chunk.as_bytes()).await.unwrap();
+			if end {
+				socket.write_all(b"0\r\n\r\n").await.unwrap();
+			}
+
+			// a reset counts as closed too
+			while let Ok(n) = socket.read(&mut buf).await
+				&& n > 0
+			{}
+		});
+
+		(url, server)
+	}
+
+	fn request_complete(req: &[u8]) -> bool {
+		let Some(end) = req.windows(4).position(|w| w == b"\r\n\r\n") else {
+			return false;
+		};
+		let head = String::from_utf8_lossy(&req[..end]).to_lowercase();
+		let len = head
+			.lines()
+			.find_map(|line| line.strip_prefix("content-length:"))
+			.map_or(0, |len| len.trim().parse().unwrap());
+		req.len() >= end + 4 + len
+	}
+
+	pub(crate) fn request() -> Request {
+		Request {
+			input: vec![Input::Text {
+				role: Role::User,
+				content: "hi".into(),
+			}],
+			instructions: String::new(),
+			model: Model::ClaudeSonnet5,
+			user_id: String::new(),
+			tools: vec![],
+			tool_choice: Default::default(),
+			reasoning_effort: None,
+			response_format: Default::default(),
+			params: Default::default(),
+			cache: false,
+			extra: Default::default(),
+			headers: Default::default(),
+			timeouts: Default::default(),
+			transcript: None,
+			snapshot: None,
+		}
+	}
+
+	async fn assert_closed_on_drop(server: JoinHandle<()>, stream: impl Sized) {
+		sleep(Duration::from_millis(100)).await;
+		assert!(
+			!server.is_finished(),
+			"closed before the stream was dropped"
+		);
+
+		drop(stream);
+		timeout(Duration::from_secs(5), server)
+			.await
+			.expect("connection still open after the drop")
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn dropping_a_provider_stream_closes_the_connection() {
+		let (url, server) = endless_server().await;
+		let anthropic = Anthropic::new("key".to_string()).base_url(url);
+
+		let mut stream =
+			LlmProvider::request(&anthropic, &request()).await.unwrap();
+		assert!(matches!(stream.next().await, Some(Ok(_))));
+
+		assert_closed_on_drop(server, stream).await;
+	}
+
+	#[tokio::test]
+	async fn dropping_a_response_stream_closes_the_connection() {
+		let (url, server) = endless_server().await;
+		let llms = Llms::new(
+			LlmsConfig::new()
+				.anthropic(Some("key".to_string()))
+				.base_url(ProviderKind::Anthropic, url),
+		);
+
+		let mut stream = llms.request(&request()).await.unwrap();
+		assert!(matches!(stream.next().await, Some(Ok(_))));
+
+		assert_closed_on_drop(server, stream).await;
+	}
+
+	#[tokio::test]
+	async fn provider_request_blocking_drains_the_stream() {
+		let rest = "event: content_block_stop\n\
+			data: {\"type\":\"content_block_stop\",\"index\":0}\n\n\
+			event: message_delta\n\
+			data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":2}}\n\n\
+			event: message_stop\n\
+			data: {\"type\":\"message_stop\"}\n\n";
+		let (url, _server) = sse_server(format!("{EVENTS}{rest}"), true).await;
+		let anthropic = Anthropic::new("key".to_string()).base_url(url);
+
+		let req =
+			anthropic::Request::from_llms(&request(), AnthropicModel::Sonnet5);
+		let resp = anthropic.request_blocking(&req).await.unwrap();
+		assert_eq!(resp.text(), "Hello");
+		assert_eq!(resp.finish_reason, Some(FinishReason::Stop));
+
+		// the stream above never completes
+		let (url, _server) = sse_server(EVENTS.into(), true).await;
+		let anthropic = Anthropic::new("key".to_string()).base_url(url);
+		let err = anthropic.request_blocking(&req).await.unwrap_err();
+		assert!(
+			matches!(&err, LlmsError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof),
+			"{err}"
+		);
+	}
+
+	#[test]
+	fn prices_and_aliases_survive_a_panic_while_locked() {
+		let llms = Llms::new(LlmsConfig::new());
+		let prices = llms.prices.clone();
+		let aliases = llms.aliases.clone();
+		std::thread::spawn(move || {
+			let _prices = prices.write().unwrap();
+			let _aliases = aliases.write().unwrap();
+			panic!("poison the locks");
+		})
+		.join()
+		.unwrap_err();
+
+		let price = Price::new(1.0, 2.0);
+		llms.set_price(Model::ClaudeSonnet5, price);
+		assert_eq!(llms.price(&Model::ClaudeSonnet5), Some(price));
+		llms.set_alias("fast", Model::ClaudeSonnet5);
+		assert_eq!(
+			llms.resolve_model(&Model::Alias("fast".into())).unwrap(),
+			Model::ClaudeSonnet5
+		);
+	}
+}
@@ -0,0 +1,154 @@
+//! Token prices, to estimate what a response cost, see
+//! [`Response::cost_estimate`](super::Response::cost_estimate).
+//!
+//! The built-in prices are the providers' list prices for standard
+//! requests in USD, without batch, priority or volume pricing. Gemini's
+//! higher rates for prompts above 200k tokens aren't applied either.
+//! Prices change, so check them against the providers' pricing pages and
+//! replace outdated ones with [`Llms::set_price`](super::Llms::set_price),
+//! which also prices the models without a built-in one.
+
+use std::{iter::Sum, ops::AddAssign};
+
+use super::{Model, Usage};
+
+/// Prices in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+	pub input: f64,
+	pub output: f64,
+	/// Input read from the cache, `None` bills it as regular input.
+	pub cached_input: Option<f64>,
+	/// Input written to the cache, `None` bills it as regular input.
+	pub cache_write: Option<f64>,
+}
+
+impl Price {
+	pub const fn new(input: f64, output: f64) -> Self {
+		Self {
+			input,
+			output,
+			cached_input: None,
+			cache_write: None,
+		}
+	}
+
+	pub const fn cached_input(mut self, price: f64) -> Self {
+		self.cached_input = Some(price);
+		self
+	}
+
+	pub const fn cache_write(mut self, price: f64) -> Self {
+		self.cache_write = Some(price);
+		self
+	}
+
+	/// What `usage` costs at these prices.
+	pub fn cost(&self, usage: &Usage) -> Cost {
+		let cached = usage.cache_read_tokens.min(usage.input_tokens);
+		let written = usage.cache_write_tokens.min(usage.input_tokens - cached);
+		let uncached = usage.input_tokens - cached - written;
+
+		Cost {
+			input: per_million(self.input, uncached),
+			cached_input: per_million(
+				self.cached_input.unwrap_or(self.input),
+				cached,
+			),
+			cache_write: per_million(
+				self.cache_write.unwrap_or(self.input),
+				written,
+			),
+			output: per_million(self.output, usage.output_tokens),
+		}
+	}
+}
+
+fn per_million(price: f64, tokens: u32) -> f64 {
+	price * f64::from(tokens) / 1_000_000.0
+}
+
+/// An estimated cost in USD, split like the [`Price`]. Costs can be added
+/// up, e.g. per customer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cost {
+	/// The input neither read from nor written to the cache.
+	pub input: f64,
+	pub cached_input: f64,
+	pub cache_write: f64,
+	/// Reasoning included.
+	pub output: f64,
+}
+
+impl Cost {
+	pub fn total(&self) -> f64 {
+		self.input + self.cached_input + self.cache_write + self.output
+	}
+}
+
+impl AddAssign for Cost {
+	fn add_assign(&mut self, other: Self) {
+		self.input += other.input;
+		self.cached_input += other.cached_input;
+		self.cache_write += other.cache_write;
+		self.output += other.output;
+	}
+}
+
+impl Sum for Cost {
+	fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+		iter.fold(Self::default(), |mut sum, cost| {
+			sum += cost;
+			sum
+		})
+	}
+}
+
+impl Model {
+	/// The built-in list price, see the [module docs](self).
+	///
+	/// `None` if it depends on what serves the model:
+	/// [`Model::Custom`], [`Model::Bedrock`], [`Model::OpenRouter`],
+	/// [`Model::OpenAiCompat`] and [`Model::Alias`], and for PublicAI,
+	/// which doesn't charge per token.
+	pub fn price(&self) -> Option<Price> {
+		let price = match self {
+			Model::Gpt5_6Sol => Price::new(15.0, 120.0).cached_input(1.5),
+			Model::Gpt5_6Terra => Price::new(1.25, 10.0).cached_input(0.125),
+			Model::Gpt5_6Luna => Price::new(0.25, 2.0).cached_input(0.025),
+			Model::ClaudeFable5 => {
+				Price::new(15.0, 75.0).cached_input(1.5).cache_write(18.75)
+			}
+			Model::ClaudeOpus4_8 => {
+				Price::new(5.0, 25.0).cached_input(0.5).cache_write(6.25)
+			}
+			Model::ClaudeSonnet5 => {
+				Price::new(3.0, 15.0).cached_input(0.3).cache_write(3.75)
+			}
+			Model::ClaudeHaiku4_5 => {
+				Price::new(1.0, 5.0).cached_input(0.1).cache_write(1.25)
+			}
+			Model::GeminiPro3_1 => Price::new(2.0, 12.0).cached_input(0.2),
+			Model::GeminiFlash3_5 => Price::new(0.5, 3.0).cached_input(0.05),
+			Model::GeminiFlash3_1Lite => {
+				Price::new(0.1, 0.4).cached_input(0.01)
+			}
+			// the output rate of images, text output is cheaper
+			Model::GeminiFlashImage => Price::new(0.3, 30.0),
+			Model::Grok4_5 => Price::new(3.0, 15.0).cached_input(0.75),
+			Model::MistralLarge => Price::new(2.0, 6.0),
+			Model::MistralMedium => Price::new(0.4, 2.0),
+			Model::MistralSmall => Price::new(0.1, 0.3),
+			Model::Ministral14b => Price::new(0.2, 0.2),
+			Model::Apertus8bInstruct
+			| Model::Custom { .. }
+			| Model::Bedrock(_)
+			| Model::OpenRouter(_)
+			| Model::OpenAiCompat(_)
+			| Model::Alias(_) => return None,
+			#[cfg(feature = "test-utils")]
+			Model::Mock => return None,
+		};
+		Some(price)
+	}
+}
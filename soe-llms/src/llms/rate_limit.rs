@@ -0,0 +1,280 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use super::router::estimate_input_tokens;
+use super::{Request, Usage};
+
+/// Limits of the requests to one provider, see
+/// [`LlmsConfig::rate_limit`](super::LlmsConfig::rate_limit).
+///
+/// [`Llms::request`](super::Llms::request) waits until the request fits
+/// every limit before sending it, requests are let through in the order
+/// they arrived. The limits are shared between clones of the
+/// [`Llms`](super::Llms).
+///
+/// Retries are sent within the slot of the original request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+	pub requests_per_minute: Option<u32>,
+	/// Input and output tokens. A request reserves its estimated input plus
+	/// [`GenerationParams::max_tokens`](super::GenerationParams) and is
+	/// charged the reported [`Usage`] once it completed.
+	pub tokens_per_minute: Option<u32>,
+	/// Requests whose response hasn't completed yet, a dropped
+	/// [`ResponseStream`](super::ResponseStream) frees its slot.
+	pub max_concurrent: Option<usize>,
+}
+
+impl RateLimit {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn requests_per_minute(mut self, requests: u32) -> Self {
+		self.requests_per_minute = Some(requests);
+		self
+	}
+
+	pub fn tokens_per_minute(mut self, tokens: u32) -> Self {
+		self.tokens_per_minute = Some(tokens);
+		self
+	}
+
+	pub fn max_concurrent(mut self, requests: usize) -> Self {
+		self.max_concurrent = Some(requests);
+		self
+	}
+}
+
+/// Enforces a [`RateLimit`].
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+	concurrent: Option<Arc<Semaphore>>,
+	/// Held while waiting for the buckets, the lock is fair so requests
+	/// are let through in order.
+	queue: tokio::sync::Mutex<()>,
+	buckets: Mutex<Buckets>,
+}
+
+#[derive(Debug)]
+struct Buckets {
+	requests: Option<Bucket>,
+	tokens: Option<Bucket>,
+}
+
+impl Buckets {
+	/// Takes a request reserving `tokens`, or returns how long to wait
+	/// until it fits.
+	fn try_take(&mut self, tokens: f64) -> Option<Duration> {
+		let now = Instant::now();
+		let mut buckets: Vec<_> =
+			[(&mut self.requests, 1.0), (&mut self.tokens, tokens)]
+				.into_iter()
+				.filter_map(|(bucket, cost)| Some((bucket.as_mut()?, cost)))
+				.collect();
+
+		let mut wait = Duration::ZERO;
+		for (bucket, cost) in &mut buckets {
+			bucket.refill(now);
+			wait = wait.max(bucket.wait(*cost));
+		}
+		if !wait.is_zero() {
+			return Some(wait);
+		}
+
+		for (bucket, cost) in buckets {
+			bucket.available = (bucket.available - cost).max(-bucket.capacity);
+		}
+		None
+	}
+}
+
+/// A token bucket refilled continuously over a minute.
+#[derive(Debug)]
+struct Bucket {
+	capacity: f64,
+	/// Can be negative if more tokens were used than reserved.
+	available: f64,
+	updated: Instant,
+}
+
+impl Bucket {
+	fn new(per_minute: u32) -> Self {
+		Self {
+			capacity: per_minute.max(1) as f64,
+			available: per_minute.max(1) as f64,
+			updated: Instant::now(),
+		}
+	}
+
+	fn refill(&mut self, now: Instant) {
+		let refilled = now.duration_since(self.updated).as_secs_f64()
+			* self.capacity
+			/ 60.0;
+		self.available = (self.available + refilled).min(self.capacity);
+		self.updated = now;
+	}
+
+	/// How long until `cost` is available, a cost above the capacity only
+	/// waits for a full bucket.
+	fn wait(&self, cost: f64) -> Duration {
+		let missing = cost.min(self.capacity) - self.available;
+		if missing <= 0.0 {
+			return Duration::ZERO;
+		}
+		Duration::from_secs_f64(missing * 60.0 / self.capacity)
+	}
+}
+
+impl RateLimiter {
+	pub fn new(limit: RateLimit) -> Self {
+		Self {
+			concurrent: limit
+				.max_concurrent
+				.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+			queue: tokio::sync::Mutex::new(()),
+			buckets: Mutex::new(Buckets {
+				requests: limit.requests_per_minute.map(Bucket::new),
+				tokens: limit.tokens_per_minute.map(Bucket::new),
+			}),
+		}
+	}
+
+	/// Waits until a request reserving `tokens` fits every limit.
+	///
+	/// # Cancel safety
+	///
+	/// This method is cancellation safe, nothing is reserved until it
+	/// returned.
+	pub async fn acquire(self: &Arc<Self>, tokens: u32) -> RateLimitPermit {
+		let slot = match &self.concurrent {
+			Some(semaphore) => Some(
+				semaphore
+					.clone()
+					.acquire_owned()
+					.await
+					.expect("the semaphore is never closed"),
+			),
+			None => None,
+		};
+
+		let _queue = self.queue.lock().await;
+		let reserved = tokens as f64;
+		loop {
+			let wait = self.buckets().try_take(reserved);
+			let Some(wait) = wait else {
+				break;
+			};
+			tokio::time::sleep(wait).await;
+		}
+
+		RateLimitPermit {
+			limiter: self.clone(),
+			_slot: slot,
+			reserved,
+		}
+	}
+
+	fn buckets(&self) -> MutexGuard<'_, Buckets> {
+		match self.buckets.lock() {
+			Ok(buckets) => buckets,
+			Err(poisoned) => poisoned.into_inner(),
+		}
+	}
+}
+
+/// The slot of a running request, freed on drop.
+#[derive(Debug)]
+pub(crate) struct RateLimitPermit {
+	limiter: Arc<RateLimiter>,
+	_slot: Option<OwnedSemaphorePermit>,
+	reserved: f64,
+}
+
+impl RateLimitPermit {
+	/// Charges the tokens actually used instead of the reserved ones.
+	pub fn complete(self, usage: &Usage) {
+		let mut buckets = self.limiter.buckets();
+		if let Some(bucket) = &mut buckets.tokens {
+			let used = usage.total_tokens() as f64;
+			bucket.available = (bucket.available + self.reserved - used)
+				.clamp(-bucket.capacity, bucket.capacity);
+		}
+	}
+}
+
+/// What a request reserves of [`RateLimit::tokens_per_minute`].
+pub(crate) fn reserved_tokens(req: &Request) -> u32 {
+	estimate_input_tokens(req)
+		.saturating_add(req.params.max_tokens.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+	use tokio::time::timeout;
+
+	use super::*;
+
+	fn limiter(limit: RateLimit) -> Arc<RateLimiter> {
+		Arc::new(RateLimiter::new(limit))
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn requests_per_minute() {
+		let limiter = limiter(RateLimit::new().requests_per_minute(2));
+		let start = Instant::now();
+
+		limiter.acquire(0).await;
+		limiter.acquire(0).await;
+		assert_eq!(start.elapsed(), Duration::ZERO);
+
+		// one request refills every 30 seconds
+		limiter.acquire(0).await;
+		assert_eq!(start.elapsed(), Duration::from_secs(30));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn tokens_per_minute() {
+		let limiter = limiter(RateLimit::new().tokens_per_minute(1000));
+		let start = Instant::now();
+
+		let permit = limiter.acquire(600).await;
+		// only 100 of the 600 reserved were used
+		permit.complete(&Usage {
+			input_tokens: 50,
+			output_tokens: 50,
+			..Default::default()
+		});
+		limiter.acquire(900).await;
+		assert_eq!(start.elapsed(), Duration::ZERO);
+
+		// 600 missing refill in 36 seconds
+		limiter.acquire(600).await;
+		assert_eq!(start.elapsed(), Duration::from_secs(36));
+
+		// more than the capacity waits for a full bucket
+		limiter.acquire(5000).await;
+		assert_eq!(start.elapsed(), Duration::from_secs(96));
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn dropped_permits_free_their_slot() {
+		let limiter = limiter(RateLimit::new().max_concurrent(1));
+
+		let permit = limiter.acquire(0).await;
+		let limited = Duration::from_secs(3600);
+		assert!(timeout(limited, limiter.acquire(0)).await.is_err());
+
+		drop(permit);
+		let permit = timeout(limited, limiter.acquire(0)).await.unwrap();
+
+		// completing frees the slot as well
+		permit.complete(&Usage::default());
+		assert!(timeout(limited, limiter.acquire(0)).await.is_ok());
+	}
+}
@@ -0,0 +1,250 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use super::LlmsError;
+
+/// Retries requests that failed before the response started, see
+/// [`LlmsConfig::retry`](super::LlmsConfig::retry).
+///
+/// Rate limits, overloaded or failing providers and connection errors are
+/// retried with exponential backoff and full jitter, a `Retry-After` the
+/// provider sent takes precedence.
+///
+/// Since some providers report being overloaded as the first event, e.g.
+/// Anthropic's `overloaded_error`, [`Llms::request`](super::Llms::request)
+/// waits for it before returning. Later errors aren't retried, since the
+/// events before them were already handed out.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+	/// Retries after the first attempt.
+	pub max_retries: u32,
+	/// Upper bound of the first backoff, doubled with every retry.
+	pub initial_backoff: Duration,
+	pub max_backoff: Duration,
+	/// Longest `Retry-After` that is waited for, the error is returned
+	/// right away if the provider asks for more.
+	pub max_retry_after: Duration,
+}
+
+impl RetryPolicy {
+	pub fn new(max_retries: u32) -> Self {
+		Self {
+			max_retries,
+			initial_backoff: Duration::from_millis(500),
+			max_backoff: Duration::from_secs(30),
+			max_retry_after: Duration::from_secs(60),
+		}
+	}
+
+	pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+		self.initial_backoff = backoff;
+		self
+	}
+
+	pub fn max_backoff(mut self, backoff: Duration) -> Self {
+		self.max_backoff = backoff;
+		self
+	}
+
+	pub fn max_retry_after(mut self, max: Duration) -> Self {
+		self.max_retry_after = max;
+		self
+	}
+
+	/// How long to wait before retrying after `attempt` failed attempts,
+	/// `None` if the error shouldn't be retried.
+	pub(crate) fn delay(
+		&self,
+		attempt: u32,
+		error: &LlmsError,
+	) -> Option<Duration> {
+		if attempt > self.max_retries || !is_transient(error) {
+			return None;
+		}
+
+		let retry_after = match error {
+			LlmsError::Api { retry_after, .. } => *retry_after,
+			_ => None,
+		};
+
+		if let Some(retry_after) = retry_after {
+			return (retry_after <= self.max_retry_after)
+				.then_some(retry_after);
+		}
+
+		let backoff = self
+			.initial_backoff
+			.saturating_mul(2u32.saturating_pow(attempt - 1))
+			.min(self.max_backoff);
+		Some(backoff.mul_f64(jitter()))
+	}
+}
+
+/// Rate limits, overloaded or failing providers and connection errors,
+/// which might not happen on another attempt.
+pub(crate) fn is_transient(error: &LlmsError) -> bool {
+	match error {
+		LlmsError::Api { retryable, .. } => *retryable,
+		LlmsError::Reqwest(e) => {
+			e.is_connect() || e.is_timeout() || e.is_request()
+		}
+		_ => false,
+	}
+}
+
+/// A random factor between 0 and 1.
+fn jitter() -> f64 {
+	let random = RandomState::new().hash_one(0u8);
+	(random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+	use reqwest::StatusCode;
+
+	use super::*;
+	use crate::llms::{ApiErrorKind, ProviderKind};
+
+	fn api_error(
+		kind: ApiErrorKind,
+		retry_after: Option<Duration>,
+	) -> LlmsError {
+		LlmsError::Api {
+			kind,
+			provider: ProviderKind::Anthropic,
+			message: "Overloaded".into(),
+			retryable: kind.is_retryable(),
+			status: StatusCode::SERVICE_UNAVAILABLE,
+			request_id: None,
+			retry_after,
+		}
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_the_max() {
+		let policy = RetryPolicy::new(10)
+			.initial_backoff(Duration::from_millis(100))
+			.max_backoff(Duration::from_secs(1));
+		let error = api_error(ApiErrorKind::Overloaded, None);
+
+		for (attempt, max) in
+			[(1, 100), (2, 200), (3, 400), (4, 800), (9, 1000)]
+		{
+			let delay = policy.delay(attempt, &error).unwrap();
+			assert!(
+				delay <= Duration::from_millis(max),
+				"attempt {attempt} waits {delay:?}"
+			);
+		}
+		assert!(policy.delay(11, &error).is_none());
+	}
+
+	#[test]
+	fn retry_after_takes_precedence_up_to_the_max() {
+		let policy =
+			RetryPolicy::new(3).max_retry_after(Duration::from_secs(10));
+
+		let error =
+			api_error(ApiErrorKind::RateLimited, Some(Duration::from_secs(7)));
+		assert_eq!(policy.delay(1, &error), Some(Duration::from_secs(7)));
+
+		let error =
+			api_error(ApiErrorKind::RateLimited, Some(Duration::from_secs(11)));
+		assert_eq!(policy.delay(1, &error), None);
+	}
+
+	#[test]
+	fn only_transient_errors_are_retried() {
+		assert!(is_transient(&api_error(ApiErrorKind::RateLimited, None)));
+		assert!(is_transient(&api_error(ApiErrorKind::Overloaded, None)));
+		assert!(is_transient(&api_error(ApiErrorKind::Server, None)));
+		assert!(!is_transient(&api_error(ApiErrorKind::AuthFailed, None)));
+		assert!(!is_transient(&api_error(ApiErrorKind::QuotaExceeded, None)));
+		assert!(!is_transient(&LlmsError::ReadTimeout(Duration::ZERO)));
+		assert!(!is_transient(&LlmsError::Cancelled));
+
+		let policy = RetryPolicy::new(3);
+		let error = api_error(ApiErrorKind::InvalidRequest, None);
+		assert!(policy.delay(1, &error).is_none());
+	}
+
+	#[cfg(feature = "test-utils")]
+	mod mock {
+		use super::*;
+		use crate::llms::{Llms, LlmsConfig, Model, Request, tests::request};
+		use crate::mock::{Mock, MockReply};
+
+		fn llms(mock: &Mock, max_retries: u32) -> Llms {
+			let retry = RetryPolicy::new(max_retries)
+				.initial_backoff(Duration::from_millis(1));
+			Llms::new(LlmsConfig::new().mock(mock.clone()).retry(retry))
+		}
+
+		fn overloaded() -> LlmsError {
+			api_error(ApiErrorKind::Overloaded, None)
+		}
+
+		#[tokio::test]
+		async fn retries_failed_requests() {
+			let mock = Mock::new();
+			mock.push_error(overloaded());
+			mock.push_error(overloaded());
+			mock.push(MockReply::new().text("Hello"));
+
+			let req = Request {
+				model: Model::Mock,
+				..request()
+			};
+			let resp = llms(&mock, 2).request_blocking(&req).await.unwrap();
+			assert_eq!(resp.text(), "Hello");
+			assert_eq!(mock.requests().len(), 3);
+		}
+
+		#[tokio::test]
+		async fn retries_errors_sent_as_the_first_event() {
+			let mock = Mock::new();
+			mock.push(MockReply::new().error(overloaded()));
+			mock.push(MockReply::new().text("Hello"));
+
+			let req = Request {
+				model: Model::Mock,
+				..request()
+			};
+			let resp = llms(&mock, 1).request_blocking(&req).await.unwrap();
+			assert_eq!(resp.text(), "Hello");
+			assert_eq!(mock.remaining(), 0);
+		}
+
+		#[tokio::test]
+		async fn gives_up_after_max_retries() {
+			let mock = Mock::new();
+			mock.push_error(overloaded());
+			mock.push_error(overloaded());
+			mock.push(MockReply::new().text("Hello"));
+
+			let req = Request {
+				model: Model::Mock,
+				..request()
+			};
+			let e = llms(&mock, 1).request(&req).await.unwrap_err();
+			assert!(matches!(e, LlmsError::Api { .. }));
+			assert_eq!(mock.remaining(), 1);
+		}
+
+		#[tokio::test]
+		async fn later_errors_are_not_retried() {
+			let mock = Mock::new();
+			mock.push(MockReply::new().text("Hel").error(overloaded()));
+			mock.push(MockReply::new().text("Hello"));
+
+			let req = Request {
+				model: Model::Mock,
+				..request()
+			};
+			let e = llms(&mock, 1).request_blocking(&req).await.unwrap_err();
+			assert!(matches!(e, LlmsError::Api { .. }));
+			assert_eq!(mock.remaining(), 1);
+		}
+	}
+}
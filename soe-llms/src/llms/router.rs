@@ -0,0 +1,252 @@
+use futures::future::{self, FutureExt as _};
+
+use super::{
+	Input, Llms, LlmsError, Model, Request, ResponseFormat, ResponseStream,
+	chunking::estimate_tokens,
+};
+
+/// Rough price level of a model, ordered from cheapest to most expensive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CostClass {
+	Low,
+	Medium,
+	High,
+}
+
+/// Rough response speed of a model, ordered from fastest to slowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LatencyClass {
+	Fast,
+	Standard,
+	Slow,
+}
+
+impl Model {
+	/// The price level compared to the other models, [`Model::OpenRouter`]
+	/// and [`Model::Alias`] are assumed to be [`CostClass::Medium`],
+	/// [`Model::OpenAiCompat`] to be [`CostClass::Low`], a
+	/// [`Model::Custom`] like its provider's flagship and a
+	/// [`Model::Bedrock`] like its family's.
+	pub fn cost_class(&self) -> CostClass {
+		match self {
+			Model::Custom { provider, .. } => {
+				Model::representative(*provider).cost_class()
+			}
+			Model::Bedrock(id) => {
+				Model::bedrock_representative(id).cost_class()
+			}
+			Model::Gpt5_6Sol | Model::ClaudeFable5 | Model::ClaudeOpus4_8 => {
+				CostClass::High
+			}
+			Model::Gpt5_6Terra
+			| Model::ClaudeSonnet5
+			| Model::GeminiPro3_1
+			| Model::Grok4_5
+			| Model::MistralLarge
+			| Model::OpenRouter(_)
+			| Model::Alias(_) => CostClass::Medium,
+			Model::Gpt5_6Luna
+			| Model::ClaudeHaiku4_5
+			| Model::GeminiFlash3_5
+			| Model::GeminiFlash3_1Lite
+			| Model::GeminiFlashImage
+			| Model::MistralMedium
+			| Model::MistralSmall
+			| Model::Ministral14b
+			| Model::Apertus8bInstruct
+			| Model::OpenAiCompat(_) => CostClass::Low,
+			#[cfg(feature = "test-utils")]
+			Model::Mock => CostClass::Low,
+		}
+	}
+
+	/// The response speed compared to the other models,
+	/// [`Model::OpenRouter`], [`Model::OpenAiCompat`] and [`Model::Alias`]
+	/// are assumed to be [`LatencyClass::Standard`], a [`Model::Custom`] like
+	/// its provider's flagship.
+	pub fn latency_class(&self) -> LatencyClass {
+		match self {
+			Model::Custom { provider, .. } => {
+				Model::representative(*provider).latency_class()
+			}
+			Model::Bedrock(id) => {
+				Model::bedrock_representative(id).latency_class()
+			}
+			Model::Gpt5_6Sol | Model::ClaudeFable5 | Model::ClaudeOpus4_8 => {
+				LatencyClass::Slow
+			}
+			Model::Gpt5_6Terra
+			| Model::ClaudeSonnet5
+			| Model::GeminiPro3_1
+			| Model::Grok4_5
+			| Model::GeminiFlashImage
+			| Model::MistralLarge
+			| Model::MistralMedium
+			| Model::OpenRouter(_)
+			| Model::OpenAiCompat(_)
+			| Model::Alias(_) => LatencyClass::Standard,
+			Model::Gpt5_6Luna
+			| Model::ClaudeHaiku4_5
+			| Model::GeminiFlash3_5
+			| Model::GeminiFlash3_1Lite
+			| Model::MistralSmall
+			| Model::Ministral14b
+			| Model::Apertus8bInstruct => LatencyClass::Fast,
+			#[cfg(feature = "test-utils")]
+			Model::Mock => LatencyClass::Fast,
+		}
+	}
+}
+
+/// Picks a model per request from a list of candidates, see
+/// [`Llms::request_routed`].
+///
+/// A candidate qualifies if it supports everything the request uses
+/// (tools, structured output, documents), its context window fits the estimated
+/// input plus [`GenerationParams::max_tokens`](super::GenerationParams)
+/// and it is within the cost and latency limits. Of those the cheapest one
+/// is picked, then the fastest, then the earliest in `candidates`.
+#[derive(Debug, Clone, Default)]
+pub struct Router {
+	pub candidates: Vec<Model>,
+	pub max_cost: Option<CostClass>,
+	pub max_latency: Option<LatencyClass>,
+	/// The request contains images.
+	pub needs_vision: bool,
+}
+
+impl Router {
+	pub fn new(candidates: Vec<Model>) -> Self {
+		Self {
+			candidates,
+			..Default::default()
+		}
+	}
+
+	pub fn max_cost(mut self, cost: CostClass) -> Self {
+		self.max_cost = Some(cost);
+		self
+	}
+
+	pub fn max_latency(mut self, latency: LatencyClass) -> Self {
+		self.max_latency = Some(latency);
+		self
+	}
+
+	pub fn needs_vision(mut self, needs_vision: bool) -> Self {
+		self.needs_vision = needs_vision;
+		self
+	}
+
+	/// Returns the best candidate for the request, ignoring the request's
+	/// own model.
+	pub fn route(&self, req: &Request) -> Option<&Model> {
+		self.route_filtered(req, |_| true)
+	}
+
+	fn route_filtered(
+		&self,
+		req: &Request,
+		available: impl Fn(&Model) -> bool,
+	) -> Option<&Model> {
+		let needed_context =
+			estimate_input_tokens(req) + req.params.max_tokens.unwrap_or(0);
+
+		self.candidates
+			.iter()
+			.filter(|model| available(model))
+			.filter(|model| {
+				let caps = model.capabilities();
+
+				(req.tools.is_empty() || caps.tools)
+					&& (matches!(req.response_format, ResponseFormat::Text)
+						|| caps.structured_output)
+					&& (!self.needs_vision || caps.vision)
+					&& (!req.has_documents() || caps.documents)
+					&& caps.max_context >= needed_context
+					&& self.max_cost.is_none_or(|max| model.cost_class() <= max)
+					&& self
+						.max_latency
+						.is_none_or(|max| model.latency_class() <= max)
+			})
+			.min_by_key(|model| (model.cost_class(), model.latency_class()))
+	}
+}
+
+/// Roughly what a medium sized image costs with most providers.
+const IMAGE_TOKENS: u32 = 1_000;
+
+/// Roughly what a document of a few pages costs.
+const DOCUMENT_TOKENS: u32 = 5_000;
+
+/// Good enough to rule out models whose context is too small.
+pub(super) fn estimate_input_tokens(req: &Request) -> u32 {
+	let inputs = req.input.iter().map(|input| match input {
+		Input::Text { content, .. } => estimate_tokens(content),
+		Input::ToolCall { name, input, .. } => {
+			estimate_tokens(name) + estimate_tokens(&input.to_string())
+		}
+		Input::ToolCallOutput { output, .. } => estimate_tokens(output),
+		Input::Image { .. } => IMAGE_TOKENS,
+		Input::Document { .. } => DOCUMENT_TOKENS,
+	});
+
+	inputs.fold(estimate_tokens(&req.instructions), u32::saturating_add)
+}
+
+impl Llms {
+	/// Sends the request to the model picked by `router`, skipping
+	/// candidates whose provider isn't configured.
+	pub async fn request_routed(
+		&self,
+		router: &Router,
+		req: &Request,
+	) -> Result<ResponseStream, LlmsError> {
+		let model = router
+			.route_filtered(req, |model| self.is_configured(model))
+			.ok_or(LlmsError::NoMatchingModel)?;
+
+		self.request(&Request {
+			model: model.clone(),
+			..req.clone()
+		})
+		.await
+	}
+
+	/// Sends the request to every model at once and streams from the first
+	/// one producing an event, the other requests are cancelled.
+	///
+	/// Models failing before their first event are skipped, if all of them
+	/// fail the last error is returned.
+	/// [`LlmsConfig::fallback`](super::LlmsConfig::fallback) doesn't apply.
+	pub async fn race(
+		&self,
+		models: &[Model],
+		req: &Request,
+	) -> Result<ResponseStream, LlmsError> {
+		if models.is_empty() {
+			return Err(LlmsError::NoMatchingModel);
+		}
+
+		let reqs: Vec<_> = models
+			.iter()
+			.map(|model| Request {
+				model: model.clone(),
+				..req.clone()
+			})
+			.collect();
+
+		let attempts = reqs.iter().map(|req| {
+			async move {
+				let mut stream = self.send(req).await?;
+				stream.wait_started().await?;
+				Ok::<_, LlmsError>(stream)
+			}
+			.boxed()
+		});
+
+		// dropping the remaining attempts cancels them
+		let (stream, _) = future::select_ok(attempts).await?;
+		Ok(stream)
+	}
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use futures::stream::{self, StreamExt as _};
+
+use super::{Llms, LlmsError, Request, Response};
+
+/// How to sample a request multiple times, see [`Llms::sample`].
+#[derive(Debug, Clone, Copy)]
+pub struct Sampling {
+	/// Number of responses to request.
+	pub samples: usize,
+	/// Maximum number of requests in flight at once, keeps bursts below
+	/// the provider's rate limits.
+	pub concurrency: usize,
+	/// Overrides [`GenerationParams::temperature`], usually raised to get
+	/// more varied answers.
+	///
+	/// [`GenerationParams::temperature`]: super::GenerationParams::temperature
+	pub temperature: Option<f32>,
+}
+
+/// The responses of [`Llms::self_consistency`].
+#[derive(Debug)]
+pub struct Samples {
+	pub candidates: Vec<Response>,
+	/// Index of the chosen response in `candidates`.
+	pub winner: usize,
+}
+
+impl Sampling {
+	pub fn new(samples: usize) -> Self {
+		Self {
+			samples,
+			concurrency: 4,
+			temperature: None,
+		}
+	}
+
+	pub fn concurrency(mut self, concurrency: usize) -> Self {
+		self.concurrency = concurrency;
+		self
+	}
+
+	pub fn temperature(mut self, temperature: f32) -> Self {
+		self.temperature = Some(temperature);
+		self
+	}
+}
+
+impl Samples {
+	pub fn best(&self) -> &Response {
+		&self.candidates[self.winner]
+	}
+
+	pub fn into_best(mut self) -> Response {
+		self.candidates.swap_remove(self.winner)
+	}
+}
+
+impl Llms {
+	/// Sends the request `sampling.samples` times and waits for all
+	/// responses, in completion order.
+	///
+	/// Failed samples are dropped, if all of them fail the last error is
+	/// returned.
+	pub async fn sample(
+		&self,
+		sampling: &Sampling,
+		req: &Request,
+	) -> Result<Vec<Response>, LlmsError> {
+		let mut req = req.clone();
+		if let Some(temperature) = sampling.temperature {
+			req.params.temperature = Some(temperature);
+		}
+		let req = &req;
+
+		let results: Vec<_> = stream::iter(0..sampling.samples)
+			.map(|_| async move { self.request_blocking(req).await })
+			.buffer_unordered(sampling.concurrency.max(1))
+			.collect()
+			.await;
+
+		let mut last_error = None;
+		let responses: Vec<_> = results
+			.into_iter()
+			.filter_map(|result| result.map_err(|e| last_error = Some(e)).ok())
+			.collect();
+
+		match last_error {
+			Some(e) if responses.is_empty() => Err(e),
+			_ => Ok(responses),
+		}
+	}
+
+	/// Samples the request and picks the answer given most often, compared
+	/// by their trimmed [`Response::text`]. Ties go to the answer that
+	/// completed first.
+	pub async fn self_consistency(
+		&self,
+		sampling: &Sampling,
+		req: &Request,
+	) -> Result<Samples, LlmsError> {
+		self.self_consistency_with(sampling, req, majority_vote)
+			.await
+	}
+
+	/// Like [`Llms::self_consistency`] but `reduce` picks the winner by
+	/// returning its index, e.g. after asking a judge model.
+	///
+	/// Returns [`LlmsError::InvalidSampling`] if `sampling.samples` is zero
+	/// or the returned index is out of bounds.
+	pub async fn self_consistency_with(
+		&self,
+		sampling: &Sampling,
+		req: &Request,
+		reduce: impl FnOnce(&[Response]) -> usize,
+	) -> Result<Samples, LlmsError> {
+		if sampling.samples == 0 {
+			return Err(LlmsError::InvalidSampling(
+				"at least one sample is required".into(),
+			));
+		}
+		let candidates = self.sample(sampling, req).await?;
+
+		let winner = reduce(&candidates);
+		if winner >= candidates.len() {
+			return Err(LlmsError::InvalidSampling(format!(
+				"winner {winner} out of bounds for {} candidates",
+				candidates.len()
+			)));
+		}
+
+		Ok(Samples { candidates, winner })
+	}
+}
+
+fn majority_vote(candidates: &[Response]) -> usize {
+	let texts: Vec<_> = candidates.iter().map(|resp| resp.text()).collect();
+
+	let mut votes: HashMap<&str, usize> = HashMap::new();
+	for text in &texts {
+		*votes.entry(text.trim()).or_default() += 1;
+	}
+
+	let max = votes.values().copied().max().unwrap_or(0);
+	texts
+		.iter()
+		.position(|text| votes[text.trim()] == max)
+		.unwrap_or(0)
+}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+	use super::*;
+	use crate::llms::{LlmsConfig, Model, tests::request};
+	use crate::mock::{Mock, MockReply};
+
+	fn mock(answers: &[&str]) -> (Mock, Llms) {
+		let mock = Mock::new();
+		for answer in answers {
+			mock.push(MockReply::new().text(*answer));
+		}
+		let llms = Llms::new(LlmsConfig::new().mock(mock.clone()));
+		(mock, llms)
+	}
+
+	fn req() -> Request {
+		Request {
+			model: Model::Mock,
+			..request()
+		}
+	}
+
+	#[tokio::test]
+	async fn picks_the_most_common_answer() {
+		let (mock, llms) = mock(&["42", "41", " 41\n", "42 ", "41"]);
+
+		// one at a time, so the candidates are in the order of the replies
+		let sampling = Sampling::new(5).concurrency(1).temperature(1.0);
+		let samples = llms.self_consistency(&sampling, &req()).await.unwrap();
+
+		assert_eq!(samples.winner, 1);
+		assert_eq!(samples.best().text().trim(), "41");
+		assert_eq!(samples.candidates.len(), 5);
+		assert!(
+			mock.requests()
+				.iter()
+				.all(|req| req.params.temperature == Some(1.0))
+		);
+	}
+
+	#[tokio::test]
+	async fn ties_go_to_the_first_answer() {
+		let (_mock, llms) = mock(&["b", "a", "a", "b"]);
+
+		let sampling = Sampling::new(4).concurrency(1);
+		let samples = llms.self_consistency(&sampling, &req()).await.unwrap();
+
+		assert_eq!(samples.winner, 0);
+		assert_eq!(samples.into_best().text(), "b");
+	}
+
+	#[tokio::test]
+	async fn rejects_invalid_sampling() {
+		let (mock, llms) = mock(&["a"]);
+
+		let err = llms
+			.self_consistency(&Sampling::new(0), &req())
+			.await
+			.unwrap_err();
+		assert!(matches!(err, LlmsError::InvalidSampling(_)));
+		assert!(mock.requests().is_empty());
+
+		let err = llms
+			.self_consistency_with(&Sampling::new(1), &req(), |_| 1)
+			.await
+			.unwrap_err();
+		assert!(matches!(err, LlmsError::InvalidSampling(_)));
+	}
+}
@@ -0,0 +1,57 @@
+use schemars::JsonSchema;
+use schemars::generate::SchemaSettings;
+use serde_json::Value;
+
+use super::{ResponseFormat, Tool};
+
+/// The JSON Schema of the values `T` deserializes from.
+///
+/// Without `$schema`, the providers translate the rest, e.g. inlining the
+/// `$defs` for Google.
+pub fn schema_for<T: JsonSchema>() -> Value {
+	SchemaSettings::draft2020_12()
+		.for_deserialize()
+		.with(|settings| settings.meta_schema = None)
+		.into_generator()
+		.into_root_schema_for::<T>()
+		.to_value()
+}
+
+impl Tool {
+	/// A strict tool taking `T` as its input, parse the calls with
+	/// [`Output::parse_tool_input`](super::Output::parse_tool_input).
+	///
+	/// ```
+	/// # use soe_llms::Tool;
+	/// #[derive(serde::Deserialize, schemars::JsonSchema)]
+	/// struct Weather {
+	///     /// City name
+	///     city: String,
+	/// }
+	///
+	/// let tool = Tool::typed::<Weather>("weather", "The current weather.");
+	/// ```
+	pub fn typed<T: JsonSchema>(
+		name: impl Into<String>,
+		description: impl Into<String>,
+	) -> Self {
+		Self {
+			name: name.into(),
+			description: description.into(),
+			parameters: Some(schema_for::<T>()),
+			strict: true,
+			cache: false,
+		}
+	}
+}
+
+impl ResponseFormat {
+	/// A strict [`ResponseFormat::JsonSchema`] of `T`.
+	pub fn typed<T: JsonSchema>(name: impl Into<String>) -> Self {
+		Self::JsonSchema {
+			name: name.into(),
+			schema: schema_for::<T>(),
+			strict: true,
+		}
+	}
+}
@@ -0,0 +1,315 @@
+use std::fmt;
+
+/// Regroups the text deltas of a stream, see [`ResponseStream::segment`].
+///
+/// [`ResponseStream::segment`]: super::ResponseStream::segment
+pub trait Segmenter: Send + Sync + 'static {
+	/// Takes the next raw delta and returns the text that can be emitted
+	/// now, if any. Held back text is returned by a later call.
+	fn push(&mut self, delta: &str) -> Option<String>;
+
+	/// Called when a tool call starts and once the stream has ended,
+	/// returns everything still held back.
+	fn finish(&mut self) -> Option<String>;
+}
+
+/// Applies segmenters in the order they were added.
+#[derive(Default)]
+pub(crate) struct Segmenters(Vec<Box<dyn Segmenter>>);
+
+impl Segmenters {
+	pub fn push(&mut self, segmenter: impl Segmenter) {
+		self.0.push(Box::new(segmenter));
+	}
+
+	pub fn process(&mut self, delta: String) -> Option<String> {
+		self.0
+			.iter_mut()
+			.try_fold(delta, |delta, segmenter| segmenter.push(&delta))
+	}
+
+	pub fn finish(&mut self) -> Option<String> {
+		let mut carry: Option<String> = None;
+
+		for segmenter in &mut self.0 {
+			let pushed = carry.take().and_then(|delta| segmenter.push(&delta));
+			carry = match (pushed, segmenter.finish()) {
+				(Some(mut a), Some(b)) => {
+					a.push_str(&b);
+					Some(a)
+				}
+				(a, b) => a.or(b),
+			};
+		}
+
+		carry
+	}
+}
+
+impl fmt::Debug for Segmenters {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Segmenters")
+			.field("len", &self.0.len())
+			.finish()
+	}
+}
+
+/// Holds back markdown that would render broken while incomplete, so a
+/// chat UI re-rendering after every delta doesn't flicker.
+///
+/// Code fences are held back until they're closed, inline code, links,
+/// images and `**` emphasis until they're closed or the paragraph ends.
+#[derive(Debug, Clone)]
+pub struct MarkdownSegmenter {
+	pending: String,
+	at_line_start: bool,
+}
+
+impl MarkdownSegmenter {
+	pub fn new() -> Self {
+		Self {
+			pending: String::new(),
+			at_line_start: true,
+		}
+	}
+}
+
+impl Default for MarkdownSegmenter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Segmenter for MarkdownSegmenter {
+	fn push(&mut self, delta: &str) -> Option<String> {
+		self.pending.push_str(delta);
+
+		let len = markdown_safe_len(&self.pending, self.at_line_start);
+		if len == 0 {
+			return None;
+		}
+
+		let ready: String = self.pending.drain(..len).collect();
+		self.at_line_start = ready.ends_with('\n');
+		Some(ready)
+	}
+
+	fn finish(&mut self) -> Option<String> {
+		self.at_line_start = true;
+		Some(std::mem::take(&mut self.pending)).filter(|s| !s.is_empty())
+	}
+}
+
+/// Length of the prefix of `text` without unclosed constructs.
+fn markdown_safe_len(text: &str, at_line_start: bool) -> usize {
+	let mut limit = text.len();
+	// where the last paragraph starts, constructs can't span paragraphs
+	let mut paragraph = 0;
+	let mut open_fence = None;
+	let mut offset = 0;
+
+	for (i, line) in text.split_inclusive('\n').enumerate() {
+		let is_fence = (i > 0 || at_line_start)
+			&& line.trim_start_matches(' ').starts_with("```");
+
+		if is_fence {
+			match open_fence.take() {
+				Some(_) => paragraph = offset + line.len(),
+				None => open_fence = Some(offset),
+			}
+		} else if open_fence.is_none() && line.trim().is_empty() {
+			paragraph = offset + line.len();
+		}
+
+		offset += line.len();
+	}
+
+	if let Some(start) = open_fence {
+		limit = start;
+		paragraph = paragraph.min(start);
+	}
+
+	let end = paragraph + inline_safe_len(&text[paragraph..limit]);
+
+	// these could become the start of a construct with the next delta
+	let trimmed = text[..end].trim_end_matches(['*', '!']);
+	trimmed.len()
+}
+
+fn inline_safe_len(text: &str) -> usize {
+	let bytes = text.as_bytes();
+	let mut open_bold: Option<usize> = None;
+	let mut open_link: Option<usize> = None;
+	let mut i = 0;
+
+	let earliest = |pos: Option<usize>, a: Option<usize>, b: Option<usize>| {
+		[pos, a, b].into_iter().flatten().min()
+	};
+
+	while i < bytes.len() {
+		match bytes[i] {
+			b'\\' => {
+				if i + 1 == bytes.len() {
+					return earliest(Some(i), open_bold, open_link).unwrap();
+				}
+				i += 2;
+				continue;
+			}
+			b'`' => {
+				let run = bytes[i..].iter().take_while(|&&b| b == b'`').count();
+				let ticks = &text[i..i + run];
+				match text[i + run..].find(ticks) {
+					Some(close) => i += run + close + run,
+					None => {
+						return earliest(Some(i), open_bold, open_link)
+							.unwrap();
+					}
+				}
+				continue;
+			}
+			b'*' if bytes.get(i + 1) == Some(&b'*') => {
+				if open_bold.take().is_none() {
+					open_bold = Some(i);
+				}
+				i += 2;
+				continue;
+			}
+			b'[' if open_link.is_none() => {
+				let image = i > 0 && bytes[i - 1] == b'!';
+				open_link = Some(if image { i - 1 } else { i });
+			}
+			b']' if open_link.is_some() => match bytes.get(i + 1) {
+				// might still be followed by the url
+				None => return earliest(None, open_bold, open_link).unwrap(),
+				Some(b'(') => match text[i..].find(')') {
+					Some(close) => {
+						open_link = None;
+						i += close + 1;
+						continue;
+					}
+					None => {
+						return earliest(None, open_bold, open_link).unwrap();
+					}
+				},
+				// just brackets
+				Some(_) => open_link = None,
+			},
+			_ => {}
+		}
+		i += 1;
+	}
+
+	earliest(None, open_bold, open_link).unwrap_or(text.len())
+}
+
+/// Coalesces deltas so every emitted one ends after whitespace and is at
+/// least `min_len` bytes long, smoothing out providers that stream single
+/// characters.
+#[derive(Debug, Clone)]
+pub struct WordSegmenter {
+	pending: String,
+	min_len: usize,
+}
+
+impl WordSegmenter {
+	pub fn new(min_len: usize) -> Self {
+		Self {
+			pending: String::new(),
+			min_len,
+		}
+	}
+}
+
+impl Default for WordSegmenter {
+	/// Emits every completed word.
+	fn default() -> Self {
+		Self::new(1)
+	}
+}
+
+impl Segmenter for WordSegmenter {
+	fn push(&mut self, delta: &str) -> Option<String> {
+		self.pending.push_str(delta);
+
+		let (i, ws) = self
+			.pending
+			.char_indices()
+			.rev()
+			.find(|(_, c)| c.is_whitespace())?;
+		let len = i + ws.len_utf8();
+		if len < self.min_len {
+			return None;
+		}
+
+		Some(self.pending.drain(..len).collect())
+	}
+
+	fn finish(&mut self) -> Option<String> {
+		Some(std::mem::take(&mut self.pending)).filter(|s| !s.is_empty())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Everything emitted for `deltas`, the last entry by `finish`.
+	fn run(mut segmenter: impl Segmenter, deltas: &[&str]) -> Vec<String> {
+		let mut out: Vec<_> =
+			deltas.iter().filter_map(|d| segmenter.push(d)).collect();
+		out.extend(segmenter.finish());
+		out
+	}
+
+	#[test]
+	fn holds_fences_split_across_deltas() {
+		let out = run(
+			MarkdownSegmenter::new(),
+			&["Intro\n``", "`rust\nfn main() {}\n", "``", "`\nAfter"],
+		);
+		assert_eq!(out, ["Intro\n", "```rust\nfn main() {}\n```\nAfter"]);
+	}
+
+	#[test]
+	fn holds_bold_split_across_deltas() {
+		let out =
+			run(MarkdownSegmenter::new(), &["Some *", "*bold", "** text"]);
+		assert_eq!(out, ["Some ", "**bold** text"]);
+	}
+
+	#[test]
+	fn holds_links_until_the_url_arrived() {
+		let out = run(
+			MarkdownSegmenter::new(),
+			&["See [docs", "]", "(https://example.com) now"],
+		);
+		assert_eq!(out, ["See ", "[docs](https://example.com) now"]);
+	}
+
+	#[test]
+	fn keeps_escapes_with_the_escaped_char() {
+		let out = run(MarkdownSegmenter::new(), &["a \\", "*b"]);
+		assert_eq!(out, ["a ", "\\*b"]);
+	}
+
+	#[test]
+	fn splits_multibyte_text_on_char_boundaries() {
+		let out = run(MarkdownSegmenter::new(), &["Grüße ", "`cödé", "` — ok"]);
+		assert_eq!(out, ["Grüße ", "`cödé` — ok"]);
+
+		let out = run(WordSegmenter::new(1), &["naï", "ve café", " au lait"]);
+		assert_eq!(out, ["naïve ", "café au ", "lait"]);
+	}
+
+	#[test]
+	fn finish_passes_held_back_text_through_later_segmenters() {
+		let mut segmenters = Segmenters::default();
+		segmenters.push(MarkdownSegmenter::new());
+		segmenters.push(WordSegmenter::new(1));
+
+		assert_eq!(segmenters.process("Hi the".into()).as_deref(), Some("Hi "));
+		assert_eq!(segmenters.process("re**b".into()), None);
+		assert_eq!(segmenters.finish().as_deref(), Some("there**b"));
+		assert_eq!(segmenters.finish(), None);
+	}
+}
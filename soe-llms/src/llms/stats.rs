@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use super::{Response, ResponseEvent};
+
+/// Timings of a completed stream, see [`ResponseStream::stats`].
+///
+/// [`ResponseStream::stats`]: super::ResponseStream::stats
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct StreamStats {
+	/// Time from sending the request until the first text delta, `None` if
+	/// the response had no text or it only arrived with the final event.
+	pub time_to_first_token: Option<Duration>,
+	/// Time from sending the request until the response completed.
+	pub duration: Duration,
+	pub output_tokens: u32,
+	/// Output tokens per second after the first token, or over the whole
+	/// duration if there was none. Includes reasoning tokens.
+	pub output_tokens_per_sec: f64,
+	/// Events returned by [`ResponseStream::next`] so far, not counting
+	/// [`ResponseEvent::Progress`].
+	///
+	/// [`ResponseStream::next`]: super::ResponseStream::next
+	pub events: usize,
+}
+
+#[derive(Debug)]
+pub(crate) struct StatsTracker {
+	started: Instant,
+	first_token: Option<Duration>,
+	events: usize,
+	/// The duration and output tokens once completed.
+	completed: Option<(Duration, u32)>,
+}
+
+impl StatsTracker {
+	pub fn new(started: Instant) -> Self {
+		Self {
+			started,
+			first_token: None,
+			events: 0,
+			completed: None,
+		}
+	}
+
+	pub fn on_event(&mut self, event: &ResponseEvent) {
+		if self.first_token.is_none()
+			&& self.completed.is_none()
+			&& matches!(event, ResponseEvent::TextDelta { .. })
+		{
+			self.first_token = Some(self.started.elapsed());
+		}
+		self.events += 1;
+	}
+
+	pub fn on_complete(&mut self, resp: &Response) {
+		self.completed =
+			Some((self.started.elapsed(), resp.usage.output_tokens));
+	}
+
+	pub fn elapsed(&self) -> Duration {
+		self.started.elapsed()
+	}
+
+	pub fn stats(&self) -> Option<StreamStats> {
+		let (duration, output_tokens) = self.completed?;
+
+		let generating = duration - self.first_token.unwrap_or_default();
+		let output_tokens_per_sec = if generating.is_zero() {
+			0.0
+		} else {
+			output_tokens as f64 / generating.as_secs_f64()
+		};
+
+		Some(StreamStats {
+			time_to_first_token: self.first_token,
+			duration,
+			output_tokens,
+			output_tokens_per_sec,
+			events: self.events,
+		})
+	}
+}
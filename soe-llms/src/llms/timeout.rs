@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+
+use super::LlmsError;
+
+/// Time limits of a single request, `None` means no limit. Hitting one
+/// closes the connection, the stream ends after the error.
+///
+/// Set per [`Request`](super::Request), through [`RequestDefaults`] or for
+/// every request with [`LlmsConfig::timeouts`].
+///
+/// [`RequestDefaults`]: super::RequestDefaults
+/// [`LlmsConfig::timeouts`]: super::LlmsConfig::timeouts
+///
+/// Connecting is limited per provider instead, since connections are
+/// pooled, see [`HttpConfig::connect_timeout`].
+///
+/// [`HttpConfig::connect_timeout`]: super::HttpConfig::connect_timeout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+	/// Longest wait for the response headers and then for each chunk of the
+	/// body, keep-alive pings included. Fails with
+	/// [`LlmsError::ReadTimeout`].
+	pub read: Option<Duration>,
+	/// Wall-clock limit from sending the request until the response
+	/// completed, e.g. a long one for reasoning models. Fails with
+	/// [`LlmsError::Timeout`].
+	pub total: Option<Duration>,
+}
+
+impl Timeouts {
+	/// Fills every limit not set in `self` from `defaults`.
+	pub fn or(self, defaults: Timeouts) -> Self {
+		Self {
+			read: self.read.or(defaults.read),
+			total: self.total.or(defaults.total),
+		}
+	}
+}
+
+/// Tracks the [`Timeouts`] of a running request.
+#[derive(Debug)]
+pub(crate) struct Deadline {
+	timeouts: Timeouts,
+	started: Instant,
+	last_read: Instant,
+}
+
+impl Deadline {
+	pub fn new(timeouts: Timeouts, started: Instant) -> Self {
+		Self {
+			timeouts,
+			started,
+			last_read: started,
+		}
+	}
+
+	/// Returns `false` if waiting `delay` would exceed the total limit.
+	pub fn can_wait(&self, delay: Duration) -> bool {
+		self.timeouts
+			.total
+			.is_none_or(|limit| self.started.elapsed() + delay < limit)
+	}
+
+	/// Starts the read limit anew, e.g. after waiting to retry.
+	pub fn restart_read(&mut self) {
+		self.last_read = Instant::now();
+	}
+
+	/// Awaits `fut`, e.g. the response headers, until whichever limit is
+	/// hit first.
+	pub async fn read<T>(
+		&mut self,
+		fut: impl Future<Output = T>,
+	) -> Result<T, LlmsError> {
+		self.limit(fut, true).await
+	}
+
+	/// Awaits `fut` until the total limit, for reading events of the body,
+	/// where the providers check the read limit per chunk.
+	pub async fn total<T>(
+		&mut self,
+		fut: impl Future<Output = T>,
+	) -> Result<T, LlmsError> {
+		self.limit(fut, false).await
+	}
+
+	async fn limit<T>(
+		&mut self,
+		fut: impl Future<Output = T>,
+		read: bool,
+	) -> Result<T, LlmsError> {
+		let now = Instant::now();
+		let read = self.timeouts.read.filter(|_| read).map(|limit| {
+			let left = (self.last_read + limit).saturating_duration_since(now);
+			(left, LlmsError::ReadTimeout(limit))
+		});
+		let total = self.timeouts.total.map(|limit| {
+			let left = (self.started + limit).saturating_duration_since(now);
+			(left, LlmsError::Timeout(limit))
+		});
+
+		let limit = match (read, total) {
+			(Some(read), Some(total)) if read.0 <= total.0 => Some(read),
+			(_, Some(total)) => Some(total),
+			(read, None) => read,
+		};
+
+		let out = match limit {
+			Some((left, err)) => {
+				tokio::time::timeout(left, fut).await.map_err(|_| err)?
+			}
+			None => fut.await,
+		};
+
+		self.last_read = Instant::now();
+		Ok(out)
+	}
+}
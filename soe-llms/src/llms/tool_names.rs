@@ -0,0 +1,248 @@
+use std::collections::{HashMap, HashSet};
+
+use tracing::warn;
+
+use super::{Input, LlmsError, Output, ProviderKind, Request, ToolChoice};
+
+/// What a provider accepts as tool name.
+#[derive(Debug, Clone, Copy)]
+struct NameRules {
+	max_len: usize,
+	/// Allowed besides ASCII letters, digits, `_` and `-`.
+	extra_chars: &'static [char],
+	/// The name has to start with a letter or an underscore.
+	letter_start: bool,
+}
+
+impl NameRules {
+	fn for_provider(kind: ProviderKind) -> Self {
+		match kind {
+			ProviderKind::Google => Self {
+				max_len: 64,
+				extra_chars: &['.', ':'],
+				letter_start: true,
+			},
+			ProviderKind::OpenAi
+			| ProviderKind::Anthropic
+			| ProviderKind::XAi
+			| ProviderKind::Mistral
+			| ProviderKind::PublicAi
+			| ProviderKind::OpenRouter
+			| ProviderKind::OpenAiCompat
+			| ProviderKind::Bedrock => Self {
+				max_len: 64,
+				extra_chars: &[],
+				letter_start: false,
+			},
+			#[cfg(feature = "test-utils")]
+			ProviderKind::Mock => Self {
+				max_len: 64,
+				extra_chars: &[],
+				letter_start: false,
+			},
+		}
+	}
+
+	fn allowed(&self, c: char) -> bool {
+		c.is_ascii_alphanumeric()
+			|| c == '_'
+			|| c == '-'
+			|| self.extra_chars.contains(&c)
+	}
+
+	fn violation(&self, name: &str) -> Option<String> {
+		if name.is_empty() {
+			Some("the name is empty".into())
+		} else if name.len() > self.max_len {
+			Some(format!("longer than {} characters", self.max_len))
+		} else if !name.chars().all(|c| self.allowed(c)) {
+			Some("contains unsupported characters".into())
+		} else if self.letter_start
+			&& !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+		{
+			Some("has to start with a letter or an underscore".into())
+		} else {
+			None
+		}
+	}
+
+	fn sanitize(&self, name: &str) -> String {
+		let mut sanitized: String = name
+			.chars()
+			.map(|c| if self.allowed(c) { c } else { '_' })
+			.collect();
+
+		if sanitized.is_empty()
+			|| (self.letter_start
+				&& !sanitized
+					.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_'))
+		{
+			sanitized.insert(0, '_');
+		}
+
+		// only ascii is left
+		sanitized.truncate(self.max_len);
+		sanitized
+	}
+}
+
+/// Maps renamed tools back to their original names, see
+/// [`Request::check_tool_names`].
+#[derive(Debug, Default)]
+pub(crate) struct ToolRenames(HashMap<String, String>);
+
+impl ToolRenames {
+	/// Replaces the sanitized names in the tool calls of a response.
+	pub fn restore(&self, output: &mut [Output]) {
+		if self.0.is_empty() {
+			return;
+		}
+
+		for output in output {
+			if let Output::ToolCall { id, name, .. } = output {
+				self.restore_call(id, name);
+			}
+		}
+	}
+
+	/// Replaces the sanitized name of a single tool call.
+	pub fn restore_call(&self, id: &mut String, name: &mut String) {
+		if let Some(original) = self.0.get(name.as_str()) {
+			// Gemini uses the name as id
+			if id == name {
+				*id = original.clone();
+			}
+			*name = original.clone();
+		}
+	}
+
+	/// Replaces a sanitized name used as id, see [`Self::restore_call`].
+	pub fn restore_id(&self, id: &mut String) {
+		if let Some(original) = self.0.get(id.as_str()) {
+			*id = original.clone();
+		}
+	}
+}
+
+impl Request {
+	/// Checks the tool names against what the model's provider accepts.
+	///
+	/// Duplicate names always fail. Invalid names fail unless `rename` is
+	/// set, then they're replaced in the tools and the history and the
+	/// returned [`ToolRenames`] reverts them in the response. Returns `None`
+	/// if nothing has to be renamed.
+	pub(crate) fn check_tool_names(
+		&self,
+		rename: bool,
+	) -> Result<Option<(Request, ToolRenames)>, LlmsError> {
+		let Some(kind) = self.model.provider() else {
+			return Ok(None);
+		};
+		let rules = NameRules::for_provider(kind);
+
+		let mut taken = HashSet::new();
+		for tool in &self.tools {
+			if !taken.insert(tool.name.as_str()) {
+				return Err(LlmsError::InvalidToolName {
+					name: tool.name.clone(),
+					reason: "defined more than once".into(),
+				});
+			}
+		}
+
+		if let ToolChoice::Tool(name) = &self.tool_choice
+			&& !taken.contains(name.as_str())
+		{
+			return Err(LlmsError::InvalidToolName {
+				name: name.clone(),
+				reason: "forced by tool_choice but not defined".into(),
+			});
+		}
+
+		let mut renames = HashMap::new();
+		for tool in &self.tools {
+			let Some(reason) = rules.violation(&tool.name) else {
+				continue;
+			};
+
+			if !rename {
+				return Err(LlmsError::InvalidToolName {
+					name: tool.name.clone(),
+					reason,
+				});
+			}
+
+			let sanitized = unique(
+				rules.sanitize(&tool.name),
+				rules.max_len,
+				&taken,
+				&renames,
+			);
+			warn!(
+				"renaming tool {:?} to {sanitized:?} for {:?}: {reason}",
+				tool.name, self.model
+			);
+			renames.insert(tool.name.clone(), sanitized);
+		}
+
+		if renames.is_empty() {
+			return Ok(None);
+		}
+
+		let mut req = self.clone();
+		let apply = |name: &mut String| {
+			if let Some(sanitized) = renames.get(name.as_str()) {
+				*name = sanitized.clone();
+			}
+		};
+
+		req.tools.iter_mut().for_each(|tool| apply(&mut tool.name));
+		if let ToolChoice::Tool(name) = &mut req.tool_choice {
+			apply(name);
+		}
+		for input in &mut req.input {
+			match input {
+				Input::ToolCall { id, name, .. } => {
+					apply(id);
+					apply(name);
+				}
+				Input::ToolCallOutput { id, .. } => apply(id),
+				Input::Text { .. }
+				| Input::Image { .. }
+				| Input::Document { .. } => {}
+			}
+		}
+
+		let reverse = renames
+			.into_iter()
+			.map(|(original, sanitized)| (sanitized, original))
+			.collect();
+
+		Ok(Some((req, ToolRenames(reverse))))
+	}
+}
+
+/// Appends a counter if `name` is already used by another tool.
+fn unique(
+	name: String,
+	max_len: usize,
+	taken: &HashSet<&str>,
+	renames: &HashMap<String, String>,
+) -> String {
+	let used = |candidate: &str| {
+		taken.contains(candidate) || renames.values().any(|n| n == candidate)
+	};
+
+	if !used(&name) {
+		return name;
+	}
+
+	(2..)
+		.map(|i| {
+			let suffix = format!("_{i}");
+			let base = &name[..name.len().min(max_len - suffix.len())];
+			format!("{base}{suffix}")
+		})
+		.find(|candidate| !used(candidate))
+		.unwrap()
+}
@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+
+use super::{Llms, Request};
+
+/// Adds trace context headers like `traceparent` to every request, so LLM
+/// calls are linked to the surrounding distributed trace, see
+/// [`LlmsConfig::trace_headers`](super::LlmsConfig::trace_headers).
+///
+/// The closure is called from within [`Llms::request`] and should inject
+/// the context of the current span. This crate doesn't depend on
+/// OpenTelemetry, with `tracing-opentelemetry` it could look like this:
+/// ```ignore
+/// TraceHeaders::new(|headers| {
+///     let cx = tracing::Span::current().context();
+///     global::get_text_map_propagator(|propagator| {
+///         propagator.inject_context(&cx, &mut HeaderInjector(headers))
+///     });
+/// })
+/// ```
+#[derive(Clone)]
+pub struct TraceHeaders(Arc<dyn Fn(&mut HeaderMap) + Send + Sync>);
+
+impl TraceHeaders {
+	pub fn new(
+		inject: impl Fn(&mut HeaderMap) + Send + Sync + 'static,
+	) -> Self {
+		Self(Arc::new(inject))
+	}
+}
+
+impl fmt::Debug for TraceHeaders {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TraceHeaders").finish_non_exhaustive()
+	}
+}
+
+impl Llms {
+	/// Adds the trace headers, [`Request::headers`] of the same name take
+	/// precedence.
+	pub(crate) fn with_trace_headers<'a>(
+		&self,
+		req: Cow<'a, Request>,
+	) -> Cow<'a, Request> {
+		let Some(TraceHeaders(inject)) = &self.trace_headers else {
+			return req;
+		};
+
+		let mut headers = HeaderMap::new();
+		inject(&mut headers);
+		if headers.is_empty() {
+			return req;
+		}
+
+		let mut req = req.into_owned();
+		headers.extend(std::mem::take(&mut req.headers));
+		req.headers = headers;
+		Cow::Owned(req)
+	}
+}
@@ -0,0 +1,135 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::{fmt, mem};
+
+use bytes::Bytes;
+use reqwest::{Response, header::SET_COOKIE};
+use tracing::warn;
+
+/// Where the raw SSE bytes of a request are copied to, see
+/// [`Request::transcript`](super::Request::transcript).
+///
+/// Every request starts with a `#` line holding the status and url,
+/// followed by the response headers and the unmodified body, except that
+/// the api key is replaced by `***` wherever it shows up. Clones write to
+/// the same sink, write errors are logged and otherwise ignored.
+///
+/// ```no_run
+/// # use soe_llms::{Request, Transcript};
+/// # fn run(mut req: Request) -> std::io::Result<()> {
+/// req.transcript = Some(Transcript::file("openai.sse")?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Transcript {
+	sink: Arc<Mutex<dyn Write + Send>>,
+}
+
+impl Transcript {
+	pub fn new(sink: impl Write + Send + 'static) -> Self {
+		Self {
+			sink: Arc::new(Mutex::new(sink)),
+		}
+	}
+
+	/// Appends to the file, creating it if it doesn't exist.
+	pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		Ok(Self::new(file))
+	}
+
+	/// Writes the head of the response and returns a tee for the body.
+	pub(crate) fn start(&self, resp: &Response, secret: &str) -> TranscriptTee {
+		let mut head = format!("# {} {}\n", resp.status(), resp.url());
+		for (name, value) in resp.headers() {
+			let value = if name == SET_COOKIE {
+				"***".into()
+			} else {
+				String::from_utf8_lossy(value.as_bytes())
+			};
+			head.push_str(&format!("{name}: {value}\n"));
+		}
+		head.push('\n');
+
+		let tee = TranscriptTee {
+			transcript: self.clone(),
+			secret: secret.into(),
+			partial: Vec::new(),
+		};
+		tee.write(head.as_bytes());
+		tee
+	}
+}
+
+impl fmt::Debug for Transcript {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Transcript").finish_non_exhaustive()
+	}
+}
+
+/// Copies the body of one response into a [`Transcript`].
+///
+/// Only complete lines are written, so a secret split across two chunks is
+/// still redacted.
+pub(crate) struct TranscriptTee {
+	transcript: Transcript,
+	secret: String,
+	/// The bytes after the last newline.
+	partial: Vec<u8>,
+}
+
+impl TranscriptTee {
+	pub fn on_chunk(&mut self, chunk: &Bytes) {
+		self.partial.extend_from_slice(chunk);
+
+		let Some(end) = self.partial.iter().rposition(|b| *b == b'\n') else {
+			return;
+		};
+		let rest = self.partial.split_off(end + 1);
+		let lines = mem::replace(&mut self.partial, rest);
+		self.write(&lines);
+	}
+
+	fn write(&self, bytes: &[u8]) {
+		let redacted = redact(bytes, self.secret.as_bytes());
+		let mut sink = match self.transcript.sink.lock() {
+			Ok(sink) => sink,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+
+		if let Err(e) = sink.write_all(&redacted).and_then(|_| sink.flush()) {
+			warn!("failed to write transcript: {e}");
+		}
+	}
+}
+
+impl Drop for TranscriptTee {
+	fn drop(&mut self) {
+		let mut rest = mem::take(&mut self.partial);
+		// keeps requests apart
+		rest.extend_from_slice(b"\n");
+		self.write(&rest);
+	}
+}
+
+fn redact(bytes: &[u8], secret: &[u8]) -> Vec<u8> {
+	if secret.is_empty() {
+		return bytes.to_vec();
+	}
+
+	let mut out = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i..].starts_with(secret) {
+			out.extend_from_slice(b"***");
+			i += secret.len();
+		} else {
+			out.push(bytes[i]);
+			i += 1;
+		}
+	}
+	out
+}
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash as _, Hasher as _};
+use std::sync::{Arc, RwLock};
+
+use tracing::warn;
+
+use super::{Input, Llms, LlmsError, Model, Request, Role, read, write};
+
+/// Summaries by tool call id, with a hash of the output they summarize so
+/// a reused id doesn't get the summary of another output.
+pub(super) type Summaries = Arc<RwLock<HashMap<String, (u64, String)>>>;
+
+/// Once reached all summaries are dropped, which ones are still needed
+/// isn't tracked.
+const MAX_SUMMARIES: usize = 1024;
+
+/// Caps the size of [`Input::ToolCallOutput`]s, see
+/// [`LlmsConfig::tool_output_limit`].
+///
+/// [`LlmsConfig::tool_output_limit`]: super::LlmsConfig::tool_output_limit
+#[derive(Debug, Clone)]
+pub struct ToolOutputLimit {
+	/// Estimated at about four characters per token.
+	pub max_tokens: u32,
+	pub truncation: Truncation,
+}
+
+/// How an oversized tool output is shortened.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Truncation {
+	/// Keep the beginning.
+	Head,
+	/// Keep the end, e.g. for logs.
+	Tail,
+	/// Keep the beginning and the end.
+	Middle,
+	/// Let this model summarize the output, usually a cheap one. Falls back
+	/// to [`Truncation::Middle`] if the summary fails or is still too long.
+	Summarize(Model),
+}
+
+impl ToolOutputLimit {
+	pub fn new(max_tokens: u32, truncation: Truncation) -> Self {
+		Self {
+			max_tokens,
+			truncation,
+		}
+	}
+
+	fn max_len(&self) -> usize {
+		(self.max_tokens as usize).saturating_mul(4)
+	}
+
+	fn exceeds(&self, req: &Request) -> bool {
+		req.input.iter().any(|input| {
+			matches!(input, Input::ToolCallOutput { output, .. }
+				if output.len() > self.max_len())
+		})
+	}
+}
+
+impl Llms {
+	/// Applies [`LlmsConfig::tool_output_limit`] to the history in place.
+	///
+	/// [`Llms::request`] does this on every call, but only for the request
+	/// being sent. Summaries of [`Truncation::Summarize`] are kept per tool
+	/// call id, shared between clones, so every output is only summarized
+	/// once either way.
+	///
+	/// [`LlmsConfig::tool_output_limit`]: super::LlmsConfig::tool_output_limit
+	pub async fn limit_tool_outputs(&self, req: &mut Request) {
+		let Some(limit) = &self.tool_output_limit else {
+			return;
+		};
+		let max_len = limit.max_len();
+
+		for input in &mut req.input {
+			let Input::ToolCallOutput { id, output } = input else {
+				continue;
+			};
+			if output.len() <= max_len {
+				continue;
+			}
+
+			*output = match &limit.truncation {
+				Truncation::Head => head(output, max_len),
+				Truncation::Tail => tail(output, max_len),
+				Truncation::Middle => middle(output, max_len),
+				Truncation::Summarize(model) => {
+					self.cached_summary(model, id, output, limit).await
+				}
+			};
+		}
+	}
+
+	/// Failed summaries aren't cached, so they are tried again with the next
+	/// request.
+	async fn cached_summary(
+		&self,
+		model: &Model,
+		id: &str,
+		output: &str,
+		limit: &ToolOutputLimit,
+	) -> String {
+		let max_len = limit.max_len();
+		let mut hasher = DefaultHasher::new();
+		output.hash(&mut hasher);
+		let hash = hasher.finish();
+
+		if let Some((_, summary)) =
+			read(&self.summaries).get(id).filter(|(h, _)| *h == hash)
+		{
+			return summary.clone();
+		}
+
+		let summary =
+			match self.summarize(model, output, limit.max_tokens).await {
+				Ok(summary) if summary.len() <= max_len => summary,
+				Ok(summary) => middle(&summary, max_len),
+				Err(e) => {
+					warn!("failed to summarize tool output: {e}");
+					return middle(output, max_len);
+				}
+			};
+
+		let mut summaries = write(&self.summaries);
+		if summaries.len() >= MAX_SUMMARIES {
+			summaries.clear();
+		}
+		summaries.insert(id.into(), (hash, summary.clone()));
+		summary
+	}
+
+	pub(crate) fn needs_tool_output_limit(&self, req: &Request) -> bool {
+		self.tool_output_limit
+			.as_ref()
+			.is_some_and(|limit| limit.exceeds(req))
+	}
+
+	async fn summarize(
+		&self,
+		model: &Model,
+		output: &str,
+		max_tokens: u32,
+	) -> Result<String, LlmsError> {
+		let req = Request {
+			input: vec![Input::Text {
+				role: Role::User,
+				content: output.into(),
+			}],
+			instructions: format!(
+				"Summarize the following tool output in at most {} words. Keep \
+				identifiers, numbers, errors and anything else the caller \
+				could need verbatim. Only respond with the summary.",
+				max_tokens * 3 / 4
+			),
+			model: model.clone(),
+			user_id: String::new(),
+			tools: vec![],
+			tool_choice: Default::default(),
+			reasoning_effort: None,
+			response_format: Default::default(),
+			params: Default::default(),
+			cache: false,
+			extra: Default::default(),
+			headers: Default::default(),
+			timeouts: Default::default(),
+			transcript: None,
+			snapshot: None,
+		};
+
+		// the summary request has no tool outputs, so this doesn't recurse
+		// any further
+		let resp = Box::pin(self.request(&req)).await?.wait().await?;
+		Ok(resp.text())
+	}
+}
+
+fn head(output: &str, max_len: usize) -> String {
+	let end = output.floor_char_boundary(max_len);
+	format!(
+		"{}\n\n[... {} bytes truncated]",
+		&output[..end],
+		output.len() - end
+	)
+}
+
+fn tail(output: &str, max_len: usize) -> String {
+	let start = output.ceil_char_boundary(output.len() - max_len);
+	format!("[{} bytes truncated ...]\n\n{}", start, &output[start..])
+}
+
+fn middle(output: &str, max_len: usize) -> String {
+	let end = output.floor_char_boundary(max_len / 2);
+	let start = output.ceil_char_boundary(output.len() - max_len / 2);
+	format!(
+		"{}\n\n[... {} bytes truncated ...]\n\n{}",
+		&output[..end],
+		start - end,
+		&output[start..]
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keeps_the_head() {
+		assert_eq!(head("abcdef", 4), "abcd\n\n[... 2 bytes truncated]");
+		// "é" is two bytes, it is dropped instead of being split
+		assert_eq!(head("abcéf", 4), "abc\n\n[... 3 bytes truncated]");
+	}
+
+	#[test]
+	fn keeps_the_tail() {
+		assert_eq!(tail("abcdef", 4), "[2 bytes truncated ...]\n\ncdef");
+		assert_eq!(tail("abcéf", 2), "[5 bytes truncated ...]\n\nf");
+	}
+
+	#[test]
+	fn keeps_head_and_tail() {
+		assert_eq!(
+			middle("abcdefgh", 4),
+			"ab\n\n[... 4 bytes truncated ...]\n\ngh"
+		);
+		assert_eq!(
+			middle("aébcdeéa", 4),
+			"a\n\n[... 8 bytes truncated ...]\n\na"
+		);
+		assert_eq!(middle("😀😀😀", 6), "\n\n[... 12 bytes truncated ...]\n\n");
+	}
+
+	#[cfg(feature = "test-utils")]
+	mod mock {
+		use super::*;
+		use crate::llms::{LlmsConfig, tests::request};
+		use crate::mock::{Mock, MockReply};
+
+		fn req(output: &str) -> Request {
+			Request {
+				model: Model::Mock,
+				input: vec![Input::ToolCallOutput {
+					id: "call_1".into(),
+					output: output.into(),
+				}],
+				..request()
+			}
+		}
+
+		fn summarized(mock: &Mock, index: usize) -> String {
+			match &mock.requests()[index].input[..] {
+				[Input::ToolCallOutput { output, .. }] => output.clone(),
+				input => panic!("not a tool output: {input:?}"),
+			}
+		}
+
+		#[tokio::test]
+		async fn summarizes_every_output_once() {
+			let mock = Mock::new();
+			let limit =
+				ToolOutputLimit::new(4, Truncation::Summarize(Model::Mock));
+			let llms = Llms::new(
+				LlmsConfig::new()
+					.mock(mock.clone())
+					.tool_output_limit(limit),
+			);
+
+			mock.push(MockReply::new().text("short"));
+			mock.push(MockReply::new().text("first"));
+			// a clone shares the summaries
+			mock.push(MockReply::new().text("second"));
+			let output = "a very long tool output";
+			llms.request_blocking(&req(output)).await.unwrap();
+			llms.clone().request_blocking(&req(output)).await.unwrap();
+
+			assert_eq!(mock.requests().len(), 3);
+			assert_eq!(summarized(&mock, 1), "short");
+			assert_eq!(summarized(&mock, 2), "short");
+
+			// the same id with another output is summarized again
+			mock.push(MockReply::new().text("other"));
+			mock.push(MockReply::new().text("third"));
+			llms.request_blocking(&req("another long tool output"))
+				.await
+				.unwrap();
+			assert_eq!(summarized(&mock, 4), "other");
+			assert_eq!(mock.remaining(), 0);
+		}
+
+		#[tokio::test]
+		async fn failed_summaries_are_retried() {
+			let mock = Mock::new();
+			let limit =
+				ToolOutputLimit::new(4, Truncation::Summarize(Model::Mock));
+			let llms = Llms::new(
+				LlmsConfig::new()
+					.mock(mock.clone())
+					.tool_output_limit(limit),
+			);
+
+			mock.push_error(LlmsError::Cancelled);
+			mock.push(MockReply::new().text("first"));
+			mock.push(MockReply::new().text("short"));
+			mock.push(MockReply::new().text("second"));
+			let output = "a very long tool output";
+			llms.request_blocking(&req(output)).await.unwrap();
+			llms.request_blocking(&req(output)).await.unwrap();
+
+			assert_eq!(summarized(&mock, 1), middle(output, 16));
+			assert_eq!(summarized(&mock, 3), "short");
+		}
+	}
+}
@@ -1,69 +1,141 @@
-use std::fmt;
+pub use crate::llms::chat::{
+	ApiContentPart, ApiImageUrl, ApiMessage, ApiToolCall, ApiToolCallFunction,
+	ApiUserContent,
+};
+
+use std::{collections::VecDeque, fmt, time::Duration};
 
-use reqwest::{Client, StatusCode};
+use reqwest::{Client, StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tracing::{debug, trace};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
+/// Default of [`Mistral::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://api.mistral.ai/v1";
+
 #[derive(Clone)]
 pub struct Mistral {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/mistral/v1"`.
+	pub base_url: String,
 }
 
 impl Mistral {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
 		}
 	}
 
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), MistralError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url("models"))
+			.bearer_auth(&*api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+
 	pub async fn request(
 		&self,
 		req: &Request,
 	) -> Result<ResponseStream, MistralError> {
-		#[derive(Debug, Serialize)]
-		struct ApiReq<'a> {
-			model: &'a str,
-			messages: &'a Vec<ApiMessage>,
-			#[serde(skip_serializing_if = "Vec::is_empty")]
-			tools: &'a Vec<ApiTool>,
-			stream: bool,
-		}
-
 		let api_req = ApiReq {
-			model: req.model.as_str(),
-			messages: &req.messages,
-			tools: &req.tools,
-			stream: true,
+			model: Some(req.model_id()),
+			stream: Some(true),
+			..req.api_req()
 		};
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://api.mistral.ai/v1/chat/completions")
-			.bearer_auth(&self.api_key)
-			.json(&api_req)
-			.send()
-			.await?;
+			.post(self.url("chat/completions"))
+			.bearer_auth(&*api_key)
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(MistralError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream {
+			inner: SseResponse::new(
+				resp,
+				req.transcript.as_ref(),
+				&api_key,
+				req.read_timeout,
+			),
+			acc: ChunkAccumulator::new(llms::ProviderKind::Mistral),
+		})
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
 	}
 }
 
@@ -80,18 +152,28 @@ impl LlmProvider for Mistral {
 		&self,
 		req: &llms::Request,
 	) -> Result<Self::Stream, LlmsError> {
-		if req.reasoning_effort.is_some() {
-			debug!("reasoning_effort is ignored for Mistral");
-		}
-
 		let model = match &req.model {
 			llms::Model::MistralLarge => MistralModel::Large,
 			llms::Model::MistralMedium => MistralModel::Medium,
 			llms::Model::MistralSmall => MistralModel::Small,
 			llms::Model::Ministral14b => MistralModel::Ministral14b,
+			llms::Model::Custom { id, .. } => MistralModel::Custom(id.clone()),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
+		self.request(&Request::from_llms(req, model))
+			.await
+			.map_err(Into::into)
+	}
+}
+
+impl Request {
+	/// Translates `req` for `model`, see [`LlmProvider::request`].
+	pub(crate) fn from_llms(req: &llms::Request, model: MistralModel) -> Self {
+		if req.reasoning_effort.is_some() {
+			debug!("reasoning_effort is ignored for Mistral");
+		}
+
 		let mut messages: Vec<ApiMessage> = Vec::new();
 
 		if !req.instructions.is_empty() {
@@ -100,108 +182,201 @@ impl LlmProvider for Mistral {
 			});
 		}
 
-		messages.extend(req.input.iter().cloned().map(ApiMessage::from));
+		messages.extend(ApiMessage::from_history(&req.input));
 
-		self.request(&Request {
+		Request {
 			messages,
 			model,
+			snapshot: req.snapshot.clone(),
 			tools: req.tools.iter().cloned().map(Into::into).collect(),
-		})
-		.await
-		.map_err(Into::into)
+			tool_choice: req.tool_choice.clone().into(),
+			response_format: req.response_format.clone().into(),
+
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_tokens: req.params.max_tokens,
+			stop: req.params.stop.clone().unwrap_or_default(),
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
+		}
+	}
+
+	pub(crate) fn model_id(&self) -> &str {
+		self.snapshot.as_deref().unwrap_or(self.model.as_str())
+	}
+
+	/// The body without the model and `stream`, which Bedrock takes from
+	/// the url.
+	pub(crate) fn api_req(&self) -> ApiReq<'_> {
+		ApiReq {
+			model: None,
+			messages: &self.messages,
+			tools: &self.tools,
+			tool_choice: self
+				.tool_choice
+				.as_ref()
+				.filter(|_| !self.tools.is_empty()),
+			response_format: self.response_format.as_ref(),
+			temperature: self.temperature,
+			top_p: self.top_p,
+			max_tokens: self.max_tokens,
+			stop: &self.stop,
+			stream: None,
+		}
 	}
 }
 
+/// The body of a chat completion request.
+#[derive(Debug, Serialize)]
+pub(crate) struct ApiReq<'a> {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub model: Option<&'a str>,
+	messages: &'a Vec<ApiMessage>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	tools: &'a Vec<ApiTool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	tool_choice: Option<&'a ToolChoice>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	response_format: Option<&'a ResponseFormat>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	temperature: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	top_p: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	max_tokens: Option<u32>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	stop: &'a Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stream: Option<bool>,
+}
+
+#[derive(Debug)]
 pub struct Request {
 	pub messages: Vec<ApiMessage>,
 	pub model: MistralModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	pub snapshot: Option<String>,
 	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// `None` lets the model answer with plain text.
+	pub response_format: Option<ResponseFormat>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_tokens: Option<u32>,
+	pub stop: Vec<String>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
+}
+
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		function: ToolChoiceFunction,
+	},
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum MistralModel {
-	Large,
-	Medium,
-	Small,
-	Ministral14b,
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+	None,
+	Required,
 }
 
-impl MistralModel {
-	pub fn as_str(&self) -> &'static str {
-		match self {
-			MistralModel::Large => "mistral-large-latest",
-			MistralModel::Medium => "mistral-medium-latest",
-			MistralModel::Small => "mistral-small-latest",
-			MistralModel::Ministral14b => "ministral-14b-latest",
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolChoiceFunction {
+	pub name: String,
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				function: ToolChoiceFunction { name },
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "role", rename_all = "lowercase")]
-pub enum ApiMessage {
-	System {
-		content: String,
-	},
-	User {
-		content: String,
-	},
-	Assistant {
-		#[serde(skip_serializing_if = "Option::is_none")]
-		content: Option<String>,
-		#[serde(skip_serializing_if = "Option::is_none")]
-		tool_calls: Option<Vec<ApiToolCall>>,
-	},
-	Tool {
-		tool_call_id: String,
-		content: String,
-	},
+/// OpenAI-style `response_format`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+	Text,
+	JsonObject,
+	JsonSchema { json_schema: JsonSchema },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSchema {
+	pub name: String,
+	pub schema: Value,
+	pub strict: bool,
 }
 
-impl From<llms::Input> for ApiMessage {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => match role {
-				llms::Role::User => ApiMessage::User { content },
-				llms::Role::Assistant => ApiMessage::Assistant {
-					content: Some(content),
-					tool_calls: None,
+impl From<llms::ResponseFormat> for Option<ResponseFormat> {
+	fn from(format: llms::ResponseFormat) -> Self {
+		match format {
+			llms::ResponseFormat::Text => None,
+			llms::ResponseFormat::JsonObject => {
+				Some(ResponseFormat::JsonObject)
+			}
+			llms::ResponseFormat::JsonSchema {
+				name,
+				schema,
+				strict,
+			} => Some(ResponseFormat::JsonSchema {
+				json_schema: JsonSchema {
+					name,
+					schema,
+					strict,
 				},
-			},
-			llms::Input::ToolCall {
-				id, name, input, ..
-			} => ApiMessage::Assistant {
-				content: None,
-				tool_calls: Some(vec![ApiToolCall {
-					id,
-					kind: "function".into(),
-					function: ApiToolCallFunction {
-						name,
-						arguments: input.to_string(),
-					},
-				}]),
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiMessage::Tool {
-				tool_call_id: id,
-				content: output,
-			},
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCall {
-	pub id: String,
-	#[serde(rename = "type")]
-	pub kind: String,
-	pub function: ApiToolCallFunction,
+#[derive(Debug, Clone)]
+pub enum MistralModel {
+	Large,
+	Medium,
+	Small,
+	Ministral14b,
+	/// Any other model id, see [`llms::Model::Custom`].
+	Custom(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCallFunction {
-	pub name: String,
-	/// JSON-encoded arguments string.
-	pub arguments: String,
+impl MistralModel {
+	pub fn as_str(&self) -> &str {
+		match self {
+			MistralModel::Large => "mistral-large-latest",
+			MistralModel::Medium => "mistral-medium-latest",
+			MistralModel::Small => "mistral-small-latest",
+			MistralModel::Ministral14b => "ministral-14b-latest",
+			MistralModel::Custom(id) => id,
+		}
+	}
 }
 
 #[derive(Debug, Serialize)]
@@ -262,11 +437,16 @@ pub struct ApiUsage {
 #[derive(Debug, Deserialize)]
 pub struct ChunkChoice {
 	pub delta: Delta,
+	/// Set on the last chunk of the choice.
+	pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Delta {
 	pub content: Option<DeltaContent>,
+	/// Reasoning text of OpenAI-compatible backends with a reasoning
+	/// parser, e.g. DeepSeek, Qwen or vLLM.
+	pub reasoning_content: Option<String>,
 	pub tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
@@ -292,21 +472,30 @@ pub struct ThinkingBlock {
 }
 
 impl DeltaContent {
-	/// Extracts the text, ignoring `"thinking"` and any other non-text blocks.
-	/// Returns `None` if the result is empty.
-	pub fn into_text(self) -> Option<String> {
-		let text = match self {
-			DeltaContent::Text(s) => s,
-			DeltaContent::Blocks(blocks) => blocks
-				.into_iter()
-				.filter_map(|b| match b {
-					ContentBlock::Text { text } => Some(text),
-					ContentBlock::Thinking { .. } => None,
-				})
-				.collect(),
+	/// Splits into the text and the `"thinking"` text, both `None` if
+	/// empty.
+	pub fn into_parts(self) -> (Option<String>, Option<String>) {
+		let (text, thinking) = match self {
+			DeltaContent::Text(s) => (s, String::new()),
+			DeltaContent::Blocks(blocks) => {
+				let mut text = String::new();
+				let mut thinking = String::new();
+				for block in blocks {
+					match block {
+						ContentBlock::Text { text: t } => text.push_str(&t),
+						ContentBlock::Thinking { thinking: t } => {
+							t.iter().for_each(|b| thinking.push_str(&b.text))
+						}
+					}
+				}
+				(text, thinking)
+			}
 		};
 
-		Some(text).filter(|t| !t.is_empty())
+		(
+			Some(text).filter(|t| !t.is_empty()),
+			Some(thinking).filter(|t| !t.is_empty()),
+		)
 	}
 }
 
@@ -332,9 +521,30 @@ pub enum MistralError {
 	#[error("No output in response")]
 	NoOutput,
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for MistralError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<MistralError> for LlmsError {
@@ -343,15 +553,29 @@ impl From<MistralError> for LlmsError {
 			MistralError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::Mistral,
+				request_id: None,
 			},
 			MistralError::NoOutput => LlmsError::Response {
 				status: StatusCode::OK,
 				body: "no output in response".into(),
+				provider: llms::ProviderKind::Mistral,
+				request_id: None,
 			},
-			MistralError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			MistralError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::Mistral,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			MistralError::ReqwestError(e) => LlmsError::Reqwest(e),
+			MistralError::Credential(e) => LlmsError::Credential(e),
 		}
 	}
 }
@@ -370,42 +594,124 @@ struct ToolCallAccumulator {
 	id: String,
 	name: String,
 	arguments: String,
+	/// Whether [`llms::LlmResponseEvent::ToolCallStarted`] was emitted.
+	started: bool,
 }
 
 pub struct ResponseStream {
 	inner: SseResponse,
-	/// Accumulated text across all content deltas. `None` until the first
-	/// non-empty content delta arrives.
-	text: Option<String>,
-	/// Per-index tool call state. The index matches the `index` field in the
-	/// streaming delta and grows on demand.
-	tool_calls: Vec<ToolCallAccumulator>,
-	/// Token usage reported by Mistral on the final chunk. `None` until that
-	/// chunk arrives.
-	usage: Option<llms::Usage>,
-	done: bool,
+	acc: ChunkAccumulator,
 }
 
 impl std::fmt::Debug for ResponseStream {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("ResponseStream")
-			.field("done", &self.done)
+			.field("done", &self.acc.done)
 			.finish()
 	}
 }
 
 impl ResponseStream {
-	fn new(inner: SseResponse) -> Self {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+}
+
+impl LlmResponseStream for ResponseStream {
+	async fn next(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		loop {
+			if let Some(ev) = self.acc.next_pending() {
+				return Some(ev);
+			}
+			if self.acc.done {
+				return None;
+			}
+
+			let chunk: Chunk = match self.inner.next().await {
+				Some(Ok(c)) => c,
+				Some(Err(e)) => return Some(Err(e.into())),
+				None => return self.acc.finish(),
+			};
+
+			trace!("mistral chunk: {chunk:?}");
+			self.acc.push(chunk, self.inner.request_id());
+		}
+	}
+}
+
+/// Builds the response from the chunks of a chat completion stream, also
+/// used for the Mistral models on Bedrock.
+pub(crate) struct ChunkAccumulator {
+	/// Reported as the provider of errors.
+	provider: llms::ProviderKind,
+	/// Accumulated text across all content deltas. `None` until the first
+	/// non-empty content delta arrives.
+	text: Option<String>,
+	/// Accumulated thinking of Magistral models or `reasoning_content`,
+	/// same as `text`.
+	reasoning: Option<String>,
+	/// Per-index tool call state. The index matches the `index` field in the
+	/// streaming delta and grows on demand.
+	tool_calls: Vec<ToolCallAccumulator>,
+	/// Events of the last chunk not returned yet, a chunk can hold
+	/// several tool call deltas and text.
+	pending: VecDeque<Result<llms::LlmResponseEvent, LlmsError>>,
+	/// Token usage reported by Mistral on the final chunk. `None` until that
+	/// chunk arrives.
+	pub usage: Option<llms::Usage>,
+	/// Of the last chunk that had one.
+	finish_reason: Option<String>,
+	/// Set after the response or an error, later chunks are ignored.
+	pub done: bool,
+}
+
+impl ChunkAccumulator {
+	pub fn new(provider: llms::ProviderKind) -> Self {
 		Self {
-			inner,
+			provider,
 			text: None,
+			reasoning: None,
 			tool_calls: Vec::new(),
+			pending: VecDeque::new(),
 			usage: None,
+			finish_reason: None,
 			done: false,
 		}
 	}
 
-	fn build_response(&mut self) -> Result<llms::Response, MistralError> {
+	fn invalid(&self, msg: String) -> LlmsError {
+		LlmsError::Response {
+			status: StatusCode::OK,
+			body: msg,
+			provider: self.provider,
+			request_id: None,
+		}
+	}
+
+	/// The next event of the chunks pushed so far.
+	pub fn next_pending(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		self.pending.pop_front()
+	}
+
+	/// Builds the response once the stream ended, `None` if it already was
+	/// or an error ended it.
+	pub fn finish(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		if self.done {
+			return None;
+		}
+
+		self.done = true;
+		Some(self.build_response().map(llms::LlmResponseEvent::Completed))
+	}
+
+	fn build_response(&mut self) -> Result<llms::Response, LlmsError> {
 		let mut output =
 			Vec::with_capacity(self.tool_calls.len() + 1 /* text */);
 
@@ -413,9 +719,9 @@ impl ResponseStream {
 			output.push(llms::Output::Text { content: text });
 		}
 
-		for tc in self.tool_calls.drain(..) {
+		for tc in std::mem::take(&mut self.tool_calls) {
 			let input = serde_json::from_str(&tc.arguments).map_err(|e| {
-				MistralError::InvalidLlmResponse(format!(
+				self.invalid(format!(
 					"invalid tool call arguments JSON for '{}': {e}",
 					tc.name
 				))
@@ -430,93 +736,138 @@ impl ResponseStream {
 		}
 
 		if output.is_empty() {
-			return Err(MistralError::NoOutput);
+			return Err(self.invalid("no output in response".into()));
 		}
 
-		let usage = self.usage.take().ok_or_else(|| {
-			MistralError::InvalidLlmResponse("missing usage in response".into())
-		})?;
+		// the model reasoned before answering
+		if let Some(reasoning) = &self.reasoning {
+			output.insert(
+				0,
+				llms::Output::Reasoning {
+					content: reasoning.clone(),
+				},
+			);
+		}
 
-		Ok(llms::Response { output, usage })
+		let usage = self
+			.usage
+			.take()
+			.ok_or_else(|| self.invalid("missing usage in response".into()))?;
+
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason: self
+				.finish_reason
+				.take()
+				.map(llms::FinishReason::from_chat),
+			reasoning: self.reasoning.take(),
+			..Default::default()
+		})
 	}
-}
 
-impl LlmResponseStream for ResponseStream {
-	async fn next(
-		&mut self,
-	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+	/// Queues the events of `chunk`, see [`ChunkAccumulator::next_pending`].
+	pub fn push(&mut self, chunk: Chunk, request_id: Option<&str>) {
 		if self.done {
-			return None;
+			return;
 		}
 
-		loop {
-			let chunk: Chunk = match self.inner.next().await {
-				Some(Ok(c)) => c,
-				Some(Err(e)) => return Some(Err(e.into())),
-				None => {
-					self.done = true;
-					let response = self
-						.build_response()
-						.map(llms::LlmResponseEvent::Completed)
-						.map_err(Into::into);
-					return Some(response);
-				}
-			};
+		if let Some(err) = chunk.error {
+			self.done = true;
+			self.pending.push_back(Err(LlmsError::api(
+				self.provider,
+				StatusCode::OK,
+				&err.message,
+				request_id.map(Into::into),
+				None,
+			)));
+			return;
+		}
 
-			trace!("mistral chunk: {chunk:?}");
+		if let Some(usage) = chunk.usage {
+			self.usage = Some(llms::Usage {
+				input_tokens: usage.prompt_tokens,
+				output_tokens: usage.completion_tokens,
+				..Default::default()
+			});
+		}
 
-			if let Some(err) = chunk.error {
-				self.done = true;
-				return Some(Err(MistralError::ResponseError {
-					status: StatusCode::OK,
-					body: err.message,
-				}
-				.into()));
-			}
+		let Some(choice) = chunk.choices.into_iter().next() else {
+			return;
+		};
 
-			if let Some(usage) = chunk.usage {
-				self.usage = Some(llms::Usage {
-					input_tokens: usage.prompt_tokens,
-					output_tokens: usage.completion_tokens,
-				});
-			}
+		if choice.finish_reason.is_some() {
+			self.finish_reason = choice.finish_reason;
+		}
 
-			let choice = match chunk.choices.into_iter().next() {
-				Some(c) => c,
-				None => continue,
-			};
+		let (text, thinking) = choice
+			.delta
+			.content
+			.map(DeltaContent::into_parts)
+			.unwrap_or_default();
+
+		let reasoning =
+			choice.delta.reasoning_content.filter(|r| !r.is_empty());
+
+		for thinking in thinking.into_iter().chain(reasoning) {
+			self.reasoning
+				.get_or_insert_with(String::new)
+				.push_str(&thinking);
+			self.pending.push_back(Ok(
+				llms::LlmResponseEvent::ReasoningDelta { content: thinking },
+			));
+		}
+
+		if let Some(text) = text {
+			self.text.get_or_insert_with(String::new).push_str(&text);
+			self.pending
+				.push_back(Ok(llms::LlmResponseEvent::TextDelta {
+					content: text,
+				}));
+		}
 
-			if let Some(tc_deltas) = choice.delta.tool_calls {
-				for delta in tc_deltas {
-					// Grow the accumulator vec on demand (indices are always
-					// contiguous and arrive in order per the spec).
-					self.tool_calls
-						.resize_with(delta.index + 1, Default::default);
+		for delta in choice.delta.tool_calls.into_iter().flatten() {
+			// Grow the accumulator vec on demand (indices are always
+			// contiguous and arrive in order per the spec).
+			self.tool_calls
+				.resize_with(delta.index + 1, Default::default);
 
-					let acc = &mut self.tool_calls[delta.index];
+			let acc = &mut self.tool_calls[delta.index];
 
-					if let Some(id) = delta.id {
-						acc.id = id;
-					}
+			if let Some(id) = delta.id {
+				acc.id = id;
+			}
 
-					if let Some(func) = delta.function {
-						if let Some(name) = func.name {
-							acc.name = name;
-						}
-						if let Some(args) = func.arguments {
-							acc.arguments.push_str(&args);
-						}
-					}
+			let mut args_delta = String::new();
+			if let Some(func) = delta.function {
+				if let Some(name) = func.name {
+					acc.name = name;
+				}
+				if let Some(args) = func.arguments {
+					acc.arguments.push_str(&args);
+					args_delta = args;
 				}
 			}
 
-			if let Some(text) =
-				choice.delta.content.and_then(DeltaContent::into_text)
-			{
-				self.text.get_or_insert_with(String::new).push_str(&text);
-				return Some(Ok(llms::LlmResponseEvent::TextDelta {
-					content: text,
-				}));
+			// the id and name usually come with the first delta
+			if !acc.started && !acc.id.is_empty() && !acc.name.is_empty() {
+				acc.started = true;
+				self.pending.push_back(Ok(
+					llms::LlmResponseEvent::ToolCallStarted {
+						id: acc.id.clone(),
+						name: acc.name.clone(),
+					},
+				));
+				// arguments received before the name
+				args_delta = acc.arguments.clone();
+			}
+			if acc.started && !args_delta.is_empty() {
+				self.pending.push_back(Ok(
+					llms::LlmResponseEvent::ToolCallArgumentsDelta {
+						id: acc.id.clone(),
+						delta: args_delta,
+					},
+				));
 			}
 		}
 	}
@@ -0,0 +1,275 @@
+//! A scripted provider for testing code built on [`Llms`](crate::Llms),
+//! e.g. agent loops, without any network access.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde_json::Value;
+
+use crate::llms::{
+	self, LlmProvider, LlmResponseEvent, LlmResponseStream, LlmsError,
+};
+
+/// Answers every request to [`llms::Model::Mock`] with the next queued
+/// [`MockReply`], see [`LlmsConfig::mock`](llms::LlmsConfig::mock).
+///
+/// Clones share the queue, so a test keeps one to push replies and to
+/// check the requests that were made. A request without a queued reply
+/// panics.
+///
+/// ```
+/// # use serde_json::json;
+/// # use soe_llms::mock::{Mock, MockReply};
+/// # use soe_llms::{Llms, LlmsConfig, Model, Request};
+/// # async fn run(mut req: Request) -> Result<(), soe_llms::LlmsError> {
+/// let mock = Mock::new();
+/// mock.push(
+///     MockReply::new()
+///         .text("Let me look that up.")
+///         .tool_call("call_1", "search", json!({ "query": "rust" })),
+/// );
+/// mock.push(MockReply::new().text("Rust is a programming language."));
+///
+/// let llms = Llms::new(LlmsConfig::new().mock(mock.clone()));
+/// req.model = Model::Mock;
+/// let resp = llms.request_blocking(&req).await?;
+///
+/// assert_eq!(mock.requests().len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Mock {
+	state: Arc<Mutex<MockState>>,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+	replies: VecDeque<Result<MockReply, LlmsError>>,
+	requests: Vec<llms::Request>,
+}
+
+impl Mock {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Queues the reply to the next request.
+	pub fn push(&self, reply: MockReply) {
+		self.state().replies.push_back(Ok(reply));
+	}
+
+	/// Fails the next request with `error` before anything is streamed,
+	/// like a provider rejecting it.
+	pub fn push_error(&self, error: LlmsError) {
+		self.state().replies.push_back(Err(error));
+	}
+
+	/// Every request received so far, as sent to the provider, so with
+	/// defaults applied and tools renamed.
+	pub fn requests(&self) -> Vec<llms::Request> {
+		self.state().requests.clone()
+	}
+
+	/// Number of queued replies not used yet.
+	pub fn remaining(&self) -> usize {
+		self.state().replies.len()
+	}
+
+	fn state(&self) -> MutexGuard<'_, MockState> {
+		match self.state.lock() {
+			Ok(state) => state,
+			Err(poisoned) => poisoned.into_inner(),
+		}
+	}
+}
+
+impl LlmProvider for Mock {
+	type Stream = ResponseStream;
+
+	async fn request(
+		&self,
+		req: &llms::Request,
+	) -> Result<Self::Stream, LlmsError> {
+		let mut state = self.state();
+		state.requests.push(req.clone());
+
+		let reply = state.replies.pop_front().unwrap_or_else(|| {
+			panic!("mock got a request without a queued reply: {req:?}")
+		})?;
+
+		Ok(ResponseStream::new(reply))
+	}
+}
+
+/// A scripted response, streamed in the order the parts were added.
+///
+/// Every [`MockReply::text`] is one text delta, consecutive ones form a
+/// single [`llms::Output::Text`].
+#[derive(Debug, Default)]
+pub struct MockReply {
+	parts: Vec<MockPart>,
+	usage: llms::Usage,
+	finish_reason: Option<llms::FinishReason>,
+}
+
+#[derive(Debug)]
+enum MockPart {
+	Text(String),
+	Reasoning(String),
+	ToolCall {
+		id: String,
+		name: String,
+		input: Value,
+	},
+	Error(LlmsError),
+}
+
+impl MockReply {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn text(mut self, content: impl Into<String>) -> Self {
+		self.parts.push(MockPart::Text(content.into()));
+		self
+	}
+
+	pub fn reasoning(mut self, content: impl Into<String>) -> Self {
+		self.parts.push(MockPart::Reasoning(content.into()));
+		self
+	}
+
+	/// Streamed as the start of the call followed by a single arguments
+	/// delta.
+	pub fn tool_call(
+		mut self,
+		id: impl Into<String>,
+		name: impl Into<String>,
+		input: Value,
+	) -> Self {
+		self.parts.push(MockPart::ToolCall {
+			id: id.into(),
+			name: name.into(),
+			input,
+		});
+		self
+	}
+
+	/// Fails the stream once the parts before it were streamed, the parts
+	/// after it are never reached.
+	pub fn error(mut self, error: LlmsError) -> Self {
+		self.parts.push(MockPart::Error(error));
+		self
+	}
+
+	/// Zero by default.
+	pub fn usage(mut self, usage: llms::Usage) -> Self {
+		self.usage = usage;
+		self
+	}
+
+	/// Defaults to [`llms::FinishReason::ToolCalls`] if the reply has tool
+	/// calls, [`llms::FinishReason::Stop`] otherwise.
+	pub fn finish_reason(mut self, reason: llms::FinishReason) -> Self {
+		self.finish_reason = Some(reason);
+		self
+	}
+}
+
+pub struct ResponseStream {
+	events: VecDeque<Result<LlmResponseEvent, LlmsError>>,
+}
+
+impl std::fmt::Debug for ResponseStream {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ResponseStream")
+			.field("remaining", &self.events.len())
+			.finish()
+	}
+}
+
+impl ResponseStream {
+	/// Always `None`.
+	pub fn request_id(&self) -> Option<&str> {
+		None
+	}
+
+	fn new(reply: MockReply) -> Self {
+		let mut events = VecDeque::new();
+		let mut output = Vec::new();
+		let mut reasoning: Vec<String> = Vec::new();
+		let mut has_tool_calls = false;
+
+		for part in reply.parts {
+			match part {
+				MockPart::Text(content) => {
+					match output.last_mut() {
+						Some(llms::Output::Text { content: text }) => {
+							text.push_str(&content)
+						}
+						_ => output.push(llms::Output::Text {
+							content: content.clone(),
+						}),
+					}
+					events
+						.push_back(Ok(LlmResponseEvent::TextDelta { content }));
+				}
+				MockPart::Reasoning(content) => {
+					reasoning.push(content.clone());
+					output.push(llms::Output::Reasoning {
+						content: content.clone(),
+					});
+					events.push_back(Ok(LlmResponseEvent::ReasoningDelta {
+						content,
+					}));
+				}
+				MockPart::ToolCall { id, name, input } => {
+					has_tool_calls = true;
+					events.push_back(Ok(LlmResponseEvent::ToolCallStarted {
+						id: id.clone(),
+						name: name.clone(),
+					}));
+					events.push_back(Ok(
+						LlmResponseEvent::ToolCallArgumentsDelta {
+							id: id.clone(),
+							delta: input.to_string(),
+						},
+					));
+					output.push(llms::Output::ToolCall {
+						id,
+						name,
+						input,
+						context: None,
+					});
+				}
+				MockPart::Error(e) => {
+					events.push_back(Err(e));
+					return Self { events };
+				}
+			}
+		}
+
+		let finish_reason = reply.finish_reason.unwrap_or(if has_tool_calls {
+			llms::FinishReason::ToolCalls
+		} else {
+			llms::FinishReason::Stop
+		});
+
+		events.push_back(Ok(LlmResponseEvent::Completed(llms::Response {
+			output,
+			usage: reply.usage,
+			finish_reason: Some(finish_reason),
+			reasoning: Some(reasoning.join("\n\n")).filter(|r| !r.is_empty()),
+			..Default::default()
+		})));
+
+		Self { events }
+	}
+}
+
+impl LlmResponseStream for ResponseStream {
+	async fn next(&mut self) -> Option<Result<LlmResponseEvent, LlmsError>> {
+		self.events.pop_front()
+	}
+}
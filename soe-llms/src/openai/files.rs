@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use futures::Stream;
+use reqwest::multipart::Form;
+use serde::Deserialize;
+
+use super::{OpenAi, OpenAiError};
+use crate::utils::error_response;
+use crate::utils::multipart::file_part;
+use crate::utils::paginate::{Page, paginate};
+
+/// What an uploaded file is going to be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FilePurpose {
+	Assistants,
+	Batch,
+	FineTune,
+	Vision,
+	UserData,
+	Evals,
+}
+
+impl FilePurpose {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Assistants => "assistants",
+			Self::Batch => "batch",
+			Self::FineTune => "fine-tune",
+			Self::Vision => "vision",
+			Self::UserData => "user_data",
+			Self::Evals => "evals",
+		}
+	}
+}
+
+/// A file uploaded through the OpenAI Files API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenAiFile {
+	pub id: String,
+	/// Size in bytes.
+	pub bytes: u64,
+	/// Unix timestamp in seconds.
+	pub created_at: u64,
+	pub filename: String,
+	pub purpose: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileList {
+	data: Vec<OpenAiFile>,
+	#[serde(default)]
+	has_more: bool,
+}
+
+impl OpenAi {
+	/// Uploads the file at `path`, streaming it from disk.
+	pub async fn upload_file(
+		&self,
+		path: impl AsRef<Path>,
+		mime_type: &str,
+		purpose: FilePurpose,
+	) -> Result<OpenAiFile, OpenAiError> {
+		let form = Form::new()
+			.text("purpose", purpose.as_str())
+			.part("file", file_part(path.as_ref(), mime_type).await?);
+
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
+			.client
+			.post(self.url("files"))
+			.bearer_auth(&*api_key)
+			.multipart(form);
+		let resp = self.account_headers(builder).send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(resp.json().await?)
+	}
+
+	/// Lists all uploaded files, the next page is fetched while the stream
+	/// is polled.
+	pub fn list_files(
+		&self,
+	) -> impl Stream<Item = Result<OpenAiFile, OpenAiError>> + '_ {
+		paginate(move |after| self.files_page(after))
+	}
+
+	async fn files_page(
+		&self,
+		after: Option<String>,
+	) -> Result<Page<OpenAiFile>, OpenAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let builder = self.client.get(self.url("files")).bearer_auth(&*api_key);
+		let mut req = self.account_headers(builder).build()?;
+		req.url_mut()
+			.query_pairs_mut()
+			.append_pair("limit", "10000");
+		if let Some(after) = &after {
+			req.url_mut().query_pairs_mut().append_pair("after", after);
+		}
+
+		let resp = self.client.execute(req).await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let list: FileList = resp.json().await?;
+		let next = list
+			.has_more
+			.then(|| list.data.last().map(|file| file.id.clone()))
+			.flatten();
+
+		Ok(Page {
+			items: list.data,
+			next,
+		})
+	}
+
+	/// Deletes an uploaded file by its id.
+	pub async fn delete_file(&self, id: &str) -> Result<(), OpenAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
+			.client
+			.delete(self.url(&format!("files/{id}")))
+			.bearer_auth(&*api_key);
+		let resp = self.account_headers(builder).send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+}
@@ -1,32 +1,119 @@
-use std::fmt;
+mod files;
+mod schema;
+mod usage;
+
+pub use files::{FilePurpose, OpenAiFile};
+pub use schema::{SchemaError, to_strict_schema};
+pub use usage::{
+	Bucket, BucketWidth, CompletionsUsage, Cost, CostAmount, UsageQuery,
+};
+
+use std::{collections::HashMap, fmt, io, time::Duration};
 
 use reqwest::{
-	Client, StatusCode,
-	header::{ACCEPT, HeaderValue},
+	Client, RequestBuilder, StatusCode,
+	header::{ACCEPT, HeaderMap, HeaderValue},
 };
 use serde::{Deserialize, Serialize};
-use tracing::{trace, warn};
+use tracing::{debug, trace, warn};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
+/// Default of [`OpenAi::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
 #[derive(Clone)]
 pub struct OpenAi {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/openai/v1"`.
+	pub base_url: String,
+	/// Sent as `OpenAI-Organization`, for keys belonging to more than one
+	/// organization.
+	pub organization: Option<String>,
+	/// Sent as `OpenAI-Project`, usage is attributed to this project.
+	///
+	/// Both can be overridden per request through [`Request::headers`].
+	pub project: Option<String>,
 }
 
 impl OpenAi {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+			organization: None,
+			project: None,
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	pub fn organization(mut self, id: impl Into<String>) -> Self {
+		self.organization = Some(id.into());
+		self
+	}
+
+	pub fn project(mut self, id: impl Into<String>) -> Self {
+		self.project = Some(id.into());
+		self
+	}
+
+	/// Adds the organization and project headers if set.
+	fn account_headers(&self, mut builder: RequestBuilder) -> RequestBuilder {
+		if let Some(organization) = &self.organization {
+			builder = builder.header("OpenAI-Organization", organization);
+		}
+		if let Some(project) = &self.project {
+			builder = builder.header("OpenAI-Project", project);
+		}
+		builder
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), OpenAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let builder =
+			self.client.get(self.url("models")).bearer_auth(&*api_key);
+		let resp = self.account_headers(builder).send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
 		}
+
+		Ok(())
 	}
 
 	pub async fn request(
@@ -37,45 +124,120 @@ impl OpenAi {
 		struct Req<'a> {
 			input: &'a Vec<Input>,
 			instructions: &'a String,
-			model: OpenAiModel,
+			model: &'a str,
 			prompt_cache_key: &'a String,
 			safety_identifier: &'a String,
 			tools: &'a Vec<Tool>,
 			#[serde(skip_serializing_if = "Option::is_none")]
+			tool_choice: Option<&'a ToolChoice>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			text: Option<Text<'a>>,
+			#[serde(skip_serializing_if = "Option::is_none")]
 			reasoning: Option<Reasoning>,
+			store: bool,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			include: &'a Vec<Include>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			temperature: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			top_p: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			max_output_tokens: Option<u32>,
 			stream: bool,
 		}
 
+		#[derive(Debug, Serialize)]
+		struct Text<'a> {
+			format: &'a TextFormat,
+		}
+
+		let extra = &req.extra;
+		let headers = &req.headers;
+		let transcript = req.transcript.as_ref();
+		let read_timeout = req.read_timeout;
 		let req = Req {
 			input: &req.input,
 			instructions: &req.instructions,
-			model: req.model,
+			model: req.snapshot.as_deref().unwrap_or(req.model.as_str()),
 			prompt_cache_key: &req.prompt_cache_key,
 			safety_identifier: &req.safety_identifier,
 			tools: &req.tools,
-			reasoning: req.reasoning_effort.map(|e| Reasoning { effort: e }),
+			tool_choice: req
+				.tool_choice
+				.as_ref()
+				.filter(|_| !req.tools.is_empty()),
+			text: req.text_format.as_ref().map(|format| Text { format }),
+			reasoning: (req.reasoning_effort.is_some()
+				|| req.reasoning_summary.is_some())
+			.then_some(Reasoning {
+				effort: req.reasoning_effort,
+				summary: req.reasoning_summary,
+			}),
+			store: req.store,
+			include: &req.include,
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_output_tokens: req.max_output_tokens,
 			stream: true,
 		};
 
 		trace!("{:?}", req);
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://api.openai.com/v1/responses")
-			.bearer_auth(&self.api_key)
-			.header(ACCEPT, HeaderValue::from_static("text/event-stream"))
-			.json(&req)
-			.send()
-			.await?;
+			.post(self.url("responses"))
+			.bearer_auth(&*api_key)
+			.header(ACCEPT, HeaderValue::from_static("text/event-stream"));
+		let builder = self.account_headers(builder).headers(headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra { body: &req, extra },
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
+			return Err(error_response(resp).await);
+		}
+
+		Ok(ResponseStream::new(
+			SseResponse::new(resp, transcript, &api_key, read_timeout),
+			self.clone(),
+		))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
+	}
+
+	/// Stops generating a response, by the id of `response.created`.
+	///
+	/// OpenAI only cancels responses running in background mode, sent
+	/// with `"background": true`.
+	pub async fn cancel_response(&self, id: &str) -> Result<(), OpenAiError> {
+		let api_key = self.api_key.get().await?;
 
-			return Err(OpenAiError::ResponseError { status, body });
+		let builder = self
+			.client
+			.post(self.url(&format!("responses/{id}/cancel")))
+			.bearer_auth(&*api_key);
+		let resp = self.account_headers(builder).send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(())
 	}
 }
 
@@ -96,17 +258,45 @@ impl LlmProvider for OpenAi {
 			llms::Model::Gpt5_6Sol => OpenAiModel::Gpt5_6Sol,
 			llms::Model::Gpt5_6Terra => OpenAiModel::Gpt5_6Terra,
 			llms::Model::Gpt5_6Luna => OpenAiModel::Gpt5_6Luna,
+			llms::Model::Custom { id, .. } => OpenAiModel::Custom(id.clone()),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
 		self.request(&Request {
-			input: req.input.iter().cloned().map(Into::into).collect(),
+			input: Input::from_history(&req.input),
 			instructions: req.instructions.clone(),
 			model,
+			snapshot: req.snapshot.clone(),
 			prompt_cache_key: req.user_id.clone(),
 			safety_identifier: req.user_id.clone(),
-			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tools: req
+				.tools
+				.iter()
+				.cloned()
+				.map(Tool::try_from)
+				.collect::<Result<_, _>>()
+				.map_err(OpenAiError::from)?,
+			tool_choice: req.tool_choice.clone().into(),
+			text_format: req
+				.response_format
+				.clone()
+				.try_into()
+				.map_err(OpenAiError::from)?,
 			reasoning_effort: req.reasoning_effort.map(|e| e.into()),
+			reasoning_summary: req
+				.reasoning_effort
+				.map(|_| ReasoningSummaryMode::Auto),
+			// Nothing is ever referenced by id, reasoning state is carried
+			// through the tool call context instead.
+			store: false,
+			include: vec![Include::ReasoningEncryptedContent],
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_output_tokens: req.params.max_tokens,
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
 		})
 		.await
 		.map_err(Into::into)
@@ -118,15 +308,64 @@ pub struct Request {
 	pub input: Vec<Input>,
 	pub instructions: String,
 	pub model: OpenAiModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	#[serde(skip)]
+	pub snapshot: Option<String>,
 	pub prompt_cache_key: String,
 	pub safety_identifier: String,
 	pub tools: Vec<Tool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// `text.format`, `None` lets the model answer with plain text.
+	pub text_format: Option<TextFormat>,
 	pub reasoning_effort: Option<ReasoningEffort>,
+	/// `None` doesn't return any reasoning summary.
+	pub reasoning_summary: Option<ReasoningSummaryMode>,
+	/// Whether OpenAI stores the response so it can be referenced later.
+	pub store: bool,
+	/// Additional data to include in the response.
+	pub include: Vec<Include>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_output_tokens: Option<u32>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	#[serde(default)]
+	pub extra: serde_json::Map<String, serde_json::Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	#[serde(skip)]
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	#[serde(skip)]
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	#[serde(skip)]
+	pub read_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum Include {
+	/// Returns reasoning items with their `encrypted_content`, required to
+	/// pass reasoning back when `store` is `false`.
+	#[serde(rename = "reasoning.encrypted_content")]
+	ReasoningEncryptedContent,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 struct Reasoning {
-	effort: ReasoningEffort,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	effort: Option<ReasoningEffort>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	summary: Option<ReasoningSummaryMode>,
+}
+
+/// How detailed the returned reasoning summary is, `reasoning.summary`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningSummaryMode {
+	/// The most detailed summary the model supports.
+	Auto,
+	Concise,
+	Detailed,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -163,17 +402,98 @@ pub enum Tool {
 	},
 }
 
-impl From<llms::Tool> for Tool {
-	fn from(tool: llms::Tool) -> Self {
-		Tool::Function {
+impl TryFrom<llms::Tool> for Tool {
+	type Error = SchemaError;
+
+	fn try_from(tool: llms::Tool) -> Result<Self, SchemaError> {
+		let parameters = tool.parameters.unwrap_or_else(default_parameters);
+
+		Ok(Tool::Function {
 			name: tool.name,
 			description: Some(tool.description).filter(|d| !d.is_empty()),
-			parameters: tool.parameters.unwrap_or_else(default_parameters),
-			strict: false,
+			parameters: if tool.strict {
+				to_strict_schema(&parameters)?
+			} else {
+				parameters
+			},
+			strict: tool.strict,
+		})
+	}
+}
+
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		name: String,
+	},
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+	None,
+	Required,
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				name,
+			}),
 		}
 	}
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TextFormat {
+	JsonObject,
+	/// The schema is normalized like a tool's if `strict` is set.
+	JsonSchema {
+		name: String,
+		schema: serde_json::Value,
+		strict: bool,
+	},
+}
+
+impl TryFrom<llms::ResponseFormat> for Option<TextFormat> {
+	type Error = SchemaError;
+
+	fn try_from(format: llms::ResponseFormat) -> Result<Self, SchemaError> {
+		Ok(match format {
+			llms::ResponseFormat::Text => None,
+			llms::ResponseFormat::JsonObject => Some(TextFormat::JsonObject),
+			llms::ResponseFormat::JsonSchema {
+				name,
+				schema,
+				strict,
+			} => Some(TextFormat::JsonSchema {
+				name,
+				schema: if strict {
+					to_strict_schema(&schema)?
+				} else {
+					schema
+				},
+				strict,
+			}),
+		})
+	}
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Input {
@@ -183,6 +503,29 @@ pub enum Input {
 	FunctionCallOutput(FunctionCallOutput),
 }
 
+impl Input {
+	/// Encodes the history item by item, restoring the reasoning items
+	/// stored in the tool call `context`.
+	pub fn from_history(input: &[llms::Input]) -> Vec<Self> {
+		let mut items = Vec::with_capacity(input.len());
+		for item in input.iter().cloned() {
+			// The encrypted reasoning item has to directly precede the
+			// function call it produced.
+			if let llms::Input::ToolCall {
+				context: Some(context),
+				..
+			} = &item && let Ok(reasoning) =
+				serde_json::from_str::<ReasoningItem>(context)
+			{
+				items.push(Input::Reasoning(reasoning));
+			}
+
+			items.push(item.into());
+		}
+		items
+	}
+}
+
 impl From<OutputItem> for Input {
 	fn from(item: OutputItem) -> Self {
 		match item {
@@ -222,6 +565,20 @@ impl From<llms::Input> for Input {
 					output,
 				})
 			}
+			llms::Input::Image { data } => {
+				Input::Message(InputMessage::Parts {
+					role: Role::User,
+					content: vec![InputContent::InputImage {
+						image_url: data.to_url(),
+					}],
+				})
+			}
+			llms::Input::Document { data, mime } => {
+				Input::Message(InputMessage::Parts {
+					role: Role::User,
+					content: vec![InputContent::file(data, &mime)],
+				})
+			}
 		}
 	}
 }
@@ -231,7 +588,62 @@ impl From<llms::Input> for Input {
 pub enum InputMessage {
 	// first because of serde untagged priority
 	Output(OutputMessage),
-	Input { role: Role, content: String },
+	Input {
+		role: Role,
+		content: String,
+	},
+	Parts {
+		role: Role,
+		content: Vec<InputContent>,
+	},
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContent {
+	/// A url or a `data:` url.
+	InputImage { image_url: String },
+	/// Either a `data:` url with a filename or a url.
+	InputFile {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		filename: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		file_data: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		file_url: Option<String>,
+	},
+}
+
+impl InputContent {
+	fn file(data: llms::DocumentSource, mime: &str) -> Self {
+		match data {
+			llms::DocumentSource::Url(url) => InputContent::InputFile {
+				filename: None,
+				file_data: None,
+				file_url: Some(url),
+			},
+			llms::DocumentSource::Base64(data) => {
+				Self::inline_file(&data, mime)
+			}
+			llms::DocumentSource::Reader(reader) => {
+				Self::inline_file(&reader.placeholder(), mime)
+			}
+		}
+	}
+
+	fn inline_file(data: &str, mime: &str) -> Self {
+		// the filename is required, its extension tells the file type
+		let extension = match mime {
+			"application/pdf" => "pdf",
+			"text/plain" => "txt",
+			mime => mime.rsplit('/').next().unwrap_or(mime),
+		};
+		InputContent::InputFile {
+			filename: Some(format!("document.{extension}")),
+			file_data: Some(format!("data:{mime};base64,{data}")),
+			file_url: None,
+		}
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -247,6 +659,7 @@ impl From<llms::Role> for Role {
 		match role {
 			llms::Role::User => Role::User,
 			llms::Role::Assistant => Role::Assistant,
+			llms::Role::System => Role::Developer,
 		}
 	}
 }
@@ -256,9 +669,34 @@ pub enum OpenAiError {
 	#[error("Invalid LLM response: {0}")]
 	InvalidLlmResponse(String),
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+	#[error("Schema error: {0}")]
+	Schema(#[from] SchemaError),
+}
+
+impl From<ErrorResponse> for OpenAiError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<OpenAiError> for LlmsError {
@@ -267,16 +705,30 @@ impl From<OpenAiError> for LlmsError {
 			OpenAiError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::OpenAi,
+				request_id: None,
 			},
-			OpenAiError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			OpenAiError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::OpenAi,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			OpenAiError::ReqwestError(e) => LlmsError::Reqwest(e),
+			OpenAiError::Credential(e) => LlmsError::Credential(e),
+			OpenAiError::Io(e) => LlmsError::Io(e),
+			OpenAiError::Schema(e) => LlmsError::InvalidSchema(e.to_string()),
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum OpenAiModel {
 	#[serde(rename = "gpt-5.6-sol")]
 	Gpt5_6Sol,
@@ -284,14 +736,18 @@ pub enum OpenAiModel {
 	Gpt5_6Terra,
 	#[serde(rename = "gpt-5.6-luna")]
 	Gpt5_6Luna,
+	/// Any other model id, see [`llms::Model::Custom`].
+	#[serde(untagged)]
+	Custom(String),
 }
 
 impl OpenAiModel {
-	pub fn as_str(&self) -> &'static str {
+	pub fn as_str(&self) -> &str {
 		match self {
 			OpenAiModel::Gpt5_6Sol => "gpt-5.6-sol",
 			OpenAiModel::Gpt5_6Terra => "gpt-5.6-terra",
 			OpenAiModel::Gpt5_6Luna => "gpt-5.6-luna",
+			OpenAiModel::Custom(id) => id,
 		}
 	}
 }
@@ -311,6 +767,11 @@ pub enum Event {
 	ResponseInProgress { response: Response },
 	#[serde(rename = "response.completed")]
 	ResponseCompleted { response: Response },
+	/// Stopped early, e.g. at the output token limit.
+	#[serde(rename = "response.incomplete")]
+	ResponseIncomplete { response: Response },
+	#[serde(rename = "response.failed")]
+	ResponseFailed { response: Response },
 	#[serde(rename = "response.output_item.added")]
 	ResponseOutputItemAdded { output_index: u32, item: OutputItem },
 	#[serde(rename = "response.output_item.done")]
@@ -349,6 +810,13 @@ pub enum Event {
 		item_id: String,
 		delta: String,
 	},
+	#[serde(rename = "response.reasoning_summary_text.delta")]
+	ResponseReasoningSummaryTextDelta {
+		output_index: u32,
+		item_id: String,
+		summary_index: u32,
+		delta: String,
+	},
 	#[serde(rename = "response.function_call_arguments.done")]
 	ResponseFunctionCallArgumentsDone {
 		output_index: u32,
@@ -360,25 +828,62 @@ pub enum Event {
 	ResponseError { error: ResponseError },
 	#[serde(rename = "keepalive")]
 	Keepalive,
+	/// Events not needed to build the response, like the reasoning summary
+	/// parts.
+	#[serde(other)]
+	Unknown,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Response {
+	#[serde(default)]
+	pub id: String,
 	pub output: Vec<OutputItem>,
 	pub status: ResponseStatus,
 	pub usage: Option<ResponseUsage>,
+	/// Set if the status is failed.
+	#[serde(default)]
+	pub error: Option<ResponseError>,
+	/// Set if the status is incomplete.
+	#[serde(default)]
+	pub incomplete_details: Option<IncompleteDetails>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IncompleteDetails {
+	/// `max_output_tokens` or `content_filter`.
+	pub reason: String,
 }
 
 impl TryFrom<Response> for llms::Response {
 	type Error = OpenAiError;
 
 	fn try_from(resp: Response) -> Result<Self, Self::Error> {
-		if !matches!(resp.status, ResponseStatus::Completed) {
-			return Err(OpenAiError::InvalidLlmResponse(format!(
-				"response status is not completed: {:?}",
-				resp.status
-			)));
-		}
+		let finish_reason = match (&resp.status, resp.incomplete_details) {
+			(ResponseStatus::Completed, _) => Some(
+				if resp
+					.output
+					.iter()
+					.any(|item| matches!(item, OutputItem::FunctionCall(_)))
+				{
+					llms::FinishReason::ToolCalls
+				} else {
+					llms::FinishReason::Stop
+				},
+			),
+			(ResponseStatus::Incomplete, Some(details)) => {
+				Some(match details.reason.as_str() {
+					"max_output_tokens" => llms::FinishReason::MaxTokens,
+					"content_filter" => llms::FinishReason::ContentFilter,
+					_ => llms::FinishReason::Other(details.reason),
+				})
+			}
+			(status, _) => {
+				return Err(OpenAiError::InvalidLlmResponse(format!(
+					"response status is not completed: {status:?}"
+				)));
+			}
+		};
 
 		let usage = resp.usage.ok_or_else(|| {
 			OpenAiError::InvalidLlmResponse("missing usage in response".into())
@@ -386,15 +891,59 @@ impl TryFrom<Response> for llms::Response {
 		let usage = llms::Usage {
 			input_tokens: usage.input_tokens,
 			output_tokens: usage.output_tokens,
+			cache_read_tokens: usage.input_tokens_details.cached_tokens,
+			reasoning_tokens: usage.output_tokens_details.reasoning_tokens,
+			..Default::default()
 		};
 
+		let mut output = Vec::with_capacity(resp.output.len());
+		// Encrypted reasoning gets attached to the following function call,
+		// the same way Gemini's thought signature is.
+		let mut reasoning = None;
+		let mut summaries = Vec::new();
+		for item in resp.output {
+			if let OutputItem::Reasoning(item) = &item {
+				let summary: Vec<_> = item
+					.summary
+					.iter()
+					.map(|ReasoningSummary::SummaryText { text }| text.clone())
+					.collect();
+				if !summary.is_empty() {
+					output.push(llms::Output::Reasoning {
+						content: summary.join("\n\n"),
+					});
+				}
+				summaries.extend(summary);
+			}
+
+			if let OutputItem::Reasoning(item) = &item
+				&& item.encrypted_content.is_some()
+			{
+				reasoning = Some(serde_json::to_string(item).map_err(|e| {
+					OpenAiError::InvalidLlmResponse(format!(
+						"failed to serialize reasoning item: {e}"
+					))
+				})?);
+				continue;
+			}
+
+			let Some(mut item) = Option::<llms::Output>::try_from(item)? else {
+				continue;
+			};
+
+			if let llms::Output::ToolCall { context, .. } = &mut item {
+				*context = reasoning.take();
+			}
+
+			output.push(item);
+		}
+
 		Ok(llms::Response {
-			output: resp
-				.output
-				.into_iter()
-				.filter_map(|o| Option::<llms::Output>::try_from(o).transpose())
-				.collect::<Result<_, Self::Error>>()?,
+			output,
 			usage,
+			reasoning: Some(summaries.join("\n\n")).filter(|s| !s.is_empty()),
+			finish_reason,
+			..Default::default()
 		})
 	}
 }
@@ -425,7 +974,13 @@ impl TryFrom<OutputItem> for Option<llms::Output> {
 	fn try_from(item: OutputItem) -> Result<Self, OpenAiError> {
 		match item {
 			OutputItem::Message(msg) => {
-				assert!(matches!(msg.status, OutputStatus::Completed));
+				// incomplete messages are cut off by the limit the response
+				// reports in its finish reason
+				if matches!(msg.status, OutputStatus::InProgress) {
+					return Err(OpenAiError::InvalidLlmResponse(
+						"output message is still in progress".into(),
+					));
+				}
 
 				if msg.content.len() > 1 {
 					warn!("output message has multiple items");
@@ -449,7 +1004,11 @@ impl TryFrom<OutputItem> for Option<llms::Output> {
 			}
 			OutputItem::Reasoning(_) => Ok(None),
 			OutputItem::FunctionCall(fc) => {
-				assert!(matches!(fc.status, Some(OutputStatus::Completed)));
+				if matches!(fc.status, Some(OutputStatus::InProgress)) {
+					return Err(OpenAiError::InvalidLlmResponse(
+						"function call is still in progress".into(),
+					));
+				}
 
 				let input =
 					serde_json::from_str(&fc.arguments).map_err(|e| {
@@ -497,6 +1056,9 @@ pub enum OutputStatus {
 pub struct ReasoningItem {
 	pub id: String,
 	pub summary: Vec<ReasoningSummary>,
+	/// Only returned when [`Include::ReasoningEncryptedContent`] is set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub encrypted_content: Option<String>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub status: Option<OutputStatus>,
 }
@@ -536,27 +1098,92 @@ pub struct ResponseUsage {
 	pub input_tokens: u32,
 	pub output_tokens: u32,
 	pub total_tokens: u32,
+	#[serde(default)]
+	pub input_tokens_details: InputTokensDetails,
+	#[serde(default)]
+	pub output_tokens_details: OutputTokensDetails,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InputTokensDetails {
+	/// Part of `input_tokens` served from the prompt cache.
+	#[serde(default)]
+	pub cached_tokens: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OutputTokensDetails {
+	/// Part of `output_tokens` spent on reasoning.
+	#[serde(default)]
+	pub reasoning_tokens: u32,
 }
 
 #[derive(Debug)]
 pub struct ResponseStream {
 	inner: SseResponse,
+	/// The call ids of the started function calls by item id, the
+	/// argument deltas only reference the item.
+	call_ids: HashMap<String, String>,
+	/// Sends the cancel request, see [`ResponseStream::cancel`].
+	client: OpenAi,
+	/// From `response.created`, `None` once the response ended.
+	response_id: Option<String>,
 }
 
 impl ResponseStream {
-	fn new(inner: SseResponse) -> Self {
-		Self { inner }
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
+	fn new(inner: SseResponse, client: OpenAi) -> Self {
+		Self {
+			inner,
+			call_ids: HashMap::new(),
+			client,
+			response_id: None,
+		}
 	}
 
 	pub async fn next(&mut self) -> Option<Result<Event, SseError>> {
 		match self.inner.next().await {
 			Some(Ok(ev)) => {
 				trace!("new event: {ev:?}");
+				match &ev {
+					Event::ResponseCreated { response } => {
+						self.response_id = Some(response.id.clone())
+							.filter(|id| !id.is_empty());
+					}
+					Event::ResponseCompleted { .. }
+					| Event::ResponseIncomplete { .. }
+					| Event::ResponseFailed { .. } => self.response_id = None,
+					_ => {}
+				}
 				Some(Ok(ev))
 			}
 			a => a,
 		}
 	}
+
+	/// Closes the connection and, if the response is still running, asks
+	/// OpenAI to stop it in the background, see [`OpenAi::cancel_response`].
+	///
+	/// Outside of a tokio runtime only the connection is closed.
+	pub fn cancel(self) {
+		let Some(id) = self.response_id else {
+			return;
+		};
+		let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+			return;
+		};
+
+		let client = self.client;
+		runtime.spawn(async move {
+			if let Err(e) = client.cancel_response(&id).await {
+				debug!("cancelling response {id} failed: {e}");
+			}
+		});
+	}
 }
 
 impl LlmResponseStream for ResponseStream {
@@ -574,15 +1201,67 @@ impl LlmResponseStream for ResponseStream {
 				Event::ResponseOutputTextDelta { delta, .. } => {
 					Ok(llms::LlmResponseEvent::TextDelta { content: delta })
 				}
-				Event::ResponseCompleted { response } => response
+				Event::ResponseReasoningSummaryTextDelta { delta, .. } => {
+					Ok(llms::LlmResponseEvent::ReasoningDelta {
+						content: delta,
+					})
+				}
+				Event::ResponseOutputItemAdded {
+					item: OutputItem::FunctionCall(fc),
+					..
+				} => {
+					if let Some(item_id) = fc.id {
+						self.call_ids.insert(item_id, fc.call_id.clone());
+					}
+					Ok(llms::LlmResponseEvent::ToolCallStarted {
+						id: fc.call_id,
+						name: fc.name,
+					})
+				}
+				Event::ResponseFunctionCallArgumentsDelta {
+					item_id,
+					delta,
+					..
+				} => {
+					let Some(id) = self.call_ids.get(&item_id) else {
+						continue;
+					};
+					Ok(llms::LlmResponseEvent::ToolCallArgumentsDelta {
+						id: id.clone(),
+						delta,
+					})
+				}
+				Event::ResponseCompleted { response }
+				| Event::ResponseIncomplete { response } => response
 					.try_into()
 					.map(llms::LlmResponseEvent::Completed)
 					.map_err(Into::into),
+				Event::ResponseFailed {
+					response: Response {
+						error: Some(error), ..
+					},
+				} => {
+					return Some(Err(LlmsError::api_event(
+						llms::ProviderKind::OpenAi,
+						&error.code,
+						error.message,
+						self.inner.request_id().map(Into::into),
+					)));
+				}
+				Event::ResponseFailed { response } => {
+					Err(OpenAiError::InvalidLlmResponse(format!(
+						"response failed without an error: {:?}",
+						response.status
+					))
+					.into())
+				}
 				Event::ResponseError { error } => {
-					return Some(Err(LlmsError::Response {
-						status: StatusCode::OK,
-						body: format!("{}: {}", error.code, error.message),
-					}));
+					return Some(Err(LlmsError::api_event(
+						llms::ProviderKind::OpenAi,
+						&error.code,
+						error.message,
+						self.inner.request_id().map(Into::into),
+					)));
 				}
 				Event::Keepalive => continue,
 				_ => continue,
@@ -590,3 +1269,156 @@ impl LlmResponseStream for ResponseStream {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use bytes::Bytes;
+	use futures::{StreamExt as _, stream};
+	use serde_json::json;
+	use tokio::{io::AsyncReadExt as _, net::TcpListener, time::timeout};
+
+	use super::*;
+	use crate::llms::history::tests::interleaved;
+
+	#[test]
+	fn encodes_items_and_restores_reasoning_before_its_call() {
+		let mut input = interleaved();
+		let reasoning = json!({
+			"id": "rs_1",
+			"summary": [],
+			"encrypted_content": "enc"
+		});
+		if let llms::Input::ToolCall { context, .. } = &mut input[3] {
+			*context = Some(reasoning.to_string());
+		}
+		// from another provider
+		if let llms::Input::ToolCall { context, .. } = &mut input[4] {
+			*context = Some("opaque".into());
+		}
+
+		let call = |call_id: &str, city: &str| {
+			json!({
+				"type": "function_call",
+				"id": null,
+				"call_id": call_id,
+				"name": "weather",
+				"arguments": json!({ "city": city }).to_string(),
+				"status": null
+			})
+		};
+		assert_eq!(
+			serde_json::to_value(Input::from_history(&input)).unwrap(),
+			json!([
+				{ "type": "message", "role": "developer", "content": "Be brief." },
+				{
+					"type": "message",
+					"role": "user",
+					"content": "Weather in Bern and Zurich?"
+				},
+				{ "type": "message", "role": "assistant", "content": "Checking." },
+				{
+					"type": "reasoning",
+					"id": "rs_1",
+					"summary": [],
+					"encrypted_content": "enc"
+				},
+				call("call_1", "Bern"),
+				call("call_2", "Zurich"),
+				{
+					"type": "function_call_output",
+					"call_id": "call_1",
+					"output": "sunny"
+				},
+				{
+					"type": "function_call_output",
+					"call_id": "call_2",
+					"output": "rainy"
+				},
+				{ "type": "message", "role": "user", "content": "And here?" },
+				{
+					"type": "message",
+					"role": "user",
+					"content": [{
+						"type": "input_image",
+						"image_url": "https://example.com/a.png"
+					}]
+				},
+				{ "type": "message", "role": "assistant", "content": "Cloudy." }
+			])
+		);
+	}
+
+	#[test]
+	fn incomplete_responses_keep_their_output() {
+		let event: Event = serde_json::from_value(json!({
+			"type": "response.incomplete",
+			"response": {
+				"status": "incomplete",
+				"incomplete_details": { "reason": "max_output_tokens" },
+				"output": [{
+					"type": "message",
+					"id": "msg_1",
+					"status": "incomplete",
+					"role": "assistant",
+					"content": [{ "type": "output_text", "text": "Once upon" }]
+				}],
+				"usage": {
+					"input_tokens": 5,
+					"output_tokens": 16,
+					"total_tokens": 21,
+					"input_tokens_details": { "cached_tokens": 0 },
+					"output_tokens_details": { "reasoning_tokens": 0 }
+				}
+			}
+		}))
+		.unwrap();
+		let Event::ResponseIncomplete { response } = event else {
+			panic!("expected response.incomplete, got {event:?}");
+		};
+
+		let response = llms::Response::try_from(response).unwrap();
+		assert_eq!(response.finish_reason, Some(llms::FinishReason::MaxTokens));
+		assert!(matches!(
+			response.output.as_slice(),
+			[llms::Output::Text { content }] if content == "Once upon"
+		));
+	}
+
+	#[tokio::test]
+	async fn cancel_stops_the_created_response() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}/v1", listener.local_addr().unwrap());
+
+		let created = json!({
+			"type": "response.created",
+			"response": { "id": "resp_1", "status": "in_progress", "output": [] }
+		});
+		let body = format!("event: response.created\ndata: {created}\n\n");
+		let sse = SseResponse::from_body(
+			stream::iter([Ok(Bytes::from(body))]).boxed(),
+			None,
+			None,
+		);
+		let mut stream =
+			ResponseStream::new(sse, OpenAi::new("key").base_url(url));
+		assert!(matches!(
+			stream.next().await,
+			Some(Ok(Event::ResponseCreated { .. }))
+		));
+		stream.cancel();
+
+		let (mut socket, _) =
+			timeout(Duration::from_secs(5), listener.accept())
+				.await
+				.expect("no cancel request")
+				.unwrap();
+		let mut buf = [0; 4096];
+		let n = socket.read(&mut buf).await.unwrap();
+		let req = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+		assert!(
+			req.starts_with("post /v1/responses/resp_1/cancel "),
+			"{req}"
+		);
+		assert!(req.contains("authorization: bearer key"), "{req}");
+	}
+}
@@ -0,0 +1,261 @@
+use serde_json::{Map, Value};
+
+/// Subschema keywords holding a map of schemas.
+const SCHEMA_MAPS: &[&str] = &["properties", "$defs", "definitions"];
+
+/// Subschema keywords holding a list of schemas.
+const SCHEMA_LISTS: &[&str] = &["anyOf", "allOf", "prefixItems"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+	/// Strict mode requires every object to have a fixed set of properties,
+	/// so maps with arbitrary keys can't be expressed.
+	#[error("Strict mode doesn't support {0:?}")]
+	Unsupported(String),
+}
+
+/// Normalizes a standard JSON Schema into what OpenAI's strict mode
+/// (`strict: true`) accepts.
+///
+/// - Every object gets `additionalProperties: false`.
+/// - Every property becomes required, previously optional ones are made
+///   nullable instead, so the model passes `null` where it would have
+///   omitted the property.
+/// - `oneOf` becomes an `anyOf`.
+///
+/// Objects allowing additional properties or using `patternProperties`
+/// fail.
+pub fn to_strict_schema(schema: &Value) -> Result<Value, SchemaError> {
+	let Value::Object(obj) = schema else {
+		return Ok(schema.clone());
+	};
+
+	let mut out = Map::new();
+
+	for (key, value) in obj {
+		match key.as_str() {
+			"additionalProperties" if value != &Value::Bool(false) => {
+				return Err(SchemaError::Unsupported(
+					"additionalProperties".into(),
+				));
+			}
+			"patternProperties" => {
+				return Err(SchemaError::Unsupported(key.clone()));
+			}
+			// set below from the properties
+			"required" => {}
+			k if SCHEMA_MAPS.contains(&k) => {
+				let mut schemas = Map::new();
+				if let Value::Object(value) = value {
+					for (k, v) in value {
+						schemas.insert(k.clone(), to_strict_schema(v)?);
+					}
+				}
+				out.insert(key.clone(), Value::Object(schemas));
+			}
+			k if SCHEMA_LISTS.contains(&k) || k == "oneOf" => {
+				let mut schemas = Vec::new();
+				for schema in value.as_array().into_iter().flatten() {
+					schemas.push(to_strict_schema(schema)?);
+				}
+				let key = if k == "oneOf" { "anyOf" } else { k };
+				out.insert(key.into(), Value::Array(schemas));
+			}
+			"items" => {
+				out.insert(key.clone(), to_strict_schema(value)?);
+			}
+			_ => {
+				out.insert(key.clone(), value.clone());
+			}
+		}
+	}
+
+	if !is_object(obj) {
+		if let Some(required) = obj.get("required") {
+			out.insert("required".into(), required.clone());
+		}
+		return Ok(Value::Object(out));
+	}
+
+	let required: Vec<&str> = obj
+		.get("required")
+		.and_then(Value::as_array)
+		.into_iter()
+		.flatten()
+		.filter_map(Value::as_str)
+		.collect();
+
+	let props = out
+		.entry("properties")
+		.or_insert_with(|| Value::Object(Map::new()));
+	let mut names = Vec::new();
+	if let Value::Object(props) = props {
+		for (name, prop) in props.iter_mut() {
+			if !required.contains(&name.as_str()) {
+				make_nullable(prop);
+			}
+			names.push(Value::String(name.clone()));
+		}
+	}
+
+	out.insert("required".into(), Value::Array(names));
+	out.insert("additionalProperties".into(), Value::Bool(false));
+
+	Ok(Value::Object(out))
+}
+
+fn is_object(obj: &Map<String, Value>) -> bool {
+	obj.contains_key("properties")
+		|| match obj.get("type") {
+			Some(Value::String(ty)) => ty == "object",
+			Some(Value::Array(types)) => {
+				types.iter().any(|ty| ty.as_str() == Some("object"))
+			}
+			_ => false,
+		}
+}
+
+fn make_nullable(schema: &mut Value) {
+	let null = Value::String("null".into());
+
+	let Value::Object(obj) = schema else {
+		return;
+	};
+
+	match obj.get_mut("type") {
+		Some(Value::String(ty)) if ty == "null" => return,
+		Some(ty @ Value::String(_)) => {
+			*ty = Value::Array(vec![ty.take(), null.clone()]);
+		}
+		Some(Value::Array(types)) => {
+			if !types.contains(&null) {
+				types.push(null.clone());
+			}
+		}
+		_ => {
+			*schema = serde_json::json!({
+				"anyOf": [schema.take(), { "type": "null" }],
+			});
+			return;
+		}
+	}
+
+	// a null would otherwise not match the enum
+	if let Some(Value::Array(values)) = obj.get_mut("enum")
+		&& !values.contains(&Value::Null)
+	{
+		values.push(Value::Null);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn optional_properties_become_required_and_nullable() {
+		let schema = json!({
+			"type": "object",
+			"properties": {
+				"name": { "type": "string" },
+				"age": { "type": "integer" },
+				"unit": { "type": "string", "enum": ["c", "f"] },
+				"home": { "$ref": "#/$defs/address" }
+			},
+			"required": ["name"],
+			"$defs": {
+				"address": {
+					"properties": { "city": { "type": "string" } }
+				}
+			}
+		});
+
+		assert_eq!(
+			to_strict_schema(&schema).unwrap(),
+			json!({
+				"type": "object",
+				"properties": {
+					"name": { "type": "string" },
+					"age": { "type": ["integer", "null"] },
+					"unit": {
+						"type": ["string", "null"],
+						"enum": ["c", "f", null]
+					},
+					"home": {
+						"anyOf": [
+							{ "$ref": "#/$defs/address" },
+							{ "type": "null" }
+						]
+					}
+				},
+				"required": ["age", "home", "name", "unit"],
+				"additionalProperties": false,
+				"$defs": {
+					"address": {
+						"properties": { "city": { "type": ["string", "null"] } },
+						"required": ["city"],
+						"additionalProperties": false
+					}
+				}
+			})
+		);
+	}
+
+	#[test]
+	fn nested_objects_are_closed() {
+		let schema = json!({
+			"type": "array",
+			"items": {
+				"type": "object",
+				"additionalProperties": false,
+				"oneOf": [{ "type": "object" }]
+			}
+		});
+
+		assert_eq!(
+			to_strict_schema(&schema).unwrap(),
+			json!({
+				"type": "array",
+				"items": {
+					"type": "object",
+					"properties": {},
+					"required": [],
+					"additionalProperties": false,
+					"anyOf": [{
+						"type": "object",
+						"properties": {},
+						"required": [],
+						"additionalProperties": false
+					}]
+				}
+			})
+		);
+	}
+
+	#[test]
+	fn open_objects_fail() {
+		for schema in [
+			json!({ "type": "object", "additionalProperties": {} }),
+			json!({ "type": "object", "additionalProperties": true }),
+			json!({
+				"type": "object",
+				"properties": {
+					"tags": {
+						"type": "object",
+						"patternProperties": { "^x-": { "type": "string" } }
+					}
+				}
+			}),
+		] {
+			assert!(
+				matches!(
+					to_strict_schema(&schema),
+					Err(SchemaError::Unsupported(_))
+				),
+				"{schema}"
+			);
+		}
+	}
+}
@@ -0,0 +1,197 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::Stream;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+use super::{OpenAi, OpenAiError};
+use crate::utils::error_response;
+use crate::utils::paginate::{Page, paginate};
+
+/// Time range and grouping of [`OpenAi::completions_usage`] and
+/// [`OpenAi::costs`].
+#[derive(Debug, Clone)]
+pub struct UsageQuery {
+	pub start: SystemTime,
+	/// `None` means until now.
+	pub end: Option<SystemTime>,
+	/// Costs are only reported per day.
+	pub bucket_width: BucketWidth,
+	/// Fields to split the results by, e.g. `"model"` or `"project_id"`.
+	pub group_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BucketWidth {
+	Minute,
+	Hour,
+	#[default]
+	Day,
+}
+
+impl BucketWidth {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Minute => "1m",
+			Self::Hour => "1h",
+			Self::Day => "1d",
+		}
+	}
+}
+
+impl UsageQuery {
+	/// Daily buckets from `start` until now.
+	pub fn new(start: SystemTime) -> Self {
+		Self {
+			start,
+			end: None,
+			bucket_width: BucketWidth::Day,
+			group_by: Vec::new(),
+		}
+	}
+
+	pub fn end(mut self, end: SystemTime) -> Self {
+		self.end = Some(end);
+		self
+	}
+
+	pub fn bucket_width(mut self, width: BucketWidth) -> Self {
+		self.bucket_width = width;
+		self
+	}
+
+	pub fn group_by(mut self, field: impl Into<String>) -> Self {
+		self.group_by.push(field.into());
+		self
+	}
+}
+
+/// The results of one time bucket.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bucket<T> {
+	/// Unix timestamp in seconds.
+	pub start_time: u64,
+	/// Unix timestamp in seconds.
+	pub end_time: u64,
+	/// One entry per group, a single one without [`UsageQuery::group_by`].
+	pub results: Vec<T>,
+}
+
+/// Token usage of the Responses and Chat Completions APIs.
+///
+/// The grouping fields are `None` unless grouped by them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompletionsUsage {
+	pub input_tokens: u64,
+	pub output_tokens: u64,
+	#[serde(default)]
+	pub input_cached_tokens: u64,
+	#[serde(default)]
+	pub input_audio_tokens: u64,
+	#[serde(default)]
+	pub output_audio_tokens: u64,
+	pub num_model_requests: u64,
+	pub model: Option<String>,
+	pub project_id: Option<String>,
+	pub user_id: Option<String>,
+	pub api_key_id: Option<String>,
+	pub batch: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cost {
+	pub amount: CostAmount,
+	pub line_item: Option<String>,
+	pub project_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CostAmount {
+	pub value: f64,
+	/// e.g. `"usd"`.
+	pub currency: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BucketPage<T> {
+	data: Vec<Bucket<T>>,
+	#[serde(default)]
+	has_more: bool,
+	next_page: Option<String>,
+}
+
+impl OpenAi {
+	/// Historical token usage of the organization, the next page is fetched
+	/// while the stream is polled.
+	///
+	/// Needs an admin key, create a separate [`OpenAi`] with it.
+	pub fn completions_usage<'a>(
+		&'a self,
+		query: &'a UsageQuery,
+	) -> impl Stream<Item = Result<Bucket<CompletionsUsage>, OpenAiError>> + 'a
+	{
+		paginate(move |page| {
+			self.buckets_page("usage/completions", query, page)
+		})
+	}
+
+	/// Historical spend of the organization in daily buckets, the next page
+	/// is fetched while the stream is polled.
+	///
+	/// Needs an admin key, create a separate [`OpenAi`] with it.
+	pub fn costs<'a>(
+		&'a self,
+		query: &'a UsageQuery,
+	) -> impl Stream<Item = Result<Bucket<Cost>, OpenAiError>> + 'a {
+		paginate(move |page| self.buckets_page("costs", query, page))
+	}
+
+	async fn buckets_page<T: DeserializeOwned>(
+		&self,
+		path: &str,
+		query: &UsageQuery,
+		page: Option<String>,
+	) -> Result<Page<Bucket<T>>, OpenAiError> {
+		let unix = |time: SystemTime| {
+			time.duration_since(UNIX_EPOCH)
+				.unwrap_or_default()
+				.as_secs()
+				.to_string()
+		};
+
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
+			.client
+			.get(self.url(&format!("organization/{path}")))
+			.bearer_auth(&*api_key);
+		let mut req = self.account_headers(builder).build()?;
+		{
+			let mut pairs = req.url_mut().query_pairs_mut();
+			pairs
+				.append_pair("start_time", &unix(query.start))
+				.append_pair("bucket_width", query.bucket_width.as_str());
+			if let Some(end) = query.end {
+				pairs.append_pair("end_time", &unix(end));
+			}
+			for field in &query.group_by {
+				pairs.append_pair("group_by", field);
+			}
+			if let Some(page) = &page {
+				pairs.append_pair("page", page);
+			}
+		}
+
+		let resp = self.client.execute(req).await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		let page: BucketPage<T> = resp.json().await?;
+		Ok(Page {
+			items: page.data,
+			next: page.next_page.filter(|_| page.has_more),
+		})
+	}
+}
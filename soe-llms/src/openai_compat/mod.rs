@@ -0,0 +1,810 @@
+//! Any server speaking OpenAI's chat completions api, like Ollama, vLLM,
+//! llama.cpp or LM Studio. The model is named by the server, see
+//! [`Model::OpenAiCompat`](crate::Model::OpenAiCompat).
+
+pub use crate::llms::chat::{
+	ApiAudioDelta, ApiContentPart, ApiImageUrl, ApiMessage, ApiToolCall,
+	ApiToolCallFunction, ApiUserContent,
+};
+
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use reqwest::{
+	Client, RequestBuilder, StatusCode,
+	header::{HeaderMap, HeaderValue, USER_AGENT},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::trace;
+
+use crate::{
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
+	utils::{
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
+		sse::{SseError, SseResponse},
+	},
+};
+
+/// Default of [`OpenAiCompat::base_url`], a local Ollama.
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434/v1";
+
+#[derive(Clone)]
+pub struct OpenAiCompat {
+	pub client: Client,
+	/// Sent as bearer token unless empty, most local servers don't check
+	/// it.
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, e.g. `"http://localhost:8000/v1"` for
+	/// vLLM.
+	pub base_url: String,
+}
+
+impl OpenAiCompat {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	/// A server that doesn't check the api key.
+	pub fn local(base_url: impl Into<String>) -> Self {
+		Self::new(String::new()).base_url(base_url)
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
+		Self {
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	async fn auth(
+		&self,
+		builder: RequestBuilder,
+	) -> Result<(RequestBuilder, String), OpenAiCompatError> {
+		let api_key = self.api_key.get().await?;
+		if api_key.is_empty() {
+			Ok((builder, String::new()))
+		} else {
+			Ok((builder.bearer_auth(&*api_key), api_key.to_string()))
+		}
+	}
+
+	/// Lists the available models.
+	///
+	/// Used to check that the server is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), OpenAiCompatError> {
+		let (builder, _) =
+			self.auth(self.client.get(self.url("models"))).await?;
+		let resp = builder.send().await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+
+	pub async fn request(
+		&self,
+		req: &Request,
+	) -> Result<ResponseStream, OpenAiCompatError> {
+		#[derive(Debug, Serialize)]
+		struct ApiReq<'a> {
+			model: &'a str,
+			messages: &'a Vec<ApiMessage>,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			tools: &'a Vec<ApiTool>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			tool_choice: Option<&'a ToolChoice>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			reasoning_effort: Option<ReasoningEffort>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			response_format: Option<&'a ResponseFormat>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			temperature: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			top_p: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			max_tokens: Option<u32>,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			stop: &'a Vec<String>,
+			stream: bool,
+			stream_options: StreamOptions,
+		}
+
+		#[derive(Debug, Serialize)]
+		struct StreamOptions {
+			include_usage: bool,
+		}
+
+		let api_req = ApiReq {
+			model: &req.model,
+			messages: &req.messages,
+			tools: &req.tools,
+			tool_choice: req
+				.tool_choice
+				.as_ref()
+				.filter(|_| !req.tools.is_empty()),
+			reasoning_effort: req.reasoning_effort,
+			response_format: req.response_format.as_ref(),
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_tokens: req.max_tokens,
+			stop: &req.stop,
+			stream: true,
+			stream_options: StreamOptions {
+				include_usage: true,
+			},
+		};
+
+		trace!("{:?}", serde_json::to_string(&api_req));
+
+		let (builder, api_key) = self
+			.auth(self.client.post(self.url("chat/completions")))
+			.await?;
+		let builder = builder
+			.header(USER_AGENT, HeaderValue::from_static("soe-llms/1.0"))
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(ResponseStream::new(SseResponse::new(
+			resp,
+			req.transcript.as_ref(),
+			&api_key,
+			req.read_timeout,
+		)))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
+	}
+}
+
+impl fmt::Debug for OpenAiCompat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("OpenAiCompat")
+			.field("api_key", &"***")
+			.field("base_url", &self.base_url)
+			.finish()
+	}
+}
+
+impl LlmProvider for OpenAiCompat {
+	type Stream = ResponseStream;
+
+	async fn request(
+		&self,
+		req: &llms::Request,
+	) -> Result<Self::Stream, LlmsError> {
+		let model = match &req.model {
+			llms::Model::OpenAiCompat(name) => name.clone(),
+			llms::Model::Custom { id, .. } => id.clone(),
+			m => unreachable!("unsupported model: {m:?}"),
+		};
+
+		let mut messages: Vec<ApiMessage> = Vec::new();
+
+		if !req.instructions.is_empty() {
+			messages.push(ApiMessage::System {
+				content: req.instructions.clone(),
+			});
+		}
+
+		messages.extend(ApiMessage::from_history(&req.input));
+
+		self.request(&Request {
+			messages,
+			model,
+			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tool_choice: req.tool_choice.clone().into(),
+			reasoning_effort: req.reasoning_effort.map(Into::into),
+			response_format: req.response_format.clone().into(),
+
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_tokens: req.params.max_tokens,
+			stop: req.params.stop.clone().unwrap_or_default(),
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
+		})
+		.await
+		.map_err(Into::into)
+	}
+}
+
+pub struct Request {
+	pub messages: Vec<ApiMessage>,
+	/// As named by the server, e.g. `"qwen3:8b"` for Ollama.
+	pub model: String,
+	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// Sent as `reasoning_effort`, servers and models without reasoning
+	/// ignore it.
+	pub reasoning_effort: Option<ReasoningEffort>,
+	pub response_format: Option<ResponseFormat>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_tokens: Option<u32>,
+	pub stop: Vec<String>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+	Low,
+	Medium,
+	High,
+}
+
+impl From<llms::ReasoningEffort> for ReasoningEffort {
+	fn from(e: llms::ReasoningEffort) -> Self {
+		match e {
+			llms::ReasoningEffort::Low => ReasoningEffort::Low,
+			llms::ReasoningEffort::Medium => ReasoningEffort::Medium,
+			llms::ReasoningEffort::High => ReasoningEffort::High,
+		}
+	}
+}
+
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		function: ToolChoiceFunction,
+	},
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+	None,
+	Required,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolChoiceFunction {
+	pub name: String,
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				function: ToolChoiceFunction { name },
+			}),
+		}
+	}
+}
+
+/// OpenAI-style `response_format`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+	Text,
+	JsonObject,
+	JsonSchema { json_schema: JsonSchema },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSchema {
+	pub name: String,
+	pub schema: Value,
+	pub strict: bool,
+}
+
+impl From<llms::ResponseFormat> for Option<ResponseFormat> {
+	fn from(format: llms::ResponseFormat) -> Self {
+		match format {
+			llms::ResponseFormat::Text => None,
+			llms::ResponseFormat::JsonObject => {
+				Some(ResponseFormat::JsonObject)
+			}
+			llms::ResponseFormat::JsonSchema {
+				name,
+				schema,
+				strict,
+			} => Some(ResponseFormat::JsonSchema {
+				json_schema: JsonSchema {
+					name,
+					schema,
+					strict,
+				},
+			}),
+		}
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiTool {
+	#[serde(rename = "type")]
+	pub kind: String,
+	pub function: ApiToolFunction,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiToolFunction {
+	pub name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// Full JSON Schema object (`{ "type": "object", "properties": { … } }`).
+	pub parameters: Value,
+}
+
+impl From<llms::Tool> for ApiTool {
+	fn from(tool: llms::Tool) -> Self {
+		ApiTool {
+			kind: "function".into(),
+			function: ApiToolFunction {
+				name: tool.name,
+				description: Some(tool.description).filter(|d| !d.is_empty()),
+				parameters: tool.parameters.unwrap_or_else(default_parameters),
+			},
+		}
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chunk {
+	#[serde(default)]
+	pub choices: Vec<ChunkChoice>,
+	/// Present on the final chunk when the upstream honors
+	/// `stream_options.include_usage`. Older deployments may omit it.
+	pub usage: Option<ApiUsage>,
+	/// Present when the upstream emits a mid-stream error frame instead of
+	/// a normal completion chunk. OpenAI-compatible APIs wrap the error in
+	/// `{ "error": { "message": ..., ... } }`.
+	pub error: Option<ApiErrorBody>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiErrorBody {
+	pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiUsage {
+	#[serde(default)]
+	pub prompt_tokens: u32,
+	#[serde(default)]
+	pub completion_tokens: u32,
+	/// Only sent by servers with prefix caching enabled.
+	#[serde(default)]
+	pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PromptTokensDetails {
+	#[serde(default)]
+	pub cached_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChunkChoice {
+	pub delta: Delta,
+	/// Set on the last chunk of the choice.
+	pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Delta {
+	pub content: Option<String>,
+	/// Reasoning text of servers with a reasoning parser, e.g. vLLM.
+	/// Ollama sends it as `reasoning`.
+	#[serde(alias = "reasoning")]
+	pub reasoning_content: Option<String>,
+	pub tool_calls: Option<Vec<ToolCallDelta>>,
+	/// Only sent for audio output, see
+	/// [`llms::ResponseEvent::TranscriptDelta`].
+	pub audio: Option<ApiAudioDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolCallDelta {
+	pub index: usize,
+	/// Only present on the first delta for a given slot.
+	pub id: Option<String>,
+	pub function: Option<ToolCallFunctionDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolCallFunctionDelta {
+	/// Only present on the first delta for a given slot.
+	pub name: Option<String>,
+	pub arguments: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OpenAiCompatError {
+	#[error("Invalid LLM response: {0}")]
+	InvalidLlmResponse(String),
+	#[error("No output in response")]
+	NoOutput,
+	#[error("Response error: status {status}, body {body}")]
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
+	#[error("Reqwest error: {0}")]
+	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for OpenAiCompatError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
+}
+
+impl From<OpenAiCompatError> for LlmsError {
+	fn from(e: OpenAiCompatError) -> Self {
+		match e {
+			OpenAiCompatError::InvalidLlmResponse(msg) => LlmsError::Response {
+				status: StatusCode::OK,
+				body: msg,
+				provider: llms::ProviderKind::OpenAiCompat,
+				request_id: None,
+			},
+			OpenAiCompatError::NoOutput => LlmsError::Response {
+				status: StatusCode::OK,
+				body: "no output in response".into(),
+				provider: llms::ProviderKind::OpenAiCompat,
+				request_id: None,
+			},
+			OpenAiCompatError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::OpenAiCompat,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
+			OpenAiCompatError::ReqwestError(e) => LlmsError::Reqwest(e),
+			OpenAiCompatError::Credential(e) => LlmsError::Credential(e),
+		}
+	}
+}
+
+impl From<SseError> for OpenAiCompatError {
+	fn from(e: SseError) -> Self {
+		match e {
+			SseError::Reqwest(e) => OpenAiCompatError::ReqwestError(e),
+			other => OpenAiCompatError::InvalidLlmResponse(other.to_string()),
+		}
+	}
+}
+
+#[derive(Default)]
+struct ToolCallAccumulator {
+	id: String,
+	name: String,
+	arguments: String,
+	/// Whether [`llms::LlmResponseEvent::ToolCallStarted`] was emitted.
+	started: bool,
+}
+
+pub struct ResponseStream {
+	inner: SseResponse,
+	/// Accumulated text across all content deltas. `None` until the first
+	/// non-empty content delta arrives.
+	text: Option<String>,
+	/// Accumulated `reasoning_content`, same as `text`.
+	reasoning: Option<String>,
+	/// Per-index tool call state. The index matches the `index` field in the
+	/// streaming delta and grows on demand.
+	tool_calls: Vec<ToolCallAccumulator>,
+	/// Events of the last chunk not returned yet, a chunk can hold
+	/// several tool call deltas and text.
+	pending: VecDeque<llms::LlmResponseEvent>,
+	/// Token usage from the final stream chunk (when reported by the upstream).
+	/// `None` until that chunk arrives.
+	usage: Option<llms::Usage>,
+	/// Of the last chunk that had one.
+	finish_reason: Option<String>,
+	done: bool,
+}
+
+impl std::fmt::Debug for ResponseStream {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ResponseStream")
+			.field("done", &self.done)
+			.finish()
+	}
+}
+
+impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
+	fn new(inner: SseResponse) -> Self {
+		Self {
+			inner,
+			text: None,
+			reasoning: None,
+			tool_calls: Vec::new(),
+			pending: VecDeque::new(),
+			usage: None,
+			finish_reason: None,
+			done: false,
+		}
+	}
+
+	fn build_response(&mut self) -> Result<llms::Response, OpenAiCompatError> {
+		let mut output =
+			Vec::with_capacity(self.tool_calls.len() + 1 /* text */);
+
+		if let Some(text) = self.text.take() {
+			output.push(llms::Output::Text { content: text });
+		}
+
+		for tc in self.tool_calls.drain(..) {
+			let input = serde_json::from_str(&tc.arguments).map_err(|e| {
+				OpenAiCompatError::InvalidLlmResponse(format!(
+					"invalid tool call arguments JSON for '{}': {e}",
+					tc.name
+				))
+			})?;
+
+			output.push(llms::Output::ToolCall {
+				id: tc.id,
+				name: tc.name,
+				input,
+				context: None,
+			});
+		}
+
+		if output.is_empty() {
+			return Err(OpenAiCompatError::NoOutput);
+		}
+
+		// the model reasoned before answering
+		if let Some(reasoning) = &self.reasoning {
+			output.insert(
+				0,
+				llms::Output::Reasoning {
+					content: reasoning.clone(),
+				},
+			);
+		}
+
+		let usage = self.usage.take().ok_or_else(|| {
+			OpenAiCompatError::InvalidLlmResponse(
+				"missing usage in response".into(),
+			)
+		})?;
+
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason: self
+				.finish_reason
+				.take()
+				.map(llms::FinishReason::from_chat),
+			reasoning: self.reasoning.take(),
+			..Default::default()
+		})
+	}
+}
+
+impl LlmResponseStream for ResponseStream {
+	async fn next(
+		&mut self,
+	) -> Option<Result<llms::LlmResponseEvent, LlmsError>> {
+		if self.done {
+			return None;
+		}
+
+		loop {
+			if let Some(ev) = self.pending.pop_front() {
+				return Some(Ok(ev));
+			}
+
+			let chunk: Chunk = match self.inner.next().await {
+				Some(Ok(c)) => c,
+				Some(Err(e)) => return Some(Err(e.into())),
+				None => {
+					self.done = true;
+					let response = self
+						.build_response()
+						.map(llms::LlmResponseEvent::Completed)
+						.map_err(Into::into);
+					return Some(response);
+				}
+			};
+
+			trace!("openai compat chunk: {chunk:?}");
+
+			if let Some(err) = chunk.error {
+				self.done = true;
+				return Some(Err(OpenAiCompatError::ResponseError {
+					status: StatusCode::OK,
+					body: err.message,
+					request_id: self.inner.request_id().map(Into::into),
+					retry_after: None,
+				}
+				.into()));
+			}
+
+			if let Some(usage) = chunk.usage {
+				self.usage = Some(llms::Usage {
+					input_tokens: usage.prompt_tokens,
+					output_tokens: usage.completion_tokens,
+					cache_read_tokens: usage
+						.prompt_tokens_details
+						.unwrap_or_default()
+						.cached_tokens,
+					..Default::default()
+				});
+			}
+
+			let choice = match chunk.choices.into_iter().next() {
+				Some(c) => c,
+				None => continue,
+			};
+
+			if choice.finish_reason.is_some() {
+				self.finish_reason = choice.finish_reason;
+			}
+
+			if let Some(reasoning) =
+				choice.delta.reasoning_content.filter(|r| !r.is_empty())
+			{
+				self.reasoning
+					.get_or_insert_with(String::new)
+					.push_str(&reasoning);
+				self.pending.push_back(
+					llms::LlmResponseEvent::ReasoningDelta {
+						content: reasoning,
+					},
+				);
+			}
+
+			if let Some(audio) = choice.delta.audio {
+				// the transcript is the text of an audio response
+				if let Some(transcript) =
+					audio.transcript.as_deref().filter(|t| !t.is_empty())
+				{
+					self.text
+						.get_or_insert_with(String::new)
+						.push_str(transcript);
+				}
+				self.pending.extend(audio.into_events());
+			}
+
+			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+				self.text.get_or_insert_with(String::new).push_str(&text);
+				self.pending.push_back(llms::LlmResponseEvent::TextDelta {
+					content: text,
+				});
+			}
+
+			if let Some(tc_deltas) = choice.delta.tool_calls {
+				for delta in tc_deltas {
+					// Grow the accumulator vec on demand (indices are always
+					// contiguous and arrive in order per the spec).
+					self.tool_calls
+						.resize_with(delta.index + 1, Default::default);
+
+					let acc = &mut self.tool_calls[delta.index];
+
+					if let Some(id) = delta.id {
+						acc.id = id;
+					}
+
+					let mut args_delta = String::new();
+					if let Some(func) = delta.function {
+						if let Some(name) = func.name {
+							acc.name = name;
+						}
+						if let Some(args) = func.arguments {
+							acc.arguments.push_str(&args);
+							args_delta = args;
+						}
+					}
+
+					// the id and name usually come with the first delta
+					if !acc.started
+						&& !acc.id.is_empty()
+						&& !acc.name.is_empty()
+					{
+						acc.started = true;
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallStarted {
+								id: acc.id.clone(),
+								name: acc.name.clone(),
+							},
+						);
+						// arguments received before the name
+						args_delta = acc.arguments.clone();
+					}
+					if acc.started && !args_delta.is_empty() {
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallArgumentsDelta {
+								id: acc.id.clone(),
+								delta: args_delta,
+							},
+						);
+					}
+				}
+			}
+		}
+	}
+}
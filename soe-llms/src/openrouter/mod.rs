@@ -1,30 +1,122 @@
-use std::fmt;
+pub use crate::llms::chat::{
+	ApiAudioDelta, ApiContentPart, ApiImageUrl, ApiMessage, ApiToolCall,
+	ApiToolCallFunction, ApiUserContent,
+};
+
+use std::{collections::VecDeque, fmt, time::Duration};
 
-use reqwest::{Client, StatusCode};
+use reqwest::{Client, RequestBuilder, StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tracing::trace;
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
+/// Default of [`OpenRouter::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
 #[derive(Clone)]
 pub struct OpenRouter {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/openrouter/v1"`.
+	pub base_url: String,
+	/// Sent as `HTTP-Referer`, attributes the requests to the app in
+	/// OpenRouter's rankings and analytics.
+	pub app_url: Option<String>,
+	/// Sent as `X-Title`, the app's name shown next to
+	/// [`OpenRouter::app_url`].
+	pub app_name: Option<String>,
+	/// Which upstream providers every request may be routed to.
+	pub provider: Option<ProviderPreferences>,
 }
 
 impl OpenRouter {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+			app_url: None,
+			app_name: None,
+			provider: None,
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	pub fn app_url(mut self, url: impl Into<String>) -> Self {
+		self.app_url = Some(url.into());
+		self
+	}
+
+	pub fn app_name(mut self, name: impl Into<String>) -> Self {
+		self.app_name = Some(name.into());
+		self
+	}
+
+	pub fn provider(mut self, provider: ProviderPreferences) -> Self {
+		self.provider = Some(provider);
+		self
+	}
+
+	/// Adds the app attribution headers if set.
+	fn app_headers(&self, mut builder: RequestBuilder) -> RequestBuilder {
+		if let Some(url) = &self.app_url {
+			builder = builder.header("HTTP-Referer", url);
+		}
+		if let Some(name) = &self.app_name {
+			builder = builder.header("X-Title", name);
 		}
+		builder
+	}
+
+	/// Fetches the info of the api key, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), OpenRouterError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url("key"))
+			.bearer_auth(&*api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
 	}
 
 	pub async fn request(
@@ -38,9 +130,23 @@ impl OpenRouter {
 			#[serde(skip_serializing_if = "Vec::is_empty")]
 			tools: &'a Vec<ApiTool>,
 			#[serde(skip_serializing_if = "Option::is_none")]
+			tool_choice: Option<&'a ToolChoice>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			response_format: Option<&'a ResponseFormat>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			temperature: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			top_p: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			max_tokens: Option<u32>,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			stop: &'a Vec<String>,
+			#[serde(skip_serializing_if = "Option::is_none")]
 			user: Option<&'a str>,
 			#[serde(skip_serializing_if = "Option::is_none")]
 			reasoning: Option<Reasoning>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			provider: Option<&'a ProviderPreferences>,
 			stream: bool,
 			stream_options: StreamOptions,
 		}
@@ -54,8 +160,18 @@ impl OpenRouter {
 			model: &req.model,
 			messages: &req.messages,
 			tools: &req.tools,
+			tool_choice: req
+				.tool_choice
+				.as_ref()
+				.filter(|_| !req.tools.is_empty()),
+			response_format: req.response_format.as_ref(),
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_tokens: req.max_tokens,
+			stop: &req.stop,
 			user: req.user.as_deref(),
 			reasoning: req.reasoning_effort.map(|effort| Reasoning { effort }),
+			provider: req.provider.as_ref(),
 			stream: true,
 			stream_options: StreamOptions {
 				include_usage: true,
@@ -64,21 +180,45 @@ impl OpenRouter {
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://openrouter.ai/api/v1/chat/completions")
-			.bearer_auth(&self.api_key)
-			.json(&api_req)
-			.send()
-			.await?;
+			.post(self.url("chat/completions"))
+			.bearer_auth(&*api_key);
+		let builder = self.app_headers(builder).headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(OpenRouterError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream::new(SseResponse::new(
+			resp,
+			req.transcript.as_ref(),
+			&api_key,
+			req.read_timeout,
+		)))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
 	}
 }
 
@@ -101,6 +241,7 @@ impl LlmProvider for OpenRouter {
 			llms::Model::OpenRouter(name) => {
 				name.clone().unwrap_or_else(|| "openrouter/auto".into())
 			}
+			llms::Model::Custom { id, .. } => id.clone(),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
@@ -112,14 +253,26 @@ impl LlmProvider for OpenRouter {
 			});
 		}
 
-		messages.extend(req.input.iter().cloned().map(ApiMessage::from));
+		messages.extend(ApiMessage::from_history(&req.input));
 
 		self.request(&Request {
 			messages,
 			model,
 			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tool_choice: req.tool_choice.clone().into(),
+			response_format: req.response_format.clone().into(),
 			user: Some(req.user_id.clone()).filter(|u| !u.is_empty()),
 			reasoning_effort: req.reasoning_effort.map(Into::into),
+			provider: self.provider.clone(),
+
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_tokens: req.params.max_tokens,
+			stop: req.params.stop.clone().unwrap_or_default(),
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
 		})
 		.await
 		.map_err(Into::into)
@@ -133,12 +286,30 @@ pub struct Request {
 	/// `"openrouter/auto"`.
 	pub model: String,
 	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// `None` lets the model answer with plain text.
+	pub response_format: Option<ResponseFormat>,
 	/// Stable identifier for the end-user, used by OpenRouter to detect and
 	/// prevent abuse.
 	pub user: Option<String>,
 	/// OpenRouter normalizes this across providers via its unified
 	/// `reasoning` parameter; models that don't reason ignore it.
 	pub reasoning_effort: Option<ReasoningEffort>,
+	/// Overrides [`OpenRouter::provider`] for this request.
+	pub provider: Option<ProviderPreferences>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_tokens: Option<u32>,
+	pub stop: Vec<String>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
 }
 
 #[derive(Debug, Serialize)]
@@ -146,89 +317,159 @@ struct Reasoning {
 	effort: ReasoningEffort,
 }
 
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		function: ToolChoiceFunction,
+	},
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 #[serde(rename_all = "lowercase")]
-pub enum ReasoningEffort {
-	Low,
-	Medium,
-	High,
+pub enum ToolChoiceMode {
+	None,
+	Required,
 }
 
-impl From<llms::ReasoningEffort> for ReasoningEffort {
-	fn from(e: llms::ReasoningEffort) -> Self {
-		match e {
-			llms::ReasoningEffort::Low => ReasoningEffort::Low,
-			llms::ReasoningEffort::Medium => ReasoningEffort::Medium,
-			llms::ReasoningEffort::High => ReasoningEffort::High,
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolChoiceFunction {
+	pub name: String,
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				function: ToolChoiceFunction { name },
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "role", rename_all = "lowercase")]
-pub enum ApiMessage {
-	System {
-		content: String,
-	},
-	User {
-		content: String,
-	},
-	Assistant {
-		#[serde(skip_serializing_if = "Option::is_none")]
-		content: Option<String>,
-		#[serde(skip_serializing_if = "Option::is_none")]
-		tool_calls: Option<Vec<ApiToolCall>>,
-	},
-	Tool {
-		tool_call_id: String,
-		content: String,
-	},
+/// OpenAI-style `response_format`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+	Text,
+	JsonObject,
+	JsonSchema { json_schema: JsonSchema },
 }
 
-impl From<llms::Input> for ApiMessage {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => match role {
-				llms::Role::User => ApiMessage::User { content },
-				llms::Role::Assistant => ApiMessage::Assistant {
-					content: Some(content),
-					tool_calls: None,
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSchema {
+	pub name: String,
+	pub schema: Value,
+	pub strict: bool,
+}
+
+impl From<llms::ResponseFormat> for Option<ResponseFormat> {
+	fn from(format: llms::ResponseFormat) -> Self {
+		match format {
+			llms::ResponseFormat::Text => None,
+			llms::ResponseFormat::JsonObject => {
+				Some(ResponseFormat::JsonObject)
+			}
+			llms::ResponseFormat::JsonSchema {
+				name,
+				schema,
+				strict,
+			} => Some(ResponseFormat::JsonSchema {
+				json_schema: JsonSchema {
+					name,
+					schema,
+					strict,
 				},
-			},
-			llms::Input::ToolCall {
-				id, name, input, ..
-			} => ApiMessage::Assistant {
-				content: None,
-				tool_calls: Some(vec![ApiToolCall {
-					id,
-					kind: "function".into(),
-					function: ApiToolCallFunction {
-						name,
-						arguments: input.to_string(),
-					},
-				}]),
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiMessage::Tool {
-				tool_call_id: id,
-				content: output,
-			},
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCall {
-	pub id: String,
-	#[serde(rename = "type")]
-	pub kind: String,
-	pub function: ApiToolCallFunction,
+/// How OpenRouter picks the upstream provider serving a model, `provider`
+/// in the body. Empty fields leave OpenRouter's defaults.
+///
+/// ```
+/// # use soe_llms::openrouter::{ProviderPreferences, ProviderSort};
+/// let prefs = ProviderPreferences {
+///     order: vec!["anthropic".into(), "amazon-bedrock".into()],
+///     sort: Some(ProviderSort::Throughput),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderPreferences {
+	/// Provider slugs tried in this order, e.g. `"anthropic"`.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub order: Vec<String>,
+	/// Only these providers are used.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub only: Vec<String>,
+	/// These providers are never used.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub ignore: Vec<String>,
+	/// Whether other providers may serve the request if the preferred
+	/// ones fail, OpenRouter defaults to `true`.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub allow_fallbacks: Option<bool>,
+	/// Only use providers supporting every parameter of the request, e.g.
+	/// tools or the response format.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub require_parameters: Option<bool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub data_collection: Option<DataCollection>,
+	/// Quantization levels allowed, e.g. `"fp8"`.
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub quantizations: Vec<String>,
+	/// Sort the providers instead of balancing the load by price.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sort: Option<ProviderSort>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCallFunction {
-	pub name: String,
-	/// JSON-encoded arguments string.
-	pub arguments: String,
+/// Whether providers storing or training on prompts may be used.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataCollection {
+	Allow,
+	Deny,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderSort {
+	Price,
+	Throughput,
+	Latency,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+	Low,
+	Medium,
+	High,
+}
+
+impl From<llms::ReasoningEffort> for ReasoningEffort {
+	fn from(e: llms::ReasoningEffort) -> Self {
+		match e {
+			llms::ReasoningEffort::Low => ReasoningEffort::Low,
+			llms::ReasoningEffort::Medium => ReasoningEffort::Medium,
+			llms::ReasoningEffort::High => ReasoningEffort::High,
+		}
+	}
 }
 
 #[derive(Debug, Serialize)]
@@ -284,11 +525,52 @@ pub struct ApiUsage {
 	pub prompt_tokens: u32,
 	#[serde(default)]
 	pub completion_tokens: u32,
+	/// Sometimes `null`.
+	#[serde(default)]
+	pub prompt_tokens_details: Option<PromptTokensDetails>,
+	#[serde(default)]
+	pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl From<ApiUsage> for llms::Usage {
+	fn from(usage: ApiUsage) -> Self {
+		let prompt = usage.prompt_tokens_details.unwrap_or_default();
+		let reasoning = usage
+			.completion_tokens_details
+			.unwrap_or_default()
+			.reasoning_tokens;
+
+		llms::Usage {
+			input_tokens: usage.prompt_tokens,
+			output_tokens: usage.completion_tokens,
+			cache_read_tokens: prompt.cached_tokens,
+			reasoning_tokens: reasoning,
+			audio_input_tokens: prompt.audio_tokens,
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PromptTokensDetails {
+	#[serde(default)]
+	pub cached_tokens: u32,
+	#[serde(default)]
+	pub audio_tokens: u32,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CompletionTokensDetails {
+	/// Part of `completion_tokens`.
+	#[serde(default)]
+	pub reasoning_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChunkChoice {
 	pub delta: Delta,
+	/// Set on the last chunk of the choice.
+	pub finish_reason: Option<String>,
 	/// OpenRouter can also report a per-choice error (e.g. when the
 	/// upstream provider failed mid-generation).
 	pub error: Option<ApiErrorBody>,
@@ -297,7 +579,13 @@ pub struct ChunkChoice {
 #[derive(Debug, Deserialize)]
 pub struct Delta {
 	pub content: Option<String>,
+	/// Reasoning text of backends with a reasoning parser, e.g. DeepSeek,
+	/// Qwen or vLLM.
+	pub reasoning_content: Option<String>,
 	pub tool_calls: Option<Vec<ToolCallDelta>>,
+	/// Only sent for audio output, see
+	/// [`llms::ResponseEvent::TranscriptDelta`].
+	pub audio: Option<ApiAudioDelta>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -322,9 +610,30 @@ pub enum OpenRouterError {
 	#[error("No output in response")]
 	NoOutput,
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for OpenRouterError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<OpenRouterError> for LlmsError {
@@ -333,15 +642,29 @@ impl From<OpenRouterError> for LlmsError {
 			OpenRouterError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::OpenRouter,
+				request_id: None,
 			},
 			OpenRouterError::NoOutput => LlmsError::Response {
 				status: StatusCode::OK,
 				body: "no output in response".into(),
+				provider: llms::ProviderKind::OpenRouter,
+				request_id: None,
 			},
-			OpenRouterError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			OpenRouterError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::OpenRouter,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			OpenRouterError::ReqwestError(e) => LlmsError::Reqwest(e),
+			OpenRouterError::Credential(e) => LlmsError::Credential(e),
 		}
 	}
 }
@@ -360,6 +683,8 @@ struct ToolCallAccumulator {
 	id: String,
 	name: String,
 	arguments: String,
+	/// Whether [`llms::LlmResponseEvent::ToolCallStarted`] was emitted.
+	started: bool,
 }
 
 pub struct ResponseStream {
@@ -367,12 +692,19 @@ pub struct ResponseStream {
 	/// Accumulated text across all content deltas. `None` until the first
 	/// non-empty content delta arrives.
 	text: Option<String>,
+	/// Accumulated `reasoning_content`, same as `text`.
+	reasoning: Option<String>,
 	/// Per-index tool call state. The index matches the `index` field in the
 	/// streaming delta and grows on demand.
 	tool_calls: Vec<ToolCallAccumulator>,
+	/// Events of the last chunk not returned yet, a chunk can hold
+	/// several tool call deltas and text.
+	pending: VecDeque<llms::LlmResponseEvent>,
 	/// Token usage from the final stream chunk. `None` until that chunk
 	/// arrives.
 	usage: Option<llms::Usage>,
+	/// Of the last chunk that had one.
+	finish_reason: Option<String>,
 	done: bool,
 }
 
@@ -385,12 +717,20 @@ impl std::fmt::Debug for ResponseStream {
 }
 
 impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
 	fn new(inner: SseResponse) -> Self {
 		Self {
 			inner,
 			text: None,
+			reasoning: None,
 			tool_calls: Vec::new(),
+			pending: VecDeque::new(),
 			usage: None,
+			finish_reason: None,
 			done: false,
 		}
 	}
@@ -423,13 +763,32 @@ impl ResponseStream {
 			return Err(OpenRouterError::NoOutput);
 		}
 
+		// the model reasoned before answering
+		if let Some(reasoning) = &self.reasoning {
+			output.insert(
+				0,
+				llms::Output::Reasoning {
+					content: reasoning.clone(),
+				},
+			);
+		}
+
 		let usage = self.usage.take().ok_or_else(|| {
 			OpenRouterError::InvalidLlmResponse(
 				"missing usage in response".into(),
 			)
 		})?;
 
-		Ok(llms::Response { output, usage })
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason: self
+				.finish_reason
+				.take()
+				.map(llms::FinishReason::from_chat),
+			reasoning: self.reasoning.take(),
+			..Default::default()
+		})
 	}
 }
 
@@ -442,6 +801,10 @@ impl LlmResponseStream for ResponseStream {
 		}
 
 		loop {
+			if let Some(ev) = self.pending.pop_front() {
+				return Some(Ok(ev));
+			}
+
 			let chunk: Chunk = match self.inner.next().await {
 				Some(Ok(c)) => c,
 				Some(Err(e)) => return Some(Err(e.into())),
@@ -462,15 +825,14 @@ impl LlmResponseStream for ResponseStream {
 				return Some(Err(OpenRouterError::ResponseError {
 					status: StatusCode::OK,
 					body: err.message,
+					request_id: self.inner.request_id().map(Into::into),
+					retry_after: None,
 				}
 				.into()));
 			}
 
 			if let Some(usage) = chunk.usage {
-				self.usage = Some(llms::Usage {
-					input_tokens: usage.prompt_tokens,
-					output_tokens: usage.completion_tokens,
-				});
+				self.usage = Some(usage.into());
 			}
 
 			let choice = match chunk.choices.into_iter().next() {
@@ -478,15 +840,53 @@ impl LlmResponseStream for ResponseStream {
 				None => continue,
 			};
 
+			if choice.finish_reason.is_some() {
+				self.finish_reason = choice.finish_reason;
+			}
+
 			if let Some(err) = choice.error {
 				self.done = true;
 				return Some(Err(OpenRouterError::ResponseError {
 					status: StatusCode::OK,
 					body: err.message,
+					request_id: self.inner.request_id().map(Into::into),
+					retry_after: None,
 				}
 				.into()));
 			}
 
+			if let Some(reasoning) =
+				choice.delta.reasoning_content.filter(|r| !r.is_empty())
+			{
+				self.reasoning
+					.get_or_insert_with(String::new)
+					.push_str(&reasoning);
+				self.pending.push_back(
+					llms::LlmResponseEvent::ReasoningDelta {
+						content: reasoning,
+					},
+				);
+			}
+
+			if let Some(audio) = choice.delta.audio {
+				// the transcript is the text of an audio response
+				if let Some(transcript) =
+					audio.transcript.as_deref().filter(|t| !t.is_empty())
+				{
+					self.text
+						.get_or_insert_with(String::new)
+						.push_str(transcript);
+				}
+				self.pending.extend(audio.into_events());
+			}
+
+			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+				self.text.get_or_insert_with(String::new).push_str(&text);
+				self.pending.push_back(llms::LlmResponseEvent::TextDelta {
+					content: text,
+				});
+			}
+
 			if let Some(tc_deltas) = choice.delta.tool_calls {
 				for delta in tc_deltas {
 					// Grow the accumulator vec on demand (indices are always
@@ -500,22 +900,41 @@ impl LlmResponseStream for ResponseStream {
 						acc.id = id;
 					}
 
+					let mut args_delta = String::new();
 					if let Some(func) = delta.function {
 						if let Some(name) = func.name {
 							acc.name = name;
 						}
 						if let Some(args) = func.arguments {
 							acc.arguments.push_str(&args);
+							args_delta = args;
 						}
 					}
-				}
-			}
 
-			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
-				self.text.get_or_insert_with(String::new).push_str(&text);
-				return Some(Ok(llms::LlmResponseEvent::TextDelta {
-					content: text,
-				}));
+					// the id and name usually come with the first delta
+					if !acc.started
+						&& !acc.id.is_empty()
+						&& !acc.name.is_empty()
+					{
+						acc.started = true;
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallStarted {
+								id: acc.id.clone(),
+								name: acc.name.clone(),
+							},
+						);
+						// arguments received before the name
+						args_delta = acc.arguments.clone();
+					}
+					if acc.started && !args_delta.is_empty() {
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallArgumentsDelta {
+								id: acc.id.clone(),
+								delta: args_delta,
+							},
+						);
+					}
+				}
 			}
 		}
 	}
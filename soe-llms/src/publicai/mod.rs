@@ -1,51 +1,110 @@
-use std::fmt;
+pub use crate::llms::chat::{
+	ApiContentPart, ApiImageUrl, ApiMessage, ApiToolCall, ApiToolCallFunction,
+	ApiUserContent,
+};
+
+use std::{collections::VecDeque, fmt, time::Duration};
 
 use reqwest::{
 	Client, StatusCode,
-	header::{HeaderValue, USER_AGENT},
+	header::{HeaderMap, HeaderValue, USER_AGENT},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tracing::{debug, trace};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
+/// Default of [`PublicAi::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://api.publicai.co/v1";
+
 #[derive(Clone)]
 pub struct PublicAi {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/publicai/v1"`.
+	pub base_url: String,
 }
 
 impl PublicAi {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
+		}
+	}
+
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), PublicAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url("models"))
+			.bearer_auth(&*api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
 		}
+
+		Ok(())
 	}
 
 	pub async fn request(
 		&self,
 		req: &Request,
 	) -> Result<ResponseStream, PublicAiError> {
-		if !req.tools.is_empty() {
-			return Err(PublicAiError::InvalidLlmResponse(
-				"tools are not supported by the PublicAI API".into(),
-			));
-		}
-
 		#[derive(Debug, Serialize)]
 		struct ApiReq<'a> {
 			model: &'a str,
 			messages: &'a Vec<ApiMessage>,
 			#[serde(skip_serializing_if = "Vec::is_empty")]
 			tools: &'a Vec<ApiTool>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			tool_choice: Option<&'a ToolChoice>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			temperature: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			top_p: Option<f32>,
+			#[serde(skip_serializing_if = "Option::is_none")]
+			max_tokens: Option<u32>,
+			#[serde(skip_serializing_if = "Vec::is_empty")]
+			stop: &'a Vec<String>,
 			stream: bool,
 			stream_options: StreamOptions,
 		}
@@ -56,9 +115,17 @@ impl PublicAi {
 		}
 
 		let api_req = ApiReq {
-			model: req.model.as_str(),
+			model: req.snapshot.as_deref().unwrap_or(req.model.as_str()),
 			messages: &req.messages,
 			tools: &req.tools,
+			tool_choice: req
+				.tool_choice
+				.as_ref()
+				.filter(|_| !req.tools.is_empty()),
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_tokens: req.max_tokens,
+			stop: &req.stop,
 			stream: true,
 			stream_options: StreamOptions {
 				include_usage: true,
@@ -67,22 +134,46 @@ impl PublicAi {
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://api.publicai.co/v1/chat/completions")
-			.bearer_auth(&self.api_key)
+			.post(self.url("chat/completions"))
+			.bearer_auth(&*api_key)
 			.header(USER_AGENT, HeaderValue::from_static("soe-llms/1.0"))
-			.json(&api_req)
-			.send()
-			.await?;
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(PublicAiError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream::new(SseResponse::new(
+			resp,
+			req.transcript.as_ref(),
+			&api_key,
+			req.read_timeout,
+		)))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
 	}
 }
 
@@ -105,6 +196,7 @@ impl LlmProvider for PublicAi {
 
 		let model = match &req.model {
 			llms::Model::Apertus8bInstruct => ApertusModel::Apertus8bInstruct,
+			llms::Model::Custom { id, .. } => ApertusModel::Custom(id.clone()),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
@@ -116,12 +208,23 @@ impl LlmProvider for PublicAi {
 			});
 		}
 
-		messages.extend(req.input.iter().cloned().map(ApiMessage::from));
+		messages.extend(ApiMessage::from_history(&req.input));
 
 		self.request(&Request {
 			messages,
 			model,
+			snapshot: req.snapshot.clone(),
 			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tool_choice: req.tool_choice.clone().into(),
+
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_tokens: req.params.max_tokens,
+			stop: req.params.stop.clone().unwrap_or_default(),
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
 		})
 		.await
 		.map_err(Into::into)
@@ -131,87 +234,81 @@ impl LlmProvider for PublicAi {
 pub struct Request {
 	pub messages: Vec<ApiMessage>,
 	pub model: ApertusModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	pub snapshot: Option<String>,
 	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_tokens: Option<u32>,
+	pub stop: Vec<String>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum ApertusModel {
-	Apertus8bInstruct,
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		function: ToolChoiceFunction,
+	},
 }
 
-impl ApertusModel {
-	pub fn as_str(&self) -> &'static str {
-		match self {
-			ApertusModel::Apertus8bInstruct => "swiss-ai/apertus-8b-instruct",
-		}
-	}
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+	None,
+	Required,
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "role", rename_all = "lowercase")]
-pub enum ApiMessage {
-	System {
-		content: String,
-	},
-	User {
-		content: String,
-	},
-	Assistant {
-		#[serde(skip_serializing_if = "Option::is_none")]
-		content: Option<String>,
-		#[serde(skip_serializing_if = "Option::is_none")]
-		tool_calls: Option<Vec<ApiToolCall>>,
-	},
-	Tool {
-		tool_call_id: String,
-		content: String,
-	},
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolChoiceFunction {
+	pub name: String,
 }
 
-impl From<llms::Input> for ApiMessage {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => match role {
-				llms::Role::User => ApiMessage::User { content },
-				llms::Role::Assistant => ApiMessage::Assistant {
-					content: Some(content),
-					tool_calls: None,
-				},
-			},
-			llms::Input::ToolCall {
-				id, name, input, ..
-			} => ApiMessage::Assistant {
-				content: None,
-				tool_calls: Some(vec![ApiToolCall {
-					id,
-					kind: "function".into(),
-					function: ApiToolCallFunction {
-						name,
-						arguments: input.to_string(),
-					},
-				}]),
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiMessage::Tool {
-				tool_call_id: id,
-				content: output,
-			},
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				function: ToolChoiceFunction { name },
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCall {
-	pub id: String,
-	#[serde(rename = "type")]
-	pub kind: String,
-	pub function: ApiToolCallFunction,
+#[derive(Debug, Clone)]
+pub enum ApertusModel {
+	Apertus8bInstruct,
+	/// Any other model id, see [`llms::Model::Custom`].
+	Custom(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCallFunction {
-	pub name: String,
-	/// JSON-encoded arguments string.
-	pub arguments: String,
+impl ApertusModel {
+	pub fn as_str(&self) -> &str {
+		match self {
+			ApertusModel::Apertus8bInstruct => "swiss-ai/apertus-8b-instruct",
+			ApertusModel::Custom(id) => id,
+		}
+	}
 }
 
 #[derive(Debug, Serialize)]
@@ -267,16 +364,30 @@ pub struct ApiUsage {
 	pub prompt_tokens: u32,
 	#[serde(default)]
 	pub completion_tokens: u32,
+	/// Only sent by backends with prefix caching enabled.
+	#[serde(default)]
+	pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PromptTokensDetails {
+	#[serde(default)]
+	pub cached_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChunkChoice {
 	pub delta: Delta,
+	/// Set on the last chunk of the choice.
+	pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Delta {
 	pub content: Option<String>,
+	/// Reasoning text of backends with a reasoning parser, e.g. DeepSeek,
+	/// Qwen or vLLM.
+	pub reasoning_content: Option<String>,
 	pub tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
@@ -302,9 +413,30 @@ pub enum PublicAiError {
 	#[error("No output in response")]
 	NoOutput,
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for PublicAiError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<PublicAiError> for LlmsError {
@@ -313,15 +445,29 @@ impl From<PublicAiError> for LlmsError {
 			PublicAiError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::PublicAi,
+				request_id: None,
 			},
 			PublicAiError::NoOutput => LlmsError::Response {
 				status: StatusCode::OK,
 				body: "no output in response".into(),
+				provider: llms::ProviderKind::PublicAi,
+				request_id: None,
 			},
-			PublicAiError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			PublicAiError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::PublicAi,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			PublicAiError::ReqwestError(e) => LlmsError::Reqwest(e),
+			PublicAiError::Credential(e) => LlmsError::Credential(e),
 		}
 	}
 }
@@ -340,6 +486,8 @@ struct ToolCallAccumulator {
 	id: String,
 	name: String,
 	arguments: String,
+	/// Whether [`llms::LlmResponseEvent::ToolCallStarted`] was emitted.
+	started: bool,
 }
 
 pub struct ResponseStream {
@@ -347,12 +495,19 @@ pub struct ResponseStream {
 	/// Accumulated text across all content deltas. `None` until the first
 	/// non-empty content delta arrives.
 	text: Option<String>,
+	/// Accumulated `reasoning_content`, same as `text`.
+	reasoning: Option<String>,
 	/// Per-index tool call state. The index matches the `index` field in the
 	/// streaming delta and grows on demand.
 	tool_calls: Vec<ToolCallAccumulator>,
+	/// Events of the last chunk not returned yet, a chunk can hold
+	/// several tool call deltas and text.
+	pending: VecDeque<llms::LlmResponseEvent>,
 	/// Token usage from the final stream chunk (when reported by the upstream).
 	/// `None` until that chunk arrives.
 	usage: Option<llms::Usage>,
+	/// Of the last chunk that had one.
+	finish_reason: Option<String>,
 	done: bool,
 }
 
@@ -365,12 +520,20 @@ impl std::fmt::Debug for ResponseStream {
 }
 
 impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
 	fn new(inner: SseResponse) -> Self {
 		Self {
 			inner,
 			text: None,
+			reasoning: None,
 			tool_calls: Vec::new(),
+			pending: VecDeque::new(),
 			usage: None,
+			finish_reason: None,
 			done: false,
 		}
 	}
@@ -403,13 +566,32 @@ impl ResponseStream {
 			return Err(PublicAiError::NoOutput);
 		}
 
+		// the model reasoned before answering
+		if let Some(reasoning) = &self.reasoning {
+			output.insert(
+				0,
+				llms::Output::Reasoning {
+					content: reasoning.clone(),
+				},
+			);
+		}
+
 		let usage = self.usage.take().ok_or_else(|| {
 			PublicAiError::InvalidLlmResponse(
 				"missing usage in response".into(),
 			)
 		})?;
 
-		Ok(llms::Response { output, usage })
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason: self
+				.finish_reason
+				.take()
+				.map(llms::FinishReason::from_chat),
+			reasoning: self.reasoning.take(),
+			..Default::default()
+		})
 	}
 }
 
@@ -422,6 +604,10 @@ impl LlmResponseStream for ResponseStream {
 		}
 
 		loop {
+			if let Some(ev) = self.pending.pop_front() {
+				return Some(Ok(ev));
+			}
+
 			let chunk: Chunk = match self.inner.next().await {
 				Some(Ok(c)) => c,
 				Some(Err(e)) => return Some(Err(e.into())),
@@ -442,6 +628,8 @@ impl LlmResponseStream for ResponseStream {
 				return Some(Err(PublicAiError::ResponseError {
 					status: StatusCode::OK,
 					body: err.message,
+					request_id: self.inner.request_id().map(Into::into),
+					retry_after: None,
 				}
 				.into()));
 			}
@@ -450,6 +638,11 @@ impl LlmResponseStream for ResponseStream {
 				self.usage = Some(llms::Usage {
 					input_tokens: usage.prompt_tokens,
 					output_tokens: usage.completion_tokens,
+					cache_read_tokens: usage
+						.prompt_tokens_details
+						.unwrap_or_default()
+						.cached_tokens,
+					..Default::default()
 				});
 			}
 
@@ -458,6 +651,30 @@ impl LlmResponseStream for ResponseStream {
 				None => continue,
 			};
 
+			if choice.finish_reason.is_some() {
+				self.finish_reason = choice.finish_reason;
+			}
+
+			if let Some(reasoning) =
+				choice.delta.reasoning_content.filter(|r| !r.is_empty())
+			{
+				self.reasoning
+					.get_or_insert_with(String::new)
+					.push_str(&reasoning);
+				self.pending.push_back(
+					llms::LlmResponseEvent::ReasoningDelta {
+						content: reasoning,
+					},
+				);
+			}
+
+			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+				self.text.get_or_insert_with(String::new).push_str(&text);
+				self.pending.push_back(llms::LlmResponseEvent::TextDelta {
+					content: text,
+				});
+			}
+
 			if let Some(tc_deltas) = choice.delta.tool_calls {
 				for delta in tc_deltas {
 					// Grow the accumulator vec on demand (indices are always
@@ -471,22 +688,41 @@ impl LlmResponseStream for ResponseStream {
 						acc.id = id;
 					}
 
+					let mut args_delta = String::new();
 					if let Some(func) = delta.function {
 						if let Some(name) = func.name {
 							acc.name = name;
 						}
 						if let Some(args) = func.arguments {
 							acc.arguments.push_str(&args);
+							args_delta = args;
 						}
 					}
-				}
-			}
 
-			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
-				self.text.get_or_insert_with(String::new).push_str(&text);
-				return Some(Ok(llms::LlmResponseEvent::TextDelta {
-					content: text,
-				}));
+					// the id and name usually come with the first delta
+					if !acc.started
+						&& !acc.id.is_empty()
+						&& !acc.name.is_empty()
+					{
+						acc.started = true;
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallStarted {
+								id: acc.id.clone(),
+								name: acc.name.clone(),
+							},
+						);
+						// arguments received before the name
+						args_delta = acc.arguments.clone();
+					}
+					if acc.started && !args_delta.is_empty() {
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallArgumentsDelta {
+								id: acc.id.clone(),
+								delta: args_delta,
+							},
+						);
+					}
+				}
 			}
 		}
 	}
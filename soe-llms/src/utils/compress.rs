@@ -0,0 +1,64 @@
+use reqwest::RequestBuilder;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE, HeaderValue};
+use serde::Serialize;
+
+use crate::llms::media;
+use crate::{COMPRESSION_MIN_SIZE, Compression};
+
+/// Sets `body` as the json body of the request, compressing it if it is
+/// large enough.
+///
+/// The data of [`MediaReader`](crate::llms::MediaReader)s in the body is
+/// streamed while sending, such bodies aren't compressed.
+///
+/// Falls back to an uncompressed body if anything fails, serialization
+/// errors are then reported by reqwest when sending.
+pub fn json_body<T: Serialize + ?Sized>(
+	builder: RequestBuilder,
+	body: &T,
+	compression: Option<Compression>,
+) -> RequestBuilder {
+	let Ok(json) = serde_json::to_vec(body) else {
+		return builder.json(body);
+	};
+
+	let builder = builder
+		.header(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+	if let Some(body) = media::streaming_body(&json) {
+		return builder.body(body);
+	}
+
+	let compressed = compression
+		.filter(|_| json.len() >= COMPRESSION_MIN_SIZE)
+		.and_then(|compression| compress(compression, &json));
+	let Some((encoding, data)) = compressed else {
+		return builder.body(json);
+	};
+
+	builder
+		.header(CONTENT_ENCODING, HeaderValue::from_static(encoding))
+		.body(data)
+}
+
+#[allow(unused_variables)]
+fn compress(
+	compression: Compression,
+	data: &[u8],
+) -> Option<(&'static str, Vec<u8>)> {
+	match compression {
+		#[cfg(feature = "gzip")]
+		Compression::Gzip => {
+			use std::io::Write;
+
+			use flate2::write::GzEncoder;
+
+			let mut encoder =
+				GzEncoder::new(Vec::new(), flate2::Compression::default());
+			encoder.write_all(data).ok()?;
+			Some(("gzip", encoder.finish().ok()?))
+		}
+		#[cfg(feature = "zstd")]
+		Compression::Zstd => Some(("zstd", zstd::encode_all(data, 0).ok()?)),
+	}
+}
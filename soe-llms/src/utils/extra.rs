@@ -0,0 +1,44 @@
+use serde::{Serialize, Serializer, ser::Error as _};
+use serde_json::{Map, Value};
+
+/// Serializes `body` with `extra` merged in, see
+/// [`Request::extra`](crate::Request::extra).
+pub struct WithExtra<'a, T: ?Sized> {
+	pub body: &'a T,
+	pub extra: &'a Map<String, Value>,
+}
+
+impl<T: Serialize + ?Sized> Serialize for WithExtra<'_, T> {
+	fn serialize<S: Serializer>(
+		&self,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		if self.extra.is_empty() {
+			return self.body.serialize(serializer);
+		}
+
+		let mut body =
+			serde_json::to_value(self.body).map_err(S::Error::custom)?;
+		merge(&mut body, self.extra);
+		body.serialize(serializer)
+	}
+}
+
+/// Merges objects recursively, anything else in `extra` replaces the value
+/// in `body`.
+fn merge(body: &mut Value, extra: &Map<String, Value>) {
+	let Value::Object(body) = body else {
+		return;
+	};
+
+	for (key, value) in extra {
+		match (body.get_mut(key), value) {
+			(Some(existing @ Value::Object(_)), Value::Object(extra)) => {
+				merge(existing, extra)
+			}
+			_ => {
+				body.insert(key.clone(), value.clone());
+			}
+		}
+	}
+}
@@ -1,4 +1,14 @@
+pub mod compress;
+pub mod extra;
+pub mod multipart;
+pub mod paginate;
 pub mod sse;
+pub mod time;
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
 
 pub fn default_parameters() -> serde_json::Value {
 	serde_json::json!({
@@ -6,3 +16,58 @@ pub fn default_parameters() -> serde_json::Value {
 		"properties": {},
 	})
 }
+
+/// The id the provider assigned to the request, for support tickets and
+/// matching errors with the provider's logs.
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+	[
+		"request-id",
+		"x-request-id",
+		"mistral-correlation-id",
+		"x-amzn-requestid",
+	]
+	.iter()
+	.find_map(|name| headers.get(*name)?.to_str().ok())
+	.map(Into::into)
+}
+
+/// The delay from `retry-after-ms` or `retry-after`, dates in
+/// `retry-after` are ignored.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+	let header = |name| headers.get(name)?.to_str().ok()?.trim().parse().ok();
+
+	header("retry-after-ms")
+		.map(Duration::from_millis)
+		.or_else(|| header("retry-after").map(Duration::from_secs))
+}
+
+/// A response with an error status, converted into the provider's
+/// `ResponseError`.
+#[derive(Debug)]
+pub struct ErrorResponse {
+	pub status: StatusCode,
+	pub body: String,
+	pub request_id: Option<String>,
+	pub retry_after: Option<Duration>,
+}
+
+/// Reads the body of a response with an error status, or returns the
+/// error reading it.
+pub async fn error_response<E>(resp: reqwest::Response) -> E
+where
+	E: From<ErrorResponse> + From<reqwest::Error>,
+{
+	let status = resp.status();
+	let request_id = request_id(resp.headers());
+	let retry_after = retry_after(resp.headers());
+	match resp.text().await {
+		Ok(body) => ErrorResponse {
+			status,
+			body,
+			request_id,
+			retry_after,
+		}
+		.into(),
+		Err(e) => e.into(),
+	}
+}
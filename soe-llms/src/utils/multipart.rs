@@ -0,0 +1,24 @@
+use std::{io, path::Path};
+
+use reqwest::{Body, multipart::Part};
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+
+/// Creates a multipart/form-data part streaming the file at `path`.
+///
+/// The file is read in chunks while the request is sent, so its size
+/// doesn't affect memory usage.
+pub async fn file_part(path: &Path, mime_type: &str) -> io::Result<Part> {
+	let file = File::open(path).await?;
+	let len = file.metadata().await?.len();
+
+	let file_name = path
+		.file_name()
+		.map(|name| name.to_string_lossy().into_owned())
+		.unwrap_or_else(|| "file".into());
+
+	Part::stream_with_length(Body::wrap_stream(ReaderStream::new(file)), len)
+		.file_name(file_name)
+		.mime_str(mime_type)
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
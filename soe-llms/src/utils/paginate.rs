@@ -0,0 +1,33 @@
+use futures::{Stream, StreamExt as _, TryStreamExt as _, stream};
+
+/// One page of a list endpoint.
+pub struct Page<T> {
+	pub items: Vec<T>,
+	/// Cursor or page token of the next page, `None` on the last one.
+	pub next: Option<String>,
+}
+
+/// Streams the items of every page, `fetch` gets the cursor of the page to
+/// load, `None` for the first one.
+///
+/// A page is only fetched once the items of the previous one were
+/// consumed, the stream ends after the first error.
+pub fn paginate<T, E, F, Fut>(mut fetch: F) -> impl Stream<Item = Result<T, E>>
+where
+	F: FnMut(Option<String>) -> Fut,
+	Fut: Future<Output = Result<Page<T>, E>>,
+{
+	// `None` once the last page was fetched
+	stream::try_unfold(Some(None), move |cursor: Option<Option<String>>| {
+		let page = cursor.map(&mut fetch);
+		async move {
+			let Some(page) = page else {
+				return Ok(None);
+			};
+			let page = page.await?;
+			let next = page.next.filter(|c| !c.is_empty()).map(Some);
+			Ok(Some((stream::iter(page.items).map(Ok), next)))
+		}
+	})
+	.try_flatten()
+}
@@ -1,37 +1,83 @@
-use std::{fmt, io};
+use std::{fmt, io, time::Duration};
 
 use bytes::Bytes;
 use futures::{StreamExt as _, TryStreamExt as _, stream::BoxStream};
 use reqwest::Response;
 use serde::de::DeserializeOwned;
-use tokio::io::{AsyncBufReadExt, Lines};
+use tokio::io::AsyncBufReadExt as _;
 use tokio_util::io::StreamReader;
 use tracing::error;
 
-use crate::llms::LlmsError;
+use crate::llms::{LlmsError, Transcript};
 
+/// Reads events from the response body as they're polled. Dropping it
+/// drops the body, which aborts the request.
 pub struct SseResponse {
-	inner: Lines<
-		StreamReader<BoxStream<'static, Result<Bytes, io::Error>>, Bytes>,
-	>,
+	inner: StreamReader<BoxStream<'static, Result<Bytes, io::Error>>, Bytes>,
+	request_id: Option<String>,
+	/// The bytes of the line being received.
+	line: Vec<u8>,
+	/// The last line ended with a CR, a LF right after it belongs to it.
+	skip_lf: bool,
+	read_timeout: Option<Duration>,
+	/// A leading byte order mark is stripped from the first line.
+	first_line: bool,
+	pending: PendingEvent,
+	last_id: Option<String>,
 }
 
 impl SseResponse {
-	pub fn new(resp: Response) -> Self {
+	/// Also copies the body into the transcript if given, with every
+	/// occurrence of `secret` redacted.
+	///
+	/// Reading fails with [`SseError::ReadTimeout`] once no chunk of the body
+	/// arrived within `read_timeout`, keep-alive comments count as well.
+	pub fn new(
+		resp: Response,
+		transcript: Option<&Transcript>,
+		secret: &str,
+		read_timeout: Option<Duration>,
+	) -> Self {
 		assert!(
 			resp.status().is_success(),
 			"response is expected to be successful"
 		);
 
+		let request_id = super::request_id(resp.headers());
+		let tee = transcript.map(|t| t.start(&resp, secret));
+		let body = resp.bytes_stream().map_err(io::Error::other);
+		let body = match tee {
+			Some(mut tee) => body.inspect_ok(move |c| tee.on_chunk(c)).boxed(),
+			None => body.boxed(),
+		};
+
+		Self::from_body(body, request_id, read_timeout)
+	}
+
+	pub(crate) fn from_body(
+		body: BoxStream<'static, Result<Bytes, io::Error>>,
+		request_id: Option<String>,
+		read_timeout: Option<Duration>,
+	) -> Self {
 		Self {
-			inner: StreamReader::new(
-				resp.bytes_stream().map_err(io::Error::other).boxed(),
-			)
-			.lines(),
+			request_id,
+			read_timeout,
+			inner: StreamReader::new(body),
+			line: Vec::new(),
+			skip_lf: false,
+			first_line: true,
+			pending: PendingEvent::default(),
+			last_id: None,
 		}
 	}
 
-	/// Get the next event from the stream.
+	pub fn request_id(&self) -> Option<&str> {
+		self.request_id.as_deref()
+	}
+
+	/// Get the next event from the stream and deserialize its data.
+	///
+	/// Returns `None` once the stream ended or sent `[DONE]`.
 	///
 	/// # Cancel safety
 	///
@@ -39,41 +85,188 @@ impl SseResponse {
 	pub async fn next<T: DeserializeOwned>(
 		&mut self,
 	) -> Option<Result<T, SseError>> {
-		let mut line_owned;
-		let line = loop {
-			match self.inner.next_line().await {
-				Ok(Some(line)) => line_owned = line,
-				Ok(None) => return None,
-				// get original error back
-				Err(e) if e.kind() == io::ErrorKind::Other => {
-					let err = match e.downcast::<reqwest::Error>() {
-						Ok(e) => e.into(),
-						Err(e) => e.into(),
-					};
-
-					return Some(Err(err));
+		let event = match self.next_event().await? {
+			Ok(event) => event,
+			Err(e) => return Some(Err(e)),
+		};
+
+		if event.data.trim() == "[DONE]" {
+			return None;
+		}
+
+		Some(serde_json::from_str(&event.data).map_err(|e| {
+			error!("received event {:?}: {}", event.event, event.data);
+			e.into()
+		}))
+	}
+
+	/// Get the next event from the stream, parsed as described in the
+	/// [WHATWG spec](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation).
+	///
+	/// Lines end with a CR, a LF or both, invalid UTF-8 is replaced. Unlike
+	/// the spec, an event not terminated by a blank line before the stream
+	/// ended is still returned, some servers omit it.
+	///
+	/// # Cancel safety
+	///
+	/// This method is cancellation safe, the lines of a partially received
+	/// event are kept.
+	pub async fn next_event(&mut self) -> Option<Result<SseEvent, SseError>> {
+		loop {
+			let line = match self.next_line().await {
+				Ok(Some(line)) => line,
+				Ok(None) => {
+					return self.pending.dispatch(&self.last_id).map(Ok);
+				}
+				Err(e) => return Some(Err(e)),
+			};
+
+			let line = if self.first_line {
+				self.first_line = false;
+				line.strip_prefix('\u{feff}').unwrap_or(&line)
+			} else {
+				&line
+			};
+
+			if line.is_empty() {
+				match self.pending.dispatch(&self.last_id) {
+					Some(event) => return Some(Ok(event)),
+					None => continue,
 				}
-				Err(err) => return Some(Err(err.into())),
 			}
 
-			if let Some(line) = line_owned.strip_prefix("data:") {
-				break line.trim();
+			let (field, value) = match line.split_once(':') {
+				// a comment, e.g. used as keep alive
+				Some(("", _)) => continue,
+				Some((field, value)) => {
+					(field, value.strip_prefix(' ').unwrap_or(value))
+				}
+				None => (line, ""),
+			};
+
+			match field {
+				"data" => {
+					self.pending.data.push_str(value);
+					self.pending.data.push('\n');
+					self.pending.has_data = true;
+				}
+				"event" => self.pending.event = Some(value.to_string()),
+				"id" if !value.contains('\0') => {
+					self.last_id = Some(value.to_string())
+				}
+				// reconnecting isn't supported, so retry is ignored as well
+				_ => {}
 			}
+		}
+	}
 
-			// ignore lines which don't start with "data:"
-		};
+	/// Reads the next line without its ending, `None` once the body ended.
+	///
+	/// # Cancel safety
+	///
+	/// This method is cancellation safe, the bytes already received are kept
+	/// in `line`.
+	async fn next_line(&mut self) -> Result<Option<String>, SseError> {
+		loop {
+			let buf = match self.read_timeout {
+				Some(limit) => {
+					tokio::time::timeout(limit, self.inner.fill_buf())
+						.await
+						.map_err(|_| SseError::ReadTimeout(limit))?
+				}
+				None => self.inner.fill_buf().await,
+			};
+			let buf = buf.map_err(body_error)?;
+			if buf.is_empty() {
+				if self.line.is_empty() {
+					return Ok(None);
+				}
+				break;
+			}
 
-		if line == "[DONE]" {
-			return None;
+			let skip =
+				(std::mem::take(&mut self.skip_lf) && buf[0] == b'\n') as usize;
+			let buf = &buf[skip..];
+			match buf.iter().position(|b| matches!(b, b'\r' | b'\n')) {
+				Some(end) => {
+					self.line.extend_from_slice(&buf[..end]);
+					self.skip_lf = buf[end] == b'\r';
+					self.inner.consume(skip + end + 1);
+					break;
+				}
+				None => {
+					self.line.extend_from_slice(buf);
+					let len = buf.len();
+					self.inner.consume(skip + len);
+				}
+			}
 		}
 
-		Some(serde_json::from_str(line).map_err(|e| {
-			error!("received line {line}");
-			e.into()
+		let line = std::mem::take(&mut self.line);
+		Ok(Some(match String::from_utf8(line) {
+			Ok(line) => line,
+			Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
 		}))
 	}
 }
 
+/// Gets the original error back from the body stream.
+fn body_error(e: io::Error) -> SseError {
+	if e.kind() != io::ErrorKind::Other {
+		return e.into();
+	}
+
+	match e.downcast::<reqwest::Error>() {
+		Ok(e) => e.into(),
+		Err(e) => e.into(),
+	}
+}
+
+/// A dispatched server-sent event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+	/// The `event` field, `"message"` if the event had none or an empty
+	/// one.
+	pub event: String,
+	/// All `data` fields joined by newlines.
+	pub data: String,
+	/// The last `id` field received so far, it carries over to the
+	/// following events.
+	pub id: Option<String>,
+}
+
+/// The fields of the event being received.
+#[derive(Debug, Default)]
+struct PendingEvent {
+	event: Option<String>,
+	data: String,
+	/// Whether a `data` field was received, an event without one isn't
+	/// dispatched.
+	has_data: bool,
+}
+
+impl PendingEvent {
+	/// Takes the received fields, `None` if there is no data.
+	fn dispatch(&mut self, last_id: &Option<String>) -> Option<SseEvent> {
+		let pending = std::mem::take(self);
+		if !pending.has_data {
+			return None;
+		}
+
+		let mut data = pending.data;
+		data.pop();
+
+		Some(SseEvent {
+			event: pending
+				.event
+				.filter(|event| !event.is_empty())
+				.unwrap_or_else(|| "message".into()),
+			data,
+			id: last_id.clone(),
+		})
+	}
+}
+
 impl fmt::Debug for SseResponse {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("SseResponse").finish()
@@ -88,6 +281,8 @@ pub enum SseError {
 	Reqwest(#[from] reqwest::Error),
 	#[error("JSON deserialization error: {0}")]
 	Json(#[from] serde_json::Error),
+	#[error("No data received within {0:?}")]
+	ReadTimeout(Duration),
 }
 
 impl From<SseError> for LlmsError {
@@ -96,6 +291,232 @@ impl From<SseError> for LlmsError {
 			SseError::Io(e) => LlmsError::Io(e),
 			SseError::Reqwest(e) => LlmsError::Reqwest(e),
 			SseError::Json(e) => LlmsError::Json(e),
+			SseError::ReadTimeout(limit) => LlmsError::ReadTimeout(limit),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::stream;
+	use tokio::{
+		io::{AsyncReadExt as _, AsyncWriteExt as _},
+		net::TcpListener,
+		time::{sleep, timeout},
+	};
+
+	use super::*;
+
+	/// Every chunk is received separately.
+	async fn events(chunks: &[&[u8]]) -> Vec<SseEvent> {
+		let chunks: Vec<_> = chunks
+			.iter()
+			.map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+			.collect();
+		let mut sse =
+			SseResponse::from_body(stream::iter(chunks).boxed(), None, None);
+
+		let mut events = Vec::new();
+		while let Some(event) = sse.next_event().await {
+			events.push(event.unwrap());
+		}
+		events
+	}
+
+	fn event(event: &str, data: &str, id: Option<&str>) -> SseEvent {
+		SseEvent {
+			event: event.into(),
+			data: data.into(),
+			id: id.map(Into::into),
+		}
+	}
+
+	#[tokio::test]
+	async fn joins_multi_line_data() {
+		assert_eq!(
+			events(&[b"data: a\ndata:b\ndata:  c\ndata\n\n"]).await,
+			[event("message", "a\nb\n c\n", None)]
+		);
+	}
+
+	#[tokio::test]
+	async fn named_and_empty_events() {
+		assert_eq!(
+			events(&[
+				b"event: delta\ndata: 1\n\nevent:\ndata: 2\n\ndata: 3\n\n"
+			])
+			.await,
+			[
+				event("delta", "1", None),
+				event("message", "2", None),
+				event("message", "3", None),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn id_carries_over() {
+		assert_eq!(
+			events(&[b"id: 1\ndata: a\n\ndata: b\n\nid\ndata: c\n\n"]).await,
+			[
+				event("message", "a", Some("1")),
+				event("message", "b", Some("1")),
+				event("message", "c", Some("")),
+			]
+		);
+		// ids with a NUL are ignored
+		assert_eq!(
+			events(&[b"id: 1\ndata: a\n\nid: 2\0\ndata: b\n\n"]).await,
+			[
+				event("message", "a", Some("1")),
+				event("message", "b", Some("1"))
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn strips_a_leading_bom() {
+		assert_eq!(
+			events(&[b"\xef\xbb", b"\xbfdata: a\n\n\xef\xbb\xbfdata: b\n\n"])
+				.await,
+			[event("message", "a", None)]
+		);
+	}
+
+	#[tokio::test]
+	async fn skips_comments_and_events_without_data() {
+		assert_eq!(
+			events(&[b": ping\n\nevent: done\n\n:\ndata: a\n: x\n\n"]).await,
+			[event("message", "a", None)]
+		);
+	}
+
+	#[tokio::test]
+	async fn returns_an_unterminated_final_event() {
+		assert_eq!(
+			events(&[b"data: a\n\ndata: b"]).await,
+			[event("message", "a", None), event("message", "b", None)]
+		);
+		assert_eq!(events(&[b"event: x\n"]).await, []);
+	}
+
+	#[tokio::test]
+	async fn splits_lines_on_cr_lf_and_crlf() {
+		let expected = [event("x", "a\nb", None), event("message", "c", None)];
+		for body in [
+			b"event: x\r\ndata: a\r\ndata: b\r\n\r\ndata: c\r\n\r\n" as &[u8],
+			b"event: x\rdata: a\rdata: b\r\rdata: c\r\r",
+			b"event: x\ndata: a\r\ndata: b\r\rdata: c\n\r\n",
+		] {
+			assert_eq!(events(&[body]).await, expected);
 		}
+
+		// a CRLF split across chunks is one line ending
+		assert_eq!(
+			events(&[
+				b"event: x\r",
+				b"\ndata: a\r",
+				b"\ndata: b\r",
+				b"\n\r",
+				b"\n",
+				b"data: c\r\n\r\n"
+			])
+			.await,
+			expected
+		);
+	}
+
+	#[tokio::test]
+	async fn replaces_invalid_utf8() {
+		assert_eq!(
+			events(&[b"data: \xc3", b"\xa4 \xff\n\n"]).await,
+			[event("message", "\u{e4} \u{fffd}", None)]
+		);
+	}
+
+	/// Ten keep-alive comments 20ms apart, then an event.
+	fn slow_body() -> BoxStream<'static, Result<Bytes, io::Error>> {
+		stream::iter(0..11)
+			.then(|i| async move {
+				tokio::time::sleep(Duration::from_millis(20)).await;
+				Ok(Bytes::from_static(match i {
+					10 => b"data: a\n\n",
+					_ => b": ping\n",
+				}))
+			})
+			.boxed()
+	}
+
+	#[tokio::test]
+	async fn read_timeout_is_per_chunk() {
+		let limit = Duration::from_millis(100);
+		let mut sse = SseResponse::from_body(slow_body(), None, Some(limit));
+		assert_eq!(
+			sse.next_event().await.unwrap().unwrap(),
+			event("message", "a", None)
+		);
+
+		let stalled = stream::iter([Ok(Bytes::from_static(b"data: a\n"))])
+			.chain(stream::pending())
+			.boxed();
+		let mut sse = SseResponse::from_body(stalled, None, Some(limit));
+		assert!(matches!(
+			sse.next_event().await,
+			Some(Err(SseError::ReadTimeout(l))) if l == limit
+		));
+	}
+
+	#[tokio::test]
+	async fn stops_at_done() {
+		let chunks = [Ok(Bytes::from_static(
+			b"data: {\"a\":1}\n\ndata: [DONE]\n\ndata: {\"a\":2}\n\n",
+		))];
+		let mut sse =
+			SseResponse::from_body(stream::iter(chunks).boxed(), None, None);
+		let first: serde_json::Value = sse.next().await.unwrap().unwrap();
+		assert_eq!(first, serde_json::json!({ "a": 1 }));
+		assert!(sse.next::<serde_json::Value>().await.is_none());
+	}
+
+	#[tokio::test]
+	async fn dropping_closes_the_connection() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+
+		// answers with one event and keeps the response open, finishes once
+		// the client closed the connection
+		let server = tokio::spawn(async move {
+			let (mut socket, _) = listener.accept().await.unwrap();
+			let mut req = Vec::new();
+			let mut buf = [0; 1024];
+			while !req.ends_with(b"\r\n\r\n") {
+				let n = socket.read(&mut buf).await.unwrap();
+				assert!(n > 0, "closed before the request was sent");
+				req.extend_from_slice(&buf[..n]);
+			}
+
+			let event = "data: {}\n\n";
+			let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+				transfer-encoding: chunked\r\n\r\n";
+			let chunk = format!("{:x}\r\n{event}\r\n", event.len());
+			socket.write_all(head.as_bytes()).await.unwrap();
+			socket.write_all(chunk.as_bytes()).await.unwrap();
+
+			while socket.read(&mut buf).await.unwrap() > 0 {}
+		});
+
+		let resp = reqwest::get(url).await.unwrap();
+		let mut sse = SseResponse::new(resp, None, "", None);
+		let event: serde_json::Value = sse.next().await.unwrap().unwrap();
+		assert_eq!(event, serde_json::json!({}));
+
+		sleep(Duration::from_millis(100)).await;
+		assert!(!server.is_finished(), "closed before the drop");
+
+		drop(sse);
+		timeout(Duration::from_secs(5), server)
+			.await
+			.expect("connection still open after the drop")
+			.unwrap();
 	}
 }
@@ -0,0 +1,27 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats `time` as RFC 3339 in UTC, e.g. `2025-01-02T03:04:05.678Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+	let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+	let secs = since.as_secs();
+	let (days, rem) = (secs / 86_400, secs % 86_400);
+
+	// civil from days, see https://howardhinnant.github.io/date_algorithms.html
+	let z = days as i64 + 719_468;
+	let era = z.div_euclid(146_097);
+	let doe = z.rem_euclid(146_097);
+	let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + i64::from(month <= 2);
+
+	format!(
+		"{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+		rem / 3600,
+		rem % 3600 / 60,
+		rem % 60,
+		since.subsec_millis()
+	)
+}
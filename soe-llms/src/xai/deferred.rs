@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use super::{ApiReq, ApiToolCall, Request, XAi, XAiError};
+use crate::{llms, utils, utils::extra::WithExtra};
+
+impl XAi {
+	/// Submits the request as a deferred completion and returns its request
+	/// id.
+	///
+	/// xAI runs deferred completions in the background, the result is
+	/// fetched later with [`XAi::poll_deferred`] or [`XAi::wait_deferred`].
+	/// Useful for long reasoning jobs that would otherwise outlast HTTP
+	/// timeouts. Results are kept for 24 hours after they become available.
+	pub async fn submit_deferred(
+		&self,
+		req: &Request,
+	) -> Result<String, XAiError> {
+		#[derive(Debug, Deserialize)]
+		struct ApiResp {
+			request_id: String,
+		}
+
+		let api_req = ApiReq {
+			stream: false,
+			stream_options: None,
+			deferred: true,
+			..ApiReq::new(req)
+		};
+
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.post(self.url("chat/completions"))
+			.bearer_auth(&*api_key)
+			.headers(req.headers.clone());
+		let resp = utils::compress::json_body(
+			resp,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
+
+		if !resp.status().is_success() {
+			return Err(utils::error_response(resp).await);
+		}
+
+		Ok(resp.json::<ApiResp>().await?.request_id)
+	}
+
+	/// Fetches the result of a deferred completion. Returns `None` while it
+	/// is still running.
+	pub async fn poll_deferred(
+		&self,
+		request_id: &str,
+	) -> Result<Option<llms::Response>, XAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url(&format!("chat/deferred-completion/{request_id}")))
+			.bearer_auth(&*api_key)
+			.send()
+			.await?;
+
+		if resp.status() == StatusCode::ACCEPTED {
+			return Ok(None);
+		}
+
+		if !resp.status().is_success() {
+			return Err(utils::error_response(resp).await);
+		}
+
+		resp.json::<Completion>().await?.try_into().map(Some)
+	}
+
+	/// Polls a deferred completion every `interval` until it is done.
+	pub async fn wait_deferred(
+		&self,
+		request_id: &str,
+		interval: Duration,
+	) -> Result<llms::Response, XAiError> {
+		loop {
+			if let Some(resp) = self.poll_deferred(request_id).await? {
+				return Ok(resp);
+			}
+
+			tokio::time::sleep(interval).await;
+		}
+	}
+}
+
+/// A non-streaming chat completion.
+#[derive(Debug, Deserialize)]
+pub struct Completion {
+	#[serde(default)]
+	pub choices: Vec<CompletionChoice>,
+	pub usage: Option<super::ApiUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletionChoice {
+	pub message: CompletionMessage,
+	pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompletionMessage {
+	pub content: Option<String>,
+	pub tool_calls: Option<Vec<ApiToolCall>>,
+}
+
+impl TryFrom<Completion> for llms::Response {
+	type Error = XAiError;
+
+	fn try_from(completion: Completion) -> Result<Self, XAiError> {
+		let choice = completion
+			.choices
+			.into_iter()
+			.next()
+			.ok_or(XAiError::NoOutput)?;
+
+		let mut output = Vec::new();
+
+		if let Some(content) = choice.message.content.filter(|c| !c.is_empty())
+		{
+			output.push(llms::Output::Text { content });
+		}
+
+		for tc in choice.message.tool_calls.unwrap_or_default() {
+			let input =
+				serde_json::from_str(&tc.function.arguments).map_err(|e| {
+					XAiError::InvalidLlmResponse(format!(
+						"invalid tool call arguments JSON for '{}': {e}",
+						tc.function.name
+					))
+				})?;
+
+			output.push(llms::Output::ToolCall {
+				id: tc.id,
+				name: tc.function.name,
+				input,
+				context: None,
+			});
+		}
+
+		if output.is_empty() {
+			return Err(XAiError::NoOutput);
+		}
+
+		let usage = completion.usage.ok_or_else(|| {
+			XAiError::InvalidLlmResponse("missing usage in response".into())
+		})?;
+
+		Ok(llms::Response {
+			output,
+			usage: usage.into(),
+			finish_reason: choice
+				.finish_reason
+				.map(llms::FinishReason::from_chat),
+			..Default::default()
+		})
+	}
+}
@@ -1,78 +1,190 @@
-use std::fmt;
+mod deferred;
 
-use reqwest::{Client, StatusCode};
+pub use deferred::{Completion, CompletionChoice, CompletionMessage};
+
+pub use crate::llms::chat::{
+	ApiContentPart, ApiImageUrl, ApiMessage, ApiToolCall, ApiToolCallFunction,
+	ApiUserContent,
+};
+
+use std::{collections::VecDeque, fmt, time::Duration};
+
+use reqwest::{Client, StatusCode, header::HeaderMap};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
 use tracing::{debug, trace};
 
 use crate::{
-	llms::{self, LlmProvider, LlmResponseStream, LlmsError},
+	llms::{
+		self, ApiKey, Compression, CredentialError, LlmProvider,
+		LlmResponseStream, LlmsError, Transcript,
+	},
 	utils::{
-		default_parameters,
+		ErrorResponse,
+		compress::json_body,
+		default_parameters, error_response,
+		extra::WithExtra,
 		sse::{SseError, SseResponse},
 	},
 };
 
+/// Default of [`XAi::base_url`].
+pub const DEFAULT_BASE_URL: &str = "https://api.x.ai/v1";
+
 #[derive(Clone)]
 pub struct XAi {
 	pub client: Client,
-	pub api_key: String,
+	pub api_key: ApiKey,
+	/// Compression for request bodies, see
+	/// [`HttpConfig::request_compression`](crate::HttpConfig::request_compression).
+	pub compression: Option<Compression>,
+	/// Prefix of every endpoint, to go through a proxy or gateway
+	/// instead, e.g. `"https://llm-proxy.internal/xai/v1"`.
+	pub base_url: String,
 }
 
 impl XAi {
-	pub fn new(api_key: String) -> Self {
+	pub fn new(api_key: impl Into<ApiKey>) -> Self {
+		Self::with_client(api_key, Client::new())
+	}
+
+	pub fn with_client(api_key: impl Into<ApiKey>, client: Client) -> Self {
 		Self {
-			client: Client::new(),
-			api_key,
+			client,
+			api_key: api_key.into(),
+			compression: None,
+			base_url: DEFAULT_BASE_URL.into(),
 		}
 	}
 
+	pub fn base_url(mut self, url: impl Into<String>) -> Self {
+		self.base_url = url.into();
+		self
+	}
+
+	fn url(&self, path: &str) -> String {
+		format!("{}/{path}", self.base_url.trim_end_matches('/'))
+	}
+
+	/// Lists the available models, the cheapest authenticated call.
+	///
+	/// Used to check that the api is reachable and the key is valid.
+	pub async fn ping(&self) -> Result<(), XAiError> {
+		let api_key = self.api_key.get().await?;
+
+		let resp = self
+			.client
+			.get(self.url("models"))
+			.bearer_auth(&*api_key)
+			.send()
+			.await?;
+
+		if !resp.status().is_success() {
+			return Err(error_response(resp).await);
+		}
+
+		Ok(())
+	}
+
 	pub async fn request(
 		&self,
 		req: &Request,
 	) -> Result<ResponseStream, XAiError> {
-		#[derive(Debug, Serialize)]
-		struct ApiReq<'a> {
-			model: &'a str,
-			messages: &'a Vec<ApiMessage>,
-			#[serde(skip_serializing_if = "Vec::is_empty")]
-			tools: &'a Vec<ApiTool>,
-			stream: bool,
-			stream_options: StreamOptions,
-		}
-
-		#[derive(Debug, Serialize)]
-		struct StreamOptions {
-			include_usage: bool,
-		}
-
-		let api_req = ApiReq {
-			model: req.model.as_str(),
-			messages: &req.messages,
-			tools: &req.tools,
-			stream: true,
-			stream_options: StreamOptions {
-				include_usage: true,
-			},
-		};
+		let api_req = ApiReq::new(req);
 
 		trace!("{:?}", serde_json::to_string(&api_req));
 
-		let resp = self
+		let api_key = self.api_key.get().await?;
+
+		let builder = self
 			.client
-			.post("https://api.x.ai/v1/chat/completions")
-			.bearer_auth(&self.api_key)
-			.json(&api_req)
-			.send()
-			.await?;
+			.post(self.url("chat/completions"))
+			.bearer_auth(&*api_key)
+			.headers(req.headers.clone());
+
+		let resp = json_body(
+			builder,
+			&WithExtra {
+				body: &api_req,
+				extra: &req.extra,
+			},
+			self.compression,
+		)
+		.send()
+		.await?;
 
 		if !resp.status().is_success() {
-			let status = resp.status();
-			let body = resp.text().await?;
-			return Err(XAiError::ResponseError { status, body });
+			return Err(error_response(resp).await);
 		}
 
-		Ok(ResponseStream::new(SseResponse::new(resp)))
+		Ok(ResponseStream::new(SseResponse::new(
+			resp,
+			req.transcript.as_ref(),
+			&api_key,
+			req.read_timeout,
+		)))
+	}
+
+	/// Sends the request and drains the stream into the complete response,
+	/// like [`Llms::request_blocking`](crate::Llms::request_blocking) but
+	/// without its hooks, retries and fallbacks.
+	pub async fn request_blocking(
+		&self,
+		req: &Request,
+	) -> Result<llms::Response, LlmsError> {
+		llms::drain(self.request(req).await?).await
+	}
+}
+
+#[derive(Debug, Serialize)]
+struct ApiReq<'a> {
+	model: &'a str,
+	messages: &'a Vec<ApiMessage>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	tools: &'a Vec<ApiTool>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	tool_choice: Option<&'a ToolChoice>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	response_format: Option<&'a ResponseFormat>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	temperature: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	top_p: Option<f32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	max_tokens: Option<u32>,
+	stream: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	stream_options: Option<StreamOptions>,
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	deferred: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+	include_usage: bool,
+}
+
+impl<'a> ApiReq<'a> {
+	/// A streaming request.
+	fn new(req: &'a Request) -> Self {
+		Self {
+			model: req.snapshot.as_deref().unwrap_or(req.model.as_str()),
+			messages: &req.messages,
+			tools: &req.tools,
+			tool_choice: req
+				.tool_choice
+				.as_ref()
+				.filter(|_| !req.tools.is_empty()),
+			response_format: req.response_format.as_ref(),
+			temperature: req.temperature,
+			top_p: req.top_p,
+			max_tokens: req.max_tokens,
+			stream: true,
+			stream_options: Some(StreamOptions {
+				include_usage: true,
+			}),
+			deferred: false,
+		}
 	}
 }
 
@@ -91,6 +203,7 @@ impl LlmProvider for XAi {
 	) -> Result<Self::Stream, LlmsError> {
 		let model = match &req.model {
 			llms::Model::Grok4_5 => XAiModel::Grok4_5,
+			llms::Model::Custom { id, .. } => XAiModel::Custom(id.clone()),
 			m => unreachable!("unsupported model: {m:?}"),
 		};
 
@@ -108,12 +221,22 @@ impl LlmProvider for XAi {
 			});
 		}
 
-		messages.extend(req.input.iter().cloned().map(ApiMessage::from));
+		messages.extend(ApiMessage::from_history(&req.input));
 
 		self.request(&Request {
 			messages,
 			model,
+			snapshot: req.snapshot.clone(),
 			tools: req.tools.iter().cloned().map(Into::into).collect(),
+			tool_choice: req.tool_choice.clone().into(),
+			response_format: req.response_format.clone().into(),
+			temperature: req.params.temperature,
+			top_p: req.params.top_p,
+			max_tokens: req.params.max_tokens,
+			extra: req.extra.clone(),
+			headers: req.headers.clone(),
+			transcript: req.transcript.clone(),
+			read_timeout: req.timeouts.read,
 		})
 		.await
 		.map_err(Into::into)
@@ -123,87 +246,120 @@ impl LlmProvider for XAi {
 pub struct Request {
 	pub messages: Vec<ApiMessage>,
 	pub model: XAiModel,
+	/// Sent instead of the model's name, see [`llms::Request::snapshot`].
+	pub snapshot: Option<String>,
 	pub tools: Vec<ApiTool>,
+	/// `None` leaves it to the model.
+	pub tool_choice: Option<ToolChoice>,
+	/// `None` lets the model answer with plain text.
+	pub response_format: Option<ResponseFormat>,
+	pub temperature: Option<f32>,
+	pub top_p: Option<f32>,
+	pub max_tokens: Option<u32>,
+	/// Merged into the JSON body, see [`llms::Request::extra`].
+	pub extra: Map<String, Value>,
+	/// Sent with the request, see [`llms::Request::headers`].
+	pub headers: HeaderMap,
+	/// See [`llms::Request::transcript`].
+	pub transcript: Option<Transcript>,
+	/// See [`llms::Timeouts::read`], checked for every chunk of the body.
+	pub read_timeout: Option<Duration>,
 }
 
-#[derive(Debug, Clone, Copy)]
-pub enum XAiModel {
-	Grok4_5,
+/// `tool_choice`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum ToolChoice {
+	Mode(ToolChoiceMode),
+	Function {
+		#[serde(rename = "type")]
+		kind: String,
+		function: ToolChoiceFunction,
+	},
 }
 
-impl XAiModel {
-	pub fn as_str(&self) -> &'static str {
-		match self {
-			XAiModel::Grok4_5 => "grok-4.5",
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolChoiceMode {
+	None,
+	Required,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolChoiceFunction {
+	pub name: String,
+}
+
+impl From<llms::ToolChoice> for Option<ToolChoice> {
+	fn from(choice: llms::ToolChoice) -> Self {
+		match choice {
+			llms::ToolChoice::Auto => None,
+			llms::ToolChoice::None => {
+				Some(ToolChoice::Mode(ToolChoiceMode::None))
+			}
+			llms::ToolChoice::Required => {
+				Some(ToolChoice::Mode(ToolChoiceMode::Required))
+			}
+			llms::ToolChoice::Tool(name) => Some(ToolChoice::Function {
+				kind: "function".into(),
+				function: ToolChoiceFunction { name },
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize)]
-#[serde(tag = "role", rename_all = "lowercase")]
-pub enum ApiMessage {
-	System {
-		content: String,
-	},
-	User {
-		content: String,
-	},
-	Assistant {
-		#[serde(skip_serializing_if = "Option::is_none")]
-		content: Option<String>,
-		#[serde(skip_serializing_if = "Option::is_none")]
-		tool_calls: Option<Vec<ApiToolCall>>,
-	},
-	Tool {
-		tool_call_id: String,
-		content: String,
-	},
+/// OpenAI-style `response_format`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+	Text,
+	JsonObject,
+	JsonSchema { json_schema: JsonSchema },
 }
 
-impl From<llms::Input> for ApiMessage {
-	fn from(input: llms::Input) -> Self {
-		match input {
-			llms::Input::Text { role, content } => match role {
-				llms::Role::User => ApiMessage::User { content },
-				llms::Role::Assistant => ApiMessage::Assistant {
-					content: Some(content),
-					tool_calls: None,
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonSchema {
+	pub name: String,
+	pub schema: Value,
+	pub strict: bool,
+}
+
+impl From<llms::ResponseFormat> for Option<ResponseFormat> {
+	fn from(format: llms::ResponseFormat) -> Self {
+		match format {
+			llms::ResponseFormat::Text => None,
+			llms::ResponseFormat::JsonObject => {
+				Some(ResponseFormat::JsonObject)
+			}
+			llms::ResponseFormat::JsonSchema {
+				name,
+				schema,
+				strict,
+			} => Some(ResponseFormat::JsonSchema {
+				json_schema: JsonSchema {
+					name,
+					schema,
+					strict,
 				},
-			},
-			llms::Input::ToolCall {
-				id, name, input, ..
-			} => ApiMessage::Assistant {
-				content: None,
-				tool_calls: Some(vec![ApiToolCall {
-					id,
-					kind: "function".into(),
-					function: ApiToolCallFunction {
-						name,
-						arguments: input.to_string(),
-					},
-				}]),
-			},
-			llms::Input::ToolCallOutput { id, output } => ApiMessage::Tool {
-				tool_call_id: id,
-				content: output,
-			},
+			}),
 		}
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCall {
-	pub id: String,
-	#[serde(rename = "type")]
-	pub kind: String,
-	pub function: ApiToolCallFunction,
+#[derive(Debug, Clone)]
+pub enum XAiModel {
+	Grok4_5,
+	/// Any other model id, see [`llms::Model::Custom`].
+	Custom(String),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct ApiToolCallFunction {
-	pub name: String,
-	/// JSON-encoded arguments string.
-	pub arguments: String,
+impl XAiModel {
+	pub fn as_str(&self) -> &str {
+		match self {
+			XAiModel::Grok4_5 => "grok-4.5",
+			XAiModel::Custom(id) => id,
+		}
+	}
 }
 
 #[derive(Debug, Serialize)]
@@ -259,16 +415,57 @@ pub struct ApiUsage {
 	pub prompt_tokens: u32,
 	#[serde(default)]
 	pub completion_tokens: u32,
+	/// Sometimes `null`.
+	#[serde(default)]
+	pub prompt_tokens_details: Option<PromptTokensDetails>,
+	#[serde(default)]
+	pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl From<ApiUsage> for llms::Usage {
+	fn from(usage: ApiUsage) -> Self {
+		let prompt = usage.prompt_tokens_details.unwrap_or_default();
+		let reasoning = usage
+			.completion_tokens_details
+			.unwrap_or_default()
+			.reasoning_tokens;
+
+		llms::Usage {
+			input_tokens: usage.prompt_tokens,
+			output_tokens: usage.completion_tokens.saturating_add(reasoning),
+			cache_read_tokens: prompt.cached_tokens,
+			reasoning_tokens: reasoning,
+			..Default::default()
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct PromptTokensDetails {
+	#[serde(default)]
+	pub cached_tokens: u32,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct CompletionTokensDetails {
+	/// Not part of `completion_tokens`.
+	#[serde(default)]
+	pub reasoning_tokens: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ChunkChoice {
 	pub delta: Delta,
+	/// Set on the last chunk of the choice.
+	pub finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Delta {
 	pub content: Option<String>,
+	/// Reasoning text of backends with a reasoning parser, e.g. DeepSeek,
+	/// Qwen or vLLM.
+	pub reasoning_content: Option<String>,
 	pub tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
@@ -294,9 +491,30 @@ pub enum XAiError {
 	#[error("No output in response")]
 	NoOutput,
 	#[error("Response error: status {status}, body {body}")]
-	ResponseError { status: StatusCode, body: String },
+	ResponseError {
+		status: StatusCode,
+		body: String,
+		/// From the response headers, if the provider returned one.
+		request_id: Option<String>,
+		/// How long the provider asked to wait, from the `Retry-After`
+		/// header.
+		retry_after: Option<Duration>,
+	},
 	#[error("Reqwest error: {0}")]
 	ReqwestError(#[from] reqwest::Error),
+	#[error("Credential error: {0}")]
+	Credential(#[from] CredentialError),
+}
+
+impl From<ErrorResponse> for XAiError {
+	fn from(resp: ErrorResponse) -> Self {
+		Self::ResponseError {
+			status: resp.status,
+			body: resp.body,
+			request_id: resp.request_id,
+			retry_after: resp.retry_after,
+		}
+	}
 }
 
 impl From<XAiError> for LlmsError {
@@ -305,15 +523,29 @@ impl From<XAiError> for LlmsError {
 			XAiError::InvalidLlmResponse(msg) => LlmsError::Response {
 				status: StatusCode::OK,
 				body: msg,
+				provider: llms::ProviderKind::XAi,
+				request_id: None,
 			},
 			XAiError::NoOutput => LlmsError::Response {
 				status: StatusCode::OK,
 				body: "no output in response".into(),
+				provider: llms::ProviderKind::XAi,
+				request_id: None,
 			},
-			XAiError::ResponseError { status, body } => {
-				LlmsError::Response { status, body }
-			}
+			XAiError::ResponseError {
+				status,
+				body,
+				request_id,
+				retry_after,
+			} => LlmsError::api(
+				llms::ProviderKind::XAi,
+				status,
+				&body,
+				request_id,
+				retry_after,
+			),
 			XAiError::ReqwestError(e) => LlmsError::Reqwest(e),
+			XAiError::Credential(e) => LlmsError::Credential(e),
 		}
 	}
 }
@@ -334,6 +566,8 @@ struct ToolCallAccumulator {
 	id: String,
 	name: String,
 	arguments: String,
+	/// Whether [`llms::LlmResponseEvent::ToolCallStarted`] was emitted.
+	started: bool,
 }
 
 pub struct ResponseStream {
@@ -341,12 +575,19 @@ pub struct ResponseStream {
 	/// Accumulated text across all content deltas. `None` until the first
 	/// non-empty content delta arrives.
 	text: Option<String>,
+	/// Accumulated `reasoning_content`, same as `text`.
+	reasoning: Option<String>,
 	/// Per-index tool call state. The index matches the `index` field in the
 	/// streaming delta and grows on demand.
 	tool_calls: Vec<ToolCallAccumulator>,
+	/// Events of the last chunk not returned yet, a chunk can hold
+	/// several tool call deltas and text.
+	pending: VecDeque<llms::LlmResponseEvent>,
 	/// Token usage from the final stream chunk (when the server honors
 	/// `stream_options.include_usage`). `None` until that chunk arrives.
 	usage: Option<llms::Usage>,
+	/// Of the last chunk that had one.
+	finish_reason: Option<String>,
 	done: bool,
 }
 
@@ -359,12 +600,20 @@ impl std::fmt::Debug for ResponseStream {
 }
 
 impl ResponseStream {
+	/// The provider's id for the request, see [`LlmsError::Api`].
+	pub fn request_id(&self) -> Option<&str> {
+		self.inner.request_id()
+	}
+
 	fn new(inner: SseResponse) -> Self {
 		Self {
 			inner,
 			text: None,
+			reasoning: None,
 			tool_calls: Vec::new(),
+			pending: VecDeque::new(),
 			usage: None,
+			finish_reason: None,
 			done: false,
 		}
 	}
@@ -397,11 +646,30 @@ impl ResponseStream {
 			return Err(XAiError::NoOutput);
 		}
 
+		// the model reasoned before answering
+		if let Some(reasoning) = &self.reasoning {
+			output.insert(
+				0,
+				llms::Output::Reasoning {
+					content: reasoning.clone(),
+				},
+			);
+		}
+
 		let usage = self.usage.take().ok_or_else(|| {
 			XAiError::InvalidLlmResponse("missing usage in response".into())
 		})?;
 
-		Ok(llms::Response { output, usage })
+		Ok(llms::Response {
+			output,
+			usage,
+			finish_reason: self
+				.finish_reason
+				.take()
+				.map(llms::FinishReason::from_chat),
+			reasoning: self.reasoning.take(),
+			..Default::default()
+		})
 	}
 }
 
@@ -414,6 +682,10 @@ impl LlmResponseStream for ResponseStream {
 		}
 
 		loop {
+			if let Some(ev) = self.pending.pop_front() {
+				return Some(Ok(ev));
+			}
+
 			let chunk: Chunk = match self.inner.next().await {
 				Some(Ok(c)) => c,
 				Some(Err(e)) => return Some(Err(e.into())),
@@ -434,15 +706,14 @@ impl LlmResponseStream for ResponseStream {
 				return Some(Err(XAiError::ResponseError {
 					status: StatusCode::OK,
 					body: err.message,
+					request_id: self.inner.request_id().map(Into::into),
+					retry_after: None,
 				}
 				.into()));
 			}
 
 			if let Some(usage) = chunk.usage {
-				self.usage = Some(llms::Usage {
-					input_tokens: usage.prompt_tokens,
-					output_tokens: usage.completion_tokens,
-				});
+				self.usage = Some(usage.into());
 			}
 
 			let choice = match chunk.choices.into_iter().next() {
@@ -450,6 +721,30 @@ impl LlmResponseStream for ResponseStream {
 				None => continue,
 			};
 
+			if choice.finish_reason.is_some() {
+				self.finish_reason = choice.finish_reason;
+			}
+
+			if let Some(reasoning) =
+				choice.delta.reasoning_content.filter(|r| !r.is_empty())
+			{
+				self.reasoning
+					.get_or_insert_with(String::new)
+					.push_str(&reasoning);
+				self.pending.push_back(
+					llms::LlmResponseEvent::ReasoningDelta {
+						content: reasoning,
+					},
+				);
+			}
+
+			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
+				self.text.get_or_insert_with(String::new).push_str(&text);
+				self.pending.push_back(llms::LlmResponseEvent::TextDelta {
+					content: text,
+				});
+			}
+
 			if let Some(tc_deltas) = choice.delta.tool_calls {
 				for delta in tc_deltas {
 					// Grow the accumulator vec on demand (indices are always
@@ -463,22 +758,41 @@ impl LlmResponseStream for ResponseStream {
 						acc.id = id;
 					}
 
+					let mut args_delta = String::new();
 					if let Some(func) = delta.function {
 						if let Some(name) = func.name {
 							acc.name = name;
 						}
 						if let Some(args) = func.arguments {
 							acc.arguments.push_str(&args);
+							args_delta = args;
 						}
 					}
-				}
-			}
 
-			if let Some(text) = choice.delta.content.filter(|t| !t.is_empty()) {
-				self.text.get_or_insert_with(String::new).push_str(&text);
-				return Some(Ok(llms::LlmResponseEvent::TextDelta {
-					content: text,
-				}));
+					// the id and name usually come with the first delta
+					if !acc.started
+						&& !acc.id.is_empty()
+						&& !acc.name.is_empty()
+					{
+						acc.started = true;
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallStarted {
+								id: acc.id.clone(),
+								name: acc.name.clone(),
+							},
+						);
+						// arguments received before the name
+						args_delta = acc.arguments.clone();
+					}
+					if acc.started && !args_delta.is_empty() {
+						self.pending.push_back(
+							llms::LlmResponseEvent::ToolCallArgumentsDelta {
+								id: acc.id.clone(),
+								delta: args_delta,
+							},
+						);
+					}
+				}
 			}
 		}
 	}